@@ -1,8 +1,27 @@
-use iced::widget::{column, horizontal_space, row, text, text_editor, toggler};
+use iced::widget::{button, column, horizontal_space, row, text, text_editor, toggler};
 use iced::{Center, Element, Fill, Font, Task, Theme};
-use iced_highlighter_tree_sitter::TSSettings;
+use iced_highlighter_tree_sitter::{
+    TSSettings,
+    code_editor::{GhostText, GutterColumn, Hover, LineNumbers, Mark, code_editor},
+    diff::{LineChange, line_changes},
+    folding::Folds,
+    shared::SharedHighlightState,
+    structural::Direction,
+};
 
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+///Scopes, definitions and references of variables, so the uses of a parameter are highlighted as one, and shadowing `let`s
+///end that. The `lang-rust` feature bundles a more complete one (`languages::RUST_LOCALS`).
+const LOCALS: &str = r#"
+[(function_item) (closure_expression) (block)] @local.scope
+(parameter pattern: (identifier) @local.definition)
+(let_declaration pattern: (identifier) @local.definition value: (_)? @local.definition-value)
+(identifier) @local.reference
+"#;
 
 pub fn main() -> iced::Result {
     iced::application("Editor - Iced", Editor::update, Editor::view)
@@ -13,34 +32,66 @@ pub fn main() -> iced::Result {
 
 struct Editor {
     file: Option<PathBuf>,
-    ts: TSSettings,
     content: text_editor::Content,
-    word_wrap: bool,
+    ///Parsed once, for both panes of the split view.
+    shared: SharedHighlightState,
+    folds: Folds,
+    ///Lines changed since the file was opened.
+    changes: BTreeMap<usize, LineChange>,
+    breakpoints: BTreeSet<usize>,
+    hovered: Option<Hover>,
+    hints: bool,
+    relative_numbers: bool,
+    split: bool,
+    focus: bool,
     is_dirty: bool,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     ActionPerformed(text_editor::Action),
-    WordWrapToggled(bool),
+    HintsToggled(bool),
+    FocusToggled(bool),
+    FoldToggled(usize),
+    BreakpointToggled(usize),
+    NodeMoved(Direction),
+    Hovered(Option<Hover>),
+    RelativeNumbersToggled(bool),
+    SplitToggled(bool),
+    FoldAll,
+    UnfoldAll,
 }
 
 impl Default for Editor {
     fn default() -> Self {
-        let config = tree_sitter_highlight::HighlightConfiguration::new(
+        //`try_new` tells which query doesn't compile, and where
+        let ts = TSSettings::try_new(
             tree_sitter_rust::LANGUAGE.into(),
             "rust",
-            tree_sitter_rust::HIGHLIGHTS_QUERY,
+            //tree-sitter-highlight only highlights references the highlight query captures too
+            &format!(
+                "(identifier) @_reference\n{}",
+                tree_sitter_rust::HIGHLIGHTS_QUERY
+            ),
             tree_sitter_rust::INJECTIONS_QUERY,
-            "",
+            LOCALS,
         )
-        .unwrap();
+        .unwrap_or_else(|error| panic!("{error}"));
+        let text = include_str!("rusteditor.rs");
 
+        let shared = SharedHighlightState::new(&ts, text);
         Self {
             file: None,
-            content: text_editor::Content::with_text(include_str!("rusteditor.rs")),
-            ts: TSSettings::new(config),
-            word_wrap: true,
+            content: text_editor::Content::with_text(text),
+            folds: shared.with_document(Folds::new),
+            shared,
+            changes: BTreeMap::new(),
+            breakpoints: BTreeSet::new(),
+            hovered: None,
+            hints: true,
+            relative_numbers: false,
+            split: false,
+            focus: false,
             is_dirty: false,
         }
     }
@@ -50,26 +101,135 @@ impl Editor {
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::ActionPerformed(action) => {
-                self.is_dirty = self.is_dirty || action.is_edit();
+                let is_edit = action.is_edit();
+                let edit = match &action {
+                    text_editor::Action::Edit(edit) => Some(edit.clone()),
+                    _ => None,
+                };
+                self.is_dirty = self.is_dirty || is_edit;
 
                 self.content.perform(action);
+                if is_edit {
+                    self.edited();
+                }
+
+                //continue comments on the new line, and re-indent closing brackets
+                let cursor = self.content.cursor_position();
+                let follow_up = self.shared.with_document(|document| match edit {
+                    Some(text_editor::Edit::Enter) => {
+                        document.line_continuation(cursor).map(|continuation| {
+                            vec![text_editor::Action::Edit(text_editor::Edit::Paste(
+                                continuation.into(),
+                            ))]
+                        })
+                    }
+                    Some(text_editor::Edit::Insert(typed)) => document
+                        .electric_indent(cursor, typed)
+                        .map(|edit| edit.actions(document)),
+                    _ => None,
+                });
+                if let Some(actions) = follow_up {
+                    for action in actions {
+                        self.content.perform(action);
+                    }
+                    self.edited();
+                }
+
+                Task::none()
+            }
+            Message::HintsToggled(hints) => {
+                self.hints = hints;
+
+                Task::none()
+            }
+            Message::FocusToggled(focus) => {
+                self.focus = focus;
+
+                Task::none()
+            }
+            Message::FoldToggled(line) => {
+                self.folds.toggle(line);
+
+                Task::none()
+            }
+            Message::BreakpointToggled(line) => {
+                if !self.breakpoints.remove(&line) {
+                    self.breakpoints.insert(line);
+                }
+
+                Task::none()
+            }
+            Message::NodeMoved(direction) => {
+                let actions = self.shared.with_document(|document| {
+                    document
+                        .move_node(self.content.cursor_position(), direction)
+                        .map(|edit| edit.actions(document))
+                });
+                if let Some(actions) = actions {
+                    for action in actions {
+                        self.content.perform(action);
+                    }
+                    self.is_dirty = true;
+                    self.edited();
+                }
+
+                Task::none()
+            }
+            Message::Hovered(hovered) => {
+                self.hovered = hovered;
 
                 Task::none()
             }
-            Message::WordWrapToggled(word_wrap) => {
-                self.word_wrap = word_wrap;
+            Message::RelativeNumbersToggled(relative_numbers) => {
+                self.relative_numbers = relative_numbers;
+
+                Task::none()
+            }
+            Message::SplitToggled(split) => {
+                self.split = split;
+
+                Task::none()
+            }
+            Message::FoldAll => {
+                self.folds.fold_all();
+
+                Task::none()
+            }
+            Message::UnfoldAll => {
+                self.folds.unfold_all();
 
                 Task::none()
             }
         }
     }
 
-    fn view(&self) -> Element<Message> {
+    ///Updates everything that depends on the text.
+    fn edited(&mut self) {
+        self.shared.set_text(self.content.text());
+        self.shared
+            .with_document(|document| self.folds.update(document));
+        self.changes = line_changes(include_str!("rusteditor.rs"), &self.content.text());
+    }
+
+    fn view(&self) -> Element<'_, Message> {
         let controls = row![
+            button("Fold all").on_press(Message::FoldAll),
+            button("Unfold all").on_press(Message::UnfoldAll),
+            button("Move up").on_press(Message::NodeMoved(Direction::Previous)),
+            button("Move down").on_press(Message::NodeMoved(Direction::Next)),
             horizontal_space(),
-            toggler(self.word_wrap)
-                .label("Word Wrap")
-                .on_toggle(Message::WordWrapToggled)
+            toggler(self.focus)
+                .label("Focus")
+                .on_toggle(Message::FocusToggled),
+            toggler(self.hints)
+                .label("Hints")
+                .on_toggle(Message::HintsToggled),
+            toggler(self.relative_numbers)
+                .label("Relative numbers")
+                .on_toggle(Message::RelativeNumbersToggled),
+            toggler(self.split)
+                .label("Split")
+                .on_toggle(Message::SplitToggled)
         ]
         .spacing(10)
         .align_y(Center);
@@ -87,6 +247,13 @@ impl Editor {
                 String::from("New file")
             }),
             horizontal_space(),
+            text(self.hovered.as_ref().map_or_else(String::new, |hover| {
+                format!(
+                    "{} ({})",
+                    hover.kind.unwrap_or_default(),
+                    hover.capture.as_deref().unwrap_or("no capture")
+                )
+            })),
             text({
                 let (line, column) = self.content.cursor_position();
 
@@ -95,25 +262,74 @@ impl Editor {
         ]
         .spacing(10);
 
-        column![
-            controls,
-            text_editor(&self.content)
+        let ts = self.shared.settings();
+        let ts = if self.focus {
+            let scope = self
+                .shared
+                .with_document(|document| document.focus_scope(self.content.cursor_position()));
+            ts.with_focus(Some(scope))
+        } else {
+            ts
+        };
+
+        let editor = if self.split {
+            row![self.editor(&ts), self.editor(&ts)]
+                .spacing(10)
                 .height(Fill)
-                .on_action(Message::ActionPerformed)
-                .wrapping(if self.word_wrap {
-                    text::Wrapping::Word
-                } else {
-                    text::Wrapping::None
-                })
-                .highlight_with::<iced_highlighter_tree_sitter::Highlighter>(
-                    self.ts.clone(),
-                    iced_highlighter_tree_sitter::to_format
-                ),
-            status,
-        ]
-        .spacing(10)
-        .padding(10)
-        .into()
+                .into()
+        } else {
+            self.editor(&ts)
+        };
+
+        column![controls, editor, status]
+            .spacing(10)
+            .padding(10)
+            .into()
+    }
+
+    fn editor<'a>(&'a self, ts: &TSSettings) -> Element<'a, Message> {
+        code_editor(&self.content, ts)
+            .height(Fill)
+            .on_action(Message::ActionPerformed)
+            .folds(&self.folds)
+            .on_fold(Message::FoldToggled)
+            .line_numbers(if self.relative_numbers {
+                LineNumbers::Hybrid
+            } else {
+                LineNumbers::Absolute
+            })
+            .marks(
+                self.breakpoints
+                    .iter()
+                    .map(|line| (*line, Mark::Breakpoint)),
+            )
+            .on_mark_click(Message::BreakpointToggled)
+            .on_hover(Message::Hovered)
+            .gutter_column(GutterColumn::Changes(self.changes.clone()))
+            .ghost_texts(if self.hints { self.hints() } else { Vec::new() })
+            .into()
+    }
+
+    ///Fake type hints: marks every `let` binding without a type annotation.
+    fn hints(&self) -> Vec<GhostText> {
+        self.shared.with_document(|document| {
+            let mut hints = Vec::new();
+            for line in 0..document.line_count() {
+                let text = document.line(line).unwrap_or_default();
+                let Some(start) = text.find("let ") else {
+                    continue;
+                };
+                let name_start =
+                    start + 4 + text[start + 4..].strip_prefix("mut ").map_or(0, |_| 4);
+                let name_end = text[name_start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .map_or(text.len(), |idx| name_start + idx);
+                if name_end > name_start && !text[name_end..].starts_with(':') {
+                    hints.push(GhostText::new((line, name_end), ": _"));
+                }
+            }
+            hints
+        })
     }
 
     fn theme(&self) -> Theme {
@@ -0,0 +1,366 @@
+//! Opens a generated Rust file of a million lines, and shows what highlighting it costs while it happens. Run it with `--release`,
+//! and pass a number of lines to try other sizes: `cargo run --release --example stress -- 200000`.
+//!
+//! The `code_editor` only lays out and highlights the lines down to the last one on screen, so the first scroll to the end of the
+//! file takes a moment.
+
+use iced::time::Instant;
+use iced::widget::{column, horizontal_space, row, slider, text, text_editor};
+use iced::{Center, Element, Fill, Font, Subscription, Task, Theme};
+use iced_highlighter_tree_sitter::{
+    TSSettings,
+    code_editor::code_editor,
+    progressive::{Priority, Progress},
+    shared::SharedHighlightState,
+    stats::Usage,
+};
+
+use std::{
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+///Lines around the cursor highlighted first, roughly a screen.
+const VISIBLE: usize = 50;
+
+pub fn main() -> iced::Result {
+    iced::application("Stress test - Iced", Stress::update, Stress::view)
+        .subscription(Stress::subscription)
+        .theme(Stress::theme)
+        .default_font(Font::MONOSPACE)
+        .run_with(Stress::new)
+}
+
+///What the stats hook saw so far.
+#[derive(Default)]
+struct Metrics {
+    parses: usize,
+    ///Parses that took the tree of an earlier text, e.g. after an undo.
+    reused: usize,
+    last_parse: Duration,
+    ///Lines the editor looked up in the shared state, and lines it had to highlight on its own, with the time that took.
+    shared_lines: usize,
+    own_lines: usize,
+    own_time: Duration,
+    ///Time of all steps until the whole text was highlighted.
+    highlighted: Option<Duration>,
+}
+
+impl Metrics {
+    fn record(&mut self, usage: &Usage<'_>) {
+        match usage {
+            Usage::Parse {
+                duration, reused, ..
+            } => {
+                self.parses += 1;
+                self.reused += usize::from(*reused);
+                self.last_parse = *duration;
+            }
+            Usage::HighlightLine {
+                duration, shared, ..
+            } => {
+                if *shared {
+                    self.shared_lines += 1;
+                } else {
+                    self.own_lines += 1;
+                    self.own_time += *duration;
+                }
+            }
+            Usage::HighlightDocument { duration, .. } => self.highlighted = Some(*duration),
+            Usage::Feature { .. } => {}
+        }
+    }
+}
+
+///What loading the text took.
+#[derive(Debug, Clone, Copy, Default)]
+struct Loading {
+    generated: Duration,
+    ///Time the editor took to take the text.
+    content: Duration,
+}
+
+///The result of the loading task. The content can't be cloned, so the first one to look takes it.
+#[derive(Clone)]
+struct Loaded(Arc<Mutex<Option<text_editor::Content>>>, Loading);
+
+impl fmt::Debug for Loaded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Loaded")
+            .field(&self.1)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Stress {
+    lines: usize,
+    ///`None` until the text is generated and parsed.
+    content: Option<text_editor::Content>,
+    loading: Loading,
+    shared: SharedHighlightState,
+    metrics: Arc<Mutex<Metrics>>,
+    ///Time each frame may spend highlighting, in milliseconds.
+    budget: f32,
+    progress: Progress,
+    ///Time the last step took, and how many steps took longer than the budget.
+    step: Duration,
+    over_budget: usize,
+    last_frame: Option<Instant>,
+    frame_interval: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Loaded(Loaded),
+    ActionPerformed(text_editor::Action),
+    BudgetChanged(f32),
+    Frame(Instant),
+}
+
+impl Stress {
+    fn new() -> (Self, Task<Message>) {
+        let lines = std::env::args()
+            .nth(1)
+            .and_then(|lines| lines.parse().ok())
+            .unwrap_or(1_000_000);
+
+        let ts = TSSettings::try_new(
+            tree_sitter_rust::LANGUAGE.into(),
+            "rust",
+            tree_sitter_rust::HIGHLIGHTS_QUERY,
+            tree_sitter_rust::INJECTIONS_QUERY,
+            "",
+        )
+        .unwrap_or_else(|error| panic!("{error}"));
+        let metrics = Arc::new(Mutex::new(Metrics::default()));
+        let recorder = metrics.clone();
+        let ts = ts.with_stats(move |usage| recorder.lock().unwrap().record(usage));
+        let shared = SharedHighlightState::new(&ts, "");
+
+        //parsing the whole text and handing it to the editor takes seconds, so do it off the GUI thread
+        let state = shared.clone();
+        let task = Task::perform(
+            async move {
+                let started = Instant::now();
+                let text = generate(lines);
+                let generated = started.elapsed();
+
+                let started = Instant::now();
+                let content = text_editor::Content::with_text(&text);
+                let loading = Loading {
+                    generated,
+                    content: started.elapsed(),
+                };
+
+                state.focus(0, 0..VISIBLE);
+                state.set_text_progressive(text);
+                Loaded(Arc::new(Mutex::new(Some(content))), loading)
+            },
+            Message::Loaded,
+        );
+
+        (
+            Self {
+                lines,
+                content: None,
+                loading: Loading::default(),
+                shared,
+                metrics,
+                budget: 4.0,
+                progress: Progress::default(),
+                step: Duration::ZERO,
+                over_budget: 0,
+                last_frame: None,
+                frame_interval: Duration::ZERO,
+            },
+            task,
+        )
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::Loaded(Loaded(content, loading)) => {
+                self.content = content.lock().unwrap().take();
+                self.loading = loading;
+                self.progress = self.shared.highlight_for(Duration::ZERO);
+
+                Task::none()
+            }
+            Message::ActionPerformed(action) => {
+                let Some(content) = &mut self.content else {
+                    return Task::none();
+                };
+                let is_edit = action.is_edit();
+                content.perform(action);
+                let (line, _) = content.cursor_position();
+                self.shared
+                    .focus(line, line.saturating_sub(VISIBLE / 2)..line + VISIBLE);
+                if is_edit {
+                    self.shared.set_text_progressive(content.text());
+                    //the cursor's line right away, the rest in the next frames
+                    self.progress = self.shared.highlight_for(Duration::ZERO);
+                }
+
+                Task::none()
+            }
+            Message::BudgetChanged(budget) => {
+                self.budget = budget;
+
+                Task::none()
+            }
+            Message::Frame(now) => {
+                if let Some(last) = self.last_frame {
+                    self.frame_interval = now - last;
+                }
+                self.last_frame = Some(now);
+
+                let budget = Duration::from_secs_f32(self.budget / 1000.0);
+                let started = Instant::now();
+                self.progress = self.shared.highlight_for(budget);
+                self.step = started.elapsed();
+                if self.step > budget {
+                    self.over_budget += 1;
+                }
+                //frames stop until the next edit
+                if self.progress.is_done() {
+                    self.last_frame = None;
+                }
+
+                Task::none()
+            }
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        if self.content.is_some() && !self.progress.is_done() {
+            iced::window::frames().map(Message::Frame)
+        } else {
+            Subscription::none()
+        }
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        let Some(content) = &self.content else {
+            return column![text(format!(
+                "Generating and parsing {} lines...",
+                self.lines
+            ))]
+            .padding(10)
+            .into();
+        };
+
+        let controls = row![
+            text("Budget per frame"),
+            slider(1.0..=16.0, self.budget, Message::BudgetChanged)
+                .step(0.5)
+                .width(200),
+            text(format!("{:.1} ms", self.budget)),
+            horizontal_space(),
+            text(format!(
+                "{} lines, {:.1} MB, generated in {:.0?}, editor took {:.0?}",
+                content.line_count(),
+                self.progress.total as f64 / 1e6,
+                self.loading.generated,
+                self.loading.content
+            )),
+        ]
+        .spacing(10)
+        .align_y(Center);
+
+        let started = Instant::now();
+        let ts = self.shared.settings();
+        let snapshot = started.elapsed();
+
+        let editor = code_editor(content, &ts)
+            .height(Fill)
+            .on_action(Message::ActionPerformed);
+
+        let pending = self.shared.pending();
+        let pending_of = |priority| {
+            pending
+                .iter()
+                .filter(|work| work.priority == priority)
+                .map(|work| work.range.len())
+                .sum::<usize>()
+        };
+        let metrics = self.metrics.lock().unwrap();
+        let highlighting = if self.progress.is_done() {
+            format!(
+                "Highlighted in {:.0?}",
+                metrics.highlighted.unwrap_or_default()
+            )
+        } else {
+            format!(
+                "Highlighting {:.1}%, pending bytes: {} cursor, {} visible, {} rest",
+                self.progress.fraction() * 100.0,
+                pending_of(Priority::Cursor),
+                pending_of(Priority::Visible),
+                pending_of(Priority::Rest)
+            )
+        };
+        let frames = if self.progress.is_done() {
+            String::from("Idle")
+        } else {
+            format!(
+                "Frame every {:.1?} ({:.0} fps)",
+                self.frame_interval,
+                1.0 / self.frame_interval.as_secs_f32().max(f32::EPSILON)
+            )
+        };
+
+        let status = column![
+            text(highlighting),
+            row![
+                text(frames),
+                text(format!(
+                    "last step {:.2?} of {:.1} ms, {} over budget",
+                    self.step, self.budget, self.over_budget
+                )),
+                text(format!("snapshot {snapshot:.1?}")),
+            ]
+            .spacing(20),
+            row![
+                text(format!(
+                    "Parsed {} times, last in {:.0?}, {} trees reused",
+                    metrics.parses, metrics.last_parse, metrics.reused
+                )),
+                text(format!(
+                    "Lines: {} from the shared state, {} on their own in {:.0?}",
+                    metrics.shared_lines, metrics.own_lines, metrics.own_time
+                )),
+            ]
+            .spacing(20),
+        ]
+        .spacing(5);
+
+        column![controls, editor, status]
+            .spacing(10)
+            .padding(10)
+            .into()
+    }
+
+    fn theme(&self) -> Theme {
+        Theme::Dark
+    }
+}
+
+///`lines` lines of Rust, with everything that spans lines: doc comments, block comments and multi-line strings.
+fn generate(lines: usize) -> String {
+    let mut text = String::with_capacity(lines * 24);
+    for item in 0..lines.div_ceil(10) {
+        write!(
+            text,
+            "/// Doc comment of item {item}.\n\
+             pub fn item_{item}(value: u64) -> u64 {{\n    \
+                 // multiply by a constant\n    \
+                 let factor = {item} * 3;\n    \
+                 let name = \"item\n        {item}\";\n    \
+                 /* block\n       comment */\n    \
+                 value.wrapping_mul(factor) + name.len() as u64\n\
+             }}\n"
+        )
+        .unwrap();
+    }
+    text
+}
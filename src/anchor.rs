@@ -0,0 +1,128 @@
+//! Positions that move along with the edits of the text, so whatever a host keeps of a document (bookmarks, the range of a
+//! diagnostic, the start of a selection) doesn't point at the wrong text once the text was edited.
+//!
+//! An [Anchor] is a byte offset with a [Bias]: the side it sticks to when text is inserted right at it, or when the text around
+//! it is replaced. [Overlay](crate::overlay::Overlay)s keep their ranges as anchors, and move them along via
+//! [Overlay::apply](crate::overlay::Overlay::apply). Edits come from [Document::subscribe](crate::document::Document::subscribe),
+//! or from the host that made them.
+
+use std::ops::Range;
+
+use crate::document::Change;
+
+///Which side of an edit an [Anchor] sticks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Bias {
+    ///Stays before text inserted at the anchor, like the end of a range that shouldn't grow when typing after it.
+    #[default]
+    Left,
+    ///Moves behind text inserted at the anchor, like the start of a range that shouldn't grow when typing before it, or a
+    ///cursor.
+    Right,
+}
+
+///A byte offset into a text that moves along with its edits, see the [module](self) documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Anchor {
+    pub offset: usize,
+    pub bias: Bias,
+}
+
+impl Anchor {
+    pub const fn new(offset: usize, bias: Bias) -> Self {
+        Self { offset, bias }
+    }
+
+    ///An anchor at `offset` that stays before text inserted there.
+    pub const fn left(offset: usize) -> Self {
+        Self::new(offset, Bias::Left)
+    }
+
+    ///An anchor at `offset` that moves behind text inserted there.
+    pub const fn right(offset: usize) -> Self {
+        Self::new(offset, Bias::Right)
+    }
+
+    ///Anchors for `range` that don't grow with text inserted at its edges, but take in what is inserted within, and what replaces
+    ///all of it.
+    pub const fn range(range: Range<usize>) -> Range<Self> {
+        Self::right(range.start)..Self::left(range.end)
+    }
+
+    ///Moves the anchor along the edit that replaced the byte `range` of the old text with `inserted` bytes. An anchor at the
+    ///edge of the replaced text stays next to the text it was next to, one within goes to the start or the end of the new text,
+    ///by its bias. So do anchors where text was inserted.
+    pub fn edit(&mut self, range: Range<usize>, inserted: usize) {
+        if self.offset > range.end {
+            self.offset = self.offset - range.len() + inserted;
+            return;
+        }
+        if self.offset < range.start {
+            return;
+        }
+        let after = if range.is_empty() || (self.offset > range.start && self.offset < range.end) {
+            self.bias == Bias::Right
+        } else {
+            self.offset == range.end
+        };
+        self.offset = if after {
+            range.start + inserted
+        } else {
+            range.start
+        };
+    }
+
+    ///Moves the anchor along `change`, see [Anchor::edit].
+    pub fn apply(&mut self, change: &Change) {
+        self.edit(change.bytes.clone(), change.inserted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Anchor;
+
+    ///Offsets of a left and a right anchor at `offset` after the edit.
+    fn moved(offset: usize, range: std::ops::Range<usize>, inserted: usize) -> (usize, usize) {
+        let (mut left, mut right) = (Anchor::left(offset), Anchor::right(offset));
+        left.edit(range.clone(), inserted);
+        right.edit(range, inserted);
+        (left.offset, right.offset)
+    }
+
+    #[test]
+    fn anchors_move_along_insertions_by_their_bias() {
+        //before, at and after the insertion of 3 bytes at 5
+        assert_eq!(moved(4, 5..5, 3), (4, 4));
+        assert_eq!(moved(5, 5..5, 3), (5, 8));
+        assert_eq!(moved(6, 5..5, 3), (9, 9));
+    }
+
+    #[test]
+    fn anchors_stay_next_to_the_text_around_replacements() {
+        //replacing 5..8 with 1 byte
+        assert_eq!(moved(5, 5..8, 1), (5, 5));
+        assert_eq!(moved(8, 5..8, 1), (6, 6));
+        assert_eq!(moved(9, 5..8, 1), (7, 7));
+        //within the replaced text it goes by the bias
+        assert_eq!(moved(6, 5..8, 1), (5, 6));
+        //and within deleted text to the start
+        assert_eq!(moved(6, 5..8, 0), (5, 5));
+    }
+
+    #[test]
+    fn ranges_take_in_what_replaces_them_but_not_what_is_typed_around() {
+        let range = |edits: &[(std::ops::Range<usize>, usize)]| {
+            let mut range = Anchor::range(2..4);
+            for (edit, inserted) in edits {
+                range.start.edit(edit.clone(), *inserted);
+                range.end.edit(edit.clone(), *inserted);
+            }
+            range.start.offset..range.end.offset
+        };
+        assert_eq!(range(&[(2..2, 1), (5..5, 1)]), 3..5);
+        assert_eq!(range(&[(3..3, 2)]), 2..6);
+        assert_eq!(range(&[(2..4, 5)]), 2..7);
+        assert_eq!(range(&[(1..5, 0)]), 1..1);
+    }
+}
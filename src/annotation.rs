@@ -0,0 +1,138 @@
+//! Per-line metadata supplied by the host, for instance _git blame_ information, rendered as an annotation gutter.
+//!
+//! The host fills [Annotations] once, and keeps them in sync with the text either by reporting edits ([Annotations::edit]) or by
+//! handing over the text before and after a change ([Annotations::sync]).
+
+use crate::diff::{Edit, edits};
+
+///Metadata of a single line.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Annotation {
+    ///Arbitrary host-defined text, e.g. a commit summary.
+    pub text: String,
+    pub author: Option<String>,
+    ///Already formatted, the crate doesn't interpret it.
+    pub date: Option<String>,
+}
+
+impl Annotation {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn with_date(mut self, date: impl Into<String>) -> Self {
+        self.date = Some(date.into());
+        self
+    }
+
+    ///What the gutter shows: author, date and text, separated by two spaces.
+    pub fn label(&self) -> String {
+        [
+            self.author.as_deref(),
+            self.date.as_deref(),
+            Some(&self.text),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("  ")
+    }
+}
+
+///[Annotation]s of a document, by zero based line.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Annotations {
+    lines: Vec<Option<Annotation>>,
+}
+
+impl Annotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, line: usize, annotation: Annotation) {
+        if self.lines.len() <= line {
+            self.lines.resize(line + 1, None);
+        }
+        self.lines[line] = Some(annotation);
+    }
+
+    pub fn remove(&mut self, line: usize) -> Option<Annotation> {
+        self.lines.get_mut(line)?.take()
+    }
+
+    pub fn get(&self, line: usize) -> Option<&Annotation> {
+        self.lines.get(line)?.as_ref()
+    }
+
+    ///Whether `line` is annotated, and its annotation differs from the previous line's. Blame views usually only label those lines,
+    ///since consecutive lines often belong to the same commit.
+    pub fn is_run_start(&self, line: usize) -> bool {
+        match self.get(line) {
+            Some(annotation) => line == 0 || self.get(line - 1) != Some(annotation),
+            None => false,
+        }
+    }
+
+    ///Annotated lines, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Annotation)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(line, annotation)| Some((line, annotation.as_ref()?)))
+    }
+
+    ///Width of the widest [label](Annotation::label) in characters, for sizing the gutter.
+    pub fn max_label_width(&self) -> usize {
+        self.iter()
+            .map(|(_, annotation)| annotation.label().chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(Option::is_none)
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    ///Call after the `replaced` lines were replaced by `inserted` new lines. Annotations below move along, the replaced lines lose theirs
+    ///(they no longer match what the host annotated).
+    ///
+    ///Typing within line `l` is `edit(l..l + 1, 1)`, inserting a line break there is `edit(l..l + 1, 2)`.
+    pub fn edit(&mut self, replaced: std::ops::Range<usize>, inserted: usize) {
+        let start = replaced.start.min(self.lines.len());
+        let end = replaced.end.clamp(start, self.lines.len());
+        if start == self.lines.len() {
+            return;
+        }
+        self.lines
+            .splice(start..end, std::iter::repeat_n(None, inserted));
+    }
+
+    ///Moves the annotations from the lines of `old` to the matching lines of `new`, changed lines lose theirs.
+    ///Use this if the individual edits are not known, e.g. after a reload.
+    pub fn sync(&mut self, old: &str, new: &str) {
+        let old_lines: Vec<&str> = old.split('\n').collect();
+        let new_lines: Vec<&str> = new.split('\n').collect();
+
+        let mut lines = vec![None; new_lines.len()];
+        for edit in edits(&old_lines, &new_lines) {
+            if let Edit::Equal(o, n) = edit {
+                lines[n] = self.lines.get_mut(o).and_then(Option::take);
+            }
+        }
+        self.lines = lines;
+    }
+}
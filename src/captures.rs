@@ -0,0 +1,45 @@
+//! Names of the captures [Highlight]s refer to, so formatting functions can match on `"comment"` instead of an index into
+//! [TSSettings::highlight_names](crate::TSSettings::highlight_names) that silently changes with the list.
+
+use std::sync::Arc;
+
+use crate::Highlight;
+
+///Resolves the capture of a [Highlight] to its name. Every highlight carries the map of the settings it was highlighted with, see
+///[Highlight::name]. Clones share the names.
+#[derive(Clone, Default)]
+pub struct CaptureMap {
+    names: Arc<[Box<str>]>,
+}
+
+impl CaptureMap {
+    pub(crate) fn new(names: &[impl AsRef<str>]) -> Self {
+        Self {
+            names: names.iter().map(|name| name.as_ref().into()).collect(),
+        }
+    }
+
+    ///Name of the capture `highlight` refers to, like `"comment"` or `"function.builtin"`. `None` for text that isn't captured.
+    pub fn name(&self, highlight: &Highlight) -> Option<&str> {
+        self.names.get(highlight.capture?.0).map(AsRef::as_ref)
+    }
+
+    ///All names, indexed like [TSSettings::highlight_names](crate::TSSettings::highlight_names).
+    pub fn names(&self) -> &[Box<str>] {
+        &self.names
+    }
+}
+
+impl PartialEq for CaptureMap {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.names, &other.names) || self.names == other.names
+    }
+}
+
+impl Eq for CaptureMap {}
+
+impl std::fmt::Debug for CaptureMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CaptureMap({} names)", self.names.len())
+    }
+}
@@ -0,0 +1,2550 @@
+//! A wrapper around iced's `text_editor` that draws things the plain widget can't express, like inline virtual text.
+//!
+//! The wrapped editor never wraps lines and is laid out from its first line down to the last one on screen, while [CodeEditor] does
+//! the scrolling. That way every line is at a known offset, and decorations can be drawn on top of the text. Long lines are cut at
+//! the widget's edge. The lines below the screen aren't laid out or highlighted until they are scrolled to.
+//!
+//! Folded lines are hidden by drawing the editor once per run of visible lines on screen, each moved up to close the gap.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use iced::{
+    Background, Border, Element, Font, Length, Padding, Pixels, Point, Rectangle, Size, Vector,
+    advanced::{
+        Clipboard, Layout, Renderer as _, Shell, Widget,
+        layout::{self, Limits},
+        mouse, renderer,
+        text::{self, Renderer as _, highlighter::Highlighter as _},
+        widget::{Operation, Tree, tree},
+    },
+    alignment, event, touch,
+    widget::text_editor::{self, Action, Content, Edit, TextEditor},
+};
+
+use crate::{
+    Highlighter, TSSettings,
+    annotation::Annotations,
+    color::ColorLiteral,
+    diff::LineChange,
+    document::{Document, Position, SyntaxContext},
+    folding::{FoldRange, Folds},
+};
+
+type Renderer = iced::Renderer;
+
+const SCROLLBAR_WIDTH: f32 = 10.0;
+///Longest annotation label in characters, longer ones are cut.
+const MAX_ANNOTATION_WIDTH: usize = 30;
+///Smallest height of the scrollbar's thumb, so it can still be grabbed in long documents.
+const MIN_THUMB_HEIGHT: f32 = 20.0;
+///Lines the wrapped editor is laid out for at a time, see `layout`.
+const LAYOUT_LINES: usize = 256;
+type Paragraph = <Renderer as text::Renderer>::Paragraph;
+
+///Text that is shown inline but is not part of the content, e.g. type hints, parameter names or completion suggestions.
+///
+///Text after `position` is moved to the right to make room. Only the first line of `text` is shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GhostText {
+    pub position: Position,
+    pub text: String,
+}
+
+impl GhostText {
+    pub fn new(position: impl Into<Position>, text: impl Into<String>) -> Self {
+        Self {
+            position: position.into(),
+            text: text.into(),
+        }
+    }
+}
+
+///How the gutter numbers lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LineNumbers {
+    ///No line numbers.
+    Hidden,
+    #[default]
+    Absolute,
+    ///Distance to the cursor's line, in shown lines.
+    Relative,
+    ///Like [LineNumbers::Relative], but the cursor's line shows its absolute number.
+    Hybrid,
+}
+
+///A mark in the gutter's mark column, see [CodeEditor::marks].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mark {
+    Breakpoint,
+    DisabledBreakpoint,
+    Bookmark,
+    ///A dot in the given color.
+    Color(iced::Color),
+}
+
+///A column of the gutter, see [CodeEditor::gutter].
+///
+///The built-in columns only take up space if there is something to show: [marks](CodeEditor::marks), [line
+///numbers](CodeEditor::line_numbers) that aren't hidden, or [folds](CodeEditor::folds).
+pub enum GutterColumn<'a, Message> {
+    Marks,
+    LineNumbers,
+    ///Fold chevrons.
+    Folds,
+    ///A bar next to changed lines, e.g. compared to the last commit. See [line_changes](crate::diff::line_changes).
+    Changes(BTreeMap<usize, LineChange>),
+    ///Labels of the annotations, e.g. git blame. Only the first line of each run (see [Annotations::is_run_start]) is labelled.
+    Annotations(&'a Annotations),
+    Custom(Box<dyn GutterComponent<Message> + 'a>),
+}
+
+impl<'a, Message> GutterColumn<'a, Message> {
+    pub fn custom(component: impl GutterComponent<Message> + 'a) -> Self {
+        Self::Custom(Box::new(component))
+    }
+}
+
+///A gutter column provided by the host, see [GutterColumn::Custom].
+pub trait GutterComponent<Message> {
+    ///Width of the column.
+    fn width(&self, line_height: f32) -> f32;
+
+    ///Draws the slot of `line`. `hovered` is set if the mouse is over it.
+    fn draw(
+        &self,
+        renderer: &mut iced::Renderer,
+        theme: &iced::Theme,
+        line: usize,
+        slot: Rectangle,
+        hovered: bool,
+    );
+
+    ///Message to publish when the slot of `line` is clicked. `None` ignores the click. Also called to decide whether hovering the slot
+    ///shows a pointer, so keep it free of side effects.
+    fn on_click(&self, _line: usize) -> Option<Message> {
+        None
+    }
+}
+
+///A color literal that was clicked, see [CodeEditor::on_color_click].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorSwatch {
+    pub line: usize,
+    ///The literal, its range is relative to the start of `line`.
+    pub literal: ColorLiteral,
+}
+
+impl ColorSwatch {
+    ///Where the literal is in the content. Replace the text there with [ColorLiteral::replacement] once a new color was picked.
+    pub fn range(&self) -> Range<Position> {
+        Position::new(self.line, self.literal.range.start)
+            ..Position::new(self.line, self.literal.range.end)
+    }
+}
+
+///The token under the mouse, see [CodeEditor::on_hover].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hover {
+    pub range: Range<Position>,
+    ///Name of the token's capture (see [TSSettings::highlight_names]), e.g. `"function"`.
+    pub capture: Option<String>,
+    ///Kind of the token's syntax node, e.g. `identifier`. Only known with a [CodeEditor::document], or settings of a
+    ///[SharedHighlightState](crate::shared::SharedHighlightState).
+    pub kind: Option<&'static str>,
+    ///Where the token is shown on the hovered line, in absolute coordinates. Anchor tooltips to it.
+    pub bounds: Rectangle,
+}
+
+///Where the editor was right-clicked, see [CodeEditor::on_context_menu].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextMenu {
+    ///Position in the content under the mouse.
+    pub position: Position,
+    ///Absolute point that was clicked, to open the menu at.
+    pub point: Point,
+    ///Syntax around `position`. Without a [CodeEditor::document] (or a
+    ///[SharedHighlightState](crate::shared::SharedHighlightState)), only `in_string` and `in_comment` are known, taken from the
+    ///highlights.
+    pub context: SyntaxContext,
+}
+
+///Creates a [CodeEditor] for `content`, highlighted with `settings`.
+pub fn code_editor<'a, Message>(
+    content: &'a Content,
+    settings: &TSSettings,
+) -> CodeEditor<'a, Message> {
+    CodeEditor::new(content, settings)
+}
+
+///A read-only [CodeEditor]: the cursor moves and selects as usual, so the text can be copied, but edits never reach the
+///[CodeEditor::on_action] handler. Like with [code_editor], the handler applies the other actions (moving the cursor,
+///selecting) to the content, so without one the cursor doesn't move.
+pub fn code_view<'a, Message>(
+    content: &'a Content,
+    settings: &TSSettings,
+) -> CodeEditor<'a, Message> {
+    let mut view = CodeEditor::new(content, settings);
+    view.view_only = true;
+    view
+}
+
+pub struct CodeEditor<'a, Message> {
+    //publishes the actions themselves, so that `on_event` can drop those that edit read-only regions
+    editor: TextEditor<'a, Highlighter, Action, iced::Theme, Renderer>,
+    content: &'a Content,
+    settings: TSSettings,
+    font: Option<Font>,
+    text_size: Option<Pixels>,
+    line_height: text::LineHeight,
+    padding: Padding,
+    width: Length,
+    height: Length,
+    ghost_text: Vec<GhostText>,
+    inline_diagnostics: bool,
+    folds: Option<&'a Folds>,
+    line_numbers: LineNumbers,
+    current_line_emphasis: bool,
+    marks: Option<BTreeMap<usize, Mark>>,
+    gutter: Vec<GutterColumn<'a, Message>>,
+    current_line_background: Option<iced::Color>,
+    highlight_current_line: bool,
+    scrollbar: bool,
+    read_only: Vec<Range<Position>>,
+    highlight_read_only: bool,
+    color_swatches: bool,
+    document: Option<&'a Document>,
+    ///Drops all edits, see [code_view].
+    view_only: bool,
+    on_action: Option<Box<dyn Fn(Action) -> Message + 'a>>,
+    on_fold: Option<Box<dyn Fn(usize) -> Message + 'a>>,
+    on_line_number_click: Option<Box<dyn Fn(usize) -> Message + 'a>>,
+    on_mark_click: Option<Box<dyn Fn(usize) -> Message + 'a>>,
+    on_color_click: Option<Box<dyn Fn(ColorSwatch) -> Message + 'a>>,
+    on_hover: Option<Box<dyn Fn(Option<Hover>) -> Message + 'a>>,
+    on_context_menu: Option<Box<dyn Fn(ContextMenu) -> Message + 'a>>,
+}
+
+impl<'a, Message> CodeEditor<'a, Message> {
+    pub fn new(content: &'a Content, settings: &TSSettings) -> Self {
+        let padding = Padding::new(5.0);
+        Self {
+            editor: TextEditor::new(content)
+                .padding(padding)
+                .height(Length::Fill)
+                .wrapping(text::Wrapping::None)
+                .font(Font::MONOSPACE)
+                .highlight_with::<Highlighter>(settings.clone(), crate::to_format)
+                //background and border are drawn for the whole widget instead, see `draw`
+                .style(|theme, status| text_editor::Style {
+                    background: Background::Color(iced::Color::TRANSPARENT),
+                    border: Border::default(),
+                    ..text_editor::default(theme, status)
+                }),
+            content,
+            settings: settings.clone(),
+            font: Some(Font::MONOSPACE),
+            text_size: None,
+            line_height: text::LineHeight::default(),
+            padding,
+            width: Length::Fill,
+            height: Length::Fill,
+            ghost_text: Vec::new(),
+            inline_diagnostics: true,
+            folds: None,
+            line_numbers: LineNumbers::default(),
+            current_line_emphasis: true,
+            marks: None,
+            gutter: vec![
+                GutterColumn::Marks,
+                GutterColumn::LineNumbers,
+                GutterColumn::Folds,
+            ],
+            current_line_background: None,
+            highlight_current_line: true,
+            scrollbar: true,
+            read_only: Vec::new(),
+            highlight_read_only: true,
+            color_swatches: false,
+            document: None,
+            view_only: false,
+            on_action: None,
+            on_fold: None,
+            on_line_number_click: None,
+            on_mark_click: None,
+            on_color_click: None,
+            on_hover: None,
+            on_context_menu: None,
+        }
+    }
+
+    ///Makes the editor editable, see `text_editor::on_action`.
+    pub fn on_action(mut self, on_action: impl Fn(Action) -> Message + 'a) -> Self {
+        self.on_action = Some(Box::new(on_action));
+        self.editor = self.editor.on_action(|action| action);
+        self
+    }
+
+    pub fn font(mut self, font: impl Into<Font>) -> Self {
+        let font = font.into();
+        self.font = Some(font);
+        self.editor = self.editor.font(font);
+        self
+    }
+
+    pub fn size(mut self, size: impl Into<Pixels>) -> Self {
+        let size = size.into();
+        self.text_size = Some(size);
+        self.editor = self.editor.size(size);
+        self
+    }
+
+    pub fn line_height(mut self, line_height: impl Into<text::LineHeight>) -> Self {
+        self.line_height = line_height.into();
+        self.editor = self.editor.line_height(self.line_height);
+        self
+    }
+
+    pub fn padding(mut self, padding: impl Into<Padding>) -> Self {
+        self.padding = padding.into();
+        self.editor = self.editor.padding(self.padding);
+        self
+    }
+
+    pub fn width(mut self, width: impl Into<Length>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    ///Height of the widget. `Shrink` grows with the content.
+    pub fn height(mut self, height: impl Into<Length>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    ///Adds `ghost_text`.
+    pub fn ghost_text(mut self, ghost_text: GhostText) -> Self {
+        self.ghost_text.push(ghost_text);
+        self
+    }
+
+    ///Adds all of `ghost_texts`.
+    pub fn ghost_texts(mut self, ghost_texts: impl IntoIterator<Item = GhostText>) -> Self {
+        self.ghost_text.extend(ghost_texts);
+        self
+    }
+
+    ///Whether the message of the most severe diagnostic of a line (see [Overlay::add_diagnostic](crate::overlay::Overlay::add_diagnostic))
+    ///is shown at the end of the line. Enabled by default.
+    pub fn inline_diagnostics(mut self, inline_diagnostics: bool) -> Self {
+        self.inline_diagnostics = inline_diagnostics;
+        self
+    }
+
+    ///Hides the lines of the folded regions in `folds`, and adds a gutter with a chevron for each region.
+    ///
+    ///A folded region that contains the cursor is shown unfolded, so the cursor never ends up on a hidden line.
+    pub fn folds(mut self, folds: &'a Folds) -> Self {
+        self.folds = Some(folds);
+        self
+    }
+
+    ///Called with the header line of a region when its chevron is clicked. Usually the host calls [Folds::toggle] with it.
+    pub fn on_fold(mut self, on_fold: impl Fn(usize) -> Message + 'a) -> Self {
+        self.on_fold = Some(Box::new(on_fold));
+        self
+    }
+
+    ///How lines are numbered in the gutter. Absolute by default.
+    pub fn line_numbers(mut self, line_numbers: LineNumbers) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    ///Whether the number of the cursor's line stands out from the others. Enabled by default.
+    pub fn current_line_emphasis(mut self, current_line_emphasis: bool) -> Self {
+        self.current_line_emphasis = current_line_emphasis;
+        self
+    }
+
+    ///Whether the cursor's line gets a background tint. Enabled by default.
+    pub fn highlight_current_line(mut self, highlight_current_line: bool) -> Self {
+        self.highlight_current_line = highlight_current_line;
+        self
+    }
+
+    ///Background of the cursor's line. Defaults to a tint of the text color. Use an opaque color, ghost text relies on it to cover the
+    ///text it moves.
+    pub fn current_line_background(mut self, color: impl Into<iced::Color>) -> Self {
+        self.current_line_background = Some(color.into());
+        self
+    }
+
+    ///Whether a scrollbar is shown. Its track marks the lines with diagnostics and search matches (see [TSSettings::with_overlay]) and
+    ///[bookmarks](Mark::Bookmark). Enabled by default.
+    pub fn scrollbar(mut self, scrollbar: bool) -> Self {
+        self.scrollbar = scrollbar;
+        self
+    }
+
+    ///Marks `regions` as read-only, e.g. generated code. Edits that would change text inside of them are dropped instead of being
+    ///passed to [CodeEditor::on_action], typing right before or after a region is fine.
+    ///
+    ///With a selection, `text_editor` doesn't tell in which direction it extends from the cursor, so the editor remembers where
+    ///the cursor was before there was a selection. If the selection was made some other way (like selecting all), an edit is
+    ///dropped if the selection could touch a region either way.
+    pub fn read_only(mut self, regions: impl IntoIterator<Item = Range<Position>>) -> Self {
+        self.read_only.extend(regions);
+        self
+    }
+
+    ///Whether lines with [read-only](CodeEditor::read_only) regions get a subtle background. Enabled by default.
+    pub fn highlight_read_only(mut self, highlight_read_only: bool) -> Self {
+        self.highlight_read_only = highlight_read_only;
+        self
+    }
+
+    ///Adds a mark column to the gutter, showing `marks` by zero based line. Typically used for breakpoints.
+    pub fn marks(mut self, marks: impl IntoIterator<Item = (usize, Mark)>) -> Self {
+        self.marks.get_or_insert_default().extend(marks);
+        self
+    }
+
+    ///Replaces the gutter's columns, from left to right. By default they are [marks](GutterColumn::Marks),
+    ///[line numbers](GutterColumn::LineNumbers) and [folds](GutterColumn::Folds).
+    pub fn gutter(mut self, columns: impl IntoIterator<Item = GutterColumn<'a, Message>>) -> Self {
+        self.gutter = columns.into_iter().collect();
+        self
+    }
+
+    ///Adds `column` to the right of the gutter.
+    pub fn gutter_column(mut self, column: GutterColumn<'a, Message>) -> Self {
+        self.gutter.push(column);
+        self
+    }
+
+    ///Called with the line whose number is clicked.
+    pub fn on_line_number_click(mut self, on_click: impl Fn(usize) -> Message + 'a) -> Self {
+        self.on_line_number_click = Some(Box::new(on_click));
+        self
+    }
+
+    ///Called with the line whose slot in the mark column is clicked, whether it has a mark or not. Adds the mark column if there are no
+    ///[marks](CodeEditor::marks) yet, so hosts can toggle breakpoints.
+    pub fn on_mark_click(mut self, on_click: impl Fn(usize) -> Message + 'a) -> Self {
+        self.marks.get_or_insert_default();
+        self.on_mark_click = Some(Box::new(on_click));
+        self
+    }
+
+    ///Whether a small swatch is shown in front of every color literal (see [color_literals](crate::color::color_literals)).
+    ///Disabled by default.
+    pub fn color_swatches(mut self, color_swatches: bool) -> Self {
+        self.color_swatches = color_swatches;
+        self
+    }
+
+    ///Called when a color swatch is clicked, e.g. to open a color picker. Enables [CodeEditor::color_swatches].
+    pub fn on_color_click(mut self, on_click: impl Fn(ColorSwatch) -> Message + 'a) -> Self {
+        self.color_swatches = true;
+        self.on_color_click = Some(Box::new(on_click));
+        self
+    }
+
+    ///The parsed document of the content, used to look up the syntax node of [hovered](CodeEditor::on_hover) tokens.
+    pub fn document(mut self, document: &'a Document) -> Self {
+        self.document = Some(document);
+        self
+    }
+
+    ///Called when the mouse moves onto another token, and with `None` once it leaves the tokens (e.g. onto whitespace).
+    pub fn on_hover(mut self, on_hover: impl Fn(Option<Hover>) -> Message + 'a) -> Self {
+        self.on_hover = Some(Box::new(on_hover));
+        self
+    }
+
+    ///Called when the text is right-clicked. The cursor is not moved, perform an [Action::Click] at the position first if the menu's
+    ///entries act on the cursor.
+    pub fn on_context_menu(
+        mut self,
+        on_context_menu: impl Fn(ContextMenu) -> Message + 'a,
+    ) -> Self {
+        self.on_context_menu = Some(Box::new(on_context_menu));
+        self
+    }
+
+    fn line_height_px(&self, renderer: &Renderer) -> f32 {
+        self.line_height
+            .to_absolute(self.text_size.unwrap_or_else(|| renderer.default_size()))
+            .0
+    }
+
+    fn gutter_layout(&self, renderer: &Renderer) -> Gutter {
+        let line_height = self.line_height_px(renderer);
+        let char_width =
+            |count: usize| self.measure_spans(renderer, &[text::Span::new("0".repeat(count))]);
+        let widths = self
+            .gutter
+            .iter()
+            .map(|column| match column {
+                GutterColumn::Marks if self.marks.is_some() => line_height,
+                GutterColumn::LineNumbers if self.line_numbers != LineNumbers::Hidden => {
+                    let digits = self.content.line_count().max(10).ilog10() as usize + 1;
+                    char_width(digits) + line_height * 0.75
+                }
+                GutterColumn::Folds if self.folds.is_some() => line_height,
+                GutterColumn::Changes(_) => line_height * 0.5,
+                GutterColumn::Annotations(annotations) if !annotations.is_empty() => {
+                    char_width(annotations.max_label_width().min(MAX_ANNOTATION_WIDTH))
+                        + line_height * 0.75
+                }
+                GutterColumn::Custom(component) => component.width(line_height),
+                _ => 0.0,
+            })
+            .collect();
+        Gutter { widths }
+    }
+
+    fn scrollbar_width(&self) -> f32 {
+        if self.scrollbar { SCROLLBAR_WIDTH } else { 0.0 }
+    }
+
+    ///Track of the scrollbar within `bounds`, if it is shown.
+    fn scrollbar_track(&self, bounds: Rectangle) -> Option<Rectangle> {
+        self.scrollbar.then_some(Rectangle {
+            x: bounds.x + bounds.width - SCROLLBAR_WIDTH,
+            width: SCROLLBAR_WIDTH,
+            ..bounds
+        })
+    }
+
+    ///The scrollbar's thumb on `track`. `None` if there is nothing to scroll.
+    fn scrollbar_thumb(&self, state: &State, track: Rectangle) -> Option<Rectangle> {
+        if state.max_offset() <= 0.0 {
+            return None;
+        }
+        let height = (track.height * state.viewport_height / state.content_height)
+            .clamp(MIN_THUMB_HEIGHT.min(track.height), track.height);
+        Some(Rectangle {
+            y: track.y + state.offset / state.max_offset() * (track.height - height),
+            height,
+            ..track
+        })
+    }
+
+    ///Scrolls so that the top of the thumb is at the absolute `y`.
+    fn scroll_thumb_to(&self, state: &mut State, track: Rectangle, y: f32) {
+        let Some(thumb) = self.scrollbar_thumb(state, track) else {
+            return;
+        };
+        let free = track.height - thumb.height;
+        if free > 0.0 {
+            state.offset = ((y - track.y) / free).clamp(0.0, 1.0) * state.max_offset();
+        }
+    }
+
+    ///Draws the scrollbar on `track`, with a marker for each line that has a diagnostic, search match or bookmark.
+    fn draw_scrollbar(
+        &self,
+        renderer: &mut Renderer,
+        theme: &iced::Theme,
+        state: &State,
+        track: Rectangle,
+        style: &text_editor::Style,
+    ) {
+        let palette = theme.extended_palette();
+        let background = background_color(theme, style);
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: track,
+                ..Default::default()
+            },
+            crate::color::mix(style.value, background, 0.96),
+        );
+        if let Some(thumb) = self.scrollbar_thumb(state, track) {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: thumb.shrink(1.0),
+                    border: Border {
+                        radius: 3.0.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                crate::color::mix(style.value, background, 0.8),
+            );
+        }
+
+        //bookmarks on the left, search matches in the middle, diagnostics on the right. Per pixel and lane only the most
+        //important marker is drawn.
+        let rows = self
+            .content
+            .line_count()
+            .saturating_sub(state.line_map.hidden_count())
+            .max(1);
+        let lane = track.width / 3.0;
+        let mut markers: BTreeMap<(i32, u8), (u8, iced::Color)> = BTreeMap::new();
+        let mut add = |line: usize, lane: u8, importance: u8, color: iced::Color| {
+            let y = (state.line_map.row(line) as f32 / rows as f32 * track.height) as i32;
+            let marker = markers.entry((y, lane)).or_insert((importance, color));
+            if importance > marker.0 {
+                *marker = (importance, color);
+            }
+        };
+
+        for (line, mark) in self.marks.iter().flatten() {
+            if *mark == Mark::Bookmark {
+                add(*line, 0, 0, palette.primary.base.color);
+            }
+        }
+        if let Some(overlay) = self.settings.overlay.as_deref() {
+            for (line, ranges) in overlay.lines() {
+                for (_, kind) in ranges {
+                    match kind {
+                        crate::overlay::OverlayKind::SearchMatch => {
+                            add(line, 1, 0, palette.primary.strong.color)
+                        }
+                        crate::overlay::OverlayKind::ReplacePreview => {
+                            add(line, 1, 1, palette.success.strong.color)
+                        }
+                        crate::overlay::OverlayKind::CurrentMatch => {
+                            add(line, 1, 2, palette.danger.base.color)
+                        }
+                        crate::overlay::OverlayKind::Diagnostic(severity) => add(
+                            line,
+                            2,
+                            //more severe is more important
+                            3 - *severity as u8,
+                            crate::severity_color(*severity, palette),
+                        ),
+                    }
+                }
+            }
+        }
+
+        for ((y, lane_idx), (_, color)) in markers {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle::new(
+                        Point::new(track.x + lane_idx as f32 * lane, track.y + y as f32),
+                        Size::new(lane, 2.0),
+                    ),
+                    ..Default::default()
+                },
+                color,
+            );
+        }
+    }
+
+    fn line_map(&self) -> LineMap {
+        LineMap {
+            hidden: self
+                .folds
+                .map(|folds| folds.hidden_lines(Some(self.content.cursor_position().0)))
+                .unwrap_or_default(),
+        }
+    }
+
+    ///Rows that are at least partially visible in a viewport of `height` at the current scroll offset.
+    fn visible_rows(&self, renderer: &Renderer, state: &State, height: f32) -> Range<usize> {
+        let line_height = self.line_height_px(renderer);
+        let rows = self
+            .content
+            .line_count()
+            .saturating_sub(state.line_map.hidden_count());
+        let top = (state.offset - self.padding.top).max(0.0);
+        let bottom = (state.offset + height - self.padding.top).max(0.0);
+        let end = ((bottom / line_height).ceil() as usize).min(rows);
+        ((top / line_height).floor() as usize).min(end)..end
+    }
+
+    ///Asks for a layout if the wrapped editor isn't laid out for the lines on screen after scrolling, see `layout`.
+    fn lay_out_scrolled(&self, renderer: &Renderer, state: &State, shell: &mut Shell<'_, Message>) {
+        let visible = self.visible_rows(renderer, state, state.viewport_height);
+        if state.line_map.line(visible.end.saturating_sub(1)) >= state.editor_lines {
+            shell.invalidate_layout();
+        }
+    }
+
+    ///Row under the widget relative `y`, not clamped to the content.
+    fn row_at(&self, renderer: &Renderer, state: &State, y: f32) -> usize {
+        ((y - self.padding.top + state.offset) / self.line_height_px(renderer)).max(0.0) as usize
+    }
+
+    ///Moves `position` (absolute) from where a line is shown to where the wrapped editor has it.
+    fn to_editor(
+        &self,
+        renderer: &Renderer,
+        state: &State,
+        bounds: Rectangle,
+        position: Point,
+    ) -> Point {
+        let row = self.row_at(renderer, state, position.y - bounds.y);
+        let line = state.line_map.line(row);
+        position
+            + Vector::new(
+                0.0,
+                (line - row) as f32 * self.line_height_px(renderer) + state.offset,
+            )
+    }
+
+    fn text<'b>(&self, renderer: &Renderer, content: &'b str) -> text::Text<&'b str> {
+        text::Text {
+            content,
+            bounds: Size::INFINITY,
+            size: self.text_size.unwrap_or_else(|| renderer.default_size()),
+            line_height: self.line_height,
+            font: self.font.unwrap_or_else(|| renderer.default_font()),
+            horizontal_alignment: alignment::Horizontal::Left,
+            vertical_alignment: alignment::Vertical::Top,
+            shaping: text::Shaping::Advanced,
+            wrapping: text::Wrapping::None,
+        }
+    }
+
+    ///Width of highlighted `spans`. Highlights may switch fonts, so this can differ from measuring the plain text.
+    fn measure_spans(&self, renderer: &Renderer, spans: &[text::Span<'_, (), Font>]) -> f32 {
+        //generic, as the paragraph of a renderer without a backend is `()`
+        fn min_width<P: text::Paragraph<Font = Font>>(
+            text: text::Text<&[text::Span<'_, (), Font>], Font>,
+        ) -> f32 {
+            P::with_spans(text).min_width()
+        }
+        let text = self.text(renderer, "");
+        min_width::<Paragraph>(text::Text {
+            content: spans,
+            bounds: text.bounds,
+            size: text.size,
+            line_height: text.line_height,
+            font: text.font,
+            horizontal_alignment: text.horizontal_alignment,
+            vertical_alignment: text.vertical_alignment,
+            shaping: text.shaping,
+            wrapping: text.wrapping,
+        })
+    }
+
+    ///Italic variant of the editor's font, for text that is not part of the content.
+    fn virtual_font(&self, renderer: &Renderer) -> Font {
+        Font {
+            style: iced::font::Style::Italic,
+            ..self.font.unwrap_or_else(|| renderer.default_font())
+        }
+    }
+
+    ///Width of the text `ghost` adds to its line.
+    fn inline_width(&self, renderer: &Renderer, inline: &Inline<'_>) -> f32 {
+        match inline {
+            Inline::Ghost(ghost) => {
+                let ghost_text = ghost.text.lines().next().unwrap_or_default();
+                self.measure_spans(
+                    renderer,
+                    &[text::Span::new(ghost_text).font(self.virtual_font(renderer))],
+                )
+            }
+            Inline::Swatch(_) => self.line_height_px(renderer),
+        }
+    }
+
+    ///Ghost texts and color swatches of a line with the text `content`, sorted by column.
+    fn inlines<'b>(&self, ghosts: Vec<&'b GhostText>, content: &str) -> Vec<Inline<'b>> {
+        let mut inlines: Vec<Inline<'b>> = ghosts.into_iter().map(Inline::Ghost).collect();
+        if self.color_swatches {
+            inlines.extend(
+                crate::color::color_literals(content)
+                    .into_iter()
+                    .map(Inline::Swatch),
+            );
+        }
+        //stable, so ghosts stay in front of a swatch at the same column
+        inlines.sort_by_key(|inline| floor_char_boundary(content, inline.column()));
+        inlines
+    }
+
+    ///`line` as it is shown, starting at the left edge of the text in `layout` (the wrapped editor's).
+    fn shown_line(
+        &self,
+        renderer: &Renderer,
+        layout: Layout<'_>,
+        line: usize,
+    ) -> Option<ShownLine<'_>> {
+        let content = self.content.line(line)?.to_string();
+        let ghosts = self
+            .ghost_text
+            .iter()
+            .filter(|ghost| ghost.position.line == line)
+            .collect();
+        let inlines = self.inlines(ghosts, &content);
+
+        let mut highlighter = Highlighter::new(&self.settings);
+        highlighter.change_line(line);
+        let mut shown = ShownLine {
+            highlights: highlighter.highlight_line(&content).collect(),
+            content,
+            parts: Vec::new(),
+        };
+
+        let mut x = layout.bounds().shrink(self.padding).x;
+        let mut column = 0;
+        for inline in inlines.into_iter().map(Some).chain(std::iter::once(None)) {
+            let next = inline.as_ref().map_or(shown.content.len(), |inline| {
+                floor_char_boundary(&shown.content, inline.column())
+            });
+            let width = self.measure_shown(renderer, &shown, column..next);
+            shown.parts.push((Part::Text(column..next), x, width));
+            x += width;
+            column = next;
+
+            if let Some(inline) = inline {
+                let width = self.inline_width(renderer, &inline);
+                shown.parts.push((Part::Inline(inline), x, width));
+                x += width;
+            }
+        }
+        Some(shown)
+    }
+
+    ///Width of the `range` of a [ShownLine]'s text.
+    fn measure_shown(
+        &self,
+        renderer: &Renderer,
+        shown: &ShownLine<'_>,
+        range: Range<usize>,
+    ) -> f32 {
+        //NOTE: measuring only depends on the fonts of the highlights, not on their colors
+        let theme = iced::Theme::default();
+        self.measure_spans(
+            renderer,
+            &spans(&shown.content, range, &shown.highlights, &theme),
+        )
+    }
+
+    ///What is shown at `x` (absolute) on `shown`.
+    fn hit(&self, renderer: &Renderer, shown: &ShownLine<'_>, x: f32) -> Option<LineHit> {
+        let (part, left, _) = shown
+            .parts
+            .iter()
+            .find(|(_, left, width)| *left <= x && x < left + width)?;
+        match part {
+            Part::Text(range) => {
+                let boundaries: Vec<usize> = shown.content[range.clone()]
+                    .char_indices()
+                    .map(|(idx, _)| range.start + idx)
+                    .collect();
+                //the last character that starts left of `x`
+                let idx = boundaries.partition_point(|boundary| {
+                    left + self.measure_shown(renderer, shown, range.start..*boundary) <= x
+                });
+                Some(LineHit::Text(boundaries[idx.saturating_sub(1)]))
+            }
+            Part::Inline(Inline::Swatch(literal)) => Some(LineHit::Swatch(literal.clone())),
+            Part::Inline(Inline::Ghost(ghost)) => Some(LineHit::Ghost(ghost.position.column)),
+        }
+    }
+
+    ///Where `column` of `shown` is shown (absolute). A column with an inline decoration is where the decoration starts if it is the
+    ///`end` of a range, otherwise where the text after the decoration starts.
+    fn column_x(
+        &self,
+        renderer: &Renderer,
+        shown: &ShownLine<'_>,
+        column: usize,
+        end: bool,
+    ) -> f32 {
+        let contains = |(part, _, _): &&(Part<'_>, f32, f32)| matches!(part, Part::Text(range) if range.start <= column && column <= range.end);
+        let part = if end {
+            shown.parts.iter().find(contains)
+        } else {
+            shown.parts.iter().rev().find(contains)
+        };
+        match part {
+            Some((Part::Text(range), left, _)) => {
+                left + self.measure_shown(renderer, shown, range.start..column)
+            }
+            _ => shown
+                .parts
+                .last()
+                .map_or(0.0, |(_, left, width)| left + width),
+        }
+    }
+
+    ///The color literal whose swatch is at `x` (absolute) on `line`.
+    fn swatch_at(
+        &self,
+        renderer: &Renderer,
+        layout: Layout<'_>,
+        line: usize,
+        x: f32,
+    ) -> Option<ColorLiteral> {
+        if !self.color_swatches {
+            return None;
+        }
+        let shown = self.shown_line(renderer, layout, line)?;
+        match self.hit(renderer, &shown, x)? {
+            LineHit::Swatch(literal) => Some(literal),
+            _ => None,
+        }
+    }
+
+    ///Content position at `point` (absolute) in the text area, with the syntax around it. See [CodeEditor::on_context_menu].
+    fn context_menu_at(
+        &self,
+        renderer: &Renderer,
+        state: &State,
+        layout: Layout<'_>,
+        point: Point,
+    ) -> Option<ContextMenu> {
+        let bounds = layout.bounds();
+        let row = self.row_at(renderer, state, point.y - bounds.y);
+        let line = state
+            .line_map
+            .line(row)
+            .min(self.content.line_count().saturating_sub(1));
+        let shown = self.shown_line(renderer, layout.children().next().unwrap(), line)?;
+        let column = match self.hit(renderer, &shown, point.x) {
+            Some(LineHit::Text(column) | LineHit::Ghost(column)) => column,
+            Some(LineHit::Swatch(literal)) => literal.range.start,
+            None if shown
+                .parts
+                .first()
+                .is_some_and(|(_, left, _)| point.x < *left) =>
+            {
+                0
+            }
+            None => shown.content.len(),
+        };
+        let column = floor_char_boundary(&shown.content, column);
+        let position = Position::new(line, column);
+
+        let context = |document: &Document| {
+            (document.line(line) == Some(shown.content.as_str()))
+                .then(|| document.context_at(position))
+        };
+        let context = match (self.document, &self.settings.shared) {
+            (Some(document), _) => context(document),
+            (None, Some(snapshot)) => snapshot.state().with_document(context),
+            (None, None) => None,
+        };
+        let context = context.unwrap_or_else(|| {
+            let names = self.settings.highlight_names();
+            let captures = shown
+                .highlights
+                .iter()
+                .filter(|(range, _)| range.contains(&column))
+                .filter_map(|(_, highlight)| names.get(highlight.capture?.0));
+            let mut context = SyntaxContext::default();
+            for name in captures {
+                context.in_string |= name.starts_with("string");
+                context.in_comment |= name.starts_with("comment");
+            }
+            context
+        });
+
+        Some(ContextMenu {
+            position,
+            point,
+            context,
+        })
+    }
+
+    ///The token at `position` (absolute), see [CodeEditor::on_hover].
+    fn hover_at(
+        &self,
+        renderer: &Renderer,
+        state: &State,
+        layout: Layout<'_>,
+        position: Point,
+    ) -> Option<Hover> {
+        let bounds = layout.bounds();
+        if position.x < bounds.x + self.gutter_layout(renderer).width()
+            || position.x >= bounds.x + bounds.width - self.scrollbar_width()
+        {
+            return None;
+        }
+        let row = self.row_at(renderer, state, position.y - bounds.y);
+        let line = state.line_map.line(row);
+        let shown = self.shown_line(renderer, layout.children().next().unwrap(), line)?;
+        let LineHit::Text(column) = self.hit(renderer, &shown, position.x)? else {
+            return None;
+        };
+        let c = shown.content[column..].chars().next()?;
+        if c.is_whitespace() {
+            return None;
+        }
+
+        let capture = shown
+            .highlights
+            .iter()
+            .rev()
+            .find(|(range, highlight)| range.contains(&column) && highlight.capture.is_some());
+        let token = |document: &Document| {
+            //the document lags behind while the host didn't apply an edit yet
+            if document.line(line) != Some(shown.content.as_str()) {
+                return None;
+            }
+            let token = document.token_at((line, column))?;
+            Some((
+                document.position(token.range.start)..document.position(token.range.end),
+                token.kind,
+            ))
+        };
+        let token = match (self.document, &self.settings.shared) {
+            (Some(document), _) => token(document),
+            (None, Some(snapshot)) => snapshot.state().with_document(token),
+            (None, None) => None,
+        };
+        let (range, kind) = match token {
+            Some((range, kind)) => (range, Some(kind)),
+            None => {
+                let range = capture.map_or_else(
+                    || word_at(&shown.content, column),
+                    |(range, _)| range.clone(),
+                );
+                (
+                    Position::new(line, range.start)..Position::new(line, range.end),
+                    None,
+                )
+            }
+        };
+
+        let start = if range.start.line == line {
+            range.start.column
+        } else {
+            0
+        };
+        let end = if range.end.line == line {
+            range.end.column
+        } else {
+            shown.content.len()
+        };
+        let x = self.column_x(renderer, &shown, start, false);
+        let line_height = self.line_height_px(renderer);
+        Some(Hover {
+            capture: capture.and_then(|(_, highlight)| {
+                let capture = highlight.capture?;
+                self.settings.highlight_names().get(capture.0).cloned()
+            }),
+            kind,
+            bounds: Rectangle {
+                x,
+                y: bounds.y + self.padding.top - state.offset + row as f32 * line_height,
+                width: self.column_x(renderer, &shown, end, true) - x,
+                height: line_height,
+            },
+            range,
+        })
+    }
+
+    ///Background behind `line`, which differs for the cursor's line and read-only lines.
+    fn line_background(
+        &self,
+        theme: &iced::Theme,
+        style: &text_editor::Style,
+        line: usize,
+    ) -> iced::Color {
+        let background = background_color(theme, style);
+        if self.highlight_current_line && line == self.content.cursor_position().0 {
+            return self
+                .current_line_background
+                .unwrap_or_else(|| crate::color::mix(style.value, background, 0.95));
+        }
+        if self.is_read_only_line(line) {
+            return read_only_background(style, background);
+        }
+        background
+    }
+
+    ///Whether `line` contains text of a [read-only](CodeEditor::read_only) region. A region that ends at the start of a line doesn't
+    ///count for that line.
+    fn is_read_only_line(&self, line: usize) -> bool {
+        self.highlight_read_only
+            && self.read_only.iter().any(|region| {
+                !region.is_empty()
+                    && region.start.line <= line
+                    && (line < region.end.line
+                        || (line == region.end.line && region.end.column > 0))
+            })
+    }
+
+    ///Whether `action` would change text inside a [read-only](CodeEditor::read_only) region, or at all in a [code_view]. A
+    ///selection starts at `anchor`, see [State].
+    fn edits_read_only(&self, action: &Action, anchor: Option<Position>) -> bool {
+        let Action::Edit(edit) = action else {
+            return false;
+        };
+        if self.view_only {
+            return true;
+        }
+        if self.read_only.is_empty() {
+            return false;
+        }
+
+        let (line, column) = self.content.cursor_position();
+        let cursor = Position::new(line, column);
+        let edited = match self.content.selection() {
+            //the selection either ends or starts at the cursor. It starts at the anchor, unless it was made some other way, like
+            //selecting all
+            Some(selection) => {
+                let before = self.step_back(cursor, selection.len())..cursor;
+                let after = cursor..self.step_forward(cursor, selection.len());
+                match anchor {
+                    Some(anchor) if anchor == before.start => vec![before],
+                    Some(anchor) if anchor == after.end => vec![after],
+                    _ => vec![before, after],
+                }
+            }
+            None => match edit {
+                Edit::Backspace => {
+                    let len = self.content.line(line).map_or(1, |text| {
+                        text[..column.min(text.len())]
+                            .chars()
+                            .next_back()
+                            .map_or(1, char::len_utf8)
+                    });
+                    vec![self.step_back(cursor, len)..cursor]
+                }
+                Edit::Delete => {
+                    let len = self.content.line(line).map_or(1, |text| {
+                        text.get(column..)
+                            .and_then(|rest| rest.chars().next())
+                            .map_or(1, char::len_utf8)
+                    });
+                    vec![cursor..self.step_forward(cursor, len)]
+                }
+                Edit::Insert(_) | Edit::Paste(_) | Edit::Enter => vec![cursor..cursor],
+            },
+        };
+
+        edited.iter().any(|edited| {
+            self.read_only.iter().any(|region| {
+                if edited.is_empty() {
+                    region.start < edited.start && edited.start < region.end
+                } else {
+                    edited.start < region.end && region.start < edited.end
+                }
+            })
+        })
+    }
+
+    ///Position `bytes` before `position`, counting line breaks as one byte.
+    fn step_back(&self, mut position: Position, mut bytes: usize) -> Position {
+        while bytes > position.column && position.line > 0 {
+            bytes -= position.column + 1;
+            position.line -= 1;
+            position.column = self
+                .content
+                .line(position.line)
+                .map_or(0, |text| text.len());
+        }
+        position.column = position.column.saturating_sub(bytes);
+        position
+    }
+
+    ///Position `bytes` after `position`, counting line breaks as one byte.
+    fn step_forward(&self, mut position: Position, mut bytes: usize) -> Position {
+        loop {
+            let len = self
+                .content
+                .line(position.line)
+                .map_or(0, |text| text.len());
+            if bytes <= len - position.column.min(len)
+                || position.line + 1 >= self.content.line_count()
+            {
+                position.column = (position.column + bytes).min(len);
+                return position;
+            }
+            bytes -= len - position.column.min(len) + 1;
+            position.line += 1;
+            position.column = 0;
+        }
+    }
+
+    ///Draws `content` at `position`, in its own `font` and `color` if given.
+    fn fill_text(
+        &self,
+        renderer: &mut Renderer,
+        content: &str,
+        font: Option<Font>,
+        position: Point,
+        color: iced::Color,
+        clip_bounds: Rectangle,
+    ) {
+        let text = self.text(renderer, content);
+        //NOTE: cached text ignores `wrapping` and would wrap at the bounds, while infinite bounds don't show anything at all.
+        //      So use huge, but finite ones.
+        renderer.fill_text(
+            text::Text {
+                content: content.to_string(),
+                bounds: Size::new(f32::MAX, self.line_height_px(renderer)),
+                size: text.size,
+                line_height: text.line_height,
+                font: font.unwrap_or(text.font),
+                horizontal_alignment: text.horizontal_alignment,
+                vertical_alignment: text.vertical_alignment,
+                shaping: text.shaping,
+                wrapping: text.wrapping,
+            },
+            position,
+            color,
+            clip_bounds,
+        );
+    }
+
+    ///Draws `spans` one after another from `position`, and returns where the last one ends.
+    fn fill_spans(
+        &self,
+        renderer: &mut Renderer,
+        spans: &[text::Span<'_, (), Font>],
+        position: Point,
+        color: iced::Color,
+        clip_bounds: Rectangle,
+    ) -> f32 {
+        let mut x = position.x;
+        //NOTE: the renderer only keeps weak references to paragraphs, so each span is drawn as text of its own.
+        for span in spans {
+            if x > clip_bounds.x + clip_bounds.width {
+                break;
+            }
+            self.fill_text(
+                renderer,
+                &span.text,
+                span.font,
+                Point::new(x, position.y),
+                span.color.unwrap_or(color),
+                clip_bounds,
+            );
+            x += self.measure_spans(renderer, std::slice::from_ref(span));
+        }
+        x
+    }
+
+    ///Draws ghost texts, fold placeholders and diagnostics of `lines`. `text_bounds` is where the editor draws its text.
+    fn draw_decorations(
+        &self,
+        renderer: &mut Renderer,
+        theme: &iced::Theme,
+        text_bounds: Rectangle,
+        lines: Range<usize>,
+        line_map: &LineMap,
+        style: &text_editor::Style,
+    ) {
+        let mut ghosts: BTreeMap<usize, Vec<&GhostText>> = BTreeMap::new();
+        for ghost in self
+            .ghost_text
+            .iter()
+            .filter(|ghost| lines.contains(&ghost.position.line))
+        {
+            ghosts.entry(ghost.position.line).or_default().push(ghost);
+        }
+        let overlay = self
+            .settings
+            .overlay
+            .as_deref()
+            .filter(|_| self.inline_diagnostics);
+
+        let line_height = self.line_height_px(renderer);
+        let mut highlighter = Highlighter::new(&self.settings);
+
+        for line in lines {
+            let ghosts = ghosts.remove(&line).unwrap_or_default();
+            let fold = self
+                .folds
+                .and_then(|folds| folds.range_at(line))
+                .filter(|_| line_map.is_hidden(line + 1));
+            let diagnostic = overlay.and_then(|overlay| overlay.worst_diagnostic(line));
+            if ghosts.is_empty() && !self.color_swatches && fold.is_none() && diagnostic.is_none() {
+                continue;
+            }
+            let Some(content) = self.content.line(line).map(|l| l.to_string()) else {
+                continue;
+            };
+            let inlines = self.inlines(ghosts, &content);
+            if inlines.is_empty() && fold.is_none() && diagnostic.is_none() {
+                continue;
+            }
+
+            highlighter.change_line(line);
+            let highlights: Vec<_> = highlighter.highlight_line(&content).collect();
+            let mut position = Point::new(text_bounds.x, text_bounds.y + line as f32 * line_height);
+
+            position.x = if inlines.is_empty() {
+                position.x
+                    + self.measure_spans(
+                        renderer,
+                        &spans(&content, 0..content.len(), &highlights, theme),
+                    )
+            } else {
+                self.draw_inline_line(
+                    renderer,
+                    theme,
+                    text_bounds,
+                    position,
+                    &content,
+                    &highlights,
+                    inlines,
+                    self.line_background(theme, style, line),
+                    style,
+                )
+            };
+            if let Some(fold) = fold {
+                position.x =
+                    self.draw_fold_placeholder(renderer, theme, text_bounds, position, fold, style);
+            }
+            if let Some(diagnostic) = diagnostic {
+                self.draw_diagnostic(renderer, theme, text_bounds, position, diagnostic, style);
+            }
+        }
+    }
+
+    ///Draws the `inlines` (sorted by column) of a line at `position`, and moves the text after them to the right. Returns where the
+    ///line ends now.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_inline_line(
+        &self,
+        renderer: &mut Renderer,
+        theme: &iced::Theme,
+        text_bounds: Rectangle,
+        position: Point,
+        content: &str,
+        highlights: &[(Range<usize>, crate::Highlight)],
+        inlines: Vec<Inline<'_>>,
+        line_background: iced::Color,
+        style: &text_editor::Style,
+    ) -> f32 {
+        let line_height = self.line_height_px(renderer);
+        let background = background_color(theme, style);
+        let ghost_color = crate::color::mix(style.value, background, 0.5);
+
+        let column = |inline: &Inline<'_>| floor_char_boundary(content, inline.column());
+
+        let mut x = position.x
+            + self.measure_spans(
+                renderer,
+                &spans(content, 0..column(&inlines[0]), highlights, theme),
+            );
+
+        //hide the original text from the first ghost on, and draw it again behind the ghosts
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: Rectangle::new(
+                    Point::new(x, position.y),
+                    Size::new(
+                        (text_bounds.x + text_bounds.width - x).max(0.0),
+                        line_height,
+                    ),
+                ),
+                ..Default::default()
+            },
+            line_background,
+        );
+
+        for (idx, inline) in inlines.iter().enumerate() {
+            let width = self.inline_width(renderer, inline);
+            match inline {
+                Inline::Ghost(ghost) => self.fill_text(
+                    renderer,
+                    ghost.text.lines().next().unwrap_or_default(),
+                    Some(self.virtual_font(renderer)),
+                    Point::new(x, position.y),
+                    ghost_color,
+                    text_bounds,
+                ),
+                Inline::Swatch(literal) => {
+                    let size = (line_height * 0.6).round();
+                    renderer.fill_quad(
+                        renderer::Quad {
+                            bounds: Rectangle::new(
+                                Point::new(
+                                    x + ((width - size) / 2.0).round(),
+                                    position.y + ((line_height - size) / 2.0).round(),
+                                ),
+                                Size::new(size, size),
+                            ),
+                            border: Border {
+                                color: ghost_color,
+                                width: 1.0,
+                                radius: 2.0.into(),
+                            },
+                            ..Default::default()
+                        },
+                        literal.color,
+                    );
+                }
+            }
+            x += width;
+
+            let end = inlines.get(idx + 1).map(column).unwrap_or(content.len());
+            x = self.fill_spans(
+                renderer,
+                &spans(content, column(inline)..end, highlights, theme),
+                Point::new(x, position.y),
+                style.value,
+                text_bounds,
+            );
+        }
+        x
+    }
+
+    ///Draws the "⋯" marker of the folded region `fold` behind its header line, followed by a preview of the region's last line if that
+    ///is short, e.g. the closing brace. Returns where the placeholder ends.
+    fn draw_fold_placeholder(
+        &self,
+        renderer: &mut Renderer,
+        theme: &iced::Theme,
+        text_bounds: Rectangle,
+        position: Point,
+        fold: &FoldRange,
+        style: &text_editor::Style,
+    ) -> f32 {
+        const MARKER: &str = "⋯";
+        const MAX_PREVIEW: usize = 16;
+
+        let line_height = self.line_height_px(renderer);
+        let background = background_color(theme, style);
+        let space = self.measure_spans(renderer, &[text::Span::new(" ")]);
+        let marker_width = self.measure_spans(renderer, &[text::Span::new(MARKER)]);
+
+        let marker = Rectangle::new(
+            Point::new(position.x + space, position.y),
+            Size::new(marker_width + space, line_height),
+        );
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: marker,
+                border: Border {
+                    radius: (line_height / 4.0).into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            crate::color::mix(style.value, background, 0.92),
+        );
+        self.fill_text(
+            renderer,
+            MARKER,
+            None,
+            Point::new(marker.x + space / 2.0, position.y),
+            crate::color::mix(style.value, background, 0.4),
+            text_bounds,
+        );
+        let mut x = marker.x + marker.width;
+
+        let last = fold.lines.end - 1;
+        let Some(content) = self.content.line(last).map(|l| l.to_string()) else {
+            return x;
+        };
+        let preview = content.trim_start();
+        if preview.is_empty() || preview.chars().count() > MAX_PREVIEW {
+            return x;
+        }
+        let mut highlighter = Highlighter::new(&self.settings);
+        highlighter.change_line(last);
+        let highlights: Vec<_> = highlighter.highlight_line(&content).collect();
+        x = self.fill_spans(
+            renderer,
+            &spans(
+                &content,
+                content.len() - preview.len()..content.len(),
+                &highlights,
+                theme,
+            ),
+            Point::new(x + space, position.y),
+            style.value,
+            text_bounds,
+        );
+        x
+    }
+
+    ///Draws the message of `diagnostic` a bit behind `position`, cut to fit.
+    fn draw_diagnostic(
+        &self,
+        renderer: &mut Renderer,
+        theme: &iced::Theme,
+        text_bounds: Rectangle,
+        position: Point,
+        diagnostic: &crate::overlay::Diagnostic,
+        style: &text_editor::Style,
+    ) {
+        let font = self.virtual_font(renderer);
+        let measure =
+            |content: &str| self.measure_spans(renderer, &[text::Span::new(content).font(font)]);
+
+        let x = position.x + measure("    ");
+        let available = text_bounds.x + text_bounds.width - x;
+        let message = diagnostic.message.lines().next().unwrap_or_default();
+        let Some(message) = truncate(message, available, measure) else {
+            return;
+        };
+
+        let color = crate::color::mix(
+            crate::severity_color(diagnostic.severity, theme.extended_palette()),
+            background_color(theme, style),
+            0.35,
+        );
+        self.fill_text(
+            renderer,
+            &message,
+            Some(font),
+            Point::new(x, position.y),
+            color,
+            text_bounds,
+        );
+    }
+
+    fn gutter_click(
+        &self,
+        renderer: &Renderer,
+        shell: &mut Shell<'_, Message>,
+        column: usize,
+        line: usize,
+    ) {
+        match &self.gutter[column] {
+            GutterColumn::Marks => {
+                if let Some(on_click) = &self.on_mark_click {
+                    shell.publish(on_click(line));
+                }
+            }
+            GutterColumn::LineNumbers => {
+                if let Some(on_click) = &self.on_line_number_click {
+                    shell.publish(on_click(line));
+                }
+            }
+            GutterColumn::Folds => {
+                let (Some(on_fold), Some(folds)) = (&self.on_fold, self.folds) else {
+                    return;
+                };
+                let Some(fold) = folds.range_at(line) else {
+                    return;
+                };
+                shell.publish(on_fold(line));
+                //folding away the cursor would reopen the region right away, so move the cursor to the header
+                if !folds.is_folded(line)
+                    && fold
+                        .hidden_lines()
+                        .contains(&self.content.cursor_position().0)
+                    && let Some(on_action) = &self.on_action
+                {
+                    let line_height = self.line_height_px(renderer);
+                    shell.publish(on_action(Action::Click(Point::new(
+                        f32::MAX / 2.0,
+                        (line as f32 + 0.5) * line_height,
+                    ))));
+                }
+            }
+            GutterColumn::Custom(component) => {
+                if let Some(message) = component.on_click(line) {
+                    shell.publish(message);
+                }
+            }
+            GutterColumn::Changes(_) | GutterColumn::Annotations(_) => {}
+        }
+    }
+
+    ///Whether clicking `column` on `line` does anything.
+    fn is_clickable(&self, column: usize, line: usize) -> bool {
+        match &self.gutter[column] {
+            GutterColumn::Marks => self.on_mark_click.is_some(),
+            GutterColumn::LineNumbers => self.on_line_number_click.is_some(),
+            GutterColumn::Folds => {
+                self.on_fold.is_some() && self.folds.and_then(|f| f.range_at(line)).is_some()
+            }
+            GutterColumn::Custom(component) => component.on_click(line).is_some(),
+            GutterColumn::Changes(_) | GutterColumn::Annotations(_) => false,
+        }
+    }
+
+    ///Draws the gutter's columns for `rows`, `top` is where row 0 is drawn.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_gutter(
+        &self,
+        renderer: &mut Renderer,
+        theme: &iced::Theme,
+        bounds: Rectangle,
+        top: f32,
+        rows: Range<usize>,
+        line_map: &LineMap,
+        hovered: Option<(usize, usize)>,
+        style: &text_editor::Style,
+    ) {
+        let gutter = self.gutter_layout(renderer);
+        let line_height = self.line_height_px(renderer);
+        let background = background_color(theme, style);
+        let muted = crate::color::mix(style.value, background, 0.5);
+        let palette = theme.extended_palette();
+        let cursor_line = self.content.cursor_position().0;
+        let cursor_row = line_map.row(cursor_line);
+
+        for row in rows {
+            let line = line_map.line(row);
+            let y = top + row as f32 * line_height;
+
+            for (idx, column) in self.gutter.iter().enumerate() {
+                let slot = Rectangle::new(
+                    Point::new(bounds.x + gutter.left(idx), y),
+                    Size::new(gutter.widths[idx], line_height),
+                );
+                if slot.width <= 0.0 {
+                    continue;
+                }
+                let is_hovered = hovered == Some((row, idx));
+                let clip = Rectangle {
+                    height: bounds.height,
+                    y: bounds.y,
+                    ..slot
+                };
+
+                match column {
+                    GutterColumn::Marks => {
+                        let Some(marks) = &self.marks else {
+                            continue;
+                        };
+                        let preview =
+                            (is_hovered && self.on_mark_click.is_some()).then_some(Mark::Color(
+                                crate::color::mix(palette.danger.base.color, background, 0.6),
+                            ));
+                        if let Some(mark) = marks.get(&line).copied().or(preview) {
+                            draw_mark(renderer, slot, mark, palette);
+                        }
+                    }
+                    GutterColumn::LineNumbers => {
+                        let is_current = line == cursor_line;
+                        let number = match self.line_numbers {
+                            LineNumbers::Relative => row.abs_diff(cursor_row),
+                            LineNumbers::Hybrid if !is_current => row.abs_diff(cursor_row),
+                            _ => line + 1,
+                        }
+                        .to_string();
+                        let emphasize = is_current && self.current_line_emphasis;
+                        let font = Font {
+                            weight: if emphasize {
+                                iced::font::Weight::Bold
+                            } else {
+                                iced::font::Weight::Normal
+                            },
+                            ..self.font.unwrap_or_else(|| renderer.default_font())
+                        };
+                        let width =
+                            self.measure_spans(renderer, &[text::Span::new(&number).font(font)]);
+                        self.fill_text(
+                            renderer,
+                            &number,
+                            Some(font),
+                            Point::new(slot.x + slot.width - line_height * 0.5 - width, y),
+                            if emphasize || is_hovered {
+                                style.value
+                            } else {
+                                muted
+                            },
+                            clip,
+                        );
+                    }
+                    GutterColumn::Folds => {
+                        if self.folds.and_then(|folds| folds.range_at(line)).is_none() {
+                            continue;
+                        }
+                        let chevron = if line_map.is_hidden(line + 1) {
+                            "▸"
+                        } else {
+                            "▾"
+                        };
+                        let width = self.measure_spans(renderer, &[text::Span::new(chevron)]);
+                        self.fill_text(
+                            renderer,
+                            chevron,
+                            None,
+                            Point::new(slot.x + (slot.width - width) / 2.0, y),
+                            if is_hovered { style.value } else { muted },
+                            clip,
+                        );
+                    }
+                    GutterColumn::Changes(changes) => {
+                        let Some(change) = changes.get(&line) else {
+                            continue;
+                        };
+                        let bar = (line_height * 0.2).round();
+                        let (bounds, color) = match change {
+                            LineChange::Added => (
+                                Rectangle {
+                                    x: slot.x + (slot.width - bar) / 2.0,
+                                    width: bar,
+                                    ..slot
+                                },
+                                palette.success.base.color,
+                            ),
+                            LineChange::Modified => (
+                                Rectangle {
+                                    x: slot.x + (slot.width - bar) / 2.0,
+                                    width: bar,
+                                    ..slot
+                                },
+                                palette.primary.base.color,
+                            ),
+                            //between this line and the previous one
+                            LineChange::Removed => (
+                                Rectangle {
+                                    y: slot.y - bar / 2.0,
+                                    height: bar,
+                                    ..slot
+                                },
+                                palette.danger.base.color,
+                            ),
+                        };
+                        renderer.fill_quad(
+                            renderer::Quad {
+                                bounds,
+                                ..Default::default()
+                            },
+                            color,
+                        );
+                    }
+                    GutterColumn::Annotations(annotations) => {
+                        let Some(annotation) = annotations
+                            .get(line)
+                            .filter(|_| annotations.is_run_start(line))
+                        else {
+                            continue;
+                        };
+                        let max_width = slot.width - line_height * 0.75;
+                        let measure =
+                            |text: &str| self.measure_spans(renderer, &[text::Span::new(text)]);
+                        if let Some(label) = truncate(&annotation.label(), max_width, measure) {
+                            self.fill_text(
+                                renderer,
+                                &label,
+                                None,
+                                Point::new(slot.x + line_height * 0.25, y),
+                                muted,
+                                clip,
+                            );
+                        }
+                    }
+                    GutterColumn::Custom(component) => {
+                        renderer.with_layer(clip, |renderer| {
+                            component.draw(renderer, theme, line, slot, is_hovered);
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+///Draws `mark` centered in `slot`.
+fn draw_mark(
+    renderer: &mut Renderer,
+    slot: Rectangle,
+    mark: Mark,
+    palette: &iced::theme::palette::Extended,
+) {
+    let size = slot.height * 0.55;
+    let dot = Rectangle::new(
+        slot.center() - Vector::new(size / 2.0, size / 2.0),
+        Size::new(size, size),
+    );
+    let (bounds, color, border) = match mark {
+        Mark::Breakpoint => (dot, palette.danger.base.color, Border::default()),
+        Mark::DisabledBreakpoint => (
+            dot,
+            iced::Color::TRANSPARENT,
+            Border {
+                color: palette.danger.base.color,
+                width: 1.5,
+                ..Default::default()
+            },
+        ),
+        Mark::Bookmark => (
+            Rectangle {
+                x: dot.x + size * 0.2,
+                width: size * 0.6,
+                ..dot
+            },
+            palette.primary.base.color,
+            Border::default(),
+        ),
+        Mark::Color(color) => (dot, color, Border::default()),
+    };
+    let radius = match mark {
+        Mark::Bookmark => size * 0.1,
+        _ => size / 2.0,
+    };
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border: Border {
+                radius: radius.into(),
+                ..border
+            },
+            ..Default::default()
+        },
+        color,
+    );
+}
+
+fn read_only_background(style: &text_editor::Style, background: iced::Color) -> iced::Color {
+    crate::color::mix(style.value, background, 0.97)
+}
+
+fn background_color(theme: &iced::Theme, style: &text_editor::Style) -> iced::Color {
+    match style.background {
+        Background::Color(color) => color,
+        _ => theme.extended_palette().background.base.color,
+    }
+}
+
+///Highlighted spans of `content[range]`. Later highlights win where they overlap, like in the editor.
+fn spans<'b>(
+    content: &'b str,
+    range: Range<usize>,
+    highlights: &[(Range<usize>, crate::Highlight)],
+    theme: &iced::Theme,
+) -> Vec<text::Span<'b, (), Font>> {
+    let mut bounds: Vec<usize> = highlights
+        .iter()
+        .flat_map(|(r, _)| [r.start, r.end])
+        .chain([range.start, range.end])
+        .filter(|b| range.contains(b) || *b == range.end)
+        .filter(|b| content.is_char_boundary(*b))
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    bounds
+        .windows(2)
+        .map(|w| {
+            let mut span = text::Span::new(&content[w[0]..w[1]]);
+            if let Some((_, highlight)) = highlights
+                .iter()
+                .rev()
+                .find(|(r, _)| r.start <= w[0] && w[1] <= r.end)
+            {
+                let format = crate::to_format(highlight, theme);
+                span = span.color_maybe(format.color).font_maybe(format.font);
+            }
+            span
+        })
+        .collect()
+}
+
+///Longest prefix of `text` that is at most `max_width` wide, with an ellipsis if it was cut. `None` if not even the ellipsis fits.
+fn truncate(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Option<String> {
+    if measure(text) <= max_width {
+        return Some(text.to_string());
+    }
+    const ELLIPSIS: &str = "…";
+    if measure(ELLIPSIS) > max_width {
+        return None;
+    }
+
+    //binary search for the number of characters that still fit
+    let boundaries: Vec<usize> = text.char_indices().map(|(idx, _)| idx).collect();
+    let prefix = |chars: usize| &text[..boundaries.get(chars).copied().unwrap_or(text.len())];
+    let (mut low, mut high) = (0, boundaries.len());
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if measure(&format!("{}{ELLIPSIS}", prefix(mid))) <= max_width {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Some(format!("{}{ELLIPSIS}", prefix(low).trim_end()))
+}
+
+///The word (or, on other characters, just the character) at `column`.
+fn word_at(text: &str, column: usize) -> Range<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let Some(c) = text[column..].chars().next() else {
+        return column..column;
+    };
+    if !is_word(c) {
+        return column..column + c.len_utf8();
+    }
+    let start = text[..column]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(column, |(idx, _)| idx);
+    let end = text[column..]
+        .find(|c: char| !is_word(c))
+        .map_or(text.len(), |idx| column + idx);
+    start..end
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+///Something shown inline that is not part of the content.
+enum Inline<'a> {
+    Ghost(&'a GhostText),
+    ///In front of the literal.
+    Swatch(ColorLiteral),
+}
+
+impl Inline<'_> {
+    fn column(&self) -> usize {
+        match self {
+            Inline::Ghost(ghost) => ghost.position.column,
+            Inline::Swatch(literal) => literal.range.start,
+        }
+    }
+}
+
+///A line as it is shown: runs of text between the inline decorations, and the decorations themselves.
+struct ShownLine<'a> {
+    content: String,
+    highlights: Vec<(Range<usize>, crate::Highlight)>,
+    ///Each part with its absolute x-coordinate and width.
+    parts: Vec<(Part<'a>, f32, f32)>,
+}
+
+enum Part<'a> {
+    Text(Range<usize>),
+    Inline(Inline<'a>),
+}
+
+enum LineHit {
+    ///Byte column of the character.
+    Text(usize),
+    Swatch(ColorLiteral),
+    ///Byte column the ghost text is shown at.
+    Ghost(usize),
+}
+
+///Widths of the gutter's columns, from left to right, indexed like [CodeEditor::gutter]. Unused columns have no width.
+#[derive(Debug, Clone)]
+struct Gutter {
+    widths: Vec<f32>,
+}
+
+impl Gutter {
+    fn width(&self) -> f32 {
+        self.widths.iter().sum()
+    }
+
+    ///Left edge of the column `idx`, relative to the widget's left edge.
+    fn left(&self, idx: usize) -> f32 {
+        self.widths[..idx].iter().sum()
+    }
+
+    ///Column at `x`, relative to the widget's left edge.
+    fn column_at(&self, x: f32) -> Option<usize> {
+        if x < 0.0 {
+            return None;
+        }
+        let mut left = 0.0;
+        for (idx, width) in self.widths.iter().enumerate() {
+            left += width;
+            if x < left && *width > 0.0 {
+                return Some(idx);
+            }
+        }
+        None
+    }
+}
+
+///Maps between lines of the content and the _rows_ they are shown in, which differ once lines are folded away.
+#[derive(Debug, Clone, Default)]
+struct LineMap {
+    ///Sorted, non-overlapping.
+    hidden: Vec<Range<usize>>,
+}
+
+impl LineMap {
+    fn hidden_count(&self) -> usize {
+        self.hidden.iter().map(Range::len).sum()
+    }
+
+    fn is_hidden(&self, line: usize) -> bool {
+        self.hidden.iter().any(|range| range.contains(&line))
+    }
+
+    ///Row `line` is shown in. Hidden lines map to the row of their region's header.
+    fn row(&self, line: usize) -> usize {
+        let mut hidden_before = 0;
+        for range in &self.hidden {
+            if range.end <= line {
+                hidden_before += range.len();
+            } else if range.start <= line {
+                return range.start - 1 - hidden_before;
+            } else {
+                break;
+            }
+        }
+        line - hidden_before
+    }
+
+    ///Line shown in `row`.
+    fn line(&self, row: usize) -> usize {
+        let mut line = row;
+        for range in &self.hidden {
+            if range.start <= line {
+                line += range.len();
+            } else {
+                break;
+            }
+        }
+        line
+    }
+
+    ///Runs of consecutive visible lines out of `line_count`.
+    fn segments(&self, line_count: usize) -> Vec<Range<usize>> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for range in &self.hidden {
+            if start < range.start {
+                segments.push(start..range.start.min(line_count));
+            }
+            start = range.end;
+        }
+        if start < line_count {
+            segments.push(start..line_count);
+        }
+        segments
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    ///Vertical scroll offset in pixels.
+    offset: f32,
+    viewport_height: f32,
+    content_height: f32,
+    ///Cursor position the view was last scrolled to.
+    last_cursor: Option<(usize, usize)>,
+    line_map: LineMap,
+    ///Where the scrollbar's thumb was grabbed, relative to its top.
+    thumb_grab: Option<f32>,
+    ///Range of the token last reported to `on_hover`.
+    hovered: Option<Range<Position>>,
+    ///Lines the wrapped editor is laid out for, see `layout`.
+    editor_lines: usize,
+    ///Where the selection started: the cursor before there was one, so it tells which way the selection extends from it.
+    selection_anchor: Option<Position>,
+}
+
+impl State {
+    fn scroll_by(&mut self, delta: f32) {
+        self.offset = (self.offset + delta).clamp(0.0, self.max_offset());
+    }
+
+    fn max_offset(&self) -> f32 {
+        (self.content_height - self.viewport_height).max(0.0)
+    }
+
+    ///Scrolls just enough to make the vertical range `top..bottom` visible.
+    fn reveal(&mut self, top: f32, bottom: f32) {
+        if top < self.offset {
+            self.offset = top;
+        } else if bottom > self.offset + self.viewport_height {
+            self.offset = bottom - self.viewport_height;
+        }
+        self.offset = self.offset.clamp(0.0, self.max_offset());
+    }
+}
+
+impl<Message> Widget<Message, iced::Theme, Renderer> for CodeEditor<'_, Message> {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.editor as &dyn Widget<_, _, _>)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(&[&self.editor as &dyn Widget<_, _, _>]);
+    }
+
+    fn size(&self) -> Size<Length> {
+        Size::new(self.width, self.height)
+    }
+
+    fn layout(&self, tree: &mut Tree, renderer: &Renderer, limits: &Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let max = limits.max();
+        let gutter = self.gutter_layout(renderer).width();
+
+        let line_height = self.line_height_px(renderer);
+        let line_map = self.line_map();
+        let rows = self
+            .content
+            .line_count()
+            .saturating_sub(line_map.hidden_count());
+        let content_height = self.padding.vertical() + rows as f32 * line_height;
+        let height = match self.height {
+            Length::Shrink => content_height.min(max.height),
+            _ => max.height,
+        };
+
+        let state = tree.state.downcast_mut::<State>();
+        state.viewport_height = height;
+        state.content_height = content_height;
+
+        //the wrapped editor can't scroll by itself, so follow the cursor here
+        let cursor = self.content.cursor_position();
+        if state.last_cursor != Some(cursor) {
+            state.last_cursor = Some(cursor);
+            let top = self.padding.top + line_map.row(cursor.0) as f32 * line_height;
+            state.reveal(top, top + line_height);
+        }
+        state.line_map = line_map;
+        state.scroll_by(0.0);
+
+        //the wrapped editor shapes and highlights the lines that fit its height, so it gets the lines up to the last one on
+        //screen, and the cursor's (it would scroll to it otherwise). It lays out all of them again on a new height, so it grows by
+        //more lines at a time
+        let visible = self.visible_rows(renderer, state, height);
+        let needed = state
+            .line_map
+            .line(visible.end.saturating_sub(1))
+            .max(cursor.0)
+            + 1;
+        if needed > state.editor_lines {
+            state.editor_lines = needed.next_multiple_of(LAYOUT_LINES);
+        }
+        let editor = self
+            .editor
+            .layout(
+                &mut tree.children[0],
+                renderer,
+                &Limits::new(
+                    Size::ZERO,
+                    Size::new(
+                        (max.width - gutter - self.scrollbar_width()).max(0.0),
+                        self.padding.vertical() + state.editor_lines as f32 * line_height,
+                    ),
+                ),
+            )
+            .move_to(Point::new(gutter, 0.0));
+
+        layout::Node::with_children(Size::new(max.width, height), vec![editor])
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation,
+    ) {
+        self.editor.operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: iced::Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+        let state = tree.state.downcast_mut::<State>();
+        let gutter = self.gutter_layout(renderer);
+        if self.content.selection().is_none() {
+            let (line, column) = self.content.cursor_position();
+            state.selection_anchor = Some(Position::new(line, column));
+        }
+
+        if let Some(track) = self.scrollbar_track(bounds) {
+            match event {
+                iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                    if let Some(position) = cursor.position_over(track) =>
+                {
+                    let grab = match self.scrollbar_thumb(state, track) {
+                        Some(thumb) if thumb.contains(position) => position.y - thumb.y,
+                        //jump, so that the thumb is centered on the click
+                        Some(thumb) => {
+                            self.scroll_thumb_to(state, track, position.y - thumb.height / 2.0);
+                            thumb.height / 2.0
+                        }
+                        None => return event::Status::Captured,
+                    };
+                    state.thumb_grab = Some(grab);
+                    self.lay_out_scrolled(renderer, state, shell);
+                    shell.request_redraw(iced::window::RedrawRequest::NextFrame);
+                    return event::Status::Captured;
+                }
+                iced::Event::Mouse(mouse::Event::CursorMoved { position })
+                    if let Some(grab) = state.thumb_grab =>
+                {
+                    self.scroll_thumb_to(state, track, position.y - grab);
+                    self.lay_out_scrolled(renderer, state, shell);
+                    shell.request_redraw(iced::window::RedrawRequest::NextFrame);
+                    return event::Status::Captured;
+                }
+                iced::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                    if state.thumb_grab.take().is_some() =>
+                {
+                    return event::Status::Captured;
+                }
+                _ => {}
+            }
+        }
+
+        if let iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event
+            && let Some(position) = cursor.position_over(bounds)
+            && let Some(column) = gutter.column_at(position.x - bounds.x)
+        {
+            let line = state
+                .line_map
+                .line(self.row_at(renderer, state, position.y - bounds.y));
+            if line < self.content.line_count() {
+                self.gutter_click(renderer, shell, column, line);
+            }
+            return event::Status::Captured;
+        }
+
+        if let iced::Event::Mouse(mouse::Event::CursorMoved { .. } | mouse::Event::CursorLeft) =
+            event
+            && let Some(on_hover) = &self.on_hover
+        {
+            let hover = cursor
+                .position_over(bounds)
+                .filter(|_| state.thumb_grab.is_none())
+                .and_then(|position| self.hover_at(renderer, state, layout, position));
+            let range = hover.as_ref().map(|hover| hover.range.clone());
+            if range != state.hovered {
+                state.hovered = range;
+                shell.publish(on_hover(hover));
+            }
+        }
+
+        if let iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) = event
+            && let Some(on_context_menu) = &self.on_context_menu
+            && let Some(position) = cursor.position_over(bounds)
+            && position.x >= bounds.x + gutter.width()
+            && position.x < bounds.x + bounds.width - self.scrollbar_width()
+        {
+            if let Some(menu) = self.context_menu_at(renderer, state, layout, position) {
+                shell.publish(on_context_menu(menu));
+            }
+            return event::Status::Captured;
+        }
+
+        if let iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event
+            && let Some(on_click) = &self.on_color_click
+            && let Some(position) = cursor.position_over(bounds)
+        {
+            let line = state
+                .line_map
+                .line(self.row_at(renderer, state, position.y - bounds.y));
+            let editor_layout = layout.children().next().unwrap();
+            if let Some(literal) = self.swatch_at(renderer, editor_layout, line, position.x) {
+                shell.publish(on_click(ColorSwatch { line, literal }));
+                return event::Status::Captured;
+            }
+        }
+
+        //the wrapped editor would scroll its own text, see `layout`
+        if let iced::Event::Mouse(mouse::Event::WheelScrolled { delta }) = event
+            && cursor.is_over(bounds)
+        {
+            let delta = match delta {
+                mouse::ScrollDelta::Lines { y, .. } => -y * 3.0 * self.line_height_px(renderer),
+                mouse::ScrollDelta::Pixels { y, .. } => -y,
+            };
+            state.scroll_by(delta);
+            self.lay_out_scrolled(renderer, state, shell);
+            shell.request_redraw(iced::window::RedrawRequest::NextFrame);
+            return event::Status::Captured;
+        }
+
+        //the editor extends past our bounds, don't let it take clicks that are outside of them
+        let is_press = matches!(
+            event,
+            iced::Event::Mouse(mouse::Event::ButtonPressed(_))
+                | iced::Event::Touch(touch::Event::FingerPressed { .. })
+        );
+        let editor_cursor = match cursor.position() {
+            Some(position) if !is_press || cursor.is_over(bounds) => {
+                mouse::Cursor::Available(self.to_editor(renderer, state, bounds, position))
+            }
+            _ => mouse::Cursor::Unavailable,
+        };
+
+        let mut actions = Vec::new();
+        let mut editor_shell = Shell::new(&mut actions);
+        let status = self.editor.on_event(
+            &mut tree.children[0],
+            event.clone(),
+            layout.children().next().unwrap(),
+            editor_cursor,
+            renderer,
+            clipboard,
+            &mut editor_shell,
+            &(bounds + Vector::new(0.0, state.offset)),
+        );
+        //like `Shell::merge`, which can't drop messages
+        if let Some(redraw) = editor_shell.redraw_request() {
+            shell.request_redraw(redraw);
+        }
+        if editor_shell.is_layout_invalid() {
+            shell.invalidate_layout();
+        }
+        if editor_shell.are_widgets_invalid() {
+            shell.invalidate_widgets();
+        }
+        for action in actions {
+            if let Some(on_action) = &self.on_action
+                && !self.edits_read_only(&action, state.selection_anchor)
+                && !matches!(action, Action::Scroll { .. })
+            {
+                shell.publish(on_action(action));
+            }
+        }
+        if status == event::Status::Captured {
+            return status;
+        }
+
+        event::Status::Ignored
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let bounds = layout.bounds();
+        let state = tree.state.downcast_ref::<State>();
+        let gutter = self.gutter_layout(renderer);
+        match cursor.position_over(bounds) {
+            Some(position) if position.x < bounds.x + gutter.width() => {
+                let line = state
+                    .line_map
+                    .line(self.row_at(renderer, state, position.y - bounds.y));
+                match gutter.column_at(position.x - bounds.x) {
+                    Some(column) if self.is_clickable(column, line) => mouse::Interaction::Pointer,
+                    _ => mouse::Interaction::default(),
+                }
+            }
+            Some(position)
+                if self.on_color_click.is_some()
+                    && self
+                        .swatch_at(
+                            renderer,
+                            layout.children().next().unwrap(),
+                            state.line_map.line(self.row_at(
+                                renderer,
+                                state,
+                                position.y - bounds.y,
+                            )),
+                            position.x,
+                        )
+                        .is_some() =>
+            {
+                mouse::Interaction::Pointer
+            }
+            Some(position) => self.editor.mouse_interaction(
+                &tree.children[0],
+                layout.children().next().unwrap(),
+                mouse::Cursor::Available(self.to_editor(renderer, state, bounds, position)),
+                &(bounds + Vector::new(0.0, state.offset)),
+                renderer,
+            ),
+            None => mouse::Interaction::default(),
+        }
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &iced::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let state = tree.state.downcast_ref::<State>();
+        let editor_layout = layout.children().next().unwrap();
+        let editor_style = text_editor::default(theme, text_editor::Status::Active);
+        let editor_cursor = match cursor.position_over(bounds) {
+            Some(position) => {
+                mouse::Cursor::Available(self.to_editor(renderer, state, bounds, position))
+            }
+            None => mouse::Cursor::Unavailable,
+        };
+        let line_height = self.line_height_px(renderer);
+        let gutter = self.gutter_layout(renderer);
+        let gutter_width = gutter.width();
+        let text_area = Rectangle {
+            x: bounds.x + gutter_width,
+            width: (bounds.width - gutter_width - self.scrollbar_width()).max(0.0),
+            ..bounds
+        };
+        let visible = self.visible_rows(renderer, state, bounds.height);
+        //where row 0 is drawn
+        let top = bounds.y + self.padding.top - state.offset;
+
+        renderer.with_layer(bounds, |renderer| {
+            //the editor might be shorter than the widget
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds,
+                    ..Default::default()
+                },
+                editor_style.background,
+            );
+
+            //the wrapped editor has no background, so these end up behind selection and text
+            if !self.read_only.is_empty() {
+                let background =
+                    read_only_background(&editor_style, background_color(theme, &editor_style));
+                for row in visible.clone() {
+                    if !self.is_read_only_line(state.line_map.line(row)) {
+                        continue;
+                    }
+                    renderer.fill_quad(
+                        renderer::Quad {
+                            bounds: Rectangle {
+                                y: top + row as f32 * line_height,
+                                height: line_height,
+                                ..text_area
+                            },
+                            ..Default::default()
+                        },
+                        background,
+                    );
+                }
+            }
+
+            if self.highlight_current_line {
+                let row = state.line_map.row(self.content.cursor_position().0);
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle {
+                            y: top + row as f32 * line_height,
+                            height: line_height,
+                            ..bounds
+                        },
+                        ..Default::default()
+                    },
+                    self.line_background(theme, &editor_style, self.content.cursor_position().0),
+                );
+            }
+
+            let segments = state.line_map.segments(self.content.line_count());
+            let last = segments.len().saturating_sub(1);
+            for (idx, lines) in segments.into_iter().enumerate() {
+                let first_row = state.line_map.row(lines.start);
+                let rows = first_row..first_row + lines.len();
+                let shown = rows.start.max(visible.start)..rows.end.min(visible.end);
+                if shown.is_empty() {
+                    continue;
+                }
+
+                //the first and last segment include the padding
+                let clip_top = if idx == 0 {
+                    bounds.y
+                } else {
+                    top + rows.start as f32 * line_height
+                };
+                let clip_bottom = if idx == last {
+                    bounds.y + bounds.height
+                } else {
+                    top + rows.end as f32 * line_height
+                };
+                let Some(clip) = text_area.intersection(&Rectangle {
+                    y: clip_top,
+                    height: clip_bottom - clip_top,
+                    ..text_area
+                }) else {
+                    continue;
+                };
+
+                let translation = Vector::new(
+                    0.0,
+                    (rows.start as f32 - lines.start as f32) * line_height - state.offset,
+                );
+                renderer.with_layer(clip, |renderer| {
+                    renderer.with_translation(translation, |renderer| {
+                        self.editor.draw(
+                            &tree.children[0],
+                            renderer,
+                            theme,
+                            style,
+                            editor_layout,
+                            editor_cursor,
+                            &(bounds - translation),
+                        );
+
+                        //a layer of its own, so decorations are drawn on top of the editor's text
+                        renderer.with_layer(clip - translation, |renderer| {
+                            let text_bounds = editor_layout.bounds().shrink(self.padding);
+                            let lines = lines.start + (shown.start.max(rows.start) - rows.start)
+                                ..lines.start + (shown.end.max(rows.start) - rows.start);
+                            self.draw_decorations(
+                                renderer,
+                                theme,
+                                text_bounds,
+                                lines,
+                                &state.line_map,
+                                &editor_style,
+                            );
+                        });
+                    });
+                });
+            }
+
+            if gutter_width > 0.0 {
+                let hovered = cursor.position_over(bounds).and_then(|position| {
+                    Some((
+                        self.row_at(renderer, state, position.y - bounds.y),
+                        gutter.column_at(position.x - bounds.x)?,
+                    ))
+                });
+                self.draw_gutter(
+                    renderer,
+                    theme,
+                    bounds,
+                    top,
+                    visible,
+                    &state.line_map,
+                    hovered,
+                    &editor_style,
+                );
+            }
+
+            if let Some(track) = self.scrollbar_track(bounds) {
+                self.draw_scrollbar(renderer, theme, state, track, &editor_style);
+            }
+
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds,
+                    border: editor_style.border,
+                    ..Default::default()
+                },
+                iced::Color::TRANSPARENT,
+            );
+        });
+    }
+}
+
+impl<'a, Message: 'a> From<CodeEditor<'a, Message>> for Element<'a, Message> {
+    fn from(editor: CodeEditor<'a, Message>) -> Self {
+        Element::new(editor)
+    }
+}
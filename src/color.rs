@@ -0,0 +1,234 @@
+//! sRGB aware color helpers.
+//!
+//! Iced's [Color] stores its channels in (gamma encoded) sRGB space. Averaging those channels directly
+//! makes blends look muddy and too dark, so everything in here converts to linear space first, does the math there,
+//! and converts back. Used by the themes and overlays of this crate, but feel free to use them in your application as well.
+//!
+//! [color_literals] finds colors written in source text, like `#ff8800` or `rgb(255, 136, 0)`.
+
+use std::ops::Range;
+
+use iced_core::Color;
+
+///Linearly interpolates from `a` to `b` by `t` (`0.0` is `a`, `1.0` is `b`). Alpha is interpolated as well.
+pub fn mix(a: Color, b: Color, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let a = a.into_linear();
+    let b = b.into_linear();
+    let lerp = |i: usize| a[i] + (b[i] - a[i]) * t;
+
+    Color::from_linear_rgba(lerp(0), lerp(1), lerp(2), lerp(3))
+}
+
+///Composits `foreground` over `background` using the foreground's alpha ("source-over").
+pub fn blend_over(foreground: Color, background: Color) -> Color {
+    let fg = foreground.into_linear();
+    let bg = background.into_linear();
+
+    let alpha = fg[3] + bg[3] * (1.0 - fg[3]);
+    if alpha <= f32::EPSILON {
+        return Color::TRANSPARENT;
+    }
+
+    let channel = |i: usize| (fg[i] * fg[3] + bg[i] * bg[3] * (1.0 - fg[3])) / alpha;
+    Color::from_linear_rgba(channel(0), channel(1), channel(2), alpha)
+}
+
+///Tints `background` with `tint` by `amount`. Same as [mix], but the result keeps the background's alpha,
+///which is what you want for selection/diff/diagnostic backgrounds.
+pub fn tint(background: Color, tint: Color, amount: f32) -> Color {
+    Color {
+        a: background.a,
+        ..mix(background, tint, amount)
+    }
+}
+
+///Relative luminance as defined by [WCAG 2](https://www.w3.org/TR/WCAG21/#dfn-relative-luminance). `0.0` is black, `1.0` white.
+pub fn relative_luminance(color: Color) -> f32 {
+    let [r, g, b, _] = color.into_linear();
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+///WCAG contrast ratio between two colors, in the range `1.0..=21.0`. The order of the arguments does not matter.
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let la = relative_luminance(a);
+    let lb = relative_luminance(b);
+    let (light, dark) = if la > lb { (la, lb) } else { (lb, la) };
+    (light + 0.05) / (dark + 0.05)
+}
+
+///Moves `color` towards black or white until it has at least a contrast ratio of `min_ratio` to `background`.
+///Colors that already have enough contrast are returned unchanged.
+///
+///Lighter-than-background colors are brightened, darker ones darkened. Only if that can't reach `min_ratio` the other direction is
+///taken. If `min_ratio` can not be reached at all (e.g. values above 21), the most contrasting color possible is returned.
+pub fn ensure_contrast(color: Color, background: Color, min_ratio: f32) -> Color {
+    if contrast_ratio(color, background) >= min_ratio {
+        return color;
+    }
+
+    let white = Color {
+        a: color.a,
+        ..Color::WHITE
+    };
+    let black = Color {
+        a: color.a,
+        ..Color::BLACK
+    };
+    let (same_side, other_side) = if relative_luminance(color) >= relative_luminance(background) {
+        (white, black)
+    } else {
+        (black, white)
+    };
+
+    let target = if contrast_ratio(same_side, background) >= min_ratio {
+        same_side
+    } else if contrast_ratio(other_side, background) >= min_ratio {
+        other_side
+    } else if contrast_ratio(same_side, background) >= contrast_ratio(other_side, background) {
+        return same_side;
+    } else {
+        return other_side;
+    };
+
+    //Along the path to the target the contrast might first drop, but once it is above `min_ratio` it stays there.
+    //So bisecting for the first "good enough" point is fine.
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..16 {
+        let t = (low + high) / 2.0;
+        if contrast_ratio(mix(color, target, t), background) >= min_ratio {
+            high = t;
+        } else {
+            low = t;
+        }
+    }
+
+    mix(color, target, high)
+}
+
+///How a [ColorLiteral] is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Notation {
+    ///`#rgb` or `#rrggbb`.
+    Hex,
+    ///`#rgba` or `#rrggbbaa`.
+    HexAlpha,
+    ///`rgb(r, g, b)`, channels from 0 to 255.
+    Rgb,
+    ///`rgba(r, g, b, a)`, alpha from 0.0 to 1.0.
+    Rgba,
+}
+
+///A color written in source text, see [color_literals].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorLiteral {
+    ///Byte range of the literal in the searched text.
+    pub range: Range<usize>,
+    pub color: Color,
+    pub notation: Notation,
+}
+
+impl ColorLiteral {
+    ///`color` written in the literal's notation, to replace the literal's range with. Short hex literals are written out in full.
+    pub fn replacement(&self, color: Color) -> String {
+        let [r, g, b, a] = color.into_rgba8();
+        match self.notation {
+            Notation::Hex => format!("#{r:02x}{g:02x}{b:02x}"),
+            Notation::HexAlpha => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+            Notation::Rgb => format!("rgb({r}, {g}, {b})"),
+            Notation::Rgba => format!("rgba({r}, {g}, {b}, {})", (color.a * 100.0).round() / 100.0),
+        }
+    }
+}
+
+///All hex (`#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`) and functional (`rgb(..)`, `rgba(..)`) color literals in `text`, in order.
+///
+///This is a plain text search, so it also finds literals in comments and strings, which is usually what you want.
+pub fn color_literals(text: &str) -> Vec<ColorLiteral> {
+    let bytes = text.as_bytes();
+    let is_word = |idx: usize| {
+        bytes
+            .get(idx)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_')
+    };
+
+    let mut literals = Vec::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let starts_word = idx == 0 || !is_word(idx - 1);
+        let literal = match bytes[idx] {
+            b'#' => parse_hex(text, idx),
+            b'r' if starts_word => parse_rgb(text, idx),
+            _ => None,
+        };
+        match literal {
+            Some(literal) if !is_word(literal.range.end) => {
+                idx = literal.range.end;
+                literals.push(literal);
+            }
+            _ => idx += 1,
+        }
+    }
+    literals
+}
+
+fn parse_hex(text: &str, start: usize) -> Option<ColorLiteral> {
+    let digits = text[start + 1..]
+        .bytes()
+        .take_while(u8::is_ascii_hexdigit)
+        .count();
+    let hex = &text[start + 1..start + 1 + digits];
+    let channel = |idx: usize, len: usize| {
+        let value = u8::from_str_radix(&hex[idx * len..(idx + 1) * len], 16).ok()?;
+        //`#f80` is short for `#ff8800`
+        Some(if len == 1 { value * 17 } else { value })
+    };
+    let (len, notation) = match digits {
+        3 => (1, Notation::Hex),
+        4 => (1, Notation::HexAlpha),
+        6 => (2, Notation::Hex),
+        8 => (2, Notation::HexAlpha),
+        _ => return None,
+    };
+    let alpha = match notation {
+        Notation::HexAlpha => channel(3, len)? as f32 / 255.0,
+        _ => 1.0,
+    };
+    Some(ColorLiteral {
+        range: start..start + 1 + digits,
+        color: Color::from_rgba8(channel(0, len)?, channel(1, len)?, channel(2, len)?, alpha),
+        notation,
+    })
+}
+
+fn parse_rgb(text: &str, start: usize) -> Option<ColorLiteral> {
+    let rest = &text[start..];
+    let (notation, open) = if rest.starts_with("rgba(") {
+        (Notation::Rgba, 5)
+    } else if rest.starts_with("rgb(") {
+        (Notation::Rgb, 4)
+    } else {
+        return None;
+    };
+    let close = rest[open..].find(')')? + open;
+    let args: Vec<&str> = rest[open..close].split(',').map(str::trim).collect();
+
+    let channels = args
+        .iter()
+        .take(3)
+        .map(|arg| arg.parse::<u8>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let alpha = match (notation, args.len()) {
+        (Notation::Rgb, 3) => 1.0,
+        (Notation::Rgba, 4) => args[3]
+            .parse::<f32>()
+            .ok()
+            .filter(|a| (0.0..=1.0).contains(a))?,
+        _ => return None,
+    };
+    Some(ColorLiteral {
+        range: start..start + close + 1,
+        color: Color::from_rgba8(channels[0], channels[1], channels[2], alpha),
+        notation,
+    })
+}
@@ -0,0 +1,474 @@
+//! Detects the language of a file from its name, its extension, its shebang (`#!/usr/bin/env python3`) or a Vim or Emacs modeline
+//! (`vim: ft=ruby`, `-*- mode: ruby -*-`). If all of that fails, [by_content] makes a guess from what the text looks like.
+//!
+//! The `language_*` functions return a `language_name` (like `rust`), so they also work for grammars the app brings itself. With a
+//! bundled language enabled (see the `languages` module), `detect`, `from_path`, `from_shebang`, `from_modeline` and `from_content` return ready-to-use settings.
+//!
+//! Org isn't detected: the app brings its grammar (see `TSSettings::org`), so it knows its files better.
+
+use std::path::Path;
+
+#[cfg(feature = "any-lang")]
+use crate::{TSSettings, languages};
+
+///File extensions, and the `language_name` they are written in.
+pub const EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("pyw", "python"),
+    ("toml", "toml"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("json", "json"),
+    ("jsonc", "json"),
+    ("geojson", "json"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascript"),
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("adoc", "asciidoc"),
+    ("asciidoc", "asciidoc"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("xhtml", "html"),
+    ("css", "css"),
+    ("sh", "bash"),
+    ("bash", "bash"),
+    ("rb", "ruby"),
+    ("lua", "lua"),
+    ("pl", "perl"),
+    ("php", "php"),
+];
+
+///Files that are recognized by their whole name, because they have no (or a misleading) extension.
+pub const FILE_NAMES: &[(&str, &str)] = &[
+    ("Cargo.lock", "toml"),
+    ("Pipfile", "toml"),
+    ("poetry.lock", "toml"),
+    ("uv.lock", "toml"),
+    (".babelrc", "json"),
+    (".eslintrc", "json"),
+    ("SConstruct", "python"),
+    ("SConscript", "python"),
+    (".bashrc", "bash"),
+    (".bash_profile", "bash"),
+    (".profile", "bash"),
+    ("Gemfile", "ruby"),
+    ("Rakefile", "ruby"),
+];
+
+///Interpreters of shebang lines, without their version (`python3.11` is looked up as `python`).
+pub const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("pypy", "python"),
+    ("node", "javascript"),
+    ("nodejs", "javascript"),
+    ("deno", "javascript"),
+    ("bun", "javascript"),
+    ("sh", "bash"),
+    ("bash", "bash"),
+    ("dash", "bash"),
+    ("zsh", "bash"),
+    ("ksh", "bash"),
+    ("ruby", "ruby"),
+    ("lua", "lua"),
+    ("luajit", "lua"),
+    ("perl", "perl"),
+    ("php", "php"),
+];
+
+///Filetypes of Vim and modes of Emacs that go by other names than the `language_name`. Names not listed are taken as they are
+///if some other table knows them.
+pub const MODE_NAMES: &[(&str, &str)] = &[
+    ("js", "javascript"),
+    ("js2", "javascript"),
+    ("rjsx", "javascript"),
+    ("sh", "bash"),
+    ("shell-script", "bash"),
+    ("zsh", "bash"),
+    ("conf-toml", "toml"),
+    ("cperl", "perl"),
+    ("python3", "python"),
+    ("adoc", "asciidoc"),
+];
+
+///Starts of lines that hint at a language, see [by_content]. A hint ending in a letter has to be followed by something else than
+///a letter, digit or `_`, so `fi` doesn't match `final`.
+pub const CONTENT_HINTS: &[(&str, &str)] = &[
+    ("fn ", "rust"),
+    ("pub fn ", "rust"),
+    ("pub struct ", "rust"),
+    ("impl ", "rust"),
+    ("impl<", "rust"),
+    ("let mut ", "rust"),
+    ("use std::", "rust"),
+    ("#[derive(", "rust"),
+    ("def ", "python"),
+    ("elif ", "python"),
+    ("from ", "python"),
+    ("if __name__", "python"),
+    ("self.", "python"),
+    ("print(", "python"),
+    ("function ", "javascript"),
+    ("const ", "javascript"),
+    ("var ", "javascript"),
+    ("export default", "javascript"),
+    ("console.", "javascript"),
+    ("module.exports", "javascript"),
+    ("echo", "bash"),
+    ("fi", "bash"),
+    ("esac", "bash"),
+    ("if [", "bash"),
+    ("puts", "ruby"),
+    ("local function", "lua"),
+    ("use strict;", "perl"),
+    ("my $", "perl"),
+    ("<?php", "php"),
+    ("[source,", "asciidoc"),
+    ("#include", "c"),
+    ("SELECT ", "sql"),
+    ("CREATE TABLE", "sql"),
+    ("INSERT INTO", "sql"),
+    ("## ", "markdown"),
+    ("```", "markdown"),
+];
+
+///Lines [by_content] looks at.
+const SNIFF_LINES: usize = 200;
+
+///Lines at the start and the end of a text that are searched for a modeline, like Vim does by default.
+const MODELINE_LINES: usize = 5;
+
+///Language of the file at `path`, by its name or extension (case doesn't matter).
+pub fn language_from_path(path: impl AsRef<Path>) -> Option<&'static str> {
+    let path = path.as_ref();
+    let name = path.file_name()?.to_str()?;
+    if let Some((_, language)) = FILE_NAMES.iter().find(|(file, _)| *file == name) {
+        return Some(language);
+    }
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, language)| *language)
+}
+
+///Language of the interpreter `first_line` names, if it is a shebang like `#!/bin/bash` or `#!/usr/bin/env -S python3 -u`.
+pub fn language_from_shebang(first_line: &str) -> Option<&'static str> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        //skip env's flags and variables
+        interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(known, _)| *known == interpreter)
+        .map(|(_, language)| *language)
+}
+
+///Language a Vim modeline (`vim: ft=ruby`, `vim: set filetype=ruby:`) or Emacs modeline (`-*- mode: ruby -*-`, `-*- ruby -*-`)
+///sets, within the first or last few lines of `text`.
+pub fn language_from_modeline(text: &str) -> Option<&'static str> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let end = lines
+        .len()
+        .saturating_sub(MODELINE_LINES)
+        .max(MODELINE_LINES);
+    lines
+        .iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(end))
+        .find_map(|line| modeline(line))
+        .and_then(known_language)
+}
+
+///Filetype or mode `line` sets, if it contains a modeline.
+fn modeline(line: &str) -> Option<&str> {
+    if let Some((_, rest)) = line.split_once("-*-") {
+        let (variables, _) = rest.split_once("-*-")?;
+        if !variables.contains(':') {
+            return Some(variables.trim());
+        }
+        return variables.split(';').find_map(|variable| {
+            let (key, value) = variable.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("mode")
+                .then(|| value.trim())
+        });
+    }
+
+    //Vim needs white space in front of the marker, unless it starts the line
+    let options = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker).find_map(|(at, _)| {
+            (at == 0 || line[..at].ends_with(char::is_whitespace))
+                .then(|| &line[at + marker.len()..])
+        })
+    })?;
+    options
+        .split([' ', '\t', ':'])
+        .find_map(|option| match option.split_once('=')? {
+            ("ft" | "filetype" | "syn" | "syntax", value) => Some(value),
+            _ => None,
+        })
+}
+
+///`language_name` of a filetype or mode like `ruby` or `js2-mode`.
+fn known_language(name: &str) -> Option<&'static str> {
+    let name = name.to_ascii_lowercase();
+    let name = name.strip_suffix("-mode").unwrap_or(&name);
+    MODE_NAMES
+        .iter()
+        .chain(EXTENSIONS)
+        .chain(INTERPRETERS)
+        .find(|(known, language)| *known == name || *language == name)
+        .map(|(_, language)| *language)
+}
+
+///Probable language of `text`, guessed from its first lines: the structure of JSON, XML and HTML, table headers of TOML, the
+///[hints](CONTENT_HINTS) of other languages (`fn main()` is likely Rust), and YAML if most lines are `key: value` pairs. `None`
+///if nothing hints at a language, or two languages are equally likely. Meant as the last resort after the other detectors, and
+///for [pasted](crate::document::Document::paste_hint) text.
+pub fn by_content(text: &str) -> Option<&'static str> {
+    let trimmed = text.trim();
+    let lowercase = trimmed
+        .chars()
+        .take(16)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if lowercase.starts_with("<?xml") {
+        return Some("xml");
+    }
+    if lowercase.starts_with("<!doctype html") || lowercase.starts_with("<html") {
+        return Some("html");
+    }
+    let mut chars = trimmed.chars().filter(|c| !c.is_whitespace());
+    let json = match (chars.next(), chars.next()) {
+        (Some('{'), Some('"' | '}')) => trimmed.ends_with('}'),
+        (Some('['), Some('{' | '"' | ']' | '-' | '0'..='9')) => trimmed.ends_with(']'),
+        _ => false,
+    };
+    if json {
+        return Some("json");
+    }
+
+    let mut scores: Vec<(&str, usize)> = Vec::new();
+    for line in text.lines().take(SNIFF_LINES).map(str::trim) {
+        let hinted = CONTENT_HINTS
+            .iter()
+            .filter(|(hint, _)| hints(line, hint))
+            .map(|(_, language)| *language)
+            .chain(is_table_header(line).then_some("toml"));
+        for language in hinted {
+            match scores.iter_mut().find(|(known, _)| *known == language) {
+                Some((_, score)) => *score += 1,
+                None => scores.push((language, 1)),
+            }
+        }
+    }
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    match scores.as_slice() {
+        [] => is_yaml(text).then_some("yaml"),
+        [(language, _)] => Some(language),
+        [(language, best), (_, second), ..] if best > second => Some(language),
+        _ => None,
+    }
+}
+
+///Whether most of the first lines are `key: value` pairs or items of lists of them.
+fn is_yaml(text: &str) -> bool {
+    let lines = text
+        .lines()
+        .take(SNIFF_LINES)
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>();
+    let pairs = lines
+        .iter()
+        .filter(|line| {
+            line.trim_start_matches("- ")
+                .split_once(':')
+                .is_some_and(|(key, value)| {
+                    !key.is_empty()
+                        && !key.contains(' ')
+                        && (value.is_empty() || value.starts_with(' '))
+                })
+        })
+        .count();
+    !lines.is_empty() && pairs * 2 >= lines.len()
+}
+
+fn hints(line: &str, hint: &str) -> bool {
+    let Some(rest) = line.strip_prefix(hint) else {
+        return false;
+    };
+    !hint.ends_with(|c: char| c.is_ascii_alphabetic())
+        || !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+///`[table]` or `[[array.of.tables]]`.
+fn is_table_header(line: &str) -> bool {
+    let Some(name) = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+    else {
+        return false;
+    };
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '"' | '\'' | ' '))
+}
+
+///Language of the file at `path` with the content `text`: by its modeline first, as it overrides the file type, then by its name,
+///then by its shebang, and if all that fails [by_content].
+pub fn language(path: impl AsRef<Path>, text: &str) -> Option<&'static str> {
+    language_from_modeline(text)
+        .or_else(|| language_from_path(path))
+        .or_else(|| language_from_shebang(text.lines().next()?))
+        .or_else(|| by_content(text))
+}
+
+///Settings for the bundled language of the file at `path` with the content `text`, see [language].
+#[cfg(feature = "any-lang")]
+pub fn detect(path: impl AsRef<Path>, text: &str) -> Option<TSSettings> {
+    languages::by_name(language(path, text)?)
+}
+
+///Settings for the bundled language of the file at `path`, see [Language::from_path](languages::Language::from_path).
+#[cfg(feature = "any-lang")]
+pub fn from_path(path: impl AsRef<Path>) -> Option<TSSettings> {
+    languages::Language::from_path(path.as_ref())
+}
+
+///Settings for the bundled language of a script starting with `first_line`, see [language_from_shebang].
+#[cfg(feature = "any-lang")]
+pub fn from_shebang(first_line: &str) -> Option<TSSettings> {
+    languages::by_name(language_from_shebang(first_line)?)
+}
+
+///Settings for the bundled language the modeline of `text` sets, see [language_from_modeline].
+#[cfg(feature = "any-lang")]
+pub fn from_modeline(text: &str) -> Option<TSSettings> {
+    languages::by_name(language_from_modeline(text)?)
+}
+
+///Settings for the bundled language `text` is probably written in, see [by_content].
+#[cfg(feature = "any-lang")]
+pub fn from_content(text: &str) -> Option<TSSettings> {
+    languages::by_name(by_content(text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_by_the_name_and_extension() {
+        assert_eq!(language_from_path("src/main.rs"), Some("rust"));
+        assert_eq!(language_from_path("/tmp/README.MD"), Some("markdown"));
+        assert_eq!(language_from_path("Cargo.lock"), Some("toml"));
+        assert_eq!(language_from_path("project/Gemfile"), Some("ruby"));
+        assert_eq!(language_from_path(".bashrc"), Some("bash"));
+        assert_eq!(language_from_path("notes.org"), None);
+        assert_eq!(language_from_path("Makefile"), None);
+        assert_eq!(language_from_path(""), None);
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn bundled_languages_by_path() {
+        use crate::languages::Language;
+
+        let settings = Language::from_path(Path::new("lib.rs")).unwrap();
+        assert_eq!(settings.tsconfig.language_name, "rust");
+        assert!(from_path("build.RS").is_some());
+        assert!(Language::from_path(Path::new("script.rb")).is_none());
+    }
+
+    #[test]
+    fn detects_by_the_shebang() {
+        assert_eq!(language_from_shebang("#!/bin/bash"), Some("bash"));
+        assert_eq!(
+            language_from_shebang("#!/usr/bin/python3.11"),
+            Some("python")
+        );
+        assert_eq!(
+            language_from_shebang("#!/usr/bin/env node"),
+            Some("javascript")
+        );
+        assert_eq!(
+            language_from_shebang("#!/usr/bin/env -S FOO=1 python3 -u"),
+            Some("python")
+        );
+        assert_eq!(language_from_shebang("#!/usr/bin/env"), None);
+        assert_eq!(language_from_shebang("#!/usr/bin/unknown"), None);
+        assert_eq!(language_from_shebang("# /bin/bash"), None);
+    }
+
+    #[test]
+    fn detects_by_the_modeline() {
+        assert_eq!(
+            language_from_modeline("# vim: ft=ruby\nputs 1"),
+            Some("ruby")
+        );
+        assert_eq!(
+            language_from_modeline("x = 1\n# vim: set filetype=python ts=4:"),
+            Some("python")
+        );
+        assert_eq!(
+            language_from_modeline("// -*- mode: js2; tab-width: 2 -*-"),
+            Some("javascript")
+        );
+        assert_eq!(
+            language_from_modeline("# -*- Shell-Script -*-"),
+            Some("bash")
+        );
+        //only the first and last lines, and Vim wants white space in front of the marker
+        let middle = format!("{}# vim: ft=ruby\n{}", "\n".repeat(10), "\n".repeat(10));
+        assert_eq!(language_from_modeline(&middle), None);
+        assert_eq!(language_from_modeline("novim: ft=ruby"), None);
+        assert_eq!(language_from_modeline("# vim: ft=cobol"), None);
+        assert_eq!(language_from_modeline("# -*- coding: utf-8 -*-"), None);
+    }
+
+    #[test]
+    fn guesses_by_the_content() {
+        assert_eq!(by_content("{\n  \"a\": [1, 2]\n}"), Some("json"));
+        assert_eq!(by_content("<!DOCTYPE html>\n<html></html>"), Some("html"));
+        assert_eq!(by_content("<?xml version=\"1.0\"?>"), Some("xml"));
+        assert_eq!(by_content("[package]\nname = \"x\"\n[[bin]]"), Some("toml"));
+        assert_eq!(
+            by_content("use std::fmt;\n\nfn main() {\n    let mut x = 1;\n}"),
+            Some("rust")
+        );
+        assert_eq!(
+            by_content("def f(self):\n    print(self.x)"),
+            Some("python")
+        );
+        assert_eq!(by_content("SELECT *\nFROM t;"), Some("sql"));
+        assert_eq!(
+            by_content("name: app\nitems:\n  - id: 1\n    tag: x"),
+            Some("yaml")
+        );
+        //`final` isn't bash's `fi`
+        assert_eq!(by_content("final x"), None);
+        //a tie
+        assert_eq!(by_content("def f():\n    pass\nfn g() {}"), None);
+        assert_eq!(by_content(""), None);
+        assert_eq!(by_content("Just some words."), None);
+    }
+
+    #[test]
+    fn the_modeline_overrides_the_extension() {
+        assert_eq!(language("build.py", "# vim: ft=ruby"), Some("ruby"));
+        assert_eq!(language("build.py", "x = 1"), Some("python"));
+        assert_eq!(language("build", "#!/bin/sh\nls"), Some("bash"));
+        assert_eq!(language("build", "fn main() {}"), Some("rust"));
+    }
+}
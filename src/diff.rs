@@ -0,0 +1,628 @@
+//! Syntax highlighted diffs between two versions of a text, e.g. for code-review UIs.
+//!
+//! [Diff::new] compares line by line. Lines that were changed (rather than added or removed) are additionally compared word by word, so the host
+//! can emphasize what exactly changed within the line. Both versions are highlighted as a whole, so the spans are correct even within multi-line constructs.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{Highlight, TSSettings, document::Document, search::is_word_char};
+
+///Edit distance (in lines or words) after which [Diff::new] gives up and reports everything as replaced.
+///Keeps the diff of two completely unrelated files from taking forever.
+pub const MAX_EDIT_DISTANCE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Unchanged,
+    ///Only in the old version.
+    Removed,
+    ///Only in the new version.
+    Added,
+}
+
+///How a line differs from an older version, see [line_changes].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineChange {
+    Added,
+    Modified,
+    ///Lines were removed in front of this line.
+    Removed,
+}
+
+///Changed lines of `new` compared to `old`, by zero based line of `new`. Meant for marking uncommitted changes in a
+///[gutter](crate::code_editor::GutterColumn::Changes), use [Diff] to show the changes themselves.
+///
+///Within a block of changes, as many added lines as were removed count as modified. Blocks that only removed lines are reported at
+///the line after them, or at the last line if there is none.
+pub fn line_changes(old: &str, new: &str) -> BTreeMap<usize, LineChange> {
+    let lines = |text: &str| -> Vec<String> {
+        text.split('\n')
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect()
+    };
+    let old_lines = lines(old);
+    let new_lines = lines(new);
+
+    let mut changes = BTreeMap::new();
+    let mut removed = 0;
+    let mut added = Vec::new();
+    let edits = edits(&old_lines, &new_lines);
+    for edit in edits.into_iter().map(Some).chain([None]) {
+        let next = match edit {
+            Some(Edit::Delete(_)) => {
+                removed += 1;
+                continue;
+            }
+            Some(Edit::Insert(n)) => {
+                added.push(n);
+                continue;
+            }
+            Some(Edit::Equal(_, n)) => n,
+            None => new_lines.len() - 1,
+        };
+        if added.is_empty() && removed > 0 {
+            changes.insert(next, LineChange::Removed);
+        }
+        for (idx, line) in added.drain(..).enumerate() {
+            let change = if idx < removed {
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            };
+            changes.insert(line, change);
+        }
+        removed = 0;
+    }
+    changes
+}
+
+///Whether `new` plausibly means something else than `old`: compares the syntax trees, ignoring whitespace and comments. Meant for
+///"dirty" indicators that shouldn't light up for formatting-only edits. See [Document::same_structure] for documents that exist
+///already.
+pub fn semantics_changed(settings: &TSSettings, old: &str, new: &str) -> bool {
+    !Document::new(settings, old).same_structure(&Document::new(settings, new))
+}
+
+impl Document {
+    ///Whether both documents have the same syntax tree, apart from comments, with the same text in every token. Whitespace between
+    ///tokens doesn't matter, in string literals it does. Documents that don't parse cleanly are compared as text, since their trees
+    ///are guesses.
+    pub fn same_structure(&self, other: &Document) -> bool {
+        self.settings().report_feature("structural_diff");
+        let (Some(tree), Some(other_tree)) = (self.tree(), other.tree()) else {
+            return self.text() == other.text();
+        };
+        if tree.root_node().has_error() || other_tree.root_node().has_error() {
+            return self.text() == other.text();
+        }
+        structure(self.text(), tree.root_node()) == structure(other.text(), other_tree.root_node())
+    }
+}
+
+///Nodes of the tree below `root` in preorder, without comments: kind, number of children (so `a(b)` and `a b` differ), and the text
+///of tokens.
+fn structure<'a>(text: &'a str, root: tree_sitter::Node<'_>) -> Vec<(u16, usize, Option<&'a str>)> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        let children = node
+            .children(&mut cursor)
+            .filter(|child| !child.kind().contains("comment"))
+            .collect::<Vec<_>>();
+        let token = children.is_empty().then(|| &text[node.byte_range()]);
+        nodes.push((node.kind_id(), children.len(), token));
+        stack.extend(children.into_iter().rev());
+    }
+    nodes
+}
+
+///Part of a [DiffLine] with uniform highlighting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffSpan {
+    ///Byte range within the line.
+    pub columns: Range<usize>,
+    ///Syntax highlighting, resolve it via [to_format](crate::to_format) like editor spans.
+    pub highlight: Highlight,
+    ///Whether this part differs from the other version. Always `false` for unchanged lines.
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub kind: ChangeKind,
+    ///Zero based line number in the old version, if the line is part of it.
+    pub old_line: Option<usize>,
+    ///Zero based line number in the new version, if the line is part of it.
+    pub new_line: Option<usize>,
+    ///Content of the line, excluding the line break.
+    pub text: String,
+    ///Spans covering the whole `text`, in order.
+    pub spans: Vec<DiffSpan>,
+}
+
+///A row of a side-by-side view. Unchanged lines are on both sides, changed lines are paired up, and additions/removals are opposite of a gap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffRow {
+    pub old: Option<DiffLine>,
+    pub new: Option<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    rows: Vec<DiffRow>,
+}
+
+impl Diff {
+    ///Diffs `old` against `new`, both are highlighted with `settings`.
+    pub fn new(settings: &TSSettings, old: &str, new: &str) -> Self {
+        settings.report_feature("diff");
+        let old = Side::new(settings, old);
+        let new = Side::new(settings, new);
+
+        let old_lines: Vec<&str> = (0..old.document.line_count())
+            .map(|l| old.document.line(l).unwrap_or_default())
+            .collect();
+        let new_lines: Vec<&str> = (0..new.document.line_count())
+            .map(|l| new.document.line(l).unwrap_or_default())
+            .collect();
+
+        let mut rows = Vec::new();
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for edit in edits(&old_lines, &new_lines) {
+            match edit {
+                Edit::Equal(o, n) => {
+                    flush_block(&old, &new, &mut removed, &mut added, &mut rows);
+                    rows.push(DiffRow {
+                        old: Some(old.line(o, Some(n), ChangeKind::Unchanged, &[])),
+                        new: Some(new.line(n, Some(o), ChangeKind::Unchanged, &[])),
+                    });
+                }
+                Edit::Delete(o) => removed.push(o),
+                Edit::Insert(n) => added.push(n),
+            }
+        }
+        flush_block(&old, &new, &mut removed, &mut added, &mut rows);
+
+        Self { rows }
+    }
+
+    ///Rows for a side-by-side view, old version on the left.
+    pub fn side_by_side(&self) -> &[DiffRow] {
+        &self.rows
+    }
+
+    ///Lines for a unified view. Within each block of changes the removed lines come first.
+    pub fn unified(&self) -> Vec<&DiffLine> {
+        let mut lines = Vec::new();
+        let mut added = Vec::new();
+        for row in &self.rows {
+            match (&row.old, &row.new) {
+                (Some(old), Some(new)) if old.kind == ChangeKind::Unchanged => {
+                    lines.append(&mut added);
+                    lines.push(new);
+                }
+                (old, new) => {
+                    lines.extend(old.as_ref());
+                    added.extend(new.as_ref());
+                }
+            }
+        }
+        lines.append(&mut added);
+        lines
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.rows.iter().any(|row| {
+            row.old
+                .as_ref()
+                .is_none_or(|l| l.kind != ChangeKind::Unchanged)
+        })
+    }
+}
+
+///One version of the text, with its highlights.
+struct Side {
+    document: Document,
+    highlights: Vec<(Range<usize>, Highlight)>,
+}
+
+impl Side {
+    fn new(settings: &TSSettings, text: &str) -> Self {
+        let document = Document::new(settings, text);
+        let highlights = document.highlights();
+        Self {
+            document,
+            highlights,
+        }
+    }
+
+    ///Builds the [DiffLine] for `line`. `other` is the line number in the other version (for unchanged lines), `changed` are the
+    ///column ranges that differ.
+    fn line(
+        &self,
+        line: usize,
+        other: Option<usize>,
+        kind: ChangeKind,
+        changed: &[Range<usize>],
+    ) -> DiffLine {
+        let range = self.document.line_range(line).unwrap_or(0..0);
+        let (old_line, new_line) = match kind {
+            ChangeKind::Removed => (Some(line), None),
+            ChangeKind::Added => (None, Some(line)),
+            ChangeKind::Unchanged => (other, Some(line)),
+        };
+
+        //highlighted pieces of the line, with plain gaps in between
+        let plain = Highlight {
+            capture: None,
+            style: None,
+            dimmed: false,
+            overlay: None,
+            names: self.document.settings().capture_map(),
+        };
+        let mut pieces = Vec::new();
+        let mut cursor = range.start;
+        let first = self
+            .highlights
+            .partition_point(|(span, _)| span.end <= range.start);
+        for (span, highlight) in &self.highlights[first..] {
+            if span.start >= range.end {
+                break;
+            }
+            let start = span.start.max(range.start);
+            if cursor < start {
+                pieces.push((cursor..start, plain.clone()));
+            }
+            cursor = span.end.min(range.end);
+            pieces.push((start..cursor, highlight.clone()));
+        }
+        if cursor < range.end {
+            pieces.push((cursor..range.end, plain));
+        }
+
+        //split the pieces where the changed state flips
+        let mut spans = Vec::new();
+        for (piece, highlight) in pieces {
+            let mut start = piece.start - range.start;
+            let end = piece.end - range.start;
+            while start < end {
+                let containing = changed.iter().find(|c| c.start <= start && start < c.end);
+                let split = match containing {
+                    Some(c) => c.end.min(end),
+                    None => changed
+                        .iter()
+                        .map(|c| c.start)
+                        .filter(|s| *s > start)
+                        .min()
+                        .unwrap_or(end)
+                        .min(end),
+                };
+                spans.push(DiffSpan {
+                    columns: start..split,
+                    highlight: highlight.clone(),
+                    changed: containing.is_some(),
+                });
+                start = split;
+            }
+        }
+
+        DiffLine {
+            kind,
+            old_line,
+            new_line,
+            text: self.document.text()[range].to_string(),
+            spans,
+        }
+    }
+}
+
+///Turns a block of removed and added lines into rows. Lines are paired in order and compared word by word, the rest is opposite of a gap.
+fn flush_block(
+    old: &Side,
+    new: &Side,
+    removed: &mut Vec<usize>,
+    added: &mut Vec<usize>,
+    rows: &mut Vec<DiffRow>,
+) {
+    for idx in 0..removed.len().max(added.len()) {
+        let row = match (removed.get(idx), added.get(idx)) {
+            (Some(&o), Some(&n)) => {
+                let old_text = old.document.line(o).unwrap_or_default();
+                let new_text = new.document.line(n).unwrap_or_default();
+                let (old_changed, new_changed) = word_changes(old_text, new_text);
+                DiffRow {
+                    old: Some(old.line(o, None, ChangeKind::Removed, &old_changed)),
+                    new: Some(new.line(n, None, ChangeKind::Added, &new_changed)),
+                }
+            }
+            (Some(&o), None) => {
+                let whole = 0..old.document.line(o).unwrap_or_default().len();
+                DiffRow {
+                    old: Some(old.line(o, None, ChangeKind::Removed, std::slice::from_ref(&whole))),
+                    new: None,
+                }
+            }
+            (None, Some(&n)) => {
+                let whole = 0..new.document.line(n).unwrap_or_default().len();
+                DiffRow {
+                    old: None,
+                    new: Some(new.line(n, None, ChangeKind::Added, std::slice::from_ref(&whole))),
+                }
+            }
+            (None, None) => unreachable!(),
+        };
+        rows.push(row);
+    }
+    removed.clear();
+    added.clear();
+}
+
+///Changed byte ranges of `old` and `new`, compared word by word.
+fn word_changes(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let old_words = words(old);
+    let new_words = words(new);
+    let old_text: Vec<&str> = old_words.iter().map(|w| &old[w.clone()]).collect();
+    let new_text: Vec<&str> = new_words.iter().map(|w| &new[w.clone()]).collect();
+
+    let mut old_changed: Vec<Range<usize>> = Vec::new();
+    let mut new_changed: Vec<Range<usize>> = Vec::new();
+    let push = |ranges: &mut Vec<Range<usize>>, range: Range<usize>| match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    };
+    for edit in edits(&old_text, &new_text) {
+        match edit {
+            Edit::Equal(..) => {}
+            Edit::Delete(o) => push(&mut old_changed, old_words[o].clone()),
+            Edit::Insert(n) => push(&mut new_changed, new_words[n].clone()),
+        }
+    }
+    (old_changed, new_changed)
+}
+
+///Splits `line` into words, runs of whitespace and single other characters.
+fn words(line: &str) -> Vec<Range<usize>> {
+    let class = |c: char| {
+        if is_word_char(c) {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut last_class = None;
+    for (idx, c) in line.char_indices() {
+        let end = idx + c.len_utf8();
+        match words.last_mut() {
+            Some(word) if last_class == Some(class(c)) && class(c) != 2 => word.end = end,
+            _ => words.push(idx..end),
+        }
+        last_class = Some(class(c));
+    }
+    words
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Edit {
+    ///Index into the old and the new sequence.
+    Equal(usize, usize),
+    ///Index into the old sequence.
+    Delete(usize),
+    ///Index into the new sequence.
+    Insert(usize),
+}
+
+///Shortest edit script from `old` to `new`, in order.
+pub(crate) fn edits<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    //common prefix and suffix are trivial, and usually most of the text
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|idx| Edit::Equal(idx, idx)).collect();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    edits.extend(
+        myers(old_middle, new_middle)
+            .into_iter()
+            .map(|edit| match edit {
+                Edit::Equal(o, n) => Edit::Equal(o + prefix, n + prefix),
+                Edit::Delete(o) => Edit::Delete(o + prefix),
+                Edit::Insert(n) => Edit::Insert(n + prefix),
+            }),
+    );
+    edits.extend(
+        (0..suffix).map(|idx| Edit::Equal(old.len() - suffix + idx, new.len() - suffix + idx)),
+    );
+    edits
+}
+
+///Myers' O(ND) diff. Falls back to replacing everything once the edit distance exceeds [MAX_EDIT_DISTANCE].
+fn myers<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    let replace_all = || {
+        (0..old.len())
+            .map(Edit::Delete)
+            .chain((0..new.len()).map(Edit::Insert))
+            .collect()
+    };
+    if old.is_empty() || new.is_empty() {
+        return replace_all();
+    }
+
+    //furthest x per diagonal k, at index k + offset. One extra slot on each side, so k±1 is always in bounds.
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    //state of v before each round d, only the diagonals -(d+1)..=d+1 that can be looked at while backtracking.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max.min(MAX_EDIT_DISTANCE) as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+        if d as usize == MAX_EDIT_DISTANCE {
+            return replace_all();
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, snapshot) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| snapshot[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(prev_y as usize));
+            } else {
+                edits.push(Edit::Delete(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edit, MAX_EDIT_DISTANCE, edits};
+
+    ///Length of the longest common subsequence, by dynamic programming.
+    fn lcs(old: &[u8], new: &[u8]) -> usize {
+        let mut table = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for (i, a) in old.iter().enumerate() {
+            for (j, b) in new.iter().enumerate() {
+                table[i + 1][j + 1] = if a == b {
+                    table[i][j] + 1
+                } else {
+                    table[i][j + 1].max(table[i + 1][j])
+                };
+            }
+        }
+        table[old.len()][new.len()]
+    }
+
+    ///Walks the edits in order, checking that they turn `old` into `new`, and returns the number of equal elements.
+    fn apply<T: PartialEq + std::fmt::Debug>(old: &[T], new: &[T], edits: &[Edit]) -> usize {
+        let (mut i, mut j, mut equal) = (0, 0, 0);
+        for edit in edits {
+            match *edit {
+                Edit::Equal(a, b) => {
+                    assert_eq!((a, b), (i, j), "{old:?} -> {new:?}");
+                    assert_eq!(old[a], new[b], "{old:?} -> {new:?}");
+                    (i, j, equal) = (i + 1, j + 1, equal + 1);
+                }
+                Edit::Delete(a) => {
+                    assert_eq!(a, i, "{old:?} -> {new:?}");
+                    i += 1;
+                }
+                Edit::Insert(b) => {
+                    assert_eq!(b, j, "{old:?} -> {new:?}");
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (old.len(), new.len()), "{old:?} -> {new:?}");
+        equal
+    }
+
+    ///All sequences of `b"abc"` up to `len` long.
+    fn sequences(len: usize) -> Vec<Vec<u8>> {
+        let mut all = vec![Vec::new()];
+        let mut last = vec![Vec::new()];
+        for _ in 0..len {
+            last = last
+                .iter()
+                .flat_map(|seq: &Vec<u8>| {
+                    b"abc".iter().map(move |c| {
+                        let mut seq = seq.clone();
+                        seq.push(*c);
+                        seq
+                    })
+                })
+                .collect();
+            all.extend(last.iter().cloned());
+        }
+        all
+    }
+
+    #[test]
+    fn edits_keep_a_longest_common_subsequence() {
+        let all = sequences(4);
+        for old in &all {
+            for new in &all {
+                let equal = apply(old, new, &edits(old, new));
+                assert_eq!(equal, lcs(old, new), "{old:?} -> {new:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn edits_replace_everything_past_the_max_distance() {
+        //every other element is new (the first and the last one too), so the distance is the length of `old`
+        let diff = |len: usize| {
+            let old: Vec<usize> = (0..len).collect();
+            let new: Vec<usize> = (0..len)
+                .flat_map(|x| [len + x, x])
+                .chain([2 * len])
+                .collect();
+            let edits = edits(&old, &new);
+            (apply(&old, &new, &edits), edits)
+        };
+        let (equal, _) = diff(100);
+        assert_eq!(equal, 100);
+        let len = MAX_EDIT_DISTANCE + 10;
+        let (equal, edits) = diff(len);
+        assert_eq!(equal, 0);
+        assert!(
+            edits[..len]
+                .iter()
+                .all(|edit| matches!(edit, Edit::Delete(_)))
+        );
+        assert!(
+            edits[len..]
+                .iter()
+                .all(|edit| matches!(edit, Edit::Insert(_)))
+        );
+    }
+}
@@ -0,0 +1,1815 @@
+//! A document that is kept parsed alongside the editor's content.
+//!
+//! The [Highlighter](crate::Highlighter) only ever sees single lines. Anything that needs the whole syntax tree
+//! (scopes, folding, search, ...) works on a [Document] instead. Keep one in your application state next to the
+//! `text_editor::Content` and update it whenever the content is edited.
+
+use std::{
+    collections::VecDeque,
+    hash::Hash,
+    ops::Range,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use tree_sitter_highlight::HighlightEvent;
+
+use crate::{
+    Highlight, TSSettings, logging, progressive::ProgressiveHighlights, span::Span, stats::Usage,
+};
+
+///Capture name the syntax errors are highlighted as, see [TSSettings::with_syntax_errors].
+pub const ERROR_CAPTURE: &str = "error";
+
+///A position in a [Document]. Same convention as `text_editor::Content::cursor_position`: zero based line, and
+///the column as _byte_ offset into that line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub const fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl From<(usize, usize)> for Position {
+    fn from((line, column): (usize, usize)) -> Self {
+        Self { line, column }
+    }
+}
+
+///Node kinds (or parts of them) that are considered an enclosing _scope_, for instance by [Document::focus_scope].
+///Matches the naming of most tree-sitter grammars (`function_item`, `function_definition`, `method_declaration`, `class_body`...).
+const SCOPE_KINDS: &[&str] = &[
+    "function",
+    "method",
+    "class",
+    "impl_item",
+    "trait_item",
+    "interface",
+];
+
+///Opening and closing characters that are auto-paired, see [Document::should_autoclose].
+pub(crate) const PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
+///Node kinds (or parts of them) auto-closing is suppressed in, see [Document::should_autoclose].
+const LITERAL_KINDS: &[&str] = &["string", "comment", "char"];
+
+///Node kinds (or parts of them) of the tokens that are patched instead of parsing, see
+///[TSSettings::with_token_edits](crate::TSSettings::with_token_edits).
+const WORD_KINDS: &[&str] = &["identifier", "integer", "number", "float"];
+
+///A syntax token, see [Document::token_at].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token {
+    ///Byte range of the token.
+    pub range: Range<usize>,
+    ///Kind of the syntax node, e.g. `identifier` or `string_content`.
+    pub kind: &'static str,
+}
+
+///Syntactic context of a position, see [Document::context_at].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SyntaxContext {
+    ///Kind of the smallest syntax node at the position.
+    pub kind: Option<&'static str>,
+    ///Name of the innermost function (or method) around the position.
+    pub function: Option<String>,
+    pub in_string: bool,
+    pub in_comment: bool,
+}
+
+///Parsing that takes longer is logged, since it stalls the editor.
+const SLOW_PARSE: Duration = Duration::from_millis(100);
+
+///How often the [Watchdog] looks at the cancellation flag of the settings.
+const CANCELLATION_POLL: Duration = Duration::from_millis(1);
+
+///How many earlier texts a [Document] remembers by default, see [Document::set_history_limit].
+pub const DEFAULT_HISTORY_LIMIT: usize = 8;
+
+///What a [Document] does after edits that only change whitespace, see
+///[TSSettings::with_whitespace_edits](crate::TSSettings::with_whitespace_edits).
+///
+///Instead of parsing, the tree is moved along by the edit, as if tree-sitter had parsed the same nodes at their new positions, and
+///a [SharedHighlightState](crate::shared::SharedHighlightState) moves the highlights of the touched lines along instead of
+///highlighting the text again. That only happens where the whitespace is between tokens: the edit can't be inside a token (like a
+///string or a comment), nor make two tokens touch or part ones that did, and the text can't have syntax errors. The next edit
+///that isn't moved along parses the text as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WhitespaceEdits {
+    ///Parse after every edit.
+    #[default]
+    Parse,
+    ///Move the tree along for edits that only add or remove blank lines, or change the whitespace on them, like pressing enter
+    ///at the end of a line. Not for languages where blank lines mean something, like Markdown.
+    BlankLines,
+    ///Move the tree along for every edit of the whitespace between tokens, like indenting a line or breaking it between two
+    ///tokens. Not for languages where indentation or line breaks mean something, like Python, YAML or JavaScript (which ends
+    ///statements at some line breaks).
+    Whitespace,
+}
+
+pub struct Document {
+    settings: TSSettings,
+    ///Locked to parse the text of a [hibernating](Document::hibernate) document on the next access of the tree.
+    parser: Mutex<tree_sitter::Parser>,
+    text: String,
+    ///Byte offset at which each line starts.
+    line_starts: Vec<usize>,
+    ///Unset while the document hibernates.
+    tree: OnceLock<Option<tree_sitter::Tree>>,
+    ///See [Document::hash]. Unset until it's asked for.
+    hash: OnceLock<u64>,
+    ///Earlier texts and their trees, most recent last, for undo.
+    history: VecDeque<Snapshot>,
+    ///Later texts an undo went back from, most recent last, for redo.
+    undone: Vec<Snapshot>,
+    history_limit: usize,
+    ///See [Document::is_patched].
+    patched: bool,
+    ///See [Document::is_parse_halted].
+    halted: AtomicBool,
+    ///UTF-16 columns of each line, see [Document::byte_to_utf16]. Unset until the first conversion.
+    utf16: OnceLock<Vec<OnceLock<Option<Box<Utf16Line>>>>>,
+    subscribers: Vec<mpsc::Sender<Change>>,
+}
+
+///A change of a [Document]'s text, see [Document::subscribe].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Change {
+    ///Lines of the old text that were replaced.
+    pub old_lines: Range<usize>,
+    ///Lines of the new text that replaced them. Lines before are unchanged, lines after unchanged but maybe moved.
+    pub lines: Range<usize>,
+    ///Byte range of the old text that was replaced, and the length of the text that replaced it. Move
+    ///[anchors](crate::anchor::Anchor) along by them.
+    pub bytes: Range<usize>,
+    pub inserted: usize,
+    ///[Document::hash] of the new text.
+    pub hash: u64,
+}
+
+///An earlier or later text of a [Document], kept as the edit that turns the text next to it into it: the next later one in the
+///history, or the document's own for the most recent one, and likewise for the undone texts.
+struct Snapshot {
+    ///Byte range of the text next to it, and what replaces it.
+    range: Range<usize>,
+    text: String,
+    tree: Option<tree_sitter::Tree>,
+    patched: bool,
+}
+
+impl Snapshot {
+    ///Whether replacing the byte `range` of `next` (the text next to the snapshot) with `text` gives the snapshot's text. Only
+    ///compares the part between both edits.
+    fn is(&self, next: &str, range: &Range<usize>, text: &str) -> bool {
+        if range.len() + self.text.len() != self.range.len() + text.len() {
+            return false;
+        }
+        let span = range.start.min(self.range.start)..range.end.max(self.range.end);
+        let edited = |range: &Range<usize>, text: &str| {
+            [
+                &next[span.start..range.start],
+                text,
+                &next[range.end..span.end],
+            ]
+            .into_iter()
+            .flat_map(str::bytes)
+            .collect::<Vec<_>>()
+        };
+        edited(range, text) == edited(&self.range, &self.text)
+    }
+}
+
+///UTF-16 columns of a line that isn't ASCII, see [Document::byte_to_utf16].
+struct Utf16Line {
+    ///UTF-16 column of each byte column, and of the end of the line. Bytes inside a character have the column of its start.
+    units: Box<[u32]>,
+    ///Byte column of each UTF-16 column, and of the end of the line. The second half of a surrogate pair has the byte column of
+    ///its character.
+    bytes: Box<[u32]>,
+}
+
+impl Utf16Line {
+    fn new(line: &str) -> Self {
+        let mut units = Vec::with_capacity(line.len() + 1);
+        let mut bytes = Vec::with_capacity(line.len() + 1);
+        for (idx, c) in line.char_indices() {
+            units.extend(std::iter::repeat_n(bytes.len() as u32, c.len_utf8()));
+            bytes.extend(std::iter::repeat_n(idx as u32, c.len_utf16()));
+        }
+        units.push(bytes.len() as u32);
+        bytes.push(line.len() as u32);
+        Self {
+            units: units.into(),
+            bytes: bytes.into(),
+        }
+    }
+}
+
+///An edit the tree was moved along by instead of parsing, see [WhitespaceEdits] and
+///[TSSettings::with_token_edits](crate::TSSettings::with_token_edits).
+pub(crate) struct Shift {
+    ///The edit of the tree. For a patched token, it replaces a character of the token, so the token grows or shrinks with it.
+    pub(crate) edit: tree_sitter::InputEdit,
+    ///Lines of the old text the edit touched, with their text and where it started, and the lines of the new text that
+    ///replaced them.
+    pub(crate) old_lines: Range<usize>,
+    pub(crate) old_start: usize,
+    pub(crate) old_text: String,
+    pub(crate) lines: Range<usize>,
+}
+
+///How [Document::replace] brought the tree up to date with the new text.
+pub(crate) enum Replaced {
+    ///Moved along instead of parsing.
+    Moved(Shift),
+    ///Parsed from the tree of the previous text, edited to match.
+    Parsed(Reparsed),
+    ///Restored from the history, parsed from scratch, or not parsed (yet).
+    Other,
+}
+
+///See [Replaced::Parsed].
+pub(crate) struct Reparsed {
+    ///Lines of the old text the edit touched, and the lines of the new text that replaced them.
+    pub(crate) old_lines: Range<usize>,
+    pub(crate) lines: Range<usize>,
+    ///Byte ranges of the new text where the syntax tree changed, see `tree_sitter::Tree::changed_ranges`.
+    pub(crate) changed: Vec<Range<usize>>,
+}
+
+impl Document {
+    pub fn new(settings: &TSSettings, text: impl Into<String>) -> Self {
+        let mut parser = tree_sitter::Parser::new();
+        //NOTE: Only fails on an ABI mismatch. In that case the highlighter doesn't work either,
+        //      so we just end up without a tree.
+        if let Err(err) = parser.set_language(&settings.tsconfig.language) {
+            logging::warning!(
+                target: logging::GRAMMAR,
+                "can't load the {} grammar: {err}",
+                settings.tsconfig.language_name
+            );
+        }
+
+        let mut document = Self {
+            settings: settings.clone(),
+            parser: Mutex::new(parser),
+            text: String::new(),
+            line_starts: vec![0],
+            tree: OnceLock::new(),
+            hash: OnceLock::new(),
+            history: VecDeque::new(),
+            undone: Vec::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            patched: false,
+            halted: AtomicBool::new(false),
+            utf16: OnceLock::new(),
+            subscribers: Vec::new(),
+        };
+        let text = text.into();
+        let edit = document.input_edit(0..0, &text);
+        document.update(edit, &text, true);
+        document
+    }
+
+    ///Replaces the whole text, and re-parses it.
+    ///
+    ///Only what changed is parsed again: the old tree is edited by the part between the common start and end of both texts, and
+    ///handed to tree-sitter, which reuses the untouched subtrees. If the text is the one before the last edit, or the one an undo
+    ///went back from (see [Document::set_history_limit]), its tree is reused instead of parsing it at all. Edits of
+    ///whitespace may not be parsed either, see [WhitespaceEdits], nor edits of a single character of a word, see
+    ///[TSSettings::with_token_edits](crate::TSSettings::with_token_edits).
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.replace(text.into());
+    }
+
+    ///[Document::set_text], telling how the tree was brought up to date.
+    pub(crate) fn replace(&mut self, text: String) -> Replaced {
+        self.replace_with(text, true)
+    }
+
+    ///[Document::replace] that leaves the parse to the first access of the tree, or to [Document::adopt_tree], unless it can
+    ///move the tree along or reuse an earlier one.
+    pub(crate) fn replace_deferred(&mut self, text: String) -> Replaced {
+        self.replace_with(text, false)
+    }
+
+    fn replace_with(&mut self, text: String, parse: bool) -> Replaced {
+        if text == self.text {
+            return Replaced::Other;
+        }
+
+        let prefix = common_prefix(&self.text, &text);
+        let suffix = common_suffix(&self.text[prefix..], &text[prefix..]);
+        let inserted = &text[prefix..text.len() - suffix];
+        let edit = self.input_edit(prefix..self.text.len() - suffix, inserted);
+        self.update(edit, inserted, parse)
+    }
+
+    ///Takes `tree`, parsed elsewhere from the text with the [hash](Document::hash) `hash`, if that's still the text and it isn't
+    ///parsed yet.
+    pub(crate) fn adopt_tree(&mut self, hash: u64, tree: Option<tree_sitter::Tree>) {
+        if self.tree.get().is_none() && self.hash() == hash {
+            self.tree = OnceLock::from(tree);
+        }
+    }
+
+    ///Replaces the byte `range` with `text`. Same as [Document::set_text] with the edited text, e.g. for edits the host knows
+    ///the range of anyways, but saves comparing the texts. A range past the end of the text is clamped to it, and ends within
+    ///a character are moved to its start.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        let boundary = |mut offset: usize| {
+            offset = offset.min(self.text.len());
+            while !self.text.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            offset
+        };
+        let end = boundary(range.end);
+        let range = boundary(range.start).min(end)..end;
+        if range.is_empty() && text.is_empty() {
+            return;
+        }
+        let edit = self.input_edit(range, text);
+        self.update(edit, text, true);
+    }
+
+    ///The tree-sitter edit that replaces the byte `range` of the current text with `text`.
+    fn input_edit(&self, range: Range<usize>, text: &str) -> tree_sitter::InputEdit {
+        let point = |position: Position| tree_sitter::Point::new(position.line, position.column);
+        let start = self.position(range.start);
+        let new_end = match text.rfind('\n') {
+            Some(idx) => Position::new(
+                start.line + text.matches('\n').count(),
+                text.len() - idx - 1,
+            ),
+            None => Position::new(start.line, start.column + text.len()),
+        };
+        tree_sitter::InputEdit {
+            start_byte: range.start,
+            old_end_byte: range.end,
+            new_end_byte: range.start + text.len(),
+            start_position: point(start),
+            old_end_position: point(self.position(range.end)),
+            new_end_position: point(new_end),
+        }
+    }
+
+    ///Replaces the bytes of `edit` with `inserted` in place, and parses the text if `parse` (or leaves that to the first access
+    ///of the tree).
+    fn update(&mut self, edit: tree_sitter::InputEdit, inserted: &str, parse: bool) -> Replaced {
+        let start = self.settings.measure();
+        let range = edit.start_byte..edit.old_end_byte;
+        //an undo or a redo goes back to the text next to this one
+        let is_next = |snapshot: &Snapshot| snapshot.is(&self.text, &range, inserted);
+        let undo = self.history.back().is_some_and(is_next);
+        let restored = if undo {
+            self.history.pop_back()
+        } else if self.undone.last().is_some_and(is_next) {
+            self.undone.pop()
+        } else {
+            self.undone.clear();
+            None
+        };
+        let starts = self.edited_line_starts(&edit, inserted);
+        let mut shift = None;
+        let mut patched = false;
+        //the tree of the old text, edited to match the new one
+        let mut old = None;
+        if restored.is_none() {
+            let patch = self.patch(inserted, &starts, &edit);
+            //moving along by whitespace keeps what was patched before
+            patched = patch.is_some() || self.patched;
+            shift = patch.or_else(|| self.shift(inserted, &starts, &edit));
+            old = self.parsed_tree().cloned().map(|mut tree| {
+                tree.edit(shift.as_ref().map_or(&edit, |shift| &shift.edit));
+                tree
+            });
+        }
+        let lines = (!self.subscribers.is_empty() || !self.settings.plugins.is_empty())
+            .then(|| self.changed_lines(&edit, inserted));
+
+        let previous = Snapshot {
+            range: edit.start_byte..edit.new_end_byte,
+            text: self.text[range.clone()].to_string(),
+            tree: None,
+            patched: self.patched,
+        };
+        self.text.replace_range(range, inserted);
+        self.line_starts = starts;
+        self.hash = OnceLock::new();
+        let reused = restored
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.tree.is_some())
+            || shift.is_some();
+        let mut reparsed = None;
+        let tree = match restored {
+            Some(snapshot) => {
+                patched = snapshot.patched;
+                snapshot
+                    .tree
+                    .map_or_else(OnceLock::new, |tree| OnceLock::from(Some(tree)))
+            }
+            None if shift.is_some() => OnceLock::from(old),
+            None if !parse => OnceLock::new(),
+            None => {
+                let tree = self.parse(&self.text, old.as_ref());
+                //the highlights of a patched tree are patched as well, they don't tell what changed
+                if let (Some(old), Some(tree)) = (&old, &tree)
+                    && !self.patched
+                {
+                    reparsed = Some(Reparsed {
+                        old_lines: edit.start_position.row..edit.old_end_position.row + 1,
+                        lines: edit.start_position.row..edit.new_end_position.row + 1,
+                        changed: old
+                            .changed_ranges(tree)
+                            .map(|range| range.start_byte..range.end_byte)
+                            .collect(),
+                    });
+                }
+                OnceLock::from(tree)
+            }
+        };
+        self.patched = patched;
+        //deferred parses are reported when they happen
+        if let Some(start) = start.filter(|_| parse || reused) {
+            self.settings.report(|language| Usage::Parse {
+                language,
+                bytes: self.text.len(),
+                duration: start.elapsed(),
+                reused,
+            });
+        }
+        let previous = Snapshot {
+            tree: std::mem::replace(&mut self.tree, tree)
+                .into_inner()
+                .flatten(),
+            ..previous
+        };
+        if undo {
+            self.undone.push(previous);
+        } else {
+            self.history.push_back(previous);
+        }
+        self.trim_history();
+
+        //lines before and after the edit keep their columns
+        if let Some(mut lines) = self.utf16.take() {
+            let (start, old_end, new_end) = (
+                edit.start_position.row,
+                edit.old_end_position.row,
+                edit.new_end_position.row,
+            );
+            lines.splice(start..=old_end, (start..=new_end).map(|_| OnceLock::new()));
+            self.utf16 = OnceLock::from(lines);
+        }
+
+        let change = lines.map(|(old_lines, lines)| Change {
+            old_lines,
+            lines,
+            bytes: edit.start_byte..edit.old_end_byte,
+            inserted: edit.new_end_byte - edit.start_byte,
+            hash: self.hash(),
+        });
+        if let Some(change) = &change {
+            self.subscribers
+                .retain(|subscriber| subscriber.send(change.clone()).is_ok());
+        }
+
+        if let Some(change) = change {
+            for plugin in &self.settings.plugins {
+                plugin.edited(self, &change);
+            }
+        }
+        match (shift, reparsed) {
+            (Some(shift), _) => Replaced::Moved(shift),
+            (None, Some(reparsed)) => Replaced::Parsed(reparsed),
+            (None, None) => Replaced::Other,
+        }
+    }
+
+    ///The line starts of the text with the bytes of `edit` replaced by `inserted`.
+    fn edited_line_starts(&self, edit: &tree_sitter::InputEdit, inserted: &str) -> Vec<usize> {
+        let (start, old_end) = (edit.start_position.row, edit.old_end_position.row);
+        let mut starts = self.line_starts[..=start].to_vec();
+        starts.extend(
+            inserted
+                .match_indices('\n')
+                .map(|(idx, _)| edit.start_byte + idx + 1),
+        );
+        starts.extend(
+            self.line_starts[old_end + 1..]
+                .iter()
+                .map(|line| line - edit.old_end_byte + edit.new_end_byte),
+        );
+        starts
+    }
+
+    ///Lines of the text before and after replacing the bytes of `edit` with `inserted` that changed: the lines the edit
+    ///touches, without the ones at their start and end that stay the same.
+    fn changed_lines(
+        &self,
+        edit: &tree_sitter::InputEdit,
+        inserted: &str,
+    ) -> (Range<usize>, Range<usize>) {
+        let first = edit.start_position.row;
+        let old = text_of(
+            &self.text,
+            &self.line_starts,
+            &(first..edit.old_end_position.row + 1),
+        );
+        let line_start = self.line_starts[first];
+        let new = [
+            &old[..edit.start_byte - line_start],
+            inserted,
+            &old[edit.old_end_byte - line_start..],
+        ]
+        .concat();
+        let old_lines = old.split('\n').collect::<Vec<_>>();
+        let new_lines = new.split('\n').collect::<Vec<_>>();
+        let prefix = old_lines
+            .iter()
+            .zip(&new_lines)
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = old_lines[prefix..]
+            .iter()
+            .rev()
+            .zip(new_lines[prefix..].iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count();
+        (
+            first + prefix..first + old_lines.len() - suffix,
+            first + prefix..first + new_lines.len() - suffix,
+        )
+    }
+
+    ///Whether the tree can be moved along by `edit` to `inserted` (with the line `starts` after it) instead of parsing, see [WhitespaceEdits].
+    fn shift(
+        &self,
+        inserted: &str,
+        starts: &[usize],
+        edit: &tree_sitter::InputEdit,
+    ) -> Option<Shift> {
+        let mode = self.settings.whitespace_edits;
+        //how tree-sitter recovers from syntax errors depends on the lines it skips, a blank line can change the tree anywhere
+        let tree = self
+            .parsed_tree()
+            .filter(|tree| mode != WhitespaceEdits::Parse && !tree.root_node().has_error())?;
+        let (start, old_end) = (edit.start_byte, edit.old_end_byte);
+        let is_blank = |text: &str| text.chars().all(char::is_whitespace);
+        if !is_blank(&self.text[start..old_end]) || !is_blank(inserted) {
+            return None;
+        }
+        //there has to be whitespace before and after, so tokens that touched aren't parted, or the other way round: `'a` is a
+        //lifetime, `' a` is not
+        //(the text around the edit is the same before and after it)
+        let has_space = |touches: bool| {
+            touches
+                || start == 0
+                || old_end == self.text.len()
+                || self.text[..start].ends_with(char::is_whitespace)
+                || self.text[old_end..].starts_with(char::is_whitespace)
+        };
+        if !has_space(start < old_end) || !has_space(!inserted.is_empty()) {
+            return None;
+        }
+        if !is_padding(tree, &self.text, start..old_end) {
+            return None;
+        }
+        //a line break ends line comments and the like, and they take the whitespace before it. Without the line break the next
+        //line may be part of one
+        let before = self.text[..start].trim_end().len();
+        let removes_break = self.text[start..old_end].contains('\n') && !inserted.contains('\n');
+        let takes_space = before == start && !inserted.starts_with(['\r', '\n']);
+        let mut node = tree
+            .root_node()
+            .descendant_for_byte_range(before.saturating_sub(1), before);
+        while let Some(ended) = node.filter(|node| node.end_byte() == before) {
+            if ended.is_extra() && (removes_break || takes_space) {
+                return None;
+            }
+            node = ended.parent();
+        }
+
+        let shift = self.moved(starts, *edit);
+        //the lines with text have to stay as they were, only the blank ones between them change
+        let (before, after) = (start - shift.old_start, old_end - shift.old_start);
+        let lines = [
+            &shift.old_text[..before],
+            inserted,
+            &shift.old_text[after..],
+        ]
+        .concat();
+        if mode == WhitespaceEdits::BlankLines
+            && !shift
+                .old_text
+                .split('\n')
+                .filter(|line| !is_blank(line))
+                .eq(lines.split('\n').filter(|line| !is_blank(line)))
+        {
+            return None;
+        }
+        Some(shift)
+    }
+
+    ///Whether `edit` to `inserted` (with the line `starts` after it) only types or deletes a character of an identifier or a number, so the
+    ///token can be patched instead of parsing, see [TSSettings::with_token_edits](crate::TSSettings::with_token_edits).
+    fn patch(
+        &self,
+        inserted: &str,
+        starts: &[usize],
+        edit: &tree_sitter::InputEdit,
+    ) -> Option<Shift> {
+        let tree = self.parsed_tree().filter(|_| self.settings.token_edits)?;
+        let (start, old_end, new_end) = (edit.start_byte, edit.old_end_byte, edit.new_end_byte);
+        let is_word = |c: &u8| c.is_ascii_alphanumeric() || *c == b'_';
+        let old = self.text.as_bytes();
+        let changed = match (&old[start..old_end], inserted.as_bytes()) {
+            ([], [typed]) => typed,
+            ([removed], []) => removed,
+            _ => return None,
+        };
+        if !is_word(changed) {
+            return None;
+        }
+        //the word around the edit, which has to be a single token before and after
+        let word_start = start - old[..start].iter().rev().take_while(|c| is_word(c)).count();
+        let word_end = old_end + old[old_end..].iter().take_while(|c| is_word(c)).count();
+        let old_word = &self.text[word_start..word_end];
+        let new_word = &[
+            &self.text[word_start..start],
+            inserted,
+            &self.text[old_end..word_end],
+        ]
+        .concat();
+        if old_word.is_empty() || new_word.is_empty() {
+            return None;
+        }
+        let is_number = |word: &str| word.starts_with(|c: char| c.is_ascii_digit());
+        if is_number(old_word) != is_number(new_word)
+            || is_number(old_word) && !changed.is_ascii_digit()
+        {
+            return None;
+        }
+        //keywords are nodes of the grammar, mostly anonymous ones
+        let language = &self.settings.tsconfig.language;
+        if [old_word, new_word.as_str()].iter().any(|word| {
+            language.id_for_node_kind(word, false) != 0
+                || language.id_for_node_kind(word, true) != 0
+        }) {
+            return None;
+        }
+        let token = tree
+            .root_node()
+            .descendant_for_byte_range(word_start, word_end)?;
+        if token.byte_range() != (word_start..word_end)
+            || token.child_count() > 0
+            || !WORD_KINDS.iter().any(|kind| token.kind().contains(kind))
+            || token.is_error()
+            || token.is_missing()
+            || token.parent().is_some_and(|parent| parent.has_error())
+        {
+            return None;
+        }
+
+        //replacing a character of the token instead of typing next to it, else the token wouldn't grow at its end
+        let (from, to) = match old_end - start {
+            0 if start > word_start => (start - 1, start),
+            0 => (start, start + 1),
+            _ => (start, old_end),
+        };
+        let point = |offset: usize| {
+            let position = self.position(offset);
+            tree_sitter::Point::new(position.line, position.column)
+        };
+        let new_to = to + new_end - old_end;
+        let edit = tree_sitter::InputEdit {
+            start_byte: from,
+            old_end_byte: to,
+            new_end_byte: new_to,
+            start_position: point(from),
+            old_end_position: point(to),
+            new_end_position: tree_sitter::Point::new(
+                edit.start_position.row,
+                point(from).column + new_to - from,
+            ),
+        };
+        Some(self.moved(starts, edit))
+    }
+
+    ///The lines of the old text and of the new one (with the line `starts`) that `edit` touches, to move the highlights along.
+    fn moved(&self, starts: &[usize], edit: tree_sitter::InputEdit) -> Shift {
+        let old_lines = self.line_of(edit.start_byte)..self.line_of(edit.old_end_byte) + 1;
+        let line_of = |offset: usize| starts.partition_point(|start| *start <= offset) - 1;
+        let lines = line_of(edit.start_byte)..line_of(edit.new_end_byte) + 1;
+        Shift {
+            edit,
+            old_start: self.line_starts[old_lines.start],
+            old_text: text_of(&self.text, &self.line_starts, &old_lines).to_string(),
+            old_lines,
+            lines,
+        }
+    }
+
+    ///Hash of the text, to tell cheaply whether two texts differ (e.g. whether a file changed since it was saved). It's computed
+    ///on the first call after an edit, and is the same across runs, Rust releases and platforms.
+    pub fn hash(&self) -> u64 {
+        *self.hash.get_or_init(|| text_hash(&self.text))
+    }
+
+    ///Notifies about every change of the text from now on, so linters, word counts and the like know what to look at again.
+    ///Unsubscribe by dropping the receiver.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<Change> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    ///Parses `text`, reusing the unchanged parts of `old` (already edited to match `text`). Starts over if a parse of another
+    ///text halted, see [Document::resume_parse].
+    fn parse(&self, text: &str, old: Option<&tree_sitter::Tree>) -> Option<tree_sitter::Tree> {
+        let mut parser = self
+            .parser
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.halted.swap(false, Ordering::Relaxed) {
+            parser.reset();
+        }
+        self.parse_with(&mut parser, text, old)
+    }
+
+    ///Parses `text` with `parser`, until the [timeout](TSSettings::with_parse_timeout) or the
+    ///[cancellation flag](TSSettings::with_cancellation_flag) stops it.
+    fn parse_with(
+        &self,
+        parser: &mut tree_sitter::Parser,
+        text: &str,
+        old: Option<&tree_sitter::Tree>,
+    ) -> Option<tree_sitter::Tree> {
+        let started = Instant::now();
+        let deadline = self.settings.parse_timeout.map(|timeout| started + timeout);
+        let flag = self.settings.cancellation_flag.as_deref();
+        let mut halt = |_: &tree_sitter::ParseState| {
+            flag.is_some_and(|flag| flag.load(Ordering::Relaxed) != 0)
+                || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        };
+        let stoppable = deadline.is_some() || flag.is_some();
+        let options =
+            stoppable.then(|| tree_sitter::ParseOptions::new().progress_callback(&mut halt));
+        let tree = parser.parse_with_options(
+            &mut |offset, _| text.as_bytes().get(offset..).unwrap_or_default(),
+            old,
+            options,
+        );
+        if tree.is_none() && stoppable {
+            //the parser keeps its state to resume from
+            self.halted.store(true, Ordering::Relaxed);
+            logging::debug!(
+                target: logging::PARSE,
+                "parsing {} bytes of {} halted after {:?}",
+                text.len(),
+                self.settings.tsconfig.language_name,
+                started.elapsed()
+            );
+        }
+        let duration = started.elapsed();
+        if duration > SLOW_PARSE {
+            logging::warning!(
+                target: logging::PARSE,
+                "parsing {} bytes of {} took {duration:?}",
+                self.text.len(),
+                self.settings.tsconfig.language_name
+            );
+        }
+        tree
+    }
+
+    ///Drops the oldest texts, and then the undone ones furthest away, down to the [limit](Document::set_history_limit).
+    fn trim_history(&mut self) {
+        while self.history.len() + self.undone.len() > self.history_limit {
+            if self.history.pop_front().is_none() {
+                self.undone.remove(0);
+            }
+        }
+    }
+
+    ///How many earlier texts, and texts an undo went back from, are kept with their trees for [Document::set_text]. Each only
+    ///keeps the part it differs in from the text next to it. `0` disables reusing them.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        self.trim_history();
+    }
+
+    pub fn settings(&self) -> &TSSettings {
+        &self.settings
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    ///The syntax tree. Only `None` if the language could not be loaded. Parses the text again if the document
+    ///[hibernates](Document::hibernate).
+    pub fn tree(&self) -> Option<&tree_sitter::Tree> {
+        self.tree
+            .get_or_init(|| {
+                let start = self.settings.measure();
+                let tree = self.parse(&self.text, None);
+                if let Some(start) = start {
+                    self.settings.report(|language| Usage::Parse {
+                        language,
+                        bytes: self.text.len(),
+                        duration: start.elapsed(),
+                        reused: false,
+                    });
+                }
+                tree
+            })
+            .as_ref()
+    }
+
+    ///The syntax tree, without waking the document up. `None` while it hibernates.
+    pub(crate) fn parsed_tree(&self) -> Option<&tree_sitter::Tree> {
+        self.tree.get().and_then(Option::as_ref)
+    }
+
+    ///Drops the syntax tree and the earlier texts kept for undo, to bound the memory of documents that aren't looked at, like the
+    ///ones in background tabs. Only the text, its lines and its hash are kept. The first access of the [tree](Document::tree)
+    ///parses the text again, from scratch, so it costs as much as opening the document did.
+    pub fn hibernate(&mut self) {
+        self.tree = OnceLock::new();
+        self.patched = false;
+        *self.halted.get_mut() = false;
+        self.utf16 = OnceLock::new();
+        self.history.clear();
+        self.history.shrink_to_fit();
+        self.undone = Vec::new();
+        self.text.shrink_to_fit();
+        self.line_starts.shrink_to_fit();
+        self.parser
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .reset();
+    }
+
+    ///Whether the document [hibernates](Document::hibernate), i.e. the tree has to be parsed again before it's used.
+    pub fn is_hibernating(&self) -> bool {
+        self.tree.get().is_none()
+    }
+
+    ///Whether [token edits](crate::TSSettings::with_token_edits) patched the tree since the text was parsed last, see
+    ///[Document::settle].
+    pub fn is_patched(&self) -> bool {
+        self.patched
+    }
+
+    ///Whether parsing the text stopped at the [timeout](TSSettings::with_parse_timeout) or by the
+    ///[cancellation flag](TSSettings::with_cancellation_flag). The document has no tree then, so it's shown without highlights,
+    ///until [Document::resume_parse] finishes the parse.
+    pub fn is_parse_halted(&self) -> bool {
+        self.halted.load(Ordering::Relaxed)
+    }
+
+    ///Carries on with a [halted](Document::is_parse_halted) parse, e.g. on the next frame, for at most the timeout again. Returns
+    ///whether the document has a tree now. Editing the text in between starts the parse over.
+    pub fn resume_parse(&mut self) -> bool {
+        if !self.is_parse_halted() {
+            return self.tree().is_some();
+        }
+        self.halted.store(false, Ordering::Relaxed);
+        let tree = {
+            let mut parser = self
+                .parser
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            //the halted parse ignores the old tree
+            self.parse_with(&mut parser, &self.text, None)
+        };
+        let parsed = tree.is_some();
+        self.tree = OnceLock::from(tree);
+        parsed
+    }
+
+    ///Parses the text of a [patched](Document::is_patched) tree, e.g. on the debounce tick of the editor. Only the patched tokens
+    ///are parsed again. Does nothing if the tree isn't patched.
+    pub fn settle(&mut self) {
+        if !std::mem::take(&mut self.patched) {
+            return;
+        }
+        let start = self.settings.measure();
+        let tree = self.parse(&self.text, self.parsed_tree());
+        self.tree = OnceLock::from(tree);
+        if let Some(start) = start {
+            self.settings.report(|language| Usage::Parse {
+                language,
+                bytes: self.text.len(),
+                duration: start.elapsed(),
+                reused: false,
+            });
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    ///Byte range of `line`, excluding the line break.
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        let start = *self.line_starts.get(line)?;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map(|next| next - 1)
+            .unwrap_or(self.text.len());
+        //handle \r\n as well
+        let end = if self.text[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        Some(start..end)
+    }
+
+    ///Content of `line`, excluding the line break.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        self.line_range(line).map(|range| &self.text[range])
+    }
+
+    ///Converts `position` into a byte offset. Positions past the end of a line (or the document) are clamped.
+    pub fn offset(&self, position: impl Into<Position>) -> usize {
+        let position = position.into();
+        match self.line_range(position.line) {
+            Some(range) => (range.start + position.column).min(range.end),
+            None => self.text.len(),
+        }
+    }
+
+    ///Converts the byte `offset` into a [Position]. Offsets past the end are clamped to the end of the document.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_of(offset);
+        Position::new(line, offset - self.line_starts[line])
+    }
+
+    ///Converts the byte `offset` into a [Position] whose column counts UTF-16 code units, like LSP and the text APIs of Windows,
+    ///Java or JavaScript (clipboards, input methods) do. Offsets past the end are clamped to the end of the document, offsets
+    ///inside a character count from its start.
+    ///
+    ///The columns of a line are computed by the first conversion on it and kept until the line is edited, so converting again
+    ///takes constant time. Lines that are ASCII need no columns at all.
+    pub fn byte_to_utf16(&self, offset: usize) -> Position {
+        let position = self.position(offset);
+        let column = match self.utf16_line(position.line) {
+            //the line break after the line
+            Some(line) => match line.units.get(position.column) {
+                Some(column) => *column as usize,
+                None => line.bytes.len() + position.column - line.units.len(),
+            },
+            None => position.column,
+        };
+        Position::new(position.line, column)
+    }
+
+    ///Converts `position`, whose column counts UTF-16 code units, into a byte offset, see [Document::byte_to_utf16]. Positions
+    ///past the end of a line (or the document) are clamped, and columns inside a surrogate pair count from its start.
+    pub fn utf16_to_byte(&self, position: impl Into<Position>) -> usize {
+        let position = position.into();
+        let column = match self.utf16_line(position.line) {
+            Some(line) => match line.bytes.get(position.column) {
+                Some(column) => *column as usize,
+                None => line.units.len() - 1,
+            },
+            None => position.column,
+        };
+        self.offset(Position::new(position.line, column))
+    }
+
+    ///`None` for lines that are ASCII, and lines past the end.
+    fn utf16_line(&self, line: usize) -> Option<&Utf16Line> {
+        self.utf16
+            .get_or_init(|| (0..self.line_count()).map(|_| OnceLock::new()).collect())
+            .get(line)?
+            .get_or_init(|| {
+                let text = self.line(line)?;
+                (!text.is_ascii()).then(|| Box::new(Utf16Line::new(text)))
+            })
+            .as_deref()
+    }
+
+    ///Line that contains the byte `offset`.
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset) - 1
+    }
+
+    ///Highlights the whole document at once. In contrast to the line based [Highlighter](crate::Highlighter), constructs spanning several
+    ///lines (block comments, raw strings...) are highlighted correctly.
+    ///
+    ///Returns sorted, non-overlapping byte ranges. Nested captures are resolved to the innermost one.
+    ///
+    ///The highlight query runs on the syntax tree, unless the settings resolve local variables or there is
+    ///[embedded code](TSSettings::with_injections), which takes tree-sitter-highlight to parse the text again. That parse stops
+    ///at the [timeout](TSSettings::with_parse_timeout) too, and leaves the text without highlights.
+    ///
+    ///While the [parse halted](Document::is_parse_halted), the text isn't highlighted at all.
+    pub fn highlights(&self) -> Vec<(Range<usize>, Highlight)> {
+        let stoppable =
+            self.settings.parse_timeout.is_some() || self.settings.cancellation_flag.is_some();
+        if stoppable && self.tree().is_none() && self.is_parse_halted() {
+            return Vec::new();
+        }
+        let range = 0..self.text.len();
+        if let Some(tree) = self.tree()
+            && !self.settings.resolves_locals()
+        {
+            let mut highlighter = ProgressiveHighlights::new(self);
+            if !highlighter.embeds(self, tree, range.clone()) {
+                let started = self.settings.measure();
+                let spans = highlighter.highlight_range(self, tree, range);
+                if let Some(started) = started {
+                    self.settings.report(|language| Usage::HighlightDocument {
+                        language,
+                        bytes: self.text.len(),
+                        duration: started.elapsed(),
+                    });
+                }
+                return spans;
+            }
+        }
+        self.mark_errors(highlights(&self.settings, &self.text), range)
+    }
+
+    ///The [highlights](Document::highlights) as [Span]s, the stable model of the crate, by the names of their captures instead
+    ///of tree-sitter-highlight's indices.
+    pub fn spans(&self) -> Vec<Span> {
+        self.highlights()
+            .into_iter()
+            .filter_map(|(range, highlight)| Span::from_highlight(range, &highlight))
+            .collect()
+    }
+
+    ///Byte ranges of the syntax errors, sorted: the tokens the parser couldn't fit in (the leaves of `ERROR` nodes, or the
+    ///empty ones as a whole), and the character after a token it had to assume (a `MISSING` node, which is empty), or the one
+    ///before it at the end of a line. Empty without a tree.
+    pub fn syntax_errors(&self) -> Vec<Range<usize>> {
+        let Some(tree) = self.tree() else {
+            return Vec::new();
+        };
+        let mut errors = Vec::new();
+        let mut nodes = vec![tree.root_node()];
+        while let Some(node) = nodes.pop() {
+            if node.is_missing() {
+                errors.push(self.missing_range(node.start_byte()));
+                continue;
+            }
+            if node.is_error() && node.child_count() == 0 {
+                errors.push(node.byte_range());
+                continue;
+            }
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if node.is_error() && child.child_count() == 0 && !child.is_missing() {
+                    errors.push(child.byte_range());
+                } else if child.has_error() {
+                    nodes.push(child);
+                }
+            }
+        }
+        errors.retain(|range| !range.is_empty());
+        errors.sort_unstable_by_key(|range| range.start);
+        errors.dedup();
+        errors
+    }
+
+    ///The character to mark for a token missing at `offset`, see [Document::syntax_errors].
+    fn missing_range(&self, offset: usize) -> Range<usize> {
+        match self.text[offset..].chars().next() {
+            Some(c) if c != '\n' && c != '\r' => offset..offset + c.len_utf8(),
+            _ => {
+                let before = self.text[..offset].chars().next_back();
+                let start = before
+                    .filter(|c| *c != '\n')
+                    .map_or(offset, |c| offset - c.len_utf8());
+                start..offset
+            }
+        }
+    }
+
+    ///`spans` of the text in `range` with the [syntax errors](Document::syntax_errors) in it on top, if the settings highlight
+    ///them. Still sorted and non-overlapping.
+    pub(crate) fn mark_errors(
+        &self,
+        spans: Vec<(Range<usize>, Highlight)>,
+        range: Range<usize>,
+    ) -> Vec<(Range<usize>, Highlight)> {
+        if !self.settings.syntax_errors {
+            return spans;
+        }
+        let Some(capture) = self.settings.capture_index(ERROR_CAPTURE) else {
+            return spans;
+        };
+        let errors: Vec<Range<usize>> = self
+            .syntax_errors()
+            .into_iter()
+            .filter(|error| error.start < range.end && error.end > range.start)
+            .map(|error| error.start.max(range.start)..error.end.min(range.end))
+            .collect();
+        if errors.is_empty() {
+            return spans;
+        }
+        let error = Highlight {
+            capture: Some(tree_sitter_highlight::Highlight(capture)),
+            style: self
+                .settings
+                .resolve_styles()
+                .get(capture)
+                .copied()
+                .flatten(),
+            dimmed: false,
+            overlay: None,
+            names: self.settings.capture_map(),
+        };
+
+        //cut the errors out of the spans they overlap
+        let mut marked = Vec::with_capacity(spans.len() + errors.len());
+        for (span, highlight) in spans {
+            let mut start = span.start;
+            for error in errors
+                .iter()
+                .filter(|error| error.start < span.end && error.end > span.start)
+            {
+                if error.start > start {
+                    marked.push((start..error.start, highlight.clone()));
+                }
+                start = start.max(error.end);
+            }
+            if start < span.end {
+                marked.push((start..span.end, highlight));
+            }
+        }
+        marked.extend(errors.into_iter().map(|range| (range, error.clone())));
+        marked.sort_by_key(|(range, _)| range.start);
+        marked
+    }
+
+    ///Line range of the function/class (or similar) that encloses `position`. If there is none, the _paragraph_
+    ///(lines between two blank lines) around `position` is returned instead.
+    ///
+    ///Use this for focus modes by passing the result to [TSSettings::with_focus].
+    pub fn focus_scope(&self, position: impl Into<Position>) -> Range<usize> {
+        self.settings.report_feature("focus");
+        let position = position.into();
+        self.enclosing_scope(position)
+            .map(|node| {
+                node.start_position().row..(node.end_position().row + 1).min(self.line_count())
+            })
+            .unwrap_or_else(|| self.paragraph(position.line))
+    }
+
+    ///Smallest syntax node that contains the character at `position`, usually a leaf like an identifier or keyword. `None` on
+    ///whitespace.
+    pub fn token_at(&self, position: impl Into<Position>) -> Option<Token> {
+        let offset = self.offset(position);
+        let c = self.text[offset..]
+            .chars()
+            .next()
+            .filter(|c| !c.is_whitespace())?;
+        let node = self
+            .tree()?
+            .root_node()
+            .descendant_for_byte_range(offset, offset + c.len_utf8())?;
+        Some(Token {
+            range: node.byte_range(),
+            kind: node.kind(),
+        })
+    }
+
+    ///Syntactic context at `position`, e.g. for context menus. Node kinds are matched by parts of their name (`function`, `string`,
+    ///`comment`, ...), so this works for most grammars.
+    pub fn context_at(&self, position: impl Into<Position>) -> SyntaxContext {
+        let offset = self.offset(position);
+        let Some(node) = self
+            .tree()
+            .and_then(|tree| tree.root_node().descendant_for_byte_range(offset, offset))
+        else {
+            return SyntaxContext::default();
+        };
+
+        let mut context = SyntaxContext {
+            kind: Some(node.kind()),
+            ..Default::default()
+        };
+        let mut ancestor = Some(node);
+        while let Some(node) = ancestor {
+            let kind = node.kind();
+            context.in_string |= kind.contains("string");
+            context.in_comment |= kind.contains("comment");
+            if context.function.is_none()
+                && (kind.contains("function") || kind.contains("method"))
+                && let Some(name) = node.child_by_field_name("name")
+            {
+                context.function = Some(self.text[name.byte_range()].to_string());
+            }
+            ancestor = node.parent();
+        }
+        context
+    }
+
+    ///Whether typing `c` at `position` should insert the closing counterpart as well, for hosts that auto-pair brackets and quotes.
+    ///`false` inside strings, comments and character literals, right before a word, and for quotes right after one (`don't`). In
+    ///generics and after `&`, `'` is a lifetime, so it isn't closed either.
+    pub fn should_autoclose(&self, position: impl Into<Position>, c: char) -> bool {
+        self.settings.report_feature("autoclose");
+        if !PAIRS.iter().any(|(open, _)| *open == c) {
+            return false;
+        }
+        let offset = self.offset(position);
+        let before = self.text[..offset].chars().next_back();
+        let after = self.text[offset..].chars().next();
+        let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if is_word(after) || (matches!(c, '"' | '\'' | '`') && is_word(before)) {
+            return false;
+        }
+        if c == '\'' && matches!(before, Some('&' | '<')) {
+            return false;
+        }
+
+        let Some(tree) = self.tree() else {
+            return true;
+        };
+        //the node of the character before the cursor, since a line comment ends right at the cursor
+        let mut ancestor = tree
+            .root_node()
+            .descendant_for_byte_range(offset.saturating_sub(1), offset);
+        while let Some(node) = ancestor {
+            let kind = node.kind();
+            let is_literal = LITERAL_KINDS.iter().any(|literal| kind.contains(literal));
+            let is_open_comment =
+                kind.contains("comment") && !self.text[node.byte_range()].ends_with("*/");
+            if is_literal
+                && node.start_byte() < offset
+                && (offset < node.end_byte() || is_open_comment)
+            {
+                return false;
+            }
+            if c == '\'' && (kind.contains("type_parameters") || kind.contains("type_arguments")) {
+                return false;
+            }
+            ancestor = node.parent();
+        }
+        true
+    }
+
+    ///Smallest node that contains `position` and is considered a scope.
+    fn enclosing_scope(&self, position: Position) -> Option<tree_sitter::Node<'_>> {
+        let offset = self.offset(position);
+        let mut node = self
+            .tree()?
+            .root_node()
+            .descendant_for_byte_range(offset, offset)?;
+
+        loop {
+            if SCOPE_KINDS.iter().any(|kind| node.kind().contains(kind)) {
+                return Some(node);
+            }
+            node = node.parent()?;
+        }
+    }
+
+    ///Lines around `line` until (excluding) the next blank line in each direction.
+    fn paragraph(&self, line: usize) -> Range<usize> {
+        let is_blank = |l: usize| self.line(l).is_none_or(|text| text.trim().is_empty());
+        let line = line.min(self.line_count() - 1);
+        if is_blank(line) {
+            return line..line + 1;
+        }
+
+        let mut start = line;
+        while start > 0 && !is_blank(start - 1) {
+            start -= 1;
+        }
+        let mut end = line + 1;
+        while end < self.line_count() && !is_blank(end) {
+            end += 1;
+        }
+        start..end
+    }
+}
+
+///Whether the bytes `range` of `text` are whitespace between the tokens of `tree`: not inside a token, nor a token of their own (like
+///the line break some grammars end a statement with).
+fn is_padding(tree: &tree_sitter::Tree, text: &str, range: Range<usize>) -> bool {
+    let Some(mut node) = tree
+        .root_node()
+        .descendant_for_byte_range(range.start, range.end)
+    else {
+        return false;
+    };
+    if node.child_count() == 0 {
+        //a token the range touches, but isn't part of
+        let is_inside = if range.is_empty() {
+            node.start_byte() < range.start && range.start < node.end_byte()
+        } else {
+            node.start_byte() < range.end && range.start < node.end_byte()
+        };
+        match node.parent() {
+            Some(parent) if !is_inside => node = parent,
+            _ => return false,
+        }
+    }
+    //the text between the children before and after has to be whitespace, too. It isn't for tokens tree-sitter doesn't show,
+    //like the content of a string in some grammars
+    let mut gap = node.start_byte()..node.end_byte();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.end_byte() <= range.start {
+            gap.start = child.end_byte();
+        } else if child.start_byte() >= range.end {
+            gap.end = child.start_byte();
+            break;
+        } else {
+            return false;
+        }
+    }
+    text.get(gap.start.min(range.start)..gap.end.max(range.end))
+        .is_some_and(|gap| gap.chars().all(char::is_whitespace))
+}
+
+///FNV-1a of `text`. Unlike the hashers of the standard library it's specified, so the hash of a text stays the same across
+///Rust releases, platforms and runs, and can be stored, e.g. to tell whether a file changed since the session was saved.
+pub(crate) fn text_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+///The text of `lines` (by the line `starts`), with the line breaks between them.
+fn text_of<'a>(text: &'a str, starts: &[usize], lines: &Range<usize>) -> &'a str {
+    let end = starts.get(lines.end).map_or(text.len(), |next| next - 1);
+    &text[starts[lines.start]..end]
+}
+
+///Length of the common start of `a` and `b` in bytes, on a character boundary.
+fn common_prefix(a: &str, b: &str) -> usize {
+    let mut len = a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count();
+    while !a.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
+
+///Length of the common end of `a` and `b` in bytes, on a character boundary.
+fn common_suffix(a: &str, b: &str) -> usize {
+    let mut len = a
+        .bytes()
+        .rev()
+        .zip(b.bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !a.is_char_boundary(a.len() - len) {
+        len -= 1;
+    }
+    len
+}
+
+///See [Document::highlights].
+pub(crate) fn highlights(settings: &TSSettings, text: &str) -> Vec<(Range<usize>, Highlight)> {
+    highlight_with(
+        &mut tree_sitter_highlight::Highlighter::new(),
+        settings,
+        &settings.resolve_styles(),
+        text,
+    )
+}
+
+///See [Document::spans].
+pub(crate) fn spans(settings: &TSSettings, text: &str) -> Vec<Span> {
+    highlights(settings, text)
+        .into_iter()
+        .filter_map(|(range, highlight)| Span::from_highlight(range, &highlight))
+        .collect()
+}
+
+///Highlights many small `(text, language)` snippets, like the code blocks of a chat or a Markdown page. Returns the spans of
+///each snippet, like [Document::highlights] does.
+///
+///Cheaper than a [Document] per snippet: nothing is kept parsed, and the highlighter and the resolved theme styles are set up
+///once for all snippets. `language` is matched against the `language_name`s of `languages`, snippets in other languages get
+///no spans.
+pub fn highlight_many(
+    languages: &[TSSettings],
+    snippets: &[(&str, &str)],
+) -> Vec<Vec<(Range<usize>, Highlight)>> {
+    let mut highlighter = tree_sitter_highlight::Highlighter::new();
+    let mut styles = vec![None; languages.len()];
+    snippets
+        .iter()
+        .map(|(text, language)| {
+            let Some(idx) = languages
+                .iter()
+                .position(|settings| settings.tsconfig.language_name == *language)
+            else {
+                return Vec::new();
+            };
+            let styles = styles[idx].get_or_insert_with(|| languages[idx].resolve_styles());
+            highlight_with(&mut highlighter, &languages[idx], styles, text)
+        })
+        .collect()
+}
+
+pub(crate) fn highlight_with(
+    highlighter: &mut tree_sitter_highlight::Highlighter,
+    settings: &TSSettings,
+    styles: &[Option<crate::theme::Style>],
+    text: &str,
+) -> Vec<(Range<usize>, Highlight)> {
+    let started = settings.measure();
+    //tree-sitter-highlight parses the text again, and only stops at a flag
+    let deadline = settings.parse_timeout.map(|timeout| {
+        settings
+            .watchdog
+            .watch(timeout, settings.cancellation_flag.clone())
+    });
+    let flag = match &deadline {
+        Some(deadline) => Some(&*deadline.flag),
+        None => settings.cancellation_flag.as_deref(),
+    };
+    let cancelled = || {
+        if deadline.as_ref().is_some_and(Deadline::passed) {
+            logging::debug!(
+                target: logging::HIGHLIGHT,
+                "highlighting {} bytes of {} stopped at the timeout",
+                text.len(),
+                settings.tsconfig.language_name
+            );
+        }
+        Vec::new()
+    };
+    let events = match highlighter.highlight(
+        settings.tsconfig.as_ref(),
+        text.as_bytes(),
+        flag,
+        |name| settings.injection(name),
+    ) {
+        Ok(events) => events,
+        //by the cancellation flag or the timeout, the text stays plain
+        Err(tree_sitter_highlight::Error::Cancelled) => return cancelled(),
+        Err(err) => {
+            logging::warning!(target: logging::HIGHLIGHT, "highlighting the document failed: {err}");
+            return Vec::new();
+        }
+    };
+
+    let mut spans = Vec::new();
+    let mut stack = Vec::new();
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(tree_sitter_highlight::Error::Cancelled) => return cancelled(),
+            Err(_) => continue,
+        };
+        match event {
+            HighlightEvent::Source { start, end } => {
+                if let Some(capture) = stack.last().copied() {
+                    spans.push((
+                        start..end,
+                        Highlight {
+                            capture: Some(capture),
+                            style: styles.get(capture.0).copied().flatten(),
+                            dimmed: false,
+                            overlay: None,
+                            names: settings.capture_map(),
+                        },
+                    ));
+                }
+            }
+            HighlightEvent::HighlightStart(capture) => stack.push(capture),
+            HighlightEvent::HighlightEnd => {
+                stack.pop();
+            }
+        }
+    }
+    if let Some(started) = started {
+        settings.report(|language| Usage::HighlightDocument {
+            language,
+            bytes: text.len(),
+            duration: started.elapsed(),
+        });
+    }
+    spans
+}
+
+///Stops tree-sitter-highlight at the [timeout](TSSettings::with_parse_timeout): it only looks at a cancellation flag, so a
+///thread sets the flags of the highlighting that ran out of time, or whose settings' cancellation flag is set. The settings and
+///their clones share one, which starts its thread with the first highlighting that has a timeout, and ends it with them.
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    watches: Mutex<Vec<Watch>>,
+    thread: OnceLock<std::thread::Thread>,
+}
+
+struct Watch {
+    ///Gone once the highlighting is done.
+    flag: std::sync::Weak<AtomicUsize>,
+    timeout: Instant,
+    cancellation_flag: Option<Arc<AtomicUsize>>,
+}
+
+///The flag of one highlighting, see [Watchdog::watch].
+struct Deadline {
+    flag: Arc<AtomicUsize>,
+    timeout: Instant,
+}
+
+impl Watchdog {
+    ///A flag that is set once `timeout` passed, or `cancellation_flag` is.
+    fn watch(
+        self: &Arc<Self>,
+        timeout: Duration,
+        cancellation_flag: Option<Arc<AtomicUsize>>,
+    ) -> Deadline {
+        let deadline = Deadline {
+            flag: Arc::new(AtomicUsize::new(0)),
+            timeout: Instant::now() + timeout,
+        };
+        self.lock().push(Watch {
+            flag: Arc::downgrade(&deadline.flag),
+            timeout: deadline.timeout,
+            cancellation_flag,
+        });
+        self.thread
+            .get_or_init(|| {
+                let watchdog = Arc::downgrade(self);
+                std::thread::Builder::new()
+                    .name("highlight watchdog".into())
+                    .spawn(move || {
+                        while let Some(wait) = watchdog.upgrade().map(|watchdog| watchdog.check()) {
+                            match wait {
+                                Some(wait) => std::thread::park_timeout(wait),
+                                None => std::thread::park(),
+                            }
+                        }
+                    })
+                    .expect("spawning the highlight watchdog")
+                    .thread()
+                    .clone()
+            })
+            //to wait for the new timeout
+            .unpark();
+        deadline
+    }
+
+    ///Sets the flags that are due, and tells how long to wait for the next one. `None` if nothing is watched.
+    fn check(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let mut watches = self.lock();
+        watches.retain(|watch| {
+            let Some(flag) = watch.flag.upgrade() else {
+                return false;
+            };
+            let cancelled = watch
+                .cancellation_flag
+                .as_deref()
+                .is_some_and(|flag| flag.load(Ordering::Relaxed) != 0);
+            if cancelled || now >= watch.timeout {
+                flag.store(1, Ordering::Relaxed);
+                return false;
+            }
+            true
+        });
+        watches
+            .iter()
+            .map(|watch| match watch.cancellation_flag {
+                Some(_) => (watch.timeout - now).min(CANCELLATION_POLL),
+                None => watch.timeout - now,
+            })
+            .min()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Watch>> {
+        self.watches
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        //the thread ends once it can't reach the watchdog anymore
+        if let Some(thread) = self.thread.get() {
+            thread.unpark();
+        }
+    }
+}
+
+impl Deadline {
+    ///Whether highlighting was stopped by the timeout.
+    fn passed(&self) -> bool {
+        self.flag.load(Ordering::Relaxed) != 0 && Instant::now() >= self.timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use super::{Document, Position, highlights};
+    use crate::stats::Usage;
+    use crate::{registry::LanguageRegistry, testing};
+
+    const TEXT: &str =
+        "/// Docs\nfn main() {\n    let x = 1; /* block\n comment */\n    println!(\"{x}\");\n}\n";
+
+    #[test]
+    fn edit_clamps_the_range() {
+        let mut document = Document::new(&testing::rust(), "fn é() {}");
+        document.edit(4..100, "x");
+        assert_eq!(document.text(), "fn x");
+        //starts after the end
+        let (start, end) = (50, 3);
+        document.edit(start..end, "y");
+        assert_eq!(document.text(), "fn yx");
+    }
+
+    #[test]
+    fn edit_parses_like_a_new_document() {
+        let mut document = Document::new(&testing::rust(), "fn main() {\n    let x = 1;\n}\n");
+        document.edit(20..21, "value");
+        let fresh = Document::new(&testing::rust(), document.text());
+        assert_eq!(
+            document.tree().map(|tree| tree.root_node().to_sexp()),
+            fresh.tree().map(|tree| tree.root_node().to_sexp())
+        );
+    }
+
+    #[test]
+    fn highlights_from_the_tree_like_tree_sitter_highlight() {
+        let settings = testing::rust();
+        assert_eq!(
+            Document::new(&settings, TEXT).highlights(),
+            highlights(&settings, TEXT)
+        );
+    }
+
+    #[test]
+    fn highlighting_stops_at_the_timeout() {
+        //embedded code takes parsing the text again
+        let registry = LanguageRegistry::new().with_language("rust", testing::rust());
+        let settings = testing::rust().with_injections(&registry);
+        let text = TEXT.repeat(2000);
+        assert!(
+            !highlights(
+                &settings.clone().with_parse_timeout(Duration::from_secs(60)),
+                &text
+            )
+            .is_empty()
+        );
+        assert!(highlights(&settings.with_parse_timeout(Duration::ZERO), &text).is_empty());
+    }
+
+    #[test]
+    fn undo_and_redo_reuse_the_trees() {
+        let parses = Arc::new(AtomicUsize::new(0));
+        let counter = parses.clone();
+        let settings = testing::rust().with_stats(move |usage| {
+            if let Usage::Parse { reused: false, .. } = usage {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let mut document = Document::new(&settings, TEXT);
+        document.edit(28..29, "value");
+        document.edit(0..0, "\n");
+        assert_eq!(parses.load(Ordering::Relaxed), 3);
+        //undo twice, by an edit and by the whole text, and redo
+        document.edit(0..1, "");
+        document.set_text(TEXT);
+        assert_eq!(document.text(), TEXT);
+        document.edit(28..29, "value");
+        assert_eq!(parses.load(Ordering::Relaxed), 3);
+        let fresh = Document::new(&testing::rust(), document.text());
+        assert_eq!(
+            document.tree().map(|tree| tree.root_node().to_sexp()),
+            fresh.tree().map(|tree| tree.root_node().to_sexp())
+        );
+        //an edit of its own ends the redo
+        document.set_text(TEXT);
+        document.edit(0..0, "//");
+        document.set_text(TEXT);
+        document.edit(28..29, "value");
+        assert_eq!(parses.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn changes_tell_the_lines_that_changed() {
+        let mut document = Document::new(&testing::rust(), TEXT);
+        let changes = document.subscribe();
+        //a line break at the end of a line only adds the next one
+        document.edit(9..9, "\n");
+        document.edit(22..22, "    let y = 2;\n");
+        document.edit(0..1000, "");
+        let changes = changes.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (change.old_lines.clone(), change.lines.clone()))
+                .collect::<Vec<_>>(),
+            [(1..1, 1..2), (3..3, 3..4), (0..8, 0..0)]
+        );
+        assert_eq!(changes[2].hash, Document::new(&testing::rust(), "").hash());
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        //FNV-1a of the texts
+        assert_eq!(
+            Document::new(&testing::rust(), "").hash(),
+            0xcbf2_9ce4_8422_2325
+        );
+        let mut document = Document::new(&testing::rust(), "a");
+        assert_eq!(document.hash(), 0xaf63_dc4c_8601_ec8c);
+        document.edit(1..1, "b");
+        assert_eq!(
+            document.hash(),
+            Document::new(&testing::rust(), "ab").hash()
+        );
+    }
+
+    ///Converts every byte offset of the text to UTF-16 and back, and compares with counting the code units of the line.
+    fn assert_utf16_round_trips(document: &Document) {
+        let text = document.text();
+        for offset in 0..=text.len() {
+            let position = document.position(offset);
+            let start = offset - position.column;
+            //offsets inside a character count from its start
+            let char_start = (0..=offset)
+                .rev()
+                .find(|idx| text.is_char_boundary(*idx))
+                .unwrap();
+            let units = text[start..char_start].encode_utf16().count();
+            assert_eq!(
+                document.byte_to_utf16(offset),
+                Position::new(position.line, units),
+                "at {offset}"
+            );
+            //like `offset`, positions in the line break are the end of the line
+            let line_end = document.line_range(position.line).unwrap().end;
+            assert_eq!(
+                document.utf16_to_byte(Position::new(position.line, units)),
+                char_start.min(line_end),
+                "at {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn utf16_columns_round_trip() {
+        let mut document = Document::new(
+            &testing::rust(),
+            "let é = \"ü😀x\";\r\nlet ascii = 1;\r\n// 😀😀\r\n\r\n",
+        );
+        assert_utf16_round_trips(&document);
+        //the columns of edited lines are computed again
+        document.edit(5..7, "😀ab");
+        document.edit(0..0, "ß\r\n");
+        assert_utf16_round_trips(&document);
+    }
+
+    #[test]
+    fn utf16_columns_inside_surrogate_pairs_and_past_the_end_are_clamped() {
+        let document = Document::new(&testing::rust(), "a😀b\r\nc");
+        //the second half of the pair
+        assert_eq!(document.utf16_to_byte(Position::new(0, 2)), 1);
+        assert_eq!(document.utf16_to_byte(Position::new(0, 3)), 5);
+        //the line break
+        assert_eq!(document.byte_to_utf16(6), Position::new(0, 4));
+        assert_eq!(document.byte_to_utf16(7), Position::new(0, 5));
+        assert_eq!(document.utf16_to_byte(Position::new(0, 100)), 6);
+        assert_eq!(document.byte_to_utf16(100), Position::new(1, 1));
+        assert_eq!(
+            document.utf16_to_byte(Position::new(5, 0)),
+            document.text().len()
+        );
+    }
+}
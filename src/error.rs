@@ -0,0 +1,154 @@
+//! Errors of setting up a language: a grammar built for another version of tree-sitter, a query that doesn't compile for
+//! it, or highlight names that can't change anymore. Returned by the fallible constructors,
+//! [TSSettings::try_new](crate::TSSettings::try_new) and
+//! [LanguageRegistry::register_grammar](crate::registry::LanguageRegistry::register_grammar), so apps that load grammars or
+//! queries at runtime can report what's wrong instead of panicking, and by the builders that change the highlight names, like
+//! [TSSettings::with_captures](crate::TSSettings::with_captures).
+//!
+//! tree-sitter-highlight compiles the highlight, injection and locals queries as one, so its errors point into their
+//! concatenation. An [Error] tells which of the queries it is in, and the line and column within that one.
+
+use tree_sitter::QueryErrorKind;
+#[cfg(feature = "presets")]
+use tree_sitter_highlight::HighlightConfiguration;
+
+///Which query of a language an [Error] is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryKind {
+    Highlights,
+    Injections,
+    Locals,
+    ///The query of [symbols](crate::symbols), see [TSSettings::with_tags_query](crate::TSSettings::with_tags_query).
+    Tags,
+}
+
+impl std::fmt::Display for QueryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Highlights => "highlight",
+            Self::Injections => "injection",
+            Self::Locals => "locals",
+            Self::Tags => "tags",
+        })
+    }
+}
+
+///Why a language couldn't be set up, see the [module](self) documentation.
+#[derive(Debug)]
+pub enum Error {
+    ///The grammar was generated for an ABI version of tree-sitter the crate can't load. Regenerate it, or use a version of the
+    ///grammar's crate that depends on the same tree-sitter.
+    Abi { language: String, version: usize },
+    ///A query doesn't compile for the grammar, like one written for another version of it. The row and column of `error` are
+    ///within the `query`, starting at 0.
+    Query {
+        language: String,
+        query: QueryKind,
+        error: tree_sitter::QueryError,
+    },
+    ///The highlight names of a configuration that is shared with other settings (their clones, or the ones of a
+    ///[registry](crate::registry::LanguageRegistry)) can't change, since they would change for all of them. Change them before
+    ///cloning the settings, see [TSSettings::with_captures](crate::TSSettings::with_captures).
+    Shared { language: String },
+}
+
+///Result of setting up a language, see [Error].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Abi { language, version } => write!(
+                f,
+                "the {language} grammar has ABI version {version}, tree-sitter loads {} to {}",
+                tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                tree_sitter::LANGUAGE_VERSION
+            ),
+            Self::Query {
+                language,
+                query,
+                error,
+            } => {
+                write!(
+                    f,
+                    "invalid {query} query for {language} at line {}, column {}: ",
+                    error.row + 1,
+                    error.column + 1
+                )?;
+                let message = &error.message;
+                match error.kind {
+                    QueryErrorKind::NodeType => {
+                        write!(f, "the grammar has no node type `{message}`")
+                    }
+                    QueryErrorKind::Field => write!(f, "the grammar has no field `{message}`"),
+                    QueryErrorKind::Capture => write!(f, "the pattern has no capture `@{message}`"),
+                    QueryErrorKind::Predicate => write!(f, "invalid predicate, {message}"),
+                    QueryErrorKind::Structure => write!(f, "the pattern can't match\n{message}"),
+                    QueryErrorKind::Syntax => write!(f, "invalid syntax\n{message}"),
+                    QueryErrorKind::Language => f.write_str(message),
+                }
+            }
+            Self::Shared { language } => write!(
+                f,
+                "the {language} configuration is shared with other settings, its highlight names can't change"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Abi { .. } | Self::Shared { .. } => None,
+            Self::Query { error, .. } => Some(error),
+        }
+    }
+}
+
+///[HighlightConfiguration::new], with the errors pointing into the query they are in.
+#[cfg(feature = "presets")]
+pub(crate) fn configuration(
+    language: tree_sitter::Language,
+    name: &str,
+    highlights: &str,
+    injections: &str,
+    locals: &str,
+) -> Result<HighlightConfiguration> {
+    let version = language.abi_version();
+    if !(tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION)
+        .contains(&version)
+    {
+        return Err(Error::Abi {
+            language: name.to_string(),
+            version,
+        });
+    }
+    HighlightConfiguration::new(language, name, highlights, injections, locals).map_err(
+        |mut error| {
+            //concatenated in this order
+            let sections = [
+                (QueryKind::Injections, injections),
+                (QueryKind::Locals, locals),
+                (QueryKind::Highlights, highlights),
+            ];
+            let mut start = 0;
+            let mut query = QueryKind::Highlights;
+            for (kind, source) in sections {
+                if error.offset < start + source.len() || kind == QueryKind::Highlights {
+                    let before = source.get(..error.offset - start).unwrap_or(source);
+                    error.offset -= start;
+                    error.row = before.matches('\n').count();
+                    error.column = before.len() - before.rfind('\n').map_or(0, |line| line + 1);
+                    query = kind;
+                    break;
+                }
+                start += source.len();
+            }
+            Error::Query {
+                language: name.to_string(),
+                query,
+                error,
+            }
+        },
+    )
+}
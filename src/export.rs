@@ -0,0 +1,889 @@
+//! Exporting highlighted text, e.g. for "Copy as rich text" or code screenshots.
+//!
+//! Small ranges are exported via [Document::export] and friends. For huge documents build an [ExportJob] instead. It owns everything it needs, so it can
+//! run on a worker thread. It also writes its output in chunks, so calling [ExportJob::step] once per frame works on the GUI thread as well.
+
+use std::{fmt::Write as _, io, ops::Range};
+
+use iced_core::Color;
+
+use crate::{TSSettings, document::Document, theme::Theme};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    ///A `<pre>` block with inline styles. Works when pasted into most mail clients and office suites.
+    Html,
+    ///Rich text format, what most native applications put on the clipboard for styled text.
+    Rtf,
+    ///Text with 24-bit ANSI escape codes for terminals.
+    Ansi,
+    ///Standalone SVG image, e.g. for snippet sharing.
+    Svg,
+}
+
+///Layout options of the exporters. [ExportOptions::default] is a plain export without any decoration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    ///Prefix every line with its line number.
+    pub line_numbers: bool,
+    ///Number of the first exported line. `None` uses the line's number in the document.
+    pub start_line: Option<usize>,
+    ///Draw a window title bar with the three "traffic light" buttons (HTML and SVG only).
+    pub window_chrome: bool,
+    ///Padding around the code in pixels (HTML and SVG only).
+    pub padding: f32,
+    ///Leave the background out (HTML and SVG only).
+    pub transparent_background: bool,
+    ///Font size in pixels (HTML and SVG only).
+    pub font_size: f32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            line_numbers: false,
+            start_line: None,
+            window_chrome: false,
+            padding: 0.0,
+            transparent_background: false,
+            font_size: 14.0,
+        }
+    }
+}
+
+impl ExportOptions {
+    ///Typical settings of snippet-sharing tools: line numbers, window chrome and some padding.
+    pub fn screenshot() -> Self {
+        Self {
+            line_numbers: true,
+            window_chrome: true,
+            padding: 32.0,
+            ..Default::default()
+        }
+    }
+}
+
+///A piece of text with uniform style.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Run {
+    ///Byte range of the text. Into the document for [styled_runs], into the exported text within an [ExportJob].
+    pub range: Range<usize>,
+    pub color: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl Run {
+    fn plain(range: Range<usize>) -> Self {
+        Self {
+            range,
+            color: None,
+            background: None,
+            bold: false,
+            italic: false,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    fn same_style(&self, other: &Run) -> bool {
+        self.color == other.color
+            && self.background == other.background
+            && self.bold == other.bold
+            && self.italic == other.italic
+            && self.underline == other.underline
+            && self.strikethrough == other.strikethrough
+    }
+
+    ///The CSS and SVG `text-decoration`, if any.
+    fn decoration(&self) -> Option<&'static str> {
+        match (self.underline, self.strikethrough) {
+            (true, true) => Some("underline line-through"),
+            (true, false) => Some("underline"),
+            (false, true) => Some("line-through"),
+            (false, false) => None,
+        }
+    }
+}
+
+///Where the exporters take their colors from.
+#[derive(Clone, Copy)]
+pub(crate) enum Colors<'a> {
+    ///Same as the editor, via [to_format](crate::to_format).
+    Iced(&'a iced_core::Theme),
+    ///Independent of the GUI.
+    Theme(&'a Theme),
+}
+
+///Owned [Colors], so a job can keep them until it runs.
+enum OwnedColors {
+    Iced(iced_core::Theme),
+    Theme(Theme),
+}
+
+impl OwnedColors {
+    fn as_colors(&self) -> Colors<'_> {
+        match self {
+            OwnedColors::Iced(theme) => Colors::Iced(theme),
+            OwnedColors::Theme(theme) => Colors::Theme(theme),
+        }
+    }
+}
+
+///How far an [ExportJob] got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportProgress {
+    pub written_lines: usize,
+    pub total_lines: usize,
+}
+
+impl ExportProgress {
+    pub fn is_done(&self) -> bool {
+        self.written_lines >= self.total_lines
+    }
+
+    ///Progress in the range `0.0..=1.0`.
+    pub fn fraction(&self) -> f32 {
+        if self.total_lines == 0 {
+            1.0
+        } else {
+            self.written_lines as f32 / self.total_lines as f32
+        }
+    }
+}
+
+///What's needed to highlight the exported range. Highlighting is deferred to the first [ExportJob::step], so creating a job is cheap.
+struct Source {
+    settings: TSSettings,
+    ///Whole document, the range alone might lack context (e.g. the start of a block comment).
+    text: String,
+    range: Range<usize>,
+    colors: OwnedColors,
+}
+
+///An export that is written in chunks, see [Document::export_job].
+///
+///Owns a copy of the document's text, so it is `Send` and independent of the [Document] it was created from.
+pub struct ExportJob {
+    format: ExportFormat,
+    options: ExportOptions,
+    source: Option<Source>,
+    ///Exported text, [Run]s index into it.
+    text: String,
+    ///[Run]s of each line, line breaks are not part of any run.
+    lines: Vec<Vec<Run>>,
+    total_lines: usize,
+    first_line: usize,
+    background: Color,
+    foreground: Color,
+    ///RTF color table, collected up front since it is part of the header.
+    colors: Vec<Color>,
+    next_line: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl ExportJob {
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    pub fn progress(&self) -> ExportProgress {
+        ExportProgress {
+            written_lines: self.next_line,
+            total_lines: self.total_lines,
+        }
+    }
+
+    ///Writes the next `max_lines` lines to `out`. The first call also highlights the range and writes the header, the last one writes the footer.
+    ///Keep calling until the returned progress [is done](ExportProgress::is_done), later calls don't write anything.
+    pub fn step(
+        &mut self,
+        out: &mut impl io::Write,
+        max_lines: usize,
+    ) -> io::Result<ExportProgress> {
+        if self.finished {
+            return Ok(self.progress());
+        }
+        if let Some(source) = self.source.take() {
+            self.layout(source);
+        }
+
+        let mut chunk = String::new();
+        if !self.started {
+            self.header(&mut chunk);
+        }
+        let end = self
+            .next_line
+            .saturating_add(max_lines)
+            .min(self.lines.len());
+        for idx in self.next_line..end {
+            self.line(idx, &mut chunk);
+        }
+        if end == self.lines.len() {
+            self.footer(&mut chunk);
+        }
+
+        out.write_all(chunk.as_bytes())?;
+        //only advance once the chunk is written, so a failed write can be retried.
+        self.started = true;
+        self.next_line = end;
+        self.finished = end == self.lines.len();
+        Ok(self.progress())
+    }
+
+    ///Writes the whole export to `out`, `lines_per_step` lines at a time. `progress` is called after each step, return `false` to cancel.
+    pub fn run(
+        &mut self,
+        out: &mut impl io::Write,
+        lines_per_step: usize,
+        mut progress: impl FnMut(ExportProgress) -> bool,
+    ) -> io::Result<ExportProgress> {
+        loop {
+            let current = self.step(out, lines_per_step.max(1))?;
+            if current.is_done() || !progress(current) {
+                return Ok(current);
+            }
+        }
+    }
+
+    ///Runs the (remaining) export into a string.
+    pub fn into_string(mut self) -> String {
+        let mut out = Vec::new();
+        //writing into a Vec can't fail
+        let _ = self.run(&mut out, usize::MAX, |_| true);
+        String::from_utf8(out).unwrap_or_default()
+    }
+
+    ///Highlights the source and splits the runs at line breaks.
+    fn layout(&mut self, source: Source) {
+        let Source {
+            settings,
+            text,
+            range,
+            colors,
+        } = source;
+        self.text = text[range.clone()].to_string();
+
+        let mut lines = vec![Vec::new()];
+        for run in styled_runs(&settings, &text, range.clone(), colors.as_colors()) {
+            //relative to the exported text from here on
+            let mut start = run.range.start - range.start;
+            let end = run.range.end - range.start;
+            loop {
+                let line_end = self.text[start..end]
+                    .find('\n')
+                    .map(|idx| start + idx)
+                    .unwrap_or(end);
+                let content_end = if self.text[start..line_end].ends_with('\r') {
+                    line_end - 1
+                } else {
+                    line_end
+                };
+                if start < content_end {
+                    lines.last_mut().unwrap().push(Run {
+                        range: start..content_end,
+                        ..run.clone()
+                    });
+                }
+                if line_end == end {
+                    break;
+                }
+                lines.push(Vec::new());
+                start = line_end + 1;
+            }
+        }
+        self.lines = lines;
+
+        //color table: 1 is background, 2 is foreground, 3 line numbers, the rest are collected from the runs.
+        let mut colors = vec![self.background, self.foreground, self.line_number_color()];
+        for color in self
+            .lines
+            .iter()
+            .flatten()
+            .flat_map(|run| [run.color, run.background])
+            .flatten()
+        {
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+        self.colors = colors;
+    }
+
+    fn text(&self, run: &Run) -> &str {
+        &self.text[run.range.clone()]
+    }
+
+    fn number_width(&self) -> usize {
+        (self.first_line + self.total_lines.saturating_sub(1))
+            .to_string()
+            .len()
+    }
+
+    fn number(&self, idx: usize) -> String {
+        format!(
+            "{:>width$}",
+            self.first_line + idx,
+            width = self.number_width()
+        )
+    }
+
+    fn line_number_color(&self) -> Color {
+        crate::color::mix(self.foreground, self.background, 0.5)
+    }
+
+    fn header(&self, out: &mut String) {
+        match self.format {
+            ExportFormat::Html => self.html_header(out),
+            ExportFormat::Rtf => self.rtf_header(out),
+            ExportFormat::Ansi => {}
+            ExportFormat::Svg => self.svg_header(out),
+        }
+    }
+
+    fn line(&self, idx: usize, out: &mut String) {
+        match self.format {
+            ExportFormat::Html => self.html_line(idx, out),
+            ExportFormat::Rtf => self.rtf_line(idx, out),
+            ExportFormat::Ansi => self.ansi_line(idx, out),
+            ExportFormat::Svg => self.svg_line(idx, out),
+        }
+    }
+
+    fn footer(&self, out: &mut String) {
+        match self.format {
+            ExportFormat::Html => out.push_str("</pre></div>"),
+            ExportFormat::Rtf => out.push('}'),
+            ExportFormat::Ansi => {}
+            ExportFormat::Svg => out.push_str("</svg>"),
+        }
+    }
+}
+
+impl Document {
+    ///Extracts the byte `range` with full styling.
+    ///
+    ///If [TSSettings::export_theme] is set, its colors are used. Otherwise colors are resolved the same way the
+    ///editor does it (via [to_format](crate::to_format)), so `theme` should be the theme the editor is drawn with.
+    pub fn copy_highlighted(
+        &self,
+        range: Range<usize>,
+        format: ExportFormat,
+        theme: &iced_core::Theme,
+    ) -> String {
+        self.export(range, format, &ExportOptions::default(), theme)
+    }
+
+    ///Same as [Document::copy_highlighted], but with layout `options`.
+    pub fn export(
+        &self,
+        range: Range<usize>,
+        format: ExportFormat,
+        options: &ExportOptions,
+        theme: &iced_core::Theme,
+    ) -> String {
+        self.export_job(range, format, options, theme).into_string()
+    }
+
+    ///Exports with an explicit `theme`, independent of the GUI's theme. For instance light-background snippets from a dark-mode app.
+    pub fn export_with_theme(
+        &self,
+        range: Range<usize>,
+        format: ExportFormat,
+        options: &ExportOptions,
+        theme: &Theme,
+    ) -> String {
+        self.export_job_with_theme(range, format, options, theme)
+            .into_string()
+    }
+
+    ///Prepares a streamed export of `range`, colors are picked like for [Document::export].
+    ///
+    ///Creating the job only copies the text, all the work happens while the [ExportJob] runs. Move it to a worker thread, or
+    ///[step](ExportJob::step) it a few thousand lines per frame, to export huge files without blocking the UI.
+    pub fn export_job(
+        &self,
+        range: Range<usize>,
+        format: ExportFormat,
+        options: &ExportOptions,
+        theme: &iced_core::Theme,
+    ) -> ExportJob {
+        let colors = match &self.settings().export_theme {
+            Some(export_theme) => OwnedColors::Theme(export_theme.as_ref().clone()),
+            None => OwnedColors::Iced(theme.clone()),
+        };
+        self.build_job(range, format, options, colors)
+    }
+
+    ///Same as [Document::export_job], but with an explicit `theme`.
+    pub fn export_job_with_theme(
+        &self,
+        range: Range<usize>,
+        format: ExportFormat,
+        options: &ExportOptions,
+        theme: &Theme,
+    ) -> ExportJob {
+        self.build_job(range, format, options, OwnedColors::Theme(theme.clone()))
+    }
+
+    fn build_job(
+        &self,
+        range: Range<usize>,
+        format: ExportFormat,
+        options: &ExportOptions,
+        colors: OwnedColors,
+    ) -> ExportJob {
+        self.settings().report_feature("export");
+        let range = range.start.min(self.text().len())..range.end.min(self.text().len());
+        let (background, foreground) = match &colors {
+            OwnedColors::Iced(theme) => {
+                let palette = theme.extended_palette();
+                (palette.background.base.color, palette.background.base.text)
+            }
+            OwnedColors::Theme(theme) => (theme.background, theme.foreground),
+        };
+
+        ExportJob {
+            format,
+            options: options.clone(),
+            total_lines: self.text()[range.clone()].matches('\n').count() + 1,
+            first_line: options
+                .start_line
+                .unwrap_or(self.position(range.start).line + 1),
+            source: Some(Source {
+                settings: self.settings().clone(),
+                text: self.text().to_string(),
+                range,
+                colors,
+            }),
+            text: String::new(),
+            lines: Vec::new(),
+            background,
+            foreground,
+            colors: Vec::new(),
+            next_line: 0,
+            started: false,
+            finished: false,
+        }
+    }
+}
+
+///Splits `range` of `text` into runs of uniformly styled text.
+pub(crate) fn styled_runs(
+    settings: &TSSettings,
+    text: &str,
+    range: Range<usize>,
+    colors: Colors<'_>,
+) -> Vec<Run> {
+    let range = range.start.min(text.len())..range.end.min(text.len());
+    let mut runs: Vec<Run> = Vec::new();
+    let mut cursor = range.start;
+    //neighboring runs often end up with the same style (e.g. uncolored punctuation), merge those
+    let mut push = |run: Run| match runs.last_mut() {
+        Some(last) if last.same_style(&run) => last.range.end = run.range.end,
+        _ => runs.push(run),
+    };
+
+    for span in crate::document::spans(settings, text) {
+        if span.range.end <= range.start {
+            continue;
+        }
+        if span.range.start >= range.end {
+            break;
+        }
+
+        let start = span.range.start.max(range.start);
+        let end = span.range.end.min(range.end);
+        if cursor < start {
+            push(Run::plain(cursor..start));
+        }
+
+        let run = match colors {
+            Colors::Iced(theme) => {
+                let format = span.to_format(theme);
+                let font = format.font.unwrap_or(iced_core::Font::MONOSPACE);
+                //what the format can't carry
+                let style = span.style.unwrap_or_default();
+                Run {
+                    range: start..end,
+                    color: format.color,
+                    background: style.background,
+                    bold: matches!(
+                        font.weight,
+                        iced_core::font::Weight::Semibold
+                            | iced_core::font::Weight::Bold
+                            | iced_core::font::Weight::ExtraBold
+                            | iced_core::font::Weight::Black
+                    ),
+                    italic: font.style != iced_core::font::Style::Normal,
+                    underline: style.underline,
+                    strikethrough: style.strikethrough,
+                }
+            }
+            Colors::Theme(theme) => {
+                let style = theme.style(&span.capture).copied().unwrap_or_default();
+                Run {
+                    range: start..end,
+                    color: style.color,
+                    background: style.background,
+                    bold: style.bold,
+                    italic: style.italic,
+                    underline: style.underline,
+                    strikethrough: style.strikethrough,
+                }
+            }
+        };
+        push(run);
+        cursor = end;
+    }
+    if cursor < range.end {
+        push(Run::plain(cursor..range.end));
+    }
+    runs
+}
+
+fn hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+///Escapes for HTML and SVG (XML).
+fn escape_xml(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+const CHROME_BUTTONS: [Color; 3] = [
+    Color::from_rgb(1.0, 0.37, 0.34),
+    Color::from_rgb(1.0, 0.74, 0.18),
+    Color::from_rgb(0.16, 0.79, 0.25),
+];
+
+//HTML
+impl ExportJob {
+    fn html_header(&self, out: &mut String) {
+        let options = &self.options;
+        let background = if options.transparent_background {
+            "transparent".to_string()
+        } else {
+            hex(self.background)
+        };
+        let _ = write!(
+            out,
+            "<div style=\"display:inline-block;background-color:{background};padding:{}px;border-radius:{}px\">",
+            options.padding,
+            if options.window_chrome { 8 } else { 0 },
+        );
+        if options.window_chrome {
+            out.push_str("<div style=\"padding-bottom:12px\">");
+            for color in CHROME_BUTTONS {
+                let _ = write!(
+                    out,
+                    "<span style=\"display:inline-block;width:12px;height:12px;margin-right:8px;border-radius:50%;background-color:{}\"></span>",
+                    hex(color)
+                );
+            }
+            out.push_str("</div>");
+        }
+
+        let _ = write!(
+            out,
+            "<pre style=\"margin:0;color:{};font-family:monospace;font-size:{}px\">",
+            hex(self.foreground),
+            options.font_size
+        );
+    }
+
+    fn html_line(&self, idx: usize, out: &mut String) {
+        if idx > 0 {
+            out.push('\n');
+        }
+        if self.options.line_numbers {
+            let _ = write!(
+                out,
+                "<span style=\"color:{};user-select:none\">{} </span>",
+                hex(self.line_number_color()),
+                self.number(idx)
+            );
+        }
+        for run in &self.lines[idx] {
+            let mut style = String::new();
+            if let Some(color) = run.color {
+                let _ = write!(style, "color:{};", hex(color));
+            }
+            if let Some(color) = run.background {
+                let _ = write!(style, "background-color:{};", hex(color));
+            }
+            if run.bold {
+                style.push_str("font-weight:bold;");
+            }
+            if run.italic {
+                style.push_str("font-style:italic;");
+            }
+            if let Some(decoration) = run.decoration() {
+                let _ = write!(style, "text-decoration:{decoration};");
+            }
+
+            if style.is_empty() {
+                escape_xml(self.text(run), out);
+            } else {
+                let _ = write!(out, "<span style=\"{style}\">");
+                escape_xml(self.text(run), out);
+                out.push_str("</span>");
+            }
+        }
+    }
+}
+
+//SVG
+impl ExportJob {
+    //monospace metrics, close enough for all common monospace fonts
+    fn char_width(&self) -> f32 {
+        self.options.font_size * 0.6
+    }
+
+    fn line_height(&self) -> f32 {
+        self.options.font_size * 1.4
+    }
+
+    fn chrome_height(&self) -> f32 {
+        if self.options.window_chrome {
+            self.options.font_size * 2.0
+        } else {
+            0.0
+        }
+    }
+
+    ///Width of the text of `run` in characters, tabs are drawn as four spaces.
+    fn columns(&self, run: &Run) -> usize {
+        self.text(run)
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum()
+    }
+
+    fn gutter_width(&self) -> f32 {
+        if self.options.line_numbers {
+            (self.number_width() + 1) as f32 * self.char_width()
+        } else {
+            0.0
+        }
+    }
+
+    fn svg_header(&self, out: &mut String) {
+        let options = &self.options;
+        let columns = self
+            .lines
+            .iter()
+            .map(|line| line.iter().map(|run| self.columns(run)).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        let width =
+            options.padding * 2.0 + self.gutter_width() + columns as f32 * self.char_width();
+        let height = options.padding * 2.0
+            + self.chrome_height()
+            + self.lines.len() as f32 * self.line_height();
+
+        let _ = write!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"{}\">",
+            options.font_size
+        );
+        if !options.transparent_background {
+            let _ = write!(
+                out,
+                "<rect width=\"100%\" height=\"100%\" rx=\"{}\" fill=\"{}\"/>",
+                if options.window_chrome { 8 } else { 0 },
+                hex(self.background)
+            );
+        }
+        if options.window_chrome {
+            let radius = options.font_size * 0.4;
+            for (idx, color) in CHROME_BUTTONS.iter().enumerate() {
+                let _ = write!(
+                    out,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{radius}\" fill=\"{}\"/>",
+                    options.padding + radius + idx as f32 * radius * 3.5,
+                    options.padding + radius,
+                    hex(*color)
+                );
+            }
+        }
+    }
+
+    fn svg_line(&self, idx: usize, out: &mut String) {
+        let options = &self.options;
+        //baseline of the line
+        let y = options.padding + self.chrome_height() + (idx as f32 + 0.8) * self.line_height();
+        if options.line_numbers {
+            let _ = write!(
+                out,
+                "<text x=\"{}\" y=\"{y}\" fill=\"{}\" xml:space=\"preserve\">{}</text>",
+                options.padding,
+                hex(self.line_number_color()),
+                self.number(idx)
+            );
+        }
+        let line = &self.lines[idx];
+        if line.is_empty() {
+            return;
+        }
+
+        //SVG text has no background, so it gets a rectangle behind it
+        let mut column = 0;
+        for run in line {
+            let columns = self.columns(run);
+            if let Some(color) = run.background {
+                let _ = write!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    options.padding + self.gutter_width() + column as f32 * self.char_width(),
+                    options.padding + self.chrome_height() + idx as f32 * self.line_height(),
+                    columns as f32 * self.char_width(),
+                    self.line_height(),
+                    hex(color)
+                );
+            }
+            column += columns;
+        }
+
+        let _ = write!(
+            out,
+            "<text x=\"{}\" y=\"{y}\" fill=\"{}\" xml:space=\"preserve\">",
+            options.padding + self.gutter_width(),
+            hex(self.foreground)
+        );
+        for run in line {
+            out.push_str("<tspan");
+            if let Some(color) = run.color {
+                let _ = write!(out, " fill=\"{}\"", hex(color));
+            }
+            if run.bold {
+                out.push_str(" font-weight=\"bold\"");
+            }
+            if run.italic {
+                out.push_str(" font-style=\"italic\"");
+            }
+            if let Some(decoration) = run.decoration() {
+                let _ = write!(out, " text-decoration=\"{decoration}\"");
+            }
+            out.push('>');
+            escape_xml(&self.text(run).replace('\t', "    "), out);
+            out.push_str("</tspan>");
+        }
+        out.push_str("</text>");
+    }
+}
+
+//RTF
+impl ExportJob {
+    fn rtf_header(&self, out: &mut String) {
+        out.push_str("{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}{\\colortbl;");
+        for color in &self.colors {
+            let [r, g, b, _] = color.into_rgba8();
+            let _ = write!(out, "\\red{r}\\green{g}\\blue{b};");
+        }
+        out.push_str("}\\f0\\cb1\\highlight1\\cf2 ");
+    }
+
+    fn rtf_line(&self, idx: usize, out: &mut String) {
+        if idx > 0 {
+            out.push_str("\\line ");
+        }
+        if self.options.line_numbers {
+            let _ = write!(out, "{{\\cf3 {} }}", self.number(idx));
+        }
+        for run in &self.lines[idx] {
+            let color_index = |color: Option<Color>, default| {
+                color
+                    .and_then(|color| self.colors.iter().position(|c| *c == color))
+                    .unwrap_or(default)
+                    + 1
+            };
+            let _ = write!(out, "{{\\cf{}", color_index(run.color, 1));
+            if run.background.is_some() {
+                let background = color_index(run.background, 0);
+                let _ = write!(out, "\\cb{background}\\highlight{background}");
+            }
+            let _ = write!(
+                out,
+                "{}{}{}{} ",
+                if run.bold { "\\b" } else { "" },
+                if run.italic { "\\i" } else { "" },
+                if run.underline { "\\ul" } else { "" },
+                if run.strikethrough { "\\strike" } else { "" }
+            );
+            for c in self.text(run).chars() {
+                match c {
+                    '\\' | '{' | '}' => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                    '\t' => out.push_str("\\tab "),
+                    c if c.is_ascii() => out.push(c),
+                    c => {
+                        //RTF wants signed 16 bit code units, with a fallback character
+                        let mut units = [0; 2];
+                        for unit in c.encode_utf16(&mut units) {
+                            let _ = write!(out, "\\u{}?", *unit as i16);
+                        }
+                    }
+                }
+            }
+            out.push('}');
+        }
+    }
+}
+
+//ANSI
+impl ExportJob {
+    fn ansi_line(&self, idx: usize, out: &mut String) {
+        if idx > 0 {
+            out.push('\n');
+        }
+        if self.options.line_numbers {
+            //dim
+            let _ = write!(out, "\x1b[2m{} \x1b[0m", self.number(idx));
+        }
+        for run in &self.lines[idx] {
+            let mut codes = Vec::new();
+            if run.bold {
+                codes.push("1".to_string());
+            }
+            if run.italic {
+                codes.push("3".to_string());
+            }
+            if run.underline {
+                codes.push("4".to_string());
+            }
+            if run.strikethrough {
+                codes.push("9".to_string());
+            }
+            if let Some(color) = run.color {
+                let [r, g, b, _] = color.into_rgba8();
+                codes.push(format!("38;2;{r};{g};{b}"));
+            }
+            if let Some(color) = run.background {
+                let [r, g, b, _] = color.into_rgba8();
+                codes.push(format!("48;2;{r};{g};{b}"));
+            }
+
+            if codes.is_empty() {
+                out.push_str(self.text(run));
+            } else {
+                let _ = write!(out, "\x1b[{}m{}\x1b[0m", codes.join(";"), self.text(run));
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! C-compatible API of the engine, for frontends that aren't written in Rust (plugins, scripting hosts...) but should highlight
+//! exactly like the Iced app they are embedded in. Requires the `ffi` feature.
+//!
+//! To get a library C can link against, build the crate as one, e.g. with
+//! `cargo rustc --release --no-default-features --features ffi,presets --crate-type cdylib`. The declarations for C are
+//!
+//! ```c
+//! typedef struct IhtsSettings IhtsSettings;
+//! typedef struct IhtsSpans IhtsSpans;
+//! typedef struct { size_t start; size_t end; uint32_t capture; } IhtsSpan;
+//!
+//! IhtsSettings *ihts_settings_new(const TSLanguage *language, const char *name, const char *highlights,
+//!                                 const char *injections, const char *locals,
+//!                                 const char *const *names, size_t names_len);
+//! void ihts_settings_free(IhtsSettings *settings);
+//! const uint8_t *ihts_highlight_name(const IhtsSettings *settings, uint32_t capture, size_t *len);
+//!
+//! IhtsSpans *ihts_highlight(const IhtsSettings *settings, const uint8_t *text, size_t len);
+//! size_t ihts_spans_len(const IhtsSpans *spans);
+//! bool ihts_spans_get(const IhtsSpans *spans, size_t index, IhtsSpan *span);
+//! void ihts_spans_free(IhtsSpans *spans);
+//! ```
+//!
+//! Everything that is created has to be freed with the matching `_free` function. Panics abort the process, they can't unwind
+//! into C.
+
+use std::ffi::{CStr, c_char};
+
+use crate::TSSettings;
+
+///[TSSettings] as seen from C.
+pub struct IhtsSettings(TSSettings);
+
+///Highlighted spans of a text, see [ihts_highlight].
+pub struct IhtsSpans(Vec<IhtsSpan>);
+
+///A highlighted byte range. `capture` is an index into the highlight names, see [ihts_highlight_name].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IhtsSpan {
+    pub start: usize,
+    pub end: usize,
+    pub capture: u32,
+}
+
+///Reads a NUL terminated UTF-8 string, the empty string for null.
+unsafe fn str_arg<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return Some("");
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+}
+
+///Creates settings for the tree-sitter `language`, with its `highlights`, `injections` and `locals` queries (null for none).
+///`names` are the highlight names to recognize, null for the standard ones (which requires the `presets` feature).
+///
+///Returns null if an argument isn't valid UTF-8 or a query doesn't compile.
+///
+///# Safety
+///
+///`language` has to be a valid tree-sitter language, the strings have to be NUL terminated (or null), and `names` has to point to
+///`names_len` strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ihts_settings_new(
+    language: *const tree_sitter::ffi::TSLanguage,
+    name: *const c_char,
+    highlights: *const c_char,
+    injections: *const c_char,
+    locals: *const c_char,
+    names: *const *const c_char,
+    names_len: usize,
+) -> *mut IhtsSettings {
+    if language.is_null() {
+        return std::ptr::null_mut();
+    }
+    let strings = unsafe {
+        (
+            str_arg(name),
+            str_arg(highlights),
+            str_arg(injections),
+            str_arg(locals),
+        )
+    };
+    let (Some(name), Some(highlights), Some(injections), Some(locals)) = strings else {
+        return std::ptr::null_mut();
+    };
+    let language = unsafe { tree_sitter::Language::from_raw(language) };
+    let Ok(config) = tree_sitter_highlight::HighlightConfiguration::new(
+        language, name, highlights, injections, locals,
+    ) else {
+        return std::ptr::null_mut();
+    };
+
+    let settings = if names.is_null() {
+        standard_settings(config)
+    } else {
+        let names = unsafe { std::slice::from_raw_parts(names, names_len) };
+        names
+            .iter()
+            .map(|name| unsafe { str_arg(*name) })
+            .collect::<Option<Vec<_>>>()
+            .map(|names| TSSettings::with_highlight_names(config, &names))
+    };
+    let Some(settings) = settings else {
+        return std::ptr::null_mut();
+    };
+    Box::into_raw(Box::new(IhtsSettings(settings)))
+}
+
+#[cfg(feature = "presets")]
+fn standard_settings(config: tree_sitter_highlight::HighlightConfiguration) -> Option<TSSettings> {
+    Some(TSSettings::new(config))
+}
+
+//NOTE: there are no standard names to fall back to
+#[cfg(not(feature = "presets"))]
+fn standard_settings(_config: tree_sitter_highlight::HighlightConfiguration) -> Option<TSSettings> {
+    None
+}
+
+///Frees settings created by [ihts_settings_new].
+///
+///# Safety
+///
+///`settings` has to come from [ihts_settings_new] (or be null), and can't be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ihts_settings_free(settings: *mut IhtsSettings) {
+    if !settings.is_null() {
+        drop(unsafe { Box::from_raw(settings) });
+    }
+}
+
+///Name of `capture`, as UTF-8 without a terminating NUL. Its length is written to `len`. Null for unknown captures.
+///
+///The name lives as long as `settings`.
+///
+///# Safety
+///
+///`settings` has to be valid, `len` has to be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ihts_highlight_name(
+    settings: *const IhtsSettings,
+    capture: u32,
+    len: *mut usize,
+) -> *const u8 {
+    let settings = unsafe { &(*settings).0 };
+    match settings.highlight_names().get(capture as usize) {
+        Some(name) => {
+            unsafe { *len = name.len() };
+            name.as_ptr()
+        }
+        None => std::ptr::null(),
+    }
+}
+
+///Highlights `len` bytes of UTF-8 `text` at once, see [Document::highlights](crate::document::Document::highlights). Null if the
+///text isn't valid UTF-8.
+///
+///# Safety
+///
+///`settings` has to be valid, and `text` has to point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ihts_highlight(
+    settings: *const IhtsSettings,
+    text: *const u8,
+    len: usize,
+) -> *mut IhtsSpans {
+    let settings = unsafe { &(*settings).0 };
+    let text = if len == 0 {
+        ""
+    } else {
+        match std::str::from_utf8(unsafe { std::slice::from_raw_parts(text, len) }) {
+            Ok(text) => text,
+            Err(_) => return std::ptr::null_mut(),
+        }
+    };
+    settings.report_feature("ffi");
+
+    let spans = crate::document::highlights(settings, text)
+        .into_iter()
+        .filter_map(|(range, highlight)| {
+            Some(IhtsSpan {
+                start: range.start,
+                end: range.end,
+                capture: highlight.capture?.0 as u32,
+            })
+        })
+        .collect();
+    Box::into_raw(Box::new(IhtsSpans(spans)))
+}
+
+///Number of spans in `spans`.
+///
+///# Safety
+///
+///`spans` has to come from [ihts_highlight].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ihts_spans_len(spans: *const IhtsSpans) -> usize {
+    unsafe { &(*spans).0 }.len()
+}
+
+///Writes the span at `index` to `span`. `false` if `index` is out of range.
+///
+///# Safety
+///
+///`spans` has to come from [ihts_highlight], `span` has to be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ihts_spans_get(
+    spans: *const IhtsSpans,
+    index: usize,
+    span: *mut IhtsSpan,
+) -> bool {
+    match unsafe { &(*spans).0 }.get(index) {
+        Some(found) => {
+            unsafe { *span = *found };
+            true
+        }
+        None => false,
+    }
+}
+
+///Frees spans returned by [ihts_highlight].
+///
+///# Safety
+///
+///`spans` has to come from [ihts_highlight] (or be null), and can't be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ihts_spans_free(spans: *mut IhtsSpans) {
+    if !spans.is_null() {
+        drop(unsafe { Box::from_raw(spans) });
+    }
+}
@@ -0,0 +1,256 @@
+//! Fold ranges derived from the syntax tree, and the [Folds] state the [CodeEditor](crate::code_editor::CodeEditor) hides lines by.
+
+use std::{collections::BTreeSet, ops::Range};
+
+use crate::document::{Document, Position};
+
+///A region that can be folded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FoldRange {
+    ///Lines the region covers. The first line is the _header_ that stays visible when folded, the rest get hidden.
+    pub lines: Range<usize>,
+    ///Kind of the syntax node that spans the region, e.g. `function_item`.
+    pub kind: &'static str,
+}
+
+impl FoldRange {
+    ///Lines that are hidden when this region is folded.
+    pub fn hidden_lines(&self) -> Range<usize> {
+        (self.lines.start + 1)..self.lines.end
+    }
+}
+
+impl Document {
+    ///All foldable regions, sorted by their first line. For every line only the outermost region starting on it is reported, so
+    ///a function and its body block don't show up twice.
+    ///
+    ///In prose documents the [sections](crate::outline) fold too, so a heading hides everything up to the next one of its level.
+    pub fn fold_ranges(&self) -> Vec<FoldRange> {
+        self.settings().report_feature("folding");
+        let mut ranges = self.section_folds();
+        if ranges.is_empty() {
+            return self.tree_folds();
+        }
+        //a heading folds its section, rather than a node of the grammar that starts on its line (Markdown's `section` runs
+        //over setext headings)
+        ranges.extend(self.tree_folds());
+        ranges.sort_by_key(|range| range.lines.start);
+        ranges.dedup_by_key(|range| range.lines.start);
+        ranges
+    }
+
+    ///The regions of the syntax nodes, see [Document::fold_ranges].
+    fn tree_folds(&self) -> Vec<FoldRange> {
+        let Some(tree) = self.tree() else {
+            return Vec::new();
+        };
+
+        let mut ranges: Vec<FoldRange> = Vec::new();
+        let mut cursor = tree.walk();
+        //skip the root, folding the whole document isn't useful
+        if !cursor.goto_first_child() {
+            return ranges;
+        }
+
+        loop {
+            let node = cursor.node();
+            let start = node.start_position().row;
+            //nodes that include their line break (e.g. line comments) end at the start of the next line
+            let end = match node.end_position() {
+                end if end.column == 0 && end.row > start => end.row - 1,
+                end => end.row,
+            };
+
+            if end > start
+                && node.is_named()
+                && ranges.last().is_none_or(|last| last.lines.start != start)
+            {
+                ranges.push(FoldRange {
+                    lines: start..end + 1,
+                    kind: node.kind(),
+                });
+            }
+
+            //only descend into nodes that span several lines. Everything else can't contain a fold.
+            if end > start && cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() || cursor.depth() == 0 {
+                    //pre-order traversal, so the ranges are already sorted
+                    return ranges;
+                }
+            }
+        }
+    }
+
+    ///Fold set for distraction free reading: folds everything, except the scope around `position` (see [Document::focus_scope]).
+    ///
+    ///Only the outermost regions are returned, so folding all of them leaves the current function (and the headers of everything else)
+    ///visible.
+    pub fn zen_folds(&self, position: impl Into<Position>) -> Vec<FoldRange> {
+        let scope = self.focus_scope(position);
+        let intersects =
+            |range: &FoldRange| range.lines.start < scope.end && scope.start < range.lines.end;
+
+        let mut folds: Vec<FoldRange> = Vec::new();
+        for range in self.fold_ranges().into_iter().filter(|r| !intersects(r)) {
+            //sorted by start, so a containing region is always pushed first
+            if folds
+                .last()
+                .is_some_and(|outer| outer.lines.end >= range.lines.end)
+            {
+                continue;
+            }
+            folds.push(range);
+        }
+        folds
+    }
+}
+
+///Foldable regions of a document, and which of them are folded. Keep it in your application state next to the `Content`, and pass it
+///to [CodeEditor::folds](crate::code_editor::CodeEditor::folds).
+///
+///A region is identified by its header line, so folded regions survive re-parsing as long as they still start on the same line.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Folds {
+    ranges: Vec<FoldRange>,
+    ///Header lines of the folded regions.
+    folded: BTreeSet<usize>,
+}
+
+impl Folds {
+    pub fn new(document: &Document) -> Self {
+        Self {
+            ranges: document.fold_ranges(),
+            folded: BTreeSet::new(),
+        }
+    }
+
+    ///Re-reads the regions of `document`, e.g. after an edit. Folded regions whose header no longer starts a region are unfolded.
+    pub fn update(&mut self, document: &Document) {
+        self.set_ranges(document.fold_ranges());
+    }
+
+    ///Like [Folds::update], for regions that are not taken from the syntax tree. `ranges` have to be sorted by their first line.
+    pub fn set_ranges(&mut self, ranges: Vec<FoldRange>) {
+        self.ranges = ranges;
+        let ranges = &self.ranges;
+        self.folded
+            .retain(|line| ranges.iter().any(|range| range.lines.start == *line));
+    }
+
+    pub fn ranges(&self) -> &[FoldRange] {
+        &self.ranges
+    }
+
+    ///The region with the header `line`.
+    pub fn range_at(&self, line: usize) -> Option<&FoldRange> {
+        self.ranges
+            .binary_search_by_key(&line, |range| range.lines.start)
+            .ok()
+            .map(|idx| &self.ranges[idx])
+    }
+
+    ///Folds the region with the header `line`. Returns false if there is none.
+    pub fn fold(&mut self, line: usize) -> bool {
+        if self.range_at(line).is_none() {
+            return false;
+        }
+        self.folded.insert(line);
+        true
+    }
+
+    pub fn unfold(&mut self, line: usize) {
+        self.folded.remove(&line);
+    }
+
+    ///Folds the region with the header `line`, or unfolds it if it already is folded.
+    pub fn toggle(&mut self, line: usize) {
+        if !self.folded.remove(&line) {
+            self.fold(line);
+        }
+    }
+
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.folded.contains(&line)
+    }
+
+    ///Folds each region of `ranges` that is known, e.g. the result of [Document::zen_folds].
+    pub fn fold_ranges(&mut self, ranges: &[FoldRange]) {
+        for range in ranges {
+            self.fold(range.lines.start);
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folded = self.ranges.iter().map(|range| range.lines.start).collect();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folded.clear();
+    }
+
+    ///Unfolds every folded region that hides `line`, e.g. to reveal a search match.
+    pub fn reveal(&mut self, line: usize) {
+        let ranges = &self.ranges;
+        self.folded.retain(|header| {
+            !ranges
+                .iter()
+                .any(|range| range.lines.start == *header && range.hidden_lines().contains(&line))
+        });
+    }
+
+    ///The folded regions, sorted by their header line.
+    pub fn folded(&self) -> impl Iterator<Item = &FoldRange> {
+        self.ranges
+            .iter()
+            .filter(|range| self.folded.contains(&range.lines.start))
+    }
+
+    ///Lines hidden by the folded regions as sorted, non-overlapping ranges. Regions that hide `keep_open` are ignored, so the line with
+    ///the cursor never disappears.
+    pub fn hidden_lines(&self, keep_open: Option<usize>) -> Vec<Range<usize>> {
+        let mut hidden: Vec<Range<usize>> = Vec::new();
+        for range in self.folded() {
+            let lines = range.hidden_lines();
+            if lines.is_empty() || keep_open.is_some_and(|line| lines.contains(&line)) {
+                continue;
+            }
+            match hidden.last_mut() {
+                Some(last) if lines.start <= last.end => last.end = last.end.max(lines.end),
+                _ => hidden.push(lines),
+            }
+        }
+        hidden
+    }
+
+    ///Call after the `replaced` lines were replaced by `inserted` new lines, see [Annotations::edit](crate::annotation::Annotations::edit).
+    ///Folded regions below move along, until the next [Folds::update] brings the regions themselves up to date.
+    pub fn edit(&mut self, replaced: Range<usize>, inserted: usize) {
+        let shift = |line: usize| {
+            if line < replaced.start {
+                Some(line)
+            } else if line >= replaced.end {
+                Some(line - replaced.len() + inserted)
+            } else {
+                None
+            }
+        };
+        self.folded = self.folded.iter().filter_map(|line| shift(*line)).collect();
+        self.ranges = std::mem::take(&mut self.ranges)
+            .into_iter()
+            .filter_map(|range| {
+                let start = shift(range.lines.start)?;
+                //a region that ends within the replaced lines now ends with the inserted ones
+                let last = shift(range.lines.end - 1)
+                    .unwrap_or((replaced.start + inserted).saturating_sub(1))
+                    .max(start);
+                Some(FoldRange {
+                    lines: start..last + 1,
+                    kind: range.kind,
+                })
+            })
+            .collect();
+    }
+}
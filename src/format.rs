@@ -0,0 +1,213 @@
+//! Hooks for external formatters like rustfmt or prettier.
+//!
+//! The host implements [Formatter] (or uses [ExternalFormatter]), and registers one per language in [Formatters]. [Document::format]
+//! runs it and maps the output back to [StructuralEdit]s, which replace only what the formatter changed, so the rest of the buffer
+//! (and the cursor on it) is left alone.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    ops::Range,
+    process::{Command, Stdio},
+};
+
+use crate::{diff, document::Document, structural::StructuralEdit};
+
+pub trait Formatter {
+    ///Formats `text`, written in `language` (the configuration's `language_name`, like `rust`).
+    ///
+    ///If `range` is set, only that byte range needs to be formatted. Formatters that can't do that may format everything, changes
+    ///outside of `range` are dropped by [Document::format].
+    fn format(&self, language: &str, text: &str, range: Option<Range<usize>>)
+    -> io::Result<String>;
+}
+
+///Runs a program that reads the text from stdin and writes the formatted text to stdout, like `rustfmt --emit stdout` or
+///`prettier --stdin-filepath file.js`. Always formats the whole text.
+#[derive(Debug, Clone)]
+pub struct ExternalFormatter {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ExternalFormatter {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+}
+
+impl Formatter for ExternalFormatter {
+    fn format(
+        &self,
+        _language: &str,
+        text: &str,
+        _range: Option<Range<usize>>,
+    ) -> io::Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        //NOTE: written from a thread, a formatter that streams its output could otherwise block on a full stdout pipe.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = text.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        writer
+            .join()
+            .map_err(|_| io::Error::other("writing to the formatter panicked"))??;
+
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "{} failed ({}): {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+///A [Formatter] per language. Formats with the one registered for the language, and fails for languages without one.
+#[derive(Default)]
+pub struct Formatters {
+    formatters: HashMap<String, Box<dyn Formatter>>,
+}
+
+impl Formatters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Formats `language` (a `language_name`, like `rust`) with `formatter`.
+    pub fn with(
+        mut self,
+        language: impl Into<String>,
+        formatter: impl Formatter + 'static,
+    ) -> Self {
+        self.formatters.insert(language.into(), Box::new(formatter));
+        self
+    }
+
+    pub fn get(&self, language: &str) -> Option<&dyn Formatter> {
+        self.formatters.get(language).map(|formatter| &**formatter)
+    }
+}
+
+impl Formatter for Formatters {
+    fn format(
+        &self,
+        language: &str,
+        text: &str,
+        range: Option<Range<usize>>,
+    ) -> io::Result<String> {
+        match self.get(language) {
+            Some(formatter) => formatter.format(language, text, range),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("no formatter for {language}"),
+            )),
+        }
+    }
+}
+
+impl Document {
+    ///Formats the document (or just `range` of it) with `formatter`, and returns the edits that turn the text into the formatted one.
+    ///
+    ///The edits are as small as possible and come last to first, so they can be applied (or turned into
+    ///[actions](StructuralEdit::actions) against this document) one after another. Each one leaves the cursor after its text.
+    pub fn format(
+        &self,
+        formatter: &dyn Formatter,
+        range: Option<Range<usize>>,
+    ) -> io::Result<Vec<StructuralEdit>> {
+        self.settings().report_feature("format");
+        let text = self.text();
+        let formatted =
+            formatter.format(&self.settings().tsconfig.language_name, text, range.clone())?;
+
+        let old = text.split_inclusive('\n').collect::<Vec<_>>();
+        let new = formatted.split_inclusive('\n').collect::<Vec<_>>();
+        let line_offsets = |lines: &[&str]| {
+            let mut offsets = vec![0];
+            offsets.extend(lines.iter().scan(0, |offset, line| {
+                *offset += line.len();
+                Some(*offset)
+            }));
+            offsets
+        };
+        let (old_offsets, new_offsets) = (line_offsets(&old), line_offsets(&new));
+
+        //blocks of changed lines, as line ranges into old and new
+        let mut blocks: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+        let (mut old_line, mut new_line) = (0, 0);
+        for edit in diff::edits(&old, &new) {
+            let changed = match edit {
+                diff::Edit::Equal(o, n) => {
+                    (old_line, new_line) = (o + 1, n + 1);
+                    continue;
+                }
+                diff::Edit::Delete(o) => (o..o + 1, new_line..new_line),
+                diff::Edit::Insert(n) => (old_line..old_line, n..n + 1),
+            };
+            (old_line, new_line) = (changed.0.end, changed.1.end);
+            match blocks.last_mut() {
+                Some((old_block, new_block))
+                    if old_block.end == changed.0.start && new_block.end == changed.1.start =>
+                {
+                    old_block.end = changed.0.end;
+                    new_block.end = changed.1.end;
+                }
+                _ => blocks.push(changed),
+            }
+        }
+
+        let mut edits = Vec::new();
+        for (old_block, new_block) in blocks.into_iter().rev() {
+            let mut replaced = old_offsets[old_block.start]..old_offsets[old_block.end];
+            if let Some(range) = &range
+                && (replaced.end < range.start || replaced.start > range.end)
+            {
+                continue;
+            }
+            let mut replacement =
+                &formatted[new_offsets[new_block.start]..new_offsets[new_block.end]];
+
+            //only what actually differs within the lines
+            let removed = &text[replaced.clone()];
+            let prefix = common_prefix(removed, replacement);
+            let suffix = common_prefix(
+                &removed[prefix..].chars().rev().collect::<String>(),
+                &replacement[prefix..].chars().rev().collect::<String>(),
+            );
+            replaced = replaced.start + prefix..replaced.end - suffix;
+            replacement = &replacement[prefix..replacement.len() - suffix];
+            edits.push(StructuralEdit {
+                cursor: replaced.start + replacement.len(),
+                range: replaced,
+                text: replacement.to_string(),
+            });
+        }
+        Ok(edits)
+    }
+}
+
+///Length of the common prefix of `a` and `b` in bytes, on a character boundary.
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.chars()
+        .zip(b.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum()
+}
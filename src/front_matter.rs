@@ -0,0 +1,50 @@
+//! The front matter of Markdown documents: the block of YAML between `---` lines (or of TOML between `+++` lines) at their
+//! start, with the title, tags or date of a note or a blog post.
+//!
+//! `TSSettings::markdown()` highlights it in its language, and [Document::front_matter] tells where it is, so apps can treat it
+//! specially, like folding it or showing it as a form. Its range comes from the syntax tree, so it's only found in documents
+//! of a grammar that parses front matter, like the bundled Markdown one.
+
+use std::ops::Range;
+
+use crate::document::Document;
+
+///Node kinds of front matter, and the language it's written in.
+pub const FRONT_MATTER_NODES: &[(&str, &str)] =
+    &[("minus_metadata", "yaml"), ("plus_metadata", "toml")];
+
+///The front matter of a document, see the [module](self) documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrontMatter {
+    ///Byte range of the front matter, with its `---` or `+++` lines.
+    pub range: Range<usize>,
+    ///Byte range of the text between those lines.
+    pub content: Range<usize>,
+    ///Name of the language it's written in, `yaml` or `toml`.
+    pub language: &'static str,
+}
+
+impl Document {
+    ///The front matter at the start of the document, `None` if there is none (or no tree yet).
+    pub fn front_matter(&self) -> Option<FrontMatter> {
+        self.settings().report_feature("front_matter");
+        let node = self.tree()?.root_node().named_child(0)?;
+        let (_, language) = FRONT_MATTER_NODES
+            .iter()
+            .find(|(kind, _)| *kind == node.kind())?;
+        let range = node.byte_range();
+        let text = &self.text()[range.clone()];
+        //the content ends with the line break before the closing line
+        let start = text.find('\n').map_or(text.len(), |end| end + 1);
+        let end = text
+            .trim_end()
+            .rfind('\n')
+            .map_or(start, |end| end + 1)
+            .max(start);
+        Some(FrontMatter {
+            content: range.start + start..range.start + end,
+            range,
+            language,
+        })
+    }
+}
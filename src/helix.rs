@@ -0,0 +1,350 @@
+//! Importing [Helix](https://helix-editor.com) themes, the `theme.toml` files of `runtime/themes` or `~/.config/helix/themes`,
+//! via [Theme::from_helix].
+//!
+//! Helix styles the captures of tree-sitter by the same names (`keyword.control`, `string.special`...), so the styles carry over
+//! as they are, and the few standard captures Helix calls differently (`namespace` for `module`...) get the style of theirs.
+//! Colors are hex colors, names of the theme's `palette`, or the names of the terminal colors (`red`, `light-blue`...). Of the
+//! modifiers, `bold`, `italic`, `underlined` and `crossed_out` are kept, and an `underline` table underlines as well. The `ui.*` and
+//! `diagnostic.*` styles of the editor's interface are left out, except `ui.background` and `ui.text` for the colors of the
+//! editor.
+
+use iced_core::Color;
+
+use crate::{
+    color, logging,
+    theme::{ImportError, Style, Theme},
+};
+
+///Themes that inherit from a theme that inherits... stop after this many.
+const MAX_INHERITS: usize = 8;
+
+///Colors of the names Helix gives the terminal's colors, like the defaults of xterm.
+const TERMINAL_COLORS: &[(&str, [u8; 3])] = &[
+    ("black", [0x00, 0x00, 0x00]),
+    ("red", [0xcd, 0x00, 0x00]),
+    ("green", [0x00, 0xcd, 0x00]),
+    ("yellow", [0xcd, 0xcd, 0x00]),
+    ("blue", [0x00, 0x00, 0xee]),
+    ("magenta", [0xcd, 0x00, 0xcd]),
+    ("cyan", [0x00, 0xcd, 0xcd]),
+    ("gray", [0xe5, 0xe5, 0xe5]),
+    ("light-red", [0xff, 0x00, 0x00]),
+    ("light-green", [0x00, 0xff, 0x00]),
+    ("light-yellow", [0xff, 0xff, 0x00]),
+    ("light-blue", [0x5c, 0x5c, 0xff]),
+    ("light-magenta", [0xff, 0x00, 0xff]),
+    ("light-cyan", [0x00, 0xff, 0xff]),
+    ("light-gray", [0x7f, 0x7f, 0x7f]),
+    ("white", [0xff, 0xff, 0xff]),
+];
+
+///Standard captures Helix has other names for. They get the style Helix would use, unless the theme styles them itself.
+const ALIASES: &[(&str, &str)] = &[
+    ("module", "namespace"),
+    ("number", "constant.numeric"),
+    ("property", "variable.other.member"),
+    ("text.emphasis", "markup.italic"),
+    ("text.literal", "markup.raw"),
+    ("text.reference", "markup.link.text"),
+    ("text.strong", "markup.bold"),
+    ("text.title", "markup.heading"),
+    ("text.uri", "markup.link.url"),
+];
+
+impl Theme {
+    ///Imports a Helix theme. A theme that `inherits` from another one can't be imported this way, see
+    ///[Theme::from_helix_with]. See the [module](crate::helix) documentation.
+    pub fn from_helix(toml: &str) -> Result<Self, ImportError> {
+        Self::from_helix_with(toml, |_| None)
+    }
+
+    ///Imports a Helix theme, with `load` handing out the themes it `inherits` from: the text of the theme file of that name, e.g.
+    ///`runtime/themes/{name}.toml`. Like in Helix, the styles and the palette of a theme replace the ones it inherits.
+    pub fn from_helix_with(
+        toml: &str,
+        mut load: impl FnMut(&str) -> Option<String>,
+    ) -> Result<Self, ImportError> {
+        let mut file = HelixFile::parse(toml)?;
+        let mut inherited = 0;
+        while let Some(name) = file.inherits.take() {
+            if inherited == MAX_INHERITS {
+                return Err(ImportError::Theme(format!(
+                    "the themes inherit from each other, up to `{name}`"
+                )));
+            }
+            let Some(parent) = load(&name) else {
+                return Err(ImportError::Theme(format!(
+                    "the theme inherits from `{name}`, which isn't available"
+                )));
+            };
+            let mut parent = HelixFile::parse(&parent)?;
+            std::mem::swap(&mut file, &mut parent);
+            //`parent` is the child now, its entries win
+            file.styles.extend(parent.styles);
+            file.palette.extend(parent.palette);
+            inherited += 1;
+        }
+        Ok(file.build())
+    }
+}
+
+///What a theme file sets, in the order of the file. Later entries of the same name win.
+struct HelixFile {
+    inherits: Option<String>,
+    styles: Vec<(String, Spec)>,
+    palette: Vec<(String, String)>,
+}
+
+///A style of a theme file, colors still unresolved.
+#[derive(Default)]
+struct Spec {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl HelixFile {
+    fn parse(toml: &str) -> Result<Self, ImportError> {
+        let document = toml
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(ImportError::Toml)?;
+        let mut file = Self {
+            inherits: None,
+            styles: Vec::new(),
+            palette: Vec::new(),
+        };
+        for (key, item) in document.iter() {
+            match key {
+                "inherits" => file.inherits = item.as_str().map(str::to_string),
+                "palette" => {
+                    let Some(palette) = item.as_table_like() else {
+                        return Err(ImportError::Theme(String::from(
+                            "the `palette` has to be a table",
+                        )));
+                    };
+                    file.palette
+                        .extend(palette.iter().filter_map(|(name, color)| {
+                            Some((name.to_string(), color.as_str()?.to_string()))
+                        }));
+                }
+                _ => {
+                    if let Some(spec) = Spec::parse(item) {
+                        file.styles.push((key.to_string(), spec));
+                    }
+                }
+            }
+        }
+        Ok(file)
+    }
+
+    fn build(self) -> Theme {
+        let color = |name: &str| {
+            //`default` is the terminal's color, i.e. none
+            if name == "default" {
+                return None;
+            }
+            let value = self
+                .palette
+                .iter()
+                .rev()
+                .find(|(known, _)| known == name)
+                .map_or(name, |(_, value)| value.as_str());
+            let color = parse_color(value);
+            if color.is_none() {
+                logging::warning!(target: logging::THEME, "the Helix theme has no color `{name}`");
+            }
+            color
+        };
+        let spec = |scope: &str| {
+            self.styles
+                .iter()
+                .rev()
+                .find(|(known, _)| known == scope)
+                .map(|(_, spec)| spec)
+        };
+
+        let background = spec("ui.background")
+            .and_then(|spec| spec.bg.as_deref())
+            .and_then(color)
+            .unwrap_or(Color::BLACK);
+        let foreground = spec("ui.text")
+            .and_then(|spec| spec.fg.as_deref())
+            .and_then(color)
+            .unwrap_or(Color::WHITE);
+        let mut theme = Theme::new(background, foreground);
+        for (scope, spec) in &self.styles {
+            let is_interface = ["ui", "diagnostic", "warning", "error", "info", "hint"]
+                .iter()
+                .any(|interface| {
+                    scope
+                        .strip_prefix(interface)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+                });
+            if is_interface {
+                continue;
+            }
+            theme.set_style(
+                scope.as_str(),
+                Style {
+                    color: spec.fg.as_deref().and_then(color),
+                    background: spec.bg.as_deref().and_then(color),
+                    bold: spec.bold,
+                    italic: spec.italic,
+                    underline: spec.underline,
+                    strikethrough: spec.strikethrough,
+                },
+            );
+        }
+        for (capture, helix) in ALIASES {
+            if let Some(style) = theme.style(helix).copied()
+                && !theme.styles().any(|(known, _)| known == *capture)
+            {
+                theme.set_style(*capture, style);
+            }
+        }
+        theme
+    }
+}
+
+impl Spec {
+    ///A color is short for a style with just that color. `None` for what isn't a style.
+    fn parse(item: &toml_edit::Item) -> Option<Self> {
+        if let Some(fg) = item.as_str() {
+            return Some(Self {
+                fg: Some(fg.to_string()),
+                ..Default::default()
+            });
+        }
+        let table = item.as_table_like()?;
+        let text = |key| table.get(key)?.as_str().map(str::to_string);
+        let modifiers = table
+            .get("modifiers")
+            .and_then(toml_edit::Item::as_array)
+            .map(|modifiers| {
+                modifiers
+                    .iter()
+                    .filter_map(|modifier| modifier.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        Some(Self {
+            fg: text("fg"),
+            bg: text("bg"),
+            bold: modifiers.contains(&"bold"),
+            italic: modifiers.contains(&"italic"),
+            underline: modifiers.contains(&"underlined") || table.contains_key("underline"),
+            strikethrough: modifiers.contains(&"crossed_out"),
+        })
+    }
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    if let Some((_, [r, g, b])) = TERMINAL_COLORS.iter().find(|(name, _)| *name == text) {
+        return Some(Color::from_rgb8(*r, *g, *b));
+    }
+    let text = text.trim();
+    match color::color_literals(text).as_slice() {
+        [literal] if literal.range == (0..text.len()) => Some(literal.color),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced_core::Color;
+
+    use crate::theme::{ImportError, Style, Theme};
+
+    const THEME: &str = r##"
+        "ui.background" = { bg = "base" }
+        "ui.text" = "text"
+        "ui.cursor" = { fg = "red" }
+        "comment" = { fg = "overlay", modifiers = ["italic"] }
+        "keyword.control" = { fg = "mauve", modifiers = ["bold", "crossed_out"] }
+        "namespace" = "light-blue"
+        "markup.link.url" = { fg = "#89b4fa", underline = { style = "line" } }
+        "diagnostic.error" = { underline = { color = "red" } }
+
+        [palette]
+        base = "#1e1e2e"
+        text = "#cdd6f4"
+        overlay = "#6c7086"
+        mauve = "#cba6f7"
+    "##;
+
+    #[test]
+    fn imports_the_styles_and_colors() {
+        let theme = Theme::from_helix(THEME).unwrap();
+        assert_eq!(theme.background, Color::from_rgb8(0x1e, 0x1e, 0x2e));
+        assert_eq!(theme.foreground, Color::from_rgb8(0xcd, 0xd6, 0xf4));
+        assert_eq!(
+            theme.style("comment"),
+            Some(&Style::color(Color::from_rgb8(0x6c, 0x70, 0x86)).italic())
+        );
+        assert_eq!(
+            theme.style("keyword.control.return"),
+            Some(
+                &Style::color(Color::from_rgb8(0xcb, 0xa6, 0xf7))
+                    .bold()
+                    .strikethrough()
+            )
+        );
+        //the standard names get the style of Helix's
+        assert_eq!(
+            theme.style("module"),
+            Some(&Style::color(Color::from_rgb8(0x5c, 0x5c, 0xff)))
+        );
+        assert_eq!(
+            theme.style("markup.link.url"),
+            Some(&Style::color(Color::from_rgb8(0x89, 0xb4, 0xfa)).underline())
+        );
+        //the editor's interface isn't highlighting
+        assert!(
+            theme.styles().all(
+                |(capture, _)| !capture.starts_with("ui") && !capture.starts_with("diagnostic")
+            )
+        );
+    }
+
+    #[test]
+    fn inherits_from_the_loaded_themes() {
+        let child = "inherits = \"base\"\nkeyword = \"accent\"\n[palette]\naccent = \"#ff0000\"";
+        let theme =
+            Theme::from_helix_with(child, |name| (name == "base").then(|| THEME.to_string()))
+                .unwrap();
+        assert_eq!(
+            theme.style("keyword"),
+            Some(&Style::color(Color::from_rgb8(0xff, 0, 0)))
+        );
+        assert_eq!(theme.background, Color::from_rgb8(0x1e, 0x1e, 0x2e));
+        assert!(matches!(
+            Theme::from_helix(child),
+            Err(ImportError::Theme(_))
+        ));
+        //a theme inheriting from itself gives up
+        let looping = "inherits = \"looping\"";
+        assert!(matches!(
+            Theme::from_helix_with(looping, |_| Some(looping.to_string())),
+            Err(ImportError::Theme(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_themes_are_errors() {
+        assert!(matches!(
+            Theme::from_helix("comment = "),
+            Err(ImportError::Toml(_))
+        ));
+        assert!(matches!(
+            Theme::from_helix("palette = \"#fff\""),
+            Err(ImportError::Theme(_))
+        ));
+        //unknown colors are left out
+        let theme = Theme::from_helix("comment = \"nope\"\nstring = 5").unwrap();
+        assert_eq!(theme.style("comment"), Some(&Style::plain()));
+        assert_eq!(theme.style("string"), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! Indentation from the syntax tree, so hosts can re-indent a line while the user types (_electric_ indent).
+//!
+//! Which nodes indent is described by an indent query, see [TSSettings::with_indent_query](crate::TSSettings::with_indent_query): the
+//! lines after the first line of an `@indent` capture are indented one level deeper, a line starting with an `@outdent` capture one level
+//! less. Several `@indent` captures starting on the same line count once. For Rust, this could be
+//!
+//! ```scheme
+//! [(block) (declaration_list) (field_declaration_list) (arguments) (parameters)] @indent
+//! ["}" ")" "]"] @outdent
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+
+use tree_sitter::StreamingIterator;
+
+use crate::{
+    document::{Document, Position},
+    structural::StructuralEdit,
+};
+
+///Characters that re-indent the line they start.
+const CLOSING: &[char] = &['}', ')', ']'];
+
+impl Document {
+    ///Indentation `line` should have, according to the indent query. `None` without a query.
+    pub fn indentation(&self, line: usize) -> Option<String> {
+        let query = self.settings().indents.as_ref()?;
+        let range = self.line_range(line)?;
+        let text = self.text();
+        let content_start = range.end - text[range.clone()].trim_start().len();
+
+        let indent = query.capture_index_for_name("indent");
+        let outdent = query.capture_index_for_name("outdent");
+        //first lines of the nodes indenting `line`
+        let mut indenting = BTreeSet::new();
+        let mut outdented = false;
+        let mut cursor = tree_sitter::QueryCursor::new();
+        cursor.set_byte_range(range.start..range.end + 1);
+        let mut captures = cursor.captures(query, self.tree()?.root_node(), text.as_bytes());
+        while let Some((query_match, idx)) = captures.next() {
+            let capture = query_match.captures[*idx];
+            let node = capture.node;
+            if Some(capture.index) == indent
+                && node.start_position().row < line
+                && node.end_position().row >= line
+            {
+                indenting.insert(node.start_position().row);
+            } else if Some(capture.index) == outdent && node.start_byte() == content_start {
+                outdented = true;
+            }
+        }
+
+        let level = indenting.len().saturating_sub(usize::from(outdented));
+        Some(self.indent_unit().repeat(level))
+    }
+
+    ///Re-indents `position`'s line after `typed` was typed at `position`. The document has to contain the typed character already.
+    ///
+    ///Closing brackets re-indent the line they start, with the indent query if there is one, otherwise they are aligned with the line
+    ///that opened them. With a query, `:` re-indents as well, for `else:` and friends in Python. `None` if nothing changes.
+    pub fn electric_indent(
+        &self,
+        position: impl Into<Position>,
+        typed: char,
+    ) -> Option<StructuralEdit> {
+        self.settings().report_feature("indent");
+        let position = position.into();
+        let range = self.line_range(position.line)?;
+        let text = self.text();
+        let content = text[range.clone()].trim_start();
+        let current = range.start..range.end - content.len();
+
+        let indentation = if CLOSING.contains(&typed) && content.starts_with(typed) {
+            match self.indentation(position.line) {
+                Some(indentation) => indentation,
+                None => self.opening_indentation(current.end)?,
+            }
+        } else if typed == ':' {
+            self.indentation(position.line)?
+        } else {
+            return None;
+        };
+        if indentation == text[current.clone()] {
+            return None;
+        }
+
+        let offset = self.offset(position).max(current.end);
+        Some(StructuralEdit {
+            cursor: offset - current.len() + indentation.len(),
+            range: current,
+            text: indentation,
+        })
+    }
+
+    ///Indentation of the line that opened the bracket at `offset`.
+    fn opening_indentation(&self, offset: usize) -> Option<String> {
+        let bracket = self
+            .tree()?
+            .root_node()
+            .descendant_for_byte_range(offset, offset + 1)?;
+        let opening = bracket.parent()?.child(0)?;
+        let line = self.line(opening.start_position().row)?;
+        Some(line[..line.len() - line.trim_start().len()].to_string())
+    }
+
+    ///A tab if lines are indented with tabs, otherwise the most common step between the indentation of two consecutive lines.
+    pub(crate) fn indent_unit(&self) -> String {
+        let mut steps = HashMap::new();
+        let mut previous = 0;
+        for line in 0..self.line_count() {
+            let text = self.line(line).unwrap_or_default();
+            if text.trim().is_empty() {
+                continue;
+            }
+            if text.starts_with('\t') {
+                return "\t".to_string();
+            }
+            let indentation = text.len() - text.trim_start_matches(' ').len();
+            if indentation > previous {
+                *steps.entry(indentation - previous).or_insert(0) += 1;
+            }
+            previous = indentation;
+        }
+        let step = steps
+            .into_iter()
+            .max_by_key(|(step, count)| (*count, std::cmp::Reverse(*step)))
+            .map_or(4, |(step, _)| step);
+        " ".repeat(step)
+    }
+}
@@ -0,0 +1,163 @@
+//! Jump targets for _easymotion_ style navigation.
+//!
+//! The host renders the labels on top of the editor, and moves the cursor to [JumpTarget::position] once a label is typed.
+
+use std::ops::Range;
+
+use crate::{
+    document::{Document, Position},
+    search::is_word_char,
+};
+
+///Home-row first, the usual choice for jump labels.
+pub const DEFAULT_LABEL_ALPHABET: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpQuery<'a> {
+    ///Every occurrence of the character.
+    Char(char),
+    ///Start of every word that begins with the given prefix (case-insensitive).
+    WordPrefix(&'a str),
+    ///Start of every syntax token (leaf node of the tree), e.g. identifiers, literals and operators.
+    Tokens,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpTarget {
+    pub position: Position,
+    ///Byte range of the target (the character, word or token).
+    pub range: Range<usize>,
+    ///Label to type in order to jump here. Labels are prefix-free, so a host can jump as soon as a label is complete.
+    pub label: String,
+}
+
+impl Document {
+    ///Jump targets within the `visible` lines. Targets close to `cursor` get labeled first. `alphabet` are the characters labels are made of,
+    ///usually [DEFAULT_LABEL_ALPHABET].
+    pub fn jump_targets(
+        &self,
+        visible: Range<usize>,
+        query: JumpQuery<'_>,
+        cursor: impl Into<Position>,
+        alphabet: &str,
+    ) -> Vec<JumpTarget> {
+        self.settings().report_feature("jump");
+        let start = self.offset(Position::new(visible.start, 0));
+        let end = self
+            .line_range(visible.end.saturating_sub(1).min(self.line_count() - 1))
+            .map(|r| r.end)
+            .unwrap_or(self.text().len());
+        if start >= end {
+            return Vec::new();
+        }
+
+        let mut ranges = match query {
+            JumpQuery::Char(c) => self.text()[start..end]
+                .match_indices(c)
+                .map(|(idx, m)| (start + idx)..(start + idx + m.len()))
+                .collect(),
+            JumpQuery::WordPrefix(prefix) => self.word_starts(start..end, prefix),
+            JumpQuery::Tokens => self.token_starts(start..end),
+        };
+
+        let cursor = self.offset(cursor);
+        ranges.sort_by_key(|range| range.start.abs_diff(cursor));
+
+        let labels = labels(ranges.len(), alphabet);
+        let mut targets: Vec<JumpTarget> = ranges
+            .into_iter()
+            .zip(labels)
+            .map(|(range, label)| JumpTarget {
+                position: self.position(range.start),
+                range,
+                label,
+            })
+            .collect();
+        targets.sort_by_key(|target| target.range.start);
+        targets
+    }
+
+    fn word_starts(&self, within: Range<usize>, prefix: &str) -> Vec<Range<usize>> {
+        let text = &self.text()[within.clone()];
+        let prefix = prefix.to_lowercase();
+        let mut ranges = Vec::new();
+        let mut word_start = None;
+
+        for (idx, c) in text
+            .char_indices()
+            .chain(std::iter::once((text.len(), ' ')))
+        {
+            match (word_start, is_word_char(c)) {
+                (None, true) => word_start = Some(idx),
+                (Some(start), false) => {
+                    if text[start..idx].to_lowercase().starts_with(&prefix) {
+                        ranges.push((within.start + start)..(within.start + idx));
+                    }
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+        ranges
+    }
+
+    fn token_starts(&self, within: Range<usize>) -> Vec<Range<usize>> {
+        let Some(tree) = self.tree() else {
+            return Vec::new();
+        };
+
+        let mut ranges = Vec::new();
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            let range = node.byte_range();
+            let overlaps = range.start < within.end && within.start < range.end;
+
+            if overlaps && cursor.goto_first_child() {
+                continue;
+            }
+            if overlaps
+                && node.child_count() == 0
+                && !range.is_empty()
+                && range.start >= within.start
+            {
+                ranges.push(range);
+            }
+            //leave nodes until there is a sibling to continue with
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return ranges;
+                }
+            }
+        }
+    }
+}
+
+///Generates `count` prefix-free labels. All labels have the same length: the shortest one that is able to label everything.
+fn labels(count: usize, alphabet: &str) -> Vec<String> {
+    let alphabet: Vec<char> = alphabet.chars().collect();
+    if alphabet.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    if alphabet.len() == 1 {
+        return vec![alphabet[0].to_string()];
+    }
+
+    let mut length = 1;
+    let mut capacity = alphabet.len();
+    while capacity < count {
+        length += 1;
+        capacity = capacity.saturating_mul(alphabet.len());
+    }
+
+    (0..count)
+        .map(|mut idx| {
+            let mut label = vec![alphabet[0]; length];
+            for slot in label.iter_mut().rev() {
+                *slot = alphabet[idx % alphabet.len()];
+                idx /= alphabet.len();
+            }
+            label.into_iter().collect()
+        })
+        .collect()
+}
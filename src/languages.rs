@@ -0,0 +1,1031 @@
+//! Bundled languages, so a working highlighter is one line away: `TSSettings::rust()?`.
+//!
+//! Each language sits behind its own feature (`lang-rust`, `lang-python`, `lang-toml`, `lang-yaml`, `lang-json`,
+//! `lang-javascript`, `lang-markdown`, `lang-asciidoc`, `lang-org`, `lang-html`, `lang-css`, or `languages` for all of them),
+//! which pulls in the grammar and bundles its highlight, injection and locals queries, and the tags query for
+//! [symbols](crate::symbols) where the grammar has one. The settings recognize the standard highlight names, so the features
+//! enable `presets` as well.
+//!
+//! The locals queries tell the scopes, definitions and references of variables apart, so the uses of a parameter are
+//! highlighted like the parameter (`variable.parameter`) until a local variable of the same name shadows it. Rust and Python
+//! don't ship one with their grammars, the crate does: [RUST_LOCALS] and [PYTHON_LOCALS], for configurations of your own.
+//!
+//! Markdown comes as two grammars: [TSSettings::markdown] parses the blocks and injects [TSSettings::markdown_inline] into the
+//! text of paragraphs and headings (its queries are adapted from the grammar's to nest the injections properly). The code
+//! blocks are highlighted with the languages of a [registry](crate::registry::LanguageRegistry):
+//! `TSSettings::markdown().with_injections(&LanguageRegistry::bundled())`. Like block comments, code blocks span lines, so
+//! highlight the whole document, e.g. through a [SharedHighlightState](crate::shared::SharedHighlightState). The YAML or TOML
+//! [front matter](crate::front_matter) is highlighted in its language right away, so `lang-markdown` enables `lang-yaml` and
+//! `lang-toml`.
+//!
+//! For notes and documentation, [TSSettings::asciidoc] injects its inline grammar ([TSSettings::asciidoc_inline]) like
+//! Markdown does, and the language of `[source,rust]` listing blocks. `lang-org` only brings the queries: the Org grammar on
+//! crates.io is built for an older tree-sitter, so [TSSettings::org] takes the grammar from the app. Both map their nodes to
+//! the standard names (`text.title` for headings, `text.literal` for blocks, `keyword` for directives), and the
+//! [outline](crate::outline) and [tables](crate::tables) know their headings and tables.
+//!
+//! [TSSettings::html] highlights the bodies of `<script>` and `<style>` elements as JavaScript and CSS, so `lang-html` enables
+//! `lang-javascript` and `lang-css`. That includes the HTML and CSS of tagged templates in the scripts (``html`<p>${text}</p>` ``),
+//! and the scripts within those.
+//!
+//! To open arbitrary files, [detect](crate::detect) picks the language by the file's name, extension or shebang.
+
+use std::path::Path;
+
+use crate::{TSSettings, error::Result, logging};
+
+///Locals query for Rust: functions, closures and blocks are scopes, parameters, closure parameters and the bindings of `let` and
+///`for` are definitions, and all identifiers are references. See the [module](self) documentation.
+#[cfg(feature = "lang-rust")]
+pub const RUST_LOCALS: &str = r#"
+[
+  (function_item)
+  (closure_expression)
+  (block)
+] @local.scope
+
+(parameter
+  pattern: (identifier) @local.definition)
+
+(closure_parameters
+  (identifier) @local.definition)
+
+(closure_parameters
+  (parameter
+    pattern: (identifier) @local.definition))
+
+(let_declaration
+  pattern: (identifier) @local.definition
+  value: (_)? @local.definition-value)
+
+(let_declaration
+  pattern: (tuple_pattern
+    (identifier) @local.definition)
+  value: (_) @local.definition-value)
+
+(let_condition
+  pattern: (_
+    (identifier) @local.definition)
+  value: (_) @local.definition-value)
+
+(for_expression
+  pattern: (identifier) @local.definition)
+
+(identifier) @local.reference
+"#;
+
+///Highlights tree-sitter-highlight needs for the [RUST_LOCALS]: it only highlights references the highlight query captures,
+///which the Rust grammar's doesn't do for plain identifiers, so they are captured without a highlight name first. And closure
+///parameters, which the grammar's only highlights with a type.
+#[cfg(feature = "lang-rust")]
+const RUST_REFERENCES: &str = r#"
+(identifier) @_reference
+"#;
+
+///See [RUST_REFERENCES]. Later patterns win, so they go last.
+#[cfg(feature = "lang-rust")]
+const RUST_PARAMETERS: &str = r#"
+(closure_parameters
+  (identifier) @variable.parameter)
+"#;
+
+///Locals query for Python: functions, lambdas and comprehensions are scopes, parameters and the targets of assignments and
+///`for` loops are definitions, and all identifiers are references. See the [module](self) documentation.
+#[cfg(feature = "lang-python")]
+pub const PYTHON_LOCALS: &str = r#"
+[
+  (function_definition)
+  (lambda)
+  (list_comprehension)
+  (set_comprehension)
+  (dictionary_comprehension)
+  (generator_expression)
+] @local.scope
+
+(parameters
+  (identifier) @local.definition)
+
+(parameters
+  (list_splat_pattern
+    (identifier) @local.definition))
+
+(parameters
+  (dictionary_splat_pattern
+    (identifier) @local.definition))
+
+(lambda_parameters
+  (identifier) @local.definition)
+
+(default_parameter
+  name: (identifier) @local.definition)
+
+(typed_parameter
+  (identifier) @local.definition)
+
+(typed_default_parameter
+  name: (identifier) @local.definition)
+
+(assignment
+  left: (identifier) @local.definition
+  right: (_)? @local.definition-value)
+
+(for_statement
+  left: (identifier) @local.definition)
+
+(for_in_clause
+  left: (identifier) @local.definition)
+
+(identifier) @local.reference
+"#;
+
+///Parameters, which the highlight query of the Python grammar doesn't highlight. Later patterns win, so they go last.
+#[cfg(feature = "lang-python")]
+const PYTHON_PARAMETERS: &str = r#"
+(parameters
+  (identifier) @variable.parameter)
+
+(parameters
+  (list_splat_pattern
+    (identifier) @variable.parameter))
+
+(parameters
+  (dictionary_splat_pattern
+    (identifier) @variable.parameter))
+
+(lambda_parameters
+  (identifier) @variable.parameter)
+
+(default_parameter
+  name: (identifier) @variable.parameter)
+
+(typed_parameter
+  (identifier) @variable.parameter)
+
+(typed_default_parameter
+  name: (identifier) @variable.parameter)
+"#;
+
+///The parameters query of the JavaScript grammar, which it doesn't export.
+#[cfg(feature = "lang-javascript")]
+const JAVASCRIPT_PARAMETERS: &str = r#"
+(formal_parameters
+  [
+    (identifier) @variable.parameter
+    (array_pattern
+      (identifier) @variable.parameter)
+    (object_pattern
+      [
+        (pair_pattern value: (identifier) @variable.parameter)
+        (shorthand_property_identifier_pattern) @variable.parameter
+      ])
+  ]
+)
+"#;
+
+///Macros whose string literals [TSSettings::rust] highlights as embedded code, by the name of the macro (without its path and
+///the `!`) and the language: SQL in `sqlx::query!`, HTML in `html!` and assembly in `asm!`. The languages are resolved like
+///all injections, see [TSSettings::with_injections]. Escapes split a string into parts that are highlighted on their own, so
+///embedded code is best kept in raw strings, as usual.
+#[cfg(feature = "lang-rust")]
+pub const RUST_MACROS: &[(&str, &str)] = &[
+    ("query", "sql"),
+    ("query_as", "sql"),
+    ("query_scalar", "sql"),
+    ("query_unchecked", "sql"),
+    ("query_as_unchecked", "sql"),
+    ("query_scalar_unchecked", "sql"),
+    ("html", "html"),
+    ("asm", "asm"),
+    ("global_asm", "asm"),
+    ("naked_asm", "asm"),
+];
+
+///The injection query of the JavaScript grammar, but with the `html` and `css` tagged templates in patterns of their own:
+///tree-sitter-highlight combines the matches of a pattern into one layer of one language, so they'd be parsed with the
+///language of the first tag otherwise.
+#[cfg(feature = "lang-javascript")]
+const JAVASCRIPT_INJECTIONS: &str = r#"
+(call_expression
+  function: [
+    (identifier) @_tag
+    (member_expression
+      property: (property_identifier) @_tag)
+  ]
+  arguments: (template_string (string_fragment) @injection.content)
+  (#eq? @_tag "html")
+  (#set! injection.language "html")
+  (#set! injection.combined)
+  (#set! injection.include-children))
+
+(call_expression
+  function: [
+    (identifier) @_tag
+    (member_expression
+      property: (property_identifier) @_tag)
+  ]
+  arguments: (template_string (string_fragment) @injection.content)
+  (#eq? @_tag "css")
+  (#set! injection.language "css")
+  (#set! injection.combined)
+  (#set! injection.include-children))
+
+(call_expression
+  function: [
+    (identifier) @injection.language
+    (member_expression
+      property: (property_identifier) @injection.language)
+  ]
+  arguments: (template_string (string_fragment) @injection.content)
+  (#not-any-of? @injection.language "html" "css")
+  (#set! injection.combined)
+  (#set! injection.include-children))
+
+((regex_pattern) @injection.content
+ (#set! injection.language "regex"))
+
+((comment) @injection.content
+ (#set! injection.language "jsdoc"))
+
+(call_expression
+  function: ((identifier) @_name
+             (#eq? @_name "hbs"))
+  arguments: ((template_string) @glimmer
+              (#offset! @glimmer 0 1 0 -1)))
+"#;
+
+///The highlight query of the Markdown grammar, but without styling the content of fenced code blocks: its `@none` starts
+///where the injected language does, which tree-sitter-highlight can't nest, and a style for the whole block would color the
+///parts the injected language leaves plain.
+#[cfg(feature = "lang-markdown")]
+const MARKDOWN_HIGHLIGHTS: &str = r#"
+(atx_heading
+  (inline) @text.title)
+
+(setext_heading
+  (paragraph) @text.title)
+
+[
+  (atx_h1_marker)
+  (atx_h2_marker)
+  (atx_h3_marker)
+  (atx_h4_marker)
+  (atx_h5_marker)
+  (atx_h6_marker)
+  (setext_h1_underline)
+  (setext_h2_underline)
+] @punctuation.special
+
+[
+  (link_title)
+  (indented_code_block)
+  (info_string)
+] @text.literal
+
+(fenced_code_block_delimiter) @punctuation.delimiter
+
+(link_destination) @text.uri
+
+(link_label) @text.reference
+
+[
+  (list_marker_plus)
+  (list_marker_minus)
+  (list_marker_star)
+  (list_marker_dot)
+  (list_marker_parenthesis)
+  (thematic_break)
+] @punctuation.special
+
+[
+  (block_continuation)
+  (block_quote_marker)
+] @punctuation.special
+
+(backslash_escape) @string.escape
+"#;
+
+///The injection query of the Markdown grammar, but with the children of code blocks and inline text included: the block
+///grammar keeps their punctuation as tokens, and without them the injected grammars would parse text with holes.
+#[cfg(feature = "lang-markdown")]
+const MARKDOWN_INJECTIONS: &str = r#"
+(fenced_code_block
+  (info_string
+    (language) @injection.language)
+  (code_fence_content) @injection.content
+  (#set! injection.include-children))
+
+((html_block) @injection.content
+  (#set! injection.language "html"))
+
+(document
+  .
+  (section
+    .
+    (thematic_break)
+    (_) @injection.content
+    (thematic_break))
+  (#set! injection.language "yaml"))
+
+((minus_metadata) @injection.content
+  (#set! injection.language "yaml"))
+
+((plus_metadata) @injection.content
+  (#set! injection.language "toml"))
+
+((inline) @injection.content
+  (#set! injection.language "markdown_inline")
+  (#set! injection.include-children))
+"#;
+
+///Highlight query for Org, see [TSSettings::org]. The grammar's own highlights capture names of its own (`@OrgStars1`), so the
+///crate brings one with the standard names: headlines are `text.title`, their stars, list bullets and checkboxes
+///`punctuation.special`, the names of blocks, drawers and directives (`#+TITLE:`) `keyword`, and the contents of blocks
+///`text.literal`.
+#[cfg(feature = "lang-org")]
+pub const ORG_HIGHLIGHTS: &str = r#"
+(headline
+  (stars) @punctuation.special
+  (item) @text.title)
+
+(item
+  .
+  (expr) @keyword
+  (#any-of? @keyword "TODO" "DONE"))
+
+(tag_list
+  (tag) @tag)
+
+(property
+  name: (expr) @property
+  value: (value)? @string)
+
+(directive
+  name: (expr) @keyword
+  value: (value)? @string)
+
+(drawer
+  name: (expr) @keyword)
+
+[
+  (block
+    name: (expr) @keyword
+    end_name: (expr) @keyword)
+  (dynamic_block
+    name: (expr) @keyword
+    end_name: (expr) @keyword)
+]
+
+;the contents of `#+BEGIN_SRC` blocks are injected instead
+(block
+  name: (expr) @_name
+  contents: (contents) @text.literal
+  (#not-match? @_name "^(src|SRC)$"))
+
+(latex_env) @text.literal
+
+(comment) @comment
+
+[
+  (bullet)
+  (checkbox)
+] @punctuation.special
+
+(timestamp) @string.special
+
+(fndef
+  label: (expr) @text.reference)
+
+(hr) @punctuation.delimiter
+"#;
+
+///Injection query for Org: the contents of `#+BEGIN_SRC rust` blocks are highlighted in their language.
+#[cfg(feature = "lang-org")]
+pub const ORG_INJECTIONS: &str = r#"
+(block
+  name: (expr) @_name
+  .
+  parameter: (expr) @injection.language
+  contents: (contents) @injection.content
+  (#match? @_name "^(src|SRC)$")
+  (#set! injection.include-children))
+"#;
+
+///Highlight query for AsciiDoc, with the standard names instead of the grammar's: titles are `text.title`, their `=` and the
+///markers of lists and delimited blocks `punctuation.special`, listing and literal blocks `text.literal`, and attributes
+///`attribute` and `property`.
+#[cfg(feature = "lang-asciidoc")]
+const ASCIIDOC_HIGHLIGHTS: &str = r#"
+(document_title
+  (line) @text.title)
+
+[
+  (title1)
+  (title2)
+  (title3)
+  (title4)
+  (title5)
+] @text.title
+
+[
+  (title_h0_marker)
+  (title_h1_marker)
+  (title_h2_marker)
+  (title_h3_marker)
+  (title_h4_marker)
+  (title_h5_marker)
+] @punctuation.special
+
+[
+  (line_comment)
+  (block_comment)
+] @comment
+
+[
+  (firstname)
+  (middlename)
+  (lastname)
+] @attribute
+
+(email) @text.uri
+
+(revnumber) @number
+
+(revdate) @string.special
+
+(revremark) @string
+
+[
+  (list_marker_star)
+  (list_marker_hyphen)
+  (list_marker_dot)
+  (list_marker_digit)
+  (list_marker_geek)
+  (list_marker_alpha)
+  (checked_list_marker_checked)
+  (checked_list_marker_unchecked)
+  (description_marker)
+  (callout_marker)
+  (callout_list_marker)
+  (list_continuation)
+] @punctuation.special
+
+(description_list_item
+  (term) @text.strong)
+
+[
+  (breaks)
+  (hard_wrap)
+  (open_block_marker)
+  (listing_block_start_marker)
+  (listing_block_end_marker)
+  (literal_block_marker)
+  (passthrough_block_marker)
+  (quoted_block_start_marker)
+  (quoted_block_end_marker)
+  (quoted_block_md_marker)
+  (quoted_paragraph_marker)
+  (sidebar_block_start_marker)
+  (sidebar_block_end_marker)
+  (delimited_block_start_marker)
+  (delimited_block_end_marker)
+  (table_block_marker)
+  (csv_table_block_marker)
+  (dsv_table_block_marker)
+  (ntable_block_marker)
+] @punctuation.special
+
+(table_cell
+  "|" @punctuation.delimiter)
+
+(table_cell_attr) @attribute
+
+;the listing blocks of `[source,rust]` are injected instead
+(section_block
+  .
+  (listing_block
+    (listing_block_body) @text.literal))
+
+[
+  (literal_block_body)
+  (ident_block)
+] @text.literal
+
+[
+  (document_attr_marker)
+  (element_attr_marker)
+] @punctuation.delimiter
+
+(document_attr
+  (attr_name) @property)
+
+(element_attr
+  (attr_value) @attribute)
+
+(block_title) @attribute
+
+(block_macro
+  (block_macro_name) @keyword
+  (target)? @text.uri)
+
+(attribute_name) @attribute
+
+(attribute_value) @string
+
+[
+  (admonition_note)
+  (admonition_tip)
+  (admonition_important)
+  (admonition_warning)
+  (admonition_caution)
+] @keyword
+"#;
+
+///Injection query for AsciiDoc: the inline grammar into the lines of paragraphs, titles and list items and into table cells,
+///and the language of `[source,rust]` into the listing block after it.
+#[cfg(feature = "lang-asciidoc")]
+const ASCIIDOC_INJECTIONS: &str = r#"
+((line) @injection.content
+  (#set! injection.language "asciidoc_inline"))
+
+(table_cell
+  (table_cell_content) @injection.content
+  (#set! injection.language "asciidoc_inline"))
+
+(section_block
+  (element_attr
+    (attr_value) @injection.language)
+  .
+  (listing_block
+    (listing_block_body) @injection.content)
+  (#match? @injection.language "^source,"))
+"#;
+
+///Highlight query for the inline grammar of AsciiDoc: `*strong*`, `_emphasis_`, `` `monospace` ``, links and macros.
+#[cfg(feature = "lang-asciidoc")]
+const ASCIIDOC_INLINE_HIGHLIGHTS: &str = r#"
+(emphasis) @text.strong
+
+[
+  (ltalic)
+  (highlight)
+] @text.emphasis
+
+[
+  (monospace)
+  (passthrough)
+] @text.literal
+
+[
+  (link_url)
+  (email)
+] @text.uri
+
+(uri_label) @text.reference
+
+(xref
+  (id) @text.reference)
+
+(replacement) @string.special
+
+(roled_text
+  (role) @attribute)
+
+(attribute_reference
+  (attribute_name) @constant)
+
+(macro_name) @keyword
+
+(inline_macro
+  (attr) @attribute)
+
+(footnote
+  (attr) @attribute)
+
+(escaped_sequence) @string.escape
+
+(hard_wrap) @punctuation.special
+"#;
+
+impl TSSettings {
+    ///Settings for a bundled grammar with its queries. The queries are written for the grammar, but a grammar crate of
+    ///another version can still reject them, see [Error](crate::error::Error).
+    fn bundled(
+        language: tree_sitter::Language,
+        name: &str,
+        highlights: &str,
+        injections: &str,
+        locals: &str,
+    ) -> Result<Self> {
+        Self::try_new(language, name, highlights, injections, locals)
+    }
+
+    ///[TSSettings::with_tags_query] for the tags query of a bundled grammar.
+    #[cfg(any(
+        feature = "lang-rust",
+        feature = "lang-python",
+        feature = "lang-javascript"
+    ))]
+    fn with_bundled_tags(self, query: &str) -> Result<Self> {
+        let language = self.tsconfig.language_name.clone();
+        self.with_tags_query(query)
+            .map_err(|error| crate::error::Error::Query {
+                language,
+                query: crate::error::QueryKind::Tags,
+                error,
+            })
+    }
+
+    ///Rust, with the string literals of the [RUST_MACROS] injected.
+    #[cfg(feature = "lang-rust")]
+    pub fn rust() -> Result<Self> {
+        Self::rust_with_macros(RUST_MACROS.iter().copied())
+    }
+
+    ///Rust, with the string literals of `macros` injected instead of the [RUST_MACROS]: the name of a macro (without its path and
+    ///the `!`) and the language of its strings. To add your own, chain them to the [RUST_MACROS].
+    #[cfg(feature = "lang-rust")]
+    pub fn rust_with_macros<'a>(
+        macros: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self> {
+        //the macros of each language, with a pattern for each form of the name and the string (tree-sitter-highlight loses
+        //matches of alternations)
+        let mut languages: Vec<(&str, Vec<&str>)> = Vec::new();
+        for (name, language) in macros {
+            match languages.iter_mut().find(|(known, _)| *known == language) {
+                Some((_, names)) => names.push(name),
+                None => languages.push((language, vec![name])),
+            }
+        }
+        let mut injections = String::new();
+        for (language, names) in languages {
+            //escaped like Rust strings, which the query syntax shares
+            let names = names
+                .iter()
+                .map(|name| format!("{name:?}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            for name in [
+                "(identifier) @_macro",
+                "(scoped_identifier name: (identifier) @_macro)",
+            ] {
+                for string in ["string_literal", "raw_string_literal"] {
+                    injections.push_str(&format!(
+                        "\n(macro_invocation\n  macro: {name}\n  (token_tree ({string} (string_content) @injection.content))\n  (#any-of? @_macro {names})\n  (#set! injection.language {language:?}))\n"
+                    ));
+                }
+            }
+        }
+        injections.push_str(tree_sitter_rust::INJECTIONS_QUERY);
+        Self::bundled(
+            tree_sitter_rust::LANGUAGE.into(),
+            "rust",
+            &format!(
+                "{RUST_REFERENCES}{}{RUST_PARAMETERS}",
+                tree_sitter_rust::HIGHLIGHTS_QUERY
+            ),
+            &injections,
+            RUST_LOCALS,
+        )?
+        .with_bundled_tags(tree_sitter_rust::TAGS_QUERY)
+    }
+
+    #[cfg(feature = "lang-python")]
+    pub fn python() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_python::LANGUAGE.into(),
+            "python",
+            &format!(
+                "{}{PYTHON_PARAMETERS}",
+                tree_sitter_python::HIGHLIGHTS_QUERY
+            ),
+            "",
+            PYTHON_LOCALS,
+        )?
+        .with_bundled_tags(tree_sitter_python::TAGS_QUERY)
+    }
+
+    #[cfg(feature = "lang-toml")]
+    pub fn toml() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_toml_ng::LANGUAGE.into(),
+            "toml",
+            tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        )
+    }
+
+    #[cfg(feature = "lang-yaml")]
+    pub fn yaml() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_yaml::LANGUAGE.into(),
+            "yaml",
+            tree_sitter_yaml::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        )
+    }
+
+    #[cfg(feature = "lang-json")]
+    pub fn json() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_json::LANGUAGE.into(),
+            "json",
+            tree_sitter_json::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        )
+    }
+
+    #[cfg(feature = "lang-javascript")]
+    pub fn javascript() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_javascript::LANGUAGE.into(),
+            "javascript",
+            &format!(
+                "{}{JAVASCRIPT_PARAMETERS}",
+                tree_sitter_javascript::HIGHLIGHT_QUERY
+            ),
+            JAVASCRIPT_INJECTIONS,
+            tree_sitter_javascript::LOCALS_QUERY,
+        )?
+        .with_bundled_tags(tree_sitter_javascript::TAGS_QUERY)
+    }
+
+    ///HTML, with JavaScript and CSS injected, see the [module](self) documentation.
+    #[cfg(feature = "lang-html")]
+    pub fn html() -> Result<Self> {
+        //the HTML of tagged templates doesn't need injections of its own, the host's resolve the languages of all layers
+        let embedded = crate::registry::LanguageRegistry::new()
+            .with_language("javascript", Self::javascript()?)
+            .with_language("css", Self::css()?)
+            .with_language("html", Self::html_plain()?);
+        Ok(Self::html_plain()?.with_injections(&embedded))
+    }
+
+    #[cfg(feature = "lang-html")]
+    fn html_plain() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_html::LANGUAGE.into(),
+            "html",
+            tree_sitter_html::HIGHLIGHTS_QUERY,
+            tree_sitter_html::INJECTIONS_QUERY,
+            "",
+        )
+    }
+
+    #[cfg(feature = "lang-css")]
+    pub fn css() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_css::LANGUAGE.into(),
+            "css",
+            tree_sitter_css::HIGHLIGHTS_QUERY,
+            "",
+            "",
+        )
+    }
+
+    ///Markdown, with the inline grammar and the YAML or TOML of the front matter injected, but no languages for the code blocks
+    ///yet, see the [module](self) documentation.
+    #[cfg(feature = "lang-markdown")]
+    pub fn markdown() -> Result<Self> {
+        let inline = crate::registry::LanguageRegistry::new()
+            .with_language("markdown_inline", Self::markdown_inline()?)
+            .with_language("yaml", Self::yaml()?)
+            .with_language("toml", Self::toml()?);
+        Ok(Self::bundled(
+            tree_sitter_md::LANGUAGE.into(),
+            "markdown",
+            MARKDOWN_HIGHLIGHTS,
+            MARKDOWN_INJECTIONS,
+            "",
+        )?
+        .with_injections(&inline))
+    }
+
+    ///The inline grammar of Markdown (emphasis, links, code spans...), injected by [TSSettings::markdown].
+    #[cfg(feature = "lang-markdown")]
+    pub fn markdown_inline() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_md::INLINE_LANGUAGE.into(),
+            "markdown_inline",
+            tree_sitter_md::HIGHLIGHT_QUERY_INLINE,
+            tree_sitter_md::INJECTION_QUERY_INLINE,
+            "",
+        )
+    }
+
+    ///Org, with the `#+BEGIN_SRC` blocks injected in their language once given a [registry](crate::registry::LanguageRegistry)
+    ///via [TSSettings::with_injections]. The grammar comes from the app: the `tree-sitter-org` crate is built for an older
+    ///tree-sitter and links a second copy of it, so build the grammar of its repository with the tree-sitter CLI, or load it
+    ///from the C symbol (`tree_sitter_language::LanguageFn::from_raw(tree_sitter_org)`). Fails if the grammar's ABI version or
+    ///node types don't match the queries, see [Error](crate::error::Error).
+    #[cfg(feature = "lang-org")]
+    pub fn org(language: tree_sitter::Language) -> Result<Self> {
+        Self::bundled(language, "org", ORG_HIGHLIGHTS, ORG_INJECTIONS, "")
+    }
+
+    ///AsciiDoc, with the inline grammar injected, and the listing blocks of `[source,rust]` once given a
+    ///[registry](crate::registry::LanguageRegistry) via [TSSettings::with_injections].
+    #[cfg(feature = "lang-asciidoc")]
+    pub fn asciidoc() -> Result<Self> {
+        let inline = crate::registry::LanguageRegistry::new()
+            .with_language("asciidoc_inline", Self::asciidoc_inline()?);
+        Ok(Self::bundled(
+            tree_sitter_asciidoc::language(),
+            "asciidoc",
+            ASCIIDOC_HIGHLIGHTS,
+            ASCIIDOC_INJECTIONS,
+            "",
+        )?
+        .with_injections(&inline))
+    }
+
+    ///The inline grammar of AsciiDoc (`*strong*`, `_emphasis_`, links, macros...), injected by [TSSettings::asciidoc].
+    #[cfg(feature = "lang-asciidoc")]
+    pub fn asciidoc_inline() -> Result<Self> {
+        Self::bundled(
+            tree_sitter_asciidoc_inline::language(),
+            "asciidoc_inline",
+            ASCIIDOC_INLINE_HIGHLIGHTS,
+            "",
+            "",
+        )
+    }
+}
+
+///A bundled language whose feature is enabled. The inline grammars of Markdown and AsciiDoc are there for the injections of
+///their block grammars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Language {
+    #[cfg(feature = "lang-rust")]
+    Rust,
+    #[cfg(feature = "lang-python")]
+    Python,
+    #[cfg(feature = "lang-toml")]
+    Toml,
+    #[cfg(feature = "lang-yaml")]
+    Yaml,
+    #[cfg(feature = "lang-json")]
+    Json,
+    #[cfg(feature = "lang-javascript")]
+    JavaScript,
+    #[cfg(feature = "lang-markdown")]
+    Markdown,
+    #[cfg(feature = "lang-markdown")]
+    MarkdownInline,
+    #[cfg(feature = "lang-asciidoc")]
+    AsciiDoc,
+    #[cfg(feature = "lang-asciidoc")]
+    AsciiDocInline,
+    #[cfg(feature = "lang-html")]
+    Html,
+    #[cfg(feature = "lang-css")]
+    Css,
+}
+
+impl Language {
+    ///All bundled languages whose feature is enabled, except the inline grammars.
+    pub const ALL: &[Self] = &[
+        #[cfg(feature = "lang-rust")]
+        Self::Rust,
+        #[cfg(feature = "lang-python")]
+        Self::Python,
+        #[cfg(feature = "lang-toml")]
+        Self::Toml,
+        #[cfg(feature = "lang-yaml")]
+        Self::Yaml,
+        #[cfg(feature = "lang-json")]
+        Self::Json,
+        #[cfg(feature = "lang-javascript")]
+        Self::JavaScript,
+        #[cfg(feature = "lang-markdown")]
+        Self::Markdown,
+        #[cfg(feature = "lang-asciidoc")]
+        Self::AsciiDoc,
+        #[cfg(feature = "lang-html")]
+        Self::Html,
+        #[cfg(feature = "lang-css")]
+        Self::Css,
+    ];
+
+    ///The bundled language called `name`, either its `language_name` or a common short name or file extension (like the info
+    ///string of a Markdown code block: `rs`, `py`, `js`...). `None` if the language isn't bundled, or its feature is off.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            #[cfg(feature = "lang-rust")]
+            "rust" | "rs" => Some(Self::Rust),
+            #[cfg(feature = "lang-python")]
+            "python" | "py" => Some(Self::Python),
+            #[cfg(feature = "lang-toml")]
+            "toml" => Some(Self::Toml),
+            #[cfg(feature = "lang-yaml")]
+            "yaml" | "yml" => Some(Self::Yaml),
+            #[cfg(feature = "lang-json")]
+            "json" => Some(Self::Json),
+            #[cfg(feature = "lang-javascript")]
+            "javascript" | "js" | "mjs" | "cjs" | "jsx" => Some(Self::JavaScript),
+            #[cfg(feature = "lang-markdown")]
+            "markdown" | "md" => Some(Self::Markdown),
+            #[cfg(feature = "lang-markdown")]
+            "markdown_inline" => Some(Self::MarkdownInline),
+            #[cfg(feature = "lang-asciidoc")]
+            "asciidoc" | "adoc" => Some(Self::AsciiDoc),
+            #[cfg(feature = "lang-asciidoc")]
+            "asciidoc_inline" => Some(Self::AsciiDocInline),
+            #[cfg(feature = "lang-html")]
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            #[cfg(feature = "lang-css")]
+            "css" => Some(Self::Css),
+            _ => None,
+        }
+    }
+
+    ///Settings for the bundled language of the file at `path`, by its name or extension, see
+    ///[language_from_path](crate::detect::language_from_path). `None` if the language isn't bundled, or its feature is off.
+    pub fn from_path(path: &Path) -> Option<TSSettings> {
+        by_name(crate::detect::language_from_path(path)?)
+    }
+
+    ///The `language_name` of the settings.
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "lang-rust")]
+            Self::Rust => "rust",
+            #[cfg(feature = "lang-python")]
+            Self::Python => "python",
+            #[cfg(feature = "lang-toml")]
+            Self::Toml => "toml",
+            #[cfg(feature = "lang-yaml")]
+            Self::Yaml => "yaml",
+            #[cfg(feature = "lang-json")]
+            Self::Json => "json",
+            #[cfg(feature = "lang-javascript")]
+            Self::JavaScript => "javascript",
+            #[cfg(feature = "lang-markdown")]
+            Self::Markdown => "markdown",
+            #[cfg(feature = "lang-markdown")]
+            Self::MarkdownInline => "markdown_inline",
+            #[cfg(feature = "lang-asciidoc")]
+            Self::AsciiDoc => "asciidoc",
+            #[cfg(feature = "lang-asciidoc")]
+            Self::AsciiDocInline => "asciidoc_inline",
+            #[cfg(feature = "lang-html")]
+            Self::Html => "html",
+            #[cfg(feature = "lang-css")]
+            Self::Css => "css",
+        }
+    }
+
+    ///The settings of the language, like [TSSettings::rust].
+    pub fn settings(self) -> Result<TSSettings> {
+        match self {
+            #[cfg(feature = "lang-rust")]
+            Self::Rust => TSSettings::rust(),
+            #[cfg(feature = "lang-python")]
+            Self::Python => TSSettings::python(),
+            #[cfg(feature = "lang-toml")]
+            Self::Toml => TSSettings::toml(),
+            #[cfg(feature = "lang-yaml")]
+            Self::Yaml => TSSettings::yaml(),
+            #[cfg(feature = "lang-json")]
+            Self::Json => TSSettings::json(),
+            #[cfg(feature = "lang-javascript")]
+            Self::JavaScript => TSSettings::javascript(),
+            #[cfg(feature = "lang-markdown")]
+            Self::Markdown => TSSettings::markdown(),
+            #[cfg(feature = "lang-markdown")]
+            Self::MarkdownInline => TSSettings::markdown_inline(),
+            #[cfg(feature = "lang-asciidoc")]
+            Self::AsciiDoc => TSSettings::asciidoc(),
+            #[cfg(feature = "lang-asciidoc")]
+            Self::AsciiDocInline => TSSettings::asciidoc_inline(),
+            #[cfg(feature = "lang-html")]
+            Self::Html => TSSettings::html(),
+            #[cfg(feature = "lang-css")]
+            Self::Css => TSSettings::css(),
+        }
+    }
+}
+
+///Settings for the bundled language called `name`, see [Language::from_name]. `None` if the language isn't bundled, or its
+///feature is off. Bundled queries that don't compile (a grammar crate of another version) are logged and give `None` too, use
+///[Language::settings] for the [Error](crate::error::Error).
+pub fn by_name(name: &str) -> Option<TSSettings> {
+    settings(Language::from_name(name)?)
+}
+
+///Settings for all bundled languages whose feature is enabled, e.g. for [highlight_many](crate::document::highlight_many). The
+///inline grammars of Markdown and AsciiDoc are part of their block grammars, they aren't listed on their own. Like [by_name],
+///languages whose queries don't compile are logged and left out.
+pub fn all() -> Vec<TSSettings> {
+    Language::ALL.iter().copied().filter_map(settings).collect()
+}
+
+fn settings(language: Language) -> Option<TSSettings> {
+    language
+        .settings()
+        .inspect_err(|error| logging::warning!(target: logging::QUERY, "{error}"))
+        .ok()
+}
@@ -0,0 +1,17 @@
+//! Custom highlight layers, for highlighting the syntax tree doesn't know about, like `TODO` markers or log levels.
+//!
+//! A [Layer] returns ranges of a line and the capture they should be highlighted as. They are styled like the syntax captures
+//! (through the [theme](crate::theme) or the palette), and drawn on top of them, but below [overlays](crate::overlay). Add layers
+//! via [TSSettings::with_layer](crate::TSSettings::with_layer). For layers written in a script, see the `script` module, for layers
+//! that keep state across edits, see [plugin](crate::plugin).
+
+use std::ops::Range;
+
+pub trait Layer: Send + Sync {
+    ///Byte ranges within `text` (the content of `line`) and the capture name to highlight them as, e.g. `"comment"`.
+    ///
+    ///Names are matched against [TSSettings::highlight_names](crate::TSSettings::highlight_names) the same way the captures of
+    ///a highlight query are, so `"comment.todo"` is highlighted as `comment` unless there is a name for it. Ranges with names
+    ///that match nothing are skipped.
+    fn highlight(&self, line: usize, text: &str) -> Vec<(Range<usize>, String)>;
+}
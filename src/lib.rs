@@ -23,71 +23,361 @@
 //! }
 //! ```
 
-use std::{ops::Range, sync::Arc};
+use std::{
+    ops::Range,
+    sync::{Arc, atomic::AtomicUsize},
+    time::Duration,
+};
+
+pub mod anchor;
+pub mod annotation;
+pub mod captures;
+#[cfg(feature = "gui")]
+pub mod code_editor;
+pub mod color;
+pub mod detect;
+pub mod diff;
+pub mod document;
+pub mod error;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod folding;
+pub mod format;
+pub mod front_matter;
+#[cfg(feature = "helix")]
+pub mod helix;
+pub mod indent;
+pub mod jump;
+#[cfg(feature = "any-lang")]
+pub mod languages;
+pub mod layer;
+mod logging;
+#[cfg(feature = "lsp-types")]
+pub mod lsp;
+#[cfg(feature = "neovim")]
+pub mod neovim;
+pub mod outline;
+pub mod overlay;
+pub mod paste;
+pub mod plugin;
+pub mod progressive;
+pub mod prose;
+pub mod registry;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod search;
+#[cfg(feature = "serde")]
+pub mod session;
+pub mod shared;
+pub mod snippets;
+pub mod span;
+pub mod stats;
+pub mod structural;
+pub mod symbols;
+pub mod tables;
+pub mod tasks;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "textmate")]
+pub mod textmate;
+pub mod theme;
+#[cfg(feature = "presets")]
+pub mod themes;
+pub mod words;
+
+pub use error::{Error, Result};
 pub use tree_sitter_highlight;
 pub use tree_sitter_highlight::HighlightConfiguration;
 use tree_sitter_highlight::HighlightEvent;
 
-///Standard formating function. Assumes that you use the `highlight_names` defined [here](https://crates.io/crates/tree-sitter-highlight).
+///Standard formating function. Colors the `highlight_names` defined [here](https://crates.io/crates/tree-sitter-highlight) by
+///Iced's palette, other names by their first part (`comment.documentation` like `comment`). Comments are set in italics and
+///keywords in bold.
 ///
-///If you want to use other names (because of a different TSQuery setup, or theme), consider building your own [TSSettings] and `to_format` function.
+///If you want to use other names (because of a different TSQuery setup, or theme), consider building your own [TSSettings] and `to_format`
+///function. Match on [Highlight::name] there, instead of the capture's index.
 ///
 ///The highlighter itself will still work 🙂.
+///
+///If a [theme::Theme] is set on the [TSSettings], its styles are used instead of the Iced palette, their `bold` and `italic`
+///picking the weight and style of the font. Without the `presets` feature
+///there are no palette colors, and captures the theme has no style for are not colored.
 pub fn to_format(
-    highlight: &tree_sitter_highlight::Highlight,
-    theme: &iced::Theme,
-) -> iced::advanced::text::highlighter::Format<iced::Font> {
+    highlight: &Highlight,
+    theme: &iced_core::Theme,
+) -> iced_core::text::highlighter::Format<iced_core::Font> {
     let palette = theme.extended_palette();
 
+    let mut format = match (&highlight.style, highlight.capture) {
+        (Some(style), _) => style.to_format(),
+        #[cfg(feature = "presets")]
+        (None, Some(_)) => palette_format(highlight.name().unwrap_or_default(), palette),
+        _ => iced_core::text::highlighter::Format {
+            color: None,
+            font: Some(iced_core::Font::MONOSPACE),
+        },
+    };
+
+    if let Some(kind) = highlight.overlay {
+        format = overlay_format(kind, palette);
+    }
+
+    if highlight.dimmed {
+        let color = format.color.unwrap_or(palette.background.base.text);
+        format.color = Some(color::mix(color, palette.background.base.color, 0.6));
+    }
+
+    format
+}
+
+fn overlay_format(
+    kind: overlay::OverlayKind,
+    palette: &iced_core::theme::palette::Extended,
+) -> iced_core::text::highlighter::Format<iced_core::Font> {
+    //NOTE: Format can't set a background, so overlays are told apart by color and weight.
+    let bold = iced_core::Font {
+        weight: iced_core::font::Weight::Bold,
+        ..iced_core::Font::MONOSPACE
+    };
+    match kind {
+        overlay::OverlayKind::SearchMatch => iced_core::text::highlighter::Format {
+            color: Some(palette.primary.strong.color),
+            font: Some(bold),
+        },
+        overlay::OverlayKind::CurrentMatch => iced_core::text::highlighter::Format {
+            color: Some(palette.danger.base.color),
+            font: Some(bold),
+        },
+        overlay::OverlayKind::ReplacePreview => iced_core::text::highlighter::Format {
+            color: Some(palette.success.strong.color),
+            font: Some(iced_core::Font {
+                style: iced_core::font::Style::Italic,
+                ..bold
+            }),
+        },
+        overlay::OverlayKind::Diagnostic(severity) => iced_core::text::highlighter::Format {
+            color: Some(severity_color(severity, palette)),
+            font: Some(iced_core::Font::MONOSPACE),
+        },
+    }
+}
+
+///Color diagnostics of `severity` are drawn in.
+pub(crate) fn severity_color(
+    severity: overlay::Severity,
+    palette: &iced_core::theme::palette::Extended,
+) -> iced_core::Color {
+    match severity {
+        overlay::Severity::Error => palette.danger.base.color,
+        //the palette has no warning color, use amber, moved a bit towards the text color so it fits light and dark themes
+        overlay::Severity::Warning => color::mix(
+            iced_core::Color::from_rgb8(0xe5, 0xa5, 0x0a),
+            palette.background.base.text,
+            0.2,
+        ),
+        overlay::Severity::Info => palette.primary.base.color,
+        overlay::Severity::Hint => palette.secondary.base.color,
+    }
+}
+
+#[cfg(feature = "presets")]
+pub(crate) fn palette_format(
+    name: &str,
+    palette: &iced_core::theme::palette::Extended,
+) -> iced_core::text::highlighter::Format<iced_core::Font> {
+    let name = if HIGHLIGHT_NAMES.contains(&name) {
+        name
+    } else {
+        name.split('.').next().unwrap_or_default()
+    };
     //sort out _what_ is being highlighted, and come up with color and font
-    match highlight.0 {
-        //comment
-        1 => iced::advanced::text::highlighter::Format {
+    match name {
+        "comment" => iced_core::text::highlighter::Format {
             color: Some(palette.secondary.weak.color),
-            font: Some(iced::Font::DEFAULT),
+            font: Some(iced_core::Font {
+                style: iced_core::font::Style::Italic,
+                ..iced_core::Font::DEFAULT
+            }),
         },
-        //constant
-        2 | 3 => iced::advanced::text::highlighter::Format {
+        "constant" | "constant.builtin" => iced_core::text::highlighter::Format {
             color: Some(palette.danger.weak.color),
-            font: Some(iced::Font::MONOSPACE),
+            font: Some(iced_core::Font::MONOSPACE),
         },
-        //strings
-        18 | 19 => iced::advanced::text::highlighter::Format {
+        "string" | "string.special" => iced_core::text::highlighter::Format {
             color: Some(palette.success.base.color),
-            font: Some(iced::Font::DEFAULT),
+            font: Some(iced_core::Font::DEFAULT),
         },
-        //functions
-        6 | 7 => iced::advanced::text::highlighter::Format {
+        "function" | "function.builtin" => iced_core::text::highlighter::Format {
             color: Some(palette.success.strong.color),
-            font: Some(iced::Font::MONOSPACE),
+            font: Some(iced_core::Font::MONOSPACE),
         },
-        //types
-        21 | 22 => iced::advanced::text::highlighter::Format {
+        "type" | "type.builtin" => iced_core::text::highlighter::Format {
             color: Some(palette.primary.weak.color),
-            font: Some(iced::Font::MONOSPACE),
+            font: Some(iced_core::Font::MONOSPACE),
         },
-        //variables
-        24 => iced::advanced::text::highlighter::Format {
+        "variable.builtin" => iced_core::text::highlighter::Format {
             color: Some(palette.danger.weak.color),
-            font: Some(iced::Font::MONOSPACE),
+            font: Some(iced_core::Font::MONOSPACE),
         },
         //anything _special_
-        8 | 9 => iced::advanced::text::highlighter::Format {
+        "keyword" => iced_core::text::highlighter::Format {
+            color: Some(palette.danger.strong.color),
+            font: Some(iced_core::Font {
+                weight: iced_core::font::Weight::Bold,
+                ..iced_core::Font::MONOSPACE
+            }),
+        },
+        "module" => iced_core::text::highlighter::Format {
             color: Some(palette.danger.strong.color),
-            font: Some(iced::Font::MONOSPACE),
+            font: Some(iced_core::Font::MONOSPACE),
+        },
+        "error" => iced_core::text::highlighter::Format {
+            color: Some(palette.danger.base.color),
+            font: Some(iced_core::Font::MONOSPACE),
+        },
+        //markup, like Markdown
+        "text.title" => iced_core::text::highlighter::Format {
+            color: Some(palette.primary.strong.color),
+            font: Some(iced_core::Font {
+                weight: iced_core::font::Weight::Bold,
+                ..iced_core::Font::MONOSPACE
+            }),
+        },
+        "text.strong" => iced_core::text::highlighter::Format {
+            color: None,
+            font: Some(iced_core::Font {
+                weight: iced_core::font::Weight::Bold,
+                ..iced_core::Font::MONOSPACE
+            }),
+        },
+        "text.emphasis" => iced_core::text::highlighter::Format {
+            color: None,
+            font: Some(iced_core::Font {
+                style: iced_core::font::Style::Italic,
+                ..iced_core::Font::MONOSPACE
+            }),
+        },
+        "text.literal" => iced_core::text::highlighter::Format {
+            color: Some(palette.success.base.color),
+            font: Some(iced_core::Font::MONOSPACE),
         },
-        _ => iced::advanced::text::highlighter::Format {
+        "text.uri" | "text.reference" => iced_core::text::highlighter::Format {
+            color: Some(palette.primary.base.color),
+            font: Some(iced_core::Font::MONOSPACE),
+        },
+        _ => iced_core::text::highlighter::Format {
             color: None,
-            font: Some(iced::Font::MONOSPACE),
+            font: Some(iced_core::Font::MONOSPACE),
         },
     }
 }
 
+///A highlighted region as emitted by the [Highlighter].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    ///The tree-sitter capture. Its index refers to [TSSettings::highlight_names].
+    ///
+    ///`None` for text that isn't captured, but still needs formatting (e.g. when it is dimmed).
+    pub capture: Option<tree_sitter_highlight::Highlight>,
+    ///Style resolved from [TSSettings::theme], if a theme is set.
+    pub style: Option<theme::Style>,
+    ///Text is outside of [TSSettings::focus] and should be drawn dimmed.
+    pub dimmed: bool,
+    ///Set if the text is covered by [TSSettings::overlay]. Overlays are drawn on top of the syntax highlighting.
+    pub overlay: Option<overlay::OverlayKind>,
+    ///Names of the captures, see [Highlight::name].
+    pub names: captures::CaptureMap,
+}
+
+impl Highlight {
+    ///Name of the capture, like `"comment"`, independent of where it is in [TSSettings::highlight_names].
+    pub fn name(&self) -> Option<&str> {
+        self.names.name(self)
+    }
+}
+
+///Capture names used by [TSSettings::new]. Those are the standard names defined in the tree-sitter-highlight [README](https://crates.io/crates/tree-sitter-highlight).
+#[cfg(feature = "presets")]
+pub const HIGHLIGHT_NAMES: &[&str] = &[
+    "attribute",
+    "comment",
+    "constant",
+    "constant.builtin",
+    "constructor",
+    "embedded",
+    "function",
+    "function.builtin",
+    "keyword",
+    "module",
+    "number",
+    "operator",
+    "property",
+    "property.builtin",
+    "punctuation",
+    "punctuation.bracket",
+    "punctuation.delimiter",
+    "punctuation.special",
+    "string",
+    "string.special",
+    "tag",
+    "text.emphasis",
+    "text.literal",
+    "text.reference",
+    "text.strong",
+    "text.title",
+    "text.uri",
+    "type",
+    "type.builtin",
+    "variable",
+    "variable.builtin",
+    "variable.parameter",
+];
+
 #[derive(Clone)]
 pub struct TSSettings {
     ///Internally used highlighting configuration. Build your own, if you want to use
     ///some
     pub tsconfig: Arc<tree_sitter_highlight::HighlightConfiguration>,
+    ///Optional theme, that is used instead of Iced's palette.
+    pub theme: Option<Arc<theme::Theme>>,
+    ///Lines that are in focus. If set, all other lines are dimmed.
+    pub focus: Option<Range<usize>>,
+    ///Ranges that are highlighted on top of the syntax, like search matches.
+    pub overlay: Option<Arc<overlay::Overlay>>,
+    ///Theme the exporters use by default, instead of the GUI's theme. See [document::Document::export].
+    pub export_theme: Option<Arc<theme::Theme>>,
+    ///Describes which nodes indent, see [indent]. Set via [TSSettings::with_indent_query].
+    pub indents: Option<Arc<tree_sitter::Query>>,
+    ///Finds the definitions for [symbols] search. Set via [TSSettings::with_tags_query].
+    pub tags: Option<Arc<tree_sitter::Query>>,
+    ///Receives usage statistics, see [stats]. Set via [TSSettings::with_stats].
+    pub stats: Option<stats::StatsHook>,
+    ///Custom highlighting on top of the syntax, see [layer]. Added via [TSSettings::with_layer].
+    pub layers: Vec<Arc<dyn layer::Layer>>,
+    ///Highlighting contributed by other crates, see [plugin]. Added via [TSSettings::with_plugin].
+    pub plugins: Vec<Arc<dyn plugin::HighlightPlugin>>,
+    ///Which source wins where highlights overlap, lowest first. Set via [TSSettings::with_precedence].
+    pub precedence: Vec<overlay::Source>,
+    ///Whether edits of whitespace are parsed, see [document::WhitespaceEdits]. Set via [TSSettings::with_whitespace_edits].
+    pub whitespace_edits: document::WhitespaceEdits,
+    ///Whether typing or deleting a character of a word is parsed right away. Set via [TSSettings::with_token_edits].
+    pub token_edits: bool,
+    ///Whether syntax errors are highlighted. Set via [TSSettings::with_syntax_errors].
+    pub syntax_errors: bool,
+    ///How long parsing a document may take. Set via [TSSettings::with_parse_timeout].
+    pub parse_timeout: Option<Duration>,
+    ///Flag that cancels parsing and highlighting. Set via [TSSettings::with_cancellation_flag].
+    pub cancellation_flag: Option<Arc<AtomicUsize>>,
+    ///Languages embedded code is highlighted with, see [registry::Injections]. Set via [TSSettings::with_injections] and
+    ///[TSSettings::with_injection_resolver].
+    pub injections: Option<Arc<registry::Injections>>,
+    ///Stops tree-sitter-highlight at the timeout.
+    watchdog: Arc<document::Watchdog>,
+    highlight_names: Arc<[String]>,
+    capture_map: captures::CaptureMap,
+    ///Set by [shared::SharedHighlightState::settings].
+    shared: Option<shared::HighlightSnapshot>,
 }
 
 impl TSSettings {
@@ -96,60 +386,435 @@ impl TSSettings {
     ///
     ///If you want to roll your own highlighting, consider setting up the config yourself, and combining it with a custom `to_format` function when
     ///applying the highlighter to a text-edit.
-    pub fn new(mut config: tree_sitter_highlight::HighlightConfiguration) -> Self {
-        let highlight_names = [
-            "attribute",
-            "comment",
-            "constant",
-            "constant.builtin",
-            "constructor",
-            "embedded",
-            "function",
-            "function.builtin",
-            "keyword",
-            "module",
-            "number",
-            "operator",
-            "property",
-            "property.builtin",
-            "punctuation",
-            "punctuation.bracket",
-            "punctuation.delimiter",
-            "punctuation.special",
-            "string",
-            "string.special",
-            "tag",
-            "type",
-            "type.builtin",
-            "variable",
-            "variable.builtin",
-            "variable.parameter",
-        ];
-
-        config.configure(&highlight_names);
+    #[cfg(feature = "presets")]
+    pub fn new(config: tree_sitter_highlight::HighlightConfiguration) -> Self {
+        Self::with_highlight_names(config, HIGHLIGHT_NAMES)
+    }
+
+    ///Sets up `language` with its queries and the standard names, like [TSSettings::new] does with a configuration, but with an
+    ///[Error] that tells which query doesn't compile, and where, instead of the one tree-sitter-highlight returns. Pass `""`
+    ///for the queries the grammar doesn't have.
+    #[cfg(feature = "presets")]
+    pub fn try_new(
+        language: tree_sitter::Language,
+        name: &str,
+        highlights: &str,
+        injections: &str,
+        locals: &str,
+    ) -> Result<Self> {
+        error::configuration(language, name, highlights, injections, locals).map(Self::new)
+    }
+
+    ///Configures `config` to recognize `names` instead of the standard ones. Captures are styled by name through a [theme::Theme],
+    ///Iced's palette only knows the standard names.
+    pub fn with_highlight_names(
+        mut config: tree_sitter_highlight::HighlightConfiguration,
+        names: &[&str],
+    ) -> Self {
+        configure(&mut config, names);
 
         Self {
             //wrap into something clonabel, so we don't have to load TS
             //whenever the highlighter is created.
             tsconfig: Arc::new(config),
+            theme: None,
+            focus: None,
+            overlay: None,
+            export_theme: None,
+            indents: None,
+            tags: None,
+            stats: None,
+            layers: Vec::new(),
+            plugins: Vec::new(),
+            precedence: overlay::DEFAULT_PRECEDENCE.to_vec(),
+            whitespace_edits: document::WhitespaceEdits::default(),
+            token_edits: false,
+            syntax_errors: false,
+            parse_timeout: None,
+            cancellation_flag: None,
+            injections: None,
+            watchdog: Arc::default(),
+            highlight_names: names.iter().map(|name| name.to_string()).collect(),
+            capture_map: captures::CaptureMap::new(names),
+            shared: None,
+        }
+    }
+
+    ///Recognizes `names` instead of the highlight names the settings were configured with, like the dozens of captures of
+    ///nvim-treesitter-style queries (`keyword.return`, `function.method.call`). [TSSettings::with_highlight_names] does the same
+    ///for your own configurations, this is for the ones built for you, like the bundled languages. Call it before
+    ///[TSSettings::with_plugin] and [TSSettings::with_injections], which build on the names. The embedded languages the settings
+    ///came with (like the inline grammar of Markdown) are dropped, they are configured with the old names; register them with
+    ///the new ones and set them again.
+    ///
+    ///Fails with [Error::Shared] once the configuration is shared with clones of the settings (or a
+    ///[registry](registry::LanguageRegistry)), since that changes the names for all of them.
+    pub fn with_captures(mut self, names: &[&str]) -> Result<Self> {
+        self.set_names(names.iter().map(|name| name.to_string()).collect())?;
+        self.injections = None;
+        Ok(self)
+    }
+
+    ///Uses `theme` instead of Iced's palette. Captures the theme has no style for are not colored.
+    pub fn with_theme(mut self, theme: theme::Theme) -> Self {
+        self.theme = Some(Arc::new(theme));
+        self
+    }
+
+    ///Dims every line outside of `lines`. Use [document::Document::focus_scope] to find the scope around the cursor.
+    ///
+    ///Pass `None` to disable the focus mode again.
+    pub fn with_focus(mut self, lines: Option<Range<usize>>) -> Self {
+        self.focus = lines;
+        self
+    }
+
+    ///Sets the theme the exporters use by default. That way a dark-mode app can export light snippets, for instance.
+    pub fn with_export_theme(mut self, theme: theme::Theme) -> Self {
+        self.export_theme = Some(Arc::new(theme));
+        self
+    }
+
+    ///Uses the indent `query` (`@indent` and `@outdent` captures, see [indent]) for the configured language.
+    pub fn with_indent_query(mut self, query: &str) -> Result<Self, tree_sitter::QueryError> {
+        let query = tree_sitter::Query::new(&self.tsconfig.language, query)?;
+        for capture in query.capture_names() {
+            if !matches!(*capture, "indent" | "outdent") {
+                logging::warning!(
+                    target: logging::QUERY,
+                    "unknown capture @{capture} in the {} indent query",
+                    self.tsconfig.language_name
+                );
+            }
+        }
+        self.indents = Some(Arc::new(query));
+        Ok(self)
+    }
+
+    ///Uses the tags `query` (`@definition.*` captures with an `@name`, like the `tags.scm` grammars ship with) to find the
+    ///[symbols] of a document.
+    pub fn with_tags_query(mut self, query: &str) -> Result<Self, tree_sitter::QueryError> {
+        let query = tree_sitter::Query::new(&self.tsconfig.language, query)?;
+        if query.capture_index_for_name("name").is_none() {
+            logging::warning!(
+                target: logging::QUERY,
+                "the {} tags query has no @name capture, so it finds no symbols",
+                self.tsconfig.language_name
+            );
+        }
+        self.tags = Some(Arc::new(query));
+        Ok(self)
+    }
+
+    ///Reports usage statistics to `hook`, see [stats].
+    pub fn with_stats(mut self, hook: impl Fn(&stats::Usage<'_>) + Send + Sync + 'static) -> Self {
+        self.stats = Some(Arc::new(hook));
+        self
+    }
+
+    ///Highlights the ranges of `overlay` on top of the syntax highlighting. Pass `None` to remove it.
+    pub fn with_overlay(mut self, overlay: Option<overlay::Overlay>) -> Self {
+        self.overlay = overlay.map(Arc::new);
+        self
+    }
+
+    ///Ranks the sources of highlights, lowest first, for ranges of different sources that overlap. By default that's
+    ///[overlay::DEFAULT_PRECEDENCE]: syntax, layers, search, diagnostics, and the current match on top.
+    ///
+    ///Sources missing from `order` are ranked below the listed ones.
+    pub fn with_precedence(mut self, order: impl IntoIterator<Item = overlay::Source>) -> Self {
+        let order = order.into_iter().collect::<Vec<_>>();
+        self.precedence = overlay::DEFAULT_PRECEDENCE
+            .iter()
+            .filter(|source| !order.contains(source))
+            .copied()
+            .collect();
+        for source in order {
+            if !self.precedence.contains(&source) {
+                self.precedence.push(source);
+            }
+        }
+        self
+    }
+
+    ///Moves the tree and the highlights along after edits that only change whitespace, instead of parsing and highlighting
+    ///again. Off by default, since what is only whitespace depends on the language, see [document::WhitespaceEdits].
+    pub fn with_whitespace_edits(mut self, edits: document::WhitespaceEdits) -> Self {
+        self.whitespace_edits = edits;
+        self
+    }
+
+    ///Patches the tree and the highlights after typing or deleting a single character of an identifier or a number, instead of
+    ///parsing and highlighting again. The token just grows or shrinks by the character, so the edit can't join or part tokens,
+    ///turn a word into a keyword (`le` into `let`) or the other way round, or turn a number into an identifier. Tokens next to
+    ///syntax errors are parsed as usual.
+    ///
+    ///The patched highlights are provisional: captures that depend on the text of the token (like types in capitals, by a
+    ///`#match?` predicate, or the uses of a renamed local variable) keep the ones they had, and so do keywords the grammar has no
+    ///node kind for (like `mut` in Rust). Call [shared::SharedHighlightState::settle] (or [document::Document::settle]) on the
+    ///debounce tick of the editor, once the typing paused, to parse and highlight properly. Off by default, since the host has
+    ///to do that.
+    pub fn with_token_edits(mut self, enabled: bool) -> Self {
+        self.token_edits = enabled;
+        self
+    }
+
+    ///Stops parsing a document after `timeout`, so pathological texts (deeply nested brackets, minified files) don't block the
+    ///GUI thread. The document is then shown without highlights, see [document::Document::is_parse_halted], and
+    ///[document::Document::resume_parse] (or [shared::SharedHighlightState::highlight_for], once per frame) carries on where the
+    ///parse stopped, until it's done. Without a timeout, parsing always runs to the end. Highlighting that parses the text again,
+    ///for local variables and embedded code (see [document::Document::highlights]), stops at the timeout as well.
+    pub fn with_parse_timeout(mut self, timeout: Duration) -> Self {
+        self.parse_timeout = Some(timeout);
+        self
+    }
+
+    ///Cancels parsing a document (like a [timeout](TSSettings::with_parse_timeout)) and highlighting while `flag` isn't `0`, e.g.
+    ///set from another thread when the user closes the file. Reset it before resuming.
+    pub fn with_cancellation_flag(mut self, flag: Arc<AtomicUsize>) -> Self {
+        self.cancellation_flag = Some(flag);
+        self
+    }
+
+    ///Highlights the syntax errors of the text (tokens the parser couldn't fit in, and where it had to assume one, see
+    ///[document::Document::syntax_errors]) as [document::ERROR_CAPTURE], so themes can mark them, e.g. in red or underlined.
+    ///Adds the name to the highlight names, unless they have it, which fails like [TSSettings::with_captures] does. Only the
+    ///highlights of the whole document have them, like those of a [shared::SharedHighlightState]: a line on its own has errors
+    ///the document doesn't. Off by default.
+    pub fn with_syntax_errors(mut self, enabled: bool) -> Result<Self> {
+        self.syntax_errors = enabled;
+        if enabled
+            && !self
+                .highlight_names
+                .iter()
+                .any(|name| name == document::ERROR_CAPTURE)
+        {
+            let mut names = self.highlight_names.to_vec();
+            names.push(document::ERROR_CAPTURE.to_string());
+            self.set_names(names)?;
+        }
+        Ok(self)
+    }
+
+    ///Highlights code embedded in the text (the code blocks of Markdown, the scripts and styles of HTML, the macros of Rust...)
+    ///with the languages of `registry`, as the injection query of the language marks it. See [registry::Injections] for how the
+    ///languages are looked up. Languages registered later aren't used, call this again to pick them up. The languages the
+    ///settings had (like `TSSettings::markdown` has its inline grammar) and the
+    ///[resolver](TSSettings::with_injection_resolver) are kept.
+    pub fn with_injections(mut self, registry: &registry::LanguageRegistry) -> Self {
+        self.injections = Some(Arc::new(registry.injections(&self)));
+        self
+    }
+
+    ///Asks `resolver` for the configurations of embedded languages the [registry](TSSettings::with_injections) doesn't have (or
+    ///all of them, without a registry), e.g. for custom or private grammars. See [registry::InjectionResolver].
+    pub fn with_injection_resolver(
+        mut self,
+        resolver: impl Fn(&str) -> Option<Arc<tree_sitter_highlight::HighlightConfiguration>>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        let injections = self.injections.take().unwrap_or_default();
+        self.injections = Some(Arc::new(injections.with_resolver(Arc::new(resolver))));
+        self
+    }
+
+    ///Adds `layer` on top of the syntax highlighting, above the layers added before. See [layer].
+    pub fn with_layer(mut self, layer: impl layer::Layer + 'static) -> Self {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    ///Adds `plugin` on top of the syntax highlighting and the [layers](TSSettings::with_layer), see [plugin]. The capture names the
+    ///plugin registers are added to the highlight names, which fails like [TSSettings::with_captures] does.
+    pub fn with_plugin(
+        mut self,
+        mut plugin: impl plugin::HighlightPlugin + 'static,
+    ) -> Result<Self> {
+        let mut registry = plugin::Registry {
+            settings: &self,
+            highlight_names: self.highlight_names.to_vec(),
+        };
+        plugin.init(&mut registry);
+        let names = registry.highlight_names;
+        if names.len() > self.highlight_names.len() {
+            self.set_names(names)?;
+        }
+        self.plugins.push(Arc::new(plugin));
+        Ok(self)
+    }
+
+    ///Configures the configuration to recognize `names`, see [TSSettings::with_captures].
+    fn set_names(&mut self, names: Vec<String>) -> Result<()> {
+        let Some(config) = Arc::get_mut(&mut self.tsconfig) else {
+            return Err(Error::Shared {
+                language: self.tsconfig.language_name.clone(),
+            });
+        };
+        configure(
+            config,
+            &names.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+        self.capture_map = captures::CaptureMap::new(&names);
+        self.highlight_names = names.into();
+        Ok(())
+    }
+
+    ///Capture names the configuration was set up with. A [Highlight]'s capture index points into this list.
+    pub fn highlight_names(&self) -> &[String] {
+        &self.highlight_names
+    }
+
+    ///Resolves the highlights of these settings to their capture names, see [Highlight::name].
+    pub fn capture_map(&self) -> captures::CaptureMap {
+        self.capture_map.clone()
+    }
+
+    ///The configuration of the language called `name` that is embedded in the text, see [TSSettings::with_injections].
+    pub(crate) fn injection(
+        &self,
+        name: &str,
+    ) -> Option<&tree_sitter_highlight::HighlightConfiguration> {
+        self.injections.as_ref()?.resolve(name)
+    }
+
+    ///Whether the queries resolve local variables, which only tree-sitter-highlight does: the highlight query on the syntax tree
+    ///highlights them like any other variable, see [progressive].
+    pub(crate) fn resolves_locals(&self) -> bool {
+        self.tsconfig
+            .query
+            .capture_names()
+            .iter()
+            .any(|name| name.starts_with("local."))
+    }
+
+    ///Starts measuring, if there is a [stats] hook.
+    pub(crate) fn measure(&self) -> Option<std::time::Instant> {
+        self.stats.as_ref().map(|_| std::time::Instant::now())
+    }
+
+    pub(crate) fn report(&self, usage: impl FnOnce(&str) -> stats::Usage<'_>) {
+        if let Some(hook) = &self.stats {
+            hook(&usage(&self.tsconfig.language_name));
+        }
+    }
+
+    ///Reports that `feature` was used.
+    pub(crate) fn report_feature(&self, feature: &'static str) {
+        self.report(|language| stats::Usage::Feature { language, feature });
+    }
+
+    ///Index of the highlight name `capture` is highlighted as. Same matching as `configure`: every part of the name has to be part
+    ///of the capture, and the name with the most parts wins.
+    pub(crate) fn capture_index(&self, capture: &str) -> Option<usize> {
+        self.highlight_names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| {
+                name.split('.')
+                    .all(|part| capture.split('.').any(|c| c == part))
+            })
+            .max_by_key(|(idx, name)| (name.split('.').count(), std::cmp::Reverse(*idx)))
+            .map(|(idx, _)| idx)
+    }
+
+    ///Resolves the theme's style for every configured capture, indexed like [TSSettings::highlight_names].
+    pub(crate) fn resolve_styles(&self) -> Vec<Option<theme::Style>> {
+        match &self.theme {
+            Some(theme) => self
+                .highlight_names
+                .iter()
+                //captures the theme doesn't know are left plain, instead of mixing in the palette
+                .map(|name| Some(theme.style(name).copied().unwrap_or_default()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+///Configures `config` to recognize `names`, and warns about the captures of its highlight query none of them matches.
+fn configure(config: &mut tree_sitter_highlight::HighlightConfiguration, names: &[&str]) {
+    config.configure(names);
+    //same matching as `configure`: every part of a name has to be part of the capture
+    let unknown = config
+        .query
+        .capture_names()
+        .iter()
+        .filter(|capture| {
+            !capture.starts_with('_')
+                && !names.iter().any(|name| {
+                    name.split('.')
+                        .all(|part| capture.split('.').any(|c| c == part))
+                })
+        })
+        .copied()
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        logging::warning!(
+            target: logging::QUERY,
+            "captures of the {} highlight query that match no highlight name, and aren't highlighted: {}",
+            config.language_name,
+            unknown.join(", ")
+        );
+    }
+}
+
+impl TSSettings {
+    ///Equal, except maybe for the [snapshot](shared::HighlightSnapshot) of the shared state.
+    fn eq_but_shared(&self, other: &Self) -> bool {
+        fn same<T>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
         }
+        fn all_same<T: ?Sized>(a: &[Arc<T>], b: &[Arc<T>]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| Arc::ptr_eq(a, b))
+        }
+        std::sync::Arc::ptr_eq(&self.tsconfig, &other.tsconfig)
+            && same(&self.theme, &other.theme)
+            && self.focus == other.focus
+            && self.precedence == other.precedence
+            && same(&self.overlay, &other.overlay)
+            && all_same(&self.layers, &other.layers)
+            && all_same(&self.plugins, &other.plugins)
+            && self.syntax_errors == other.syntax_errors
+            && same(&self.injections, &other.injections)
+            && self.parse_timeout == other.parse_timeout
+            && same(&self.cancellation_flag, &other.cancellation_flag)
+            && (Arc::ptr_eq(&self.highlight_names, &other.highlight_names)
+                || self.highlight_names == other.highlight_names)
+            && self.capture_map == other.capture_map
+            && same(&self.export_theme, &other.export_theme)
+            && self.whitespace_edits == other.whitespace_edits
+            && self.token_edits == other.token_edits
     }
 }
 
 impl PartialEq for TSSettings {
     fn eq(&self, other: &Self) -> bool {
-        std::sync::Arc::ptr_eq(&self.tsconfig, &other.tsconfig)
+        self.eq_but_shared(other)
+            && match (&self.shared, &other.shared) {
+                (Some(a), Some(b)) => a.ptr_eq(b),
+                (None, None) => true,
+                _ => false,
+            }
     }
 }
 
+///Highlights the lines Iced hands it one by one. Constructs spanning several lines (block comments, raw strings...) are only
+///highlighted correctly if the settings come from a [shared::SharedHighlightState], which highlights the whole document.
 pub struct Highlighter {
     highlighter: tree_sitter_highlight::Highlighter,
     settings: TSSettings,
+    styles: Vec<Option<theme::Style>>,
     line: usize,
 }
 
-impl iced::advanced::text::Highlighter for Highlighter {
-    type Highlight = tree_sitter_highlight::Highlight;
+impl iced_core::text::Highlighter for Highlighter {
+    type Highlight = Highlight;
     type Settings = TSSettings;
     type Iterator<'a> = Box<dyn Iterator<Item = (Range<usize>, Self::Highlight)> + 'a>;
 
@@ -157,13 +822,26 @@ impl iced::advanced::text::Highlighter for Highlighter {
         let highlighter = tree_sitter_highlight::Highlighter::new();
         Self {
             settings: settings.clone(),
+            styles: settings.resolve_styles(),
             highlighter,
             line: 0,
         }
     }
 
     fn update(&mut self, new_settings: &Self::Settings) {
+        //a new snapshot of the same state only changes some lines, otherwise styles might have changed, so everything needs to
+        //be re-highlighted
+        let first_change = match (&self.settings.shared, &new_settings.shared) {
+            (Some(old), Some(new)) if self.settings.eq_but_shared(new_settings) => {
+                old.first_change(new)
+            }
+            _ => Some(0),
+        };
         self.settings = new_settings.clone();
+        self.styles = new_settings.resolve_styles();
+        if let Some(line) = first_change {
+            self.line = self.line.min(line);
+        }
     }
 
     fn change_line(&mut self, line: usize) {
@@ -175,50 +853,210 @@ impl iced::advanced::text::Highlighter for Highlighter {
     }
 
     fn highlight_line(&mut self, line: &str) -> Self::Iterator<'_> {
-        //NOTE: we ignore anything that _fails_.
-        //      In the future one might want to tag those areas.
-        let events = match self.highlighter.highlight(
-            &self.settings.tsconfig.as_ref(),
-            line.as_bytes(),
-            None,
-            |_| None,
-        ) {
-            Ok(events) => events,
-            Err(_e) => return Box::new([].into_iter()),
-        };
-        let alloc_size = {
-            let (lower, upper) = events.size_hint();
-            if let Some(upper) = upper {
-                upper
-            } else {
-                lower
+        //NOTE: we ignore anything that _fails_. Syntax errors are tagged by the highlights of the whole document, see
+        //      `TSSettings::with_syntax_errors`.
+        let line_index = self.line;
+        self.line += 1;
+        let start = self.settings.measure();
+        let dimmed = self
+            .settings
+            .focus
+            .as_ref()
+            .is_some_and(|focus| !focus.contains(&line_index));
+
+        let shared = self
+            .settings
+            .shared
+            .as_ref()
+            .and_then(|snapshot| snapshot.captures(line_index, line))
+            //lines that aren't highlighted yet stay plain until they are, instead of highlighting each on its own
+            .map(Option::unwrap_or_default);
+        let is_shared = shared.is_some();
+        let mut format_instructions = Vec::new();
+
+        //dim the whole line first, captures overwrite that span where they are.
+        if dimmed {
+            format_instructions.push((
+                None,
+                0..line.len(),
+                Highlight {
+                    capture: None,
+                    style: None,
+                    dimmed,
+                    overlay: None,
+                    names: self.settings.capture_map.clone(),
+                },
+            ));
+        }
+
+        if let Some(captures) = shared {
+            //already highlighted as part of the whole text
+            for (range, capture) in captures.iter().cloned() {
+                format_instructions.push((
+                    Some(overlay::Source::Syntax),
+                    range,
+                    Highlight {
+                        capture: Some(capture),
+                        style: self.styles.get(capture.0).copied().flatten(),
+                        dimmed,
+                        overlay: None,
+                        names: self.settings.capture_map.clone(),
+                    },
+                ));
             }
-        };
-        let mut format_instructions = Vec::with_capacity(alloc_size);
-
-        let mut current_style = None;
-        for event in events {
-            let event = if let Ok(ev) = event {
-                ev
-            } else {
-                continue;
+        } else {
+            let events = match self.highlighter.highlight(
+                self.settings.tsconfig.as_ref(),
+                line.as_bytes(),
+                self.settings.cancellation_flag.as_deref(),
+                |name| self.settings.injection(name),
+            ) {
+                Ok(events) => events,
+                Err(err) => {
+                    logging::debug!(target: logging::HIGHLIGHT, "highlighting line {line_index} failed: {err}");
+                    return Box::new([].into_iter());
+                }
             };
-            match event {
-                HighlightEvent::Source { start, end } => {
-                    if let Some(style) = current_style {
-                        format_instructions.push((start..end, style));
+
+            let mut current_style = None;
+            for event in events {
+                let event = match event {
+                    Ok(ev) => ev,
+                    //the rest of the line stays plain
+                    Err(tree_sitter_highlight::Error::Cancelled) => break,
+                    Err(_) => continue,
+                };
+                match event {
+                    HighlightEvent::Source { start, end } => {
+                        if let Some(capture) = current_style {
+                            format_instructions.push((
+                                Some(overlay::Source::Syntax),
+                                start..end,
+                                Highlight {
+                                    capture: Some(capture),
+                                    style: self.styles.get(capture.0).copied().flatten(),
+                                    dimmed,
+                                    overlay: None,
+                                    names: self.settings.capture_map.clone(),
+                                },
+                            ));
+                        }
+                    }
+                    HighlightEvent::HighlightStart(styleid) => {
+                        current_style = Some(styleid);
+                    }
+                    HighlightEvent::HighlightEnd => {
+                        current_style = None;
                     }
                 }
-                HighlightEvent::HighlightStart(styleid) => {
-                    current_style = Some(styleid);
-                }
-                HighlightEvent::HighlightEnd => {
-                    current_style = None;
-                }
             }
         }
 
+        //plugins are layers as well, and go on top of the plain ones
+        let layers = self.settings.layers.iter().map(|layer| &**layer).chain(
+            self.settings
+                .plugins
+                .iter()
+                .map(|plugin| &**plugin as &dyn layer::Layer),
+        );
+        for layer in layers {
+            for (columns, name) in layer.highlight(line_index, line) {
+                let Some(capture) = self.settings.capture_index(&name) else {
+                    continue;
+                };
+                format_instructions.push((
+                    Some(overlay::Source::Layers),
+                    columns.start.min(line.len())..columns.end.min(line.len()),
+                    Highlight {
+                        capture: Some(tree_sitter_highlight::Highlight(capture)),
+                        style: self.styles.get(capture).copied().flatten(),
+                        dimmed,
+                        overlay: None,
+                        names: self.settings.capture_map.clone(),
+                    },
+                ));
+            }
+        }
+
+        if let Some(overlay) = &self.settings.overlay {
+            for (columns, kind) in overlay.line(line_index) {
+                format_instructions.push((
+                    Some(kind.source()),
+                    columns.start.min(line.len())..columns.end.min(line.len()),
+                    Highlight {
+                        capture: None,
+                        style: None,
+                        dimmed,
+                        overlay: Some(*kind),
+                        names: self.settings.capture_map.clone(),
+                    },
+                ));
+            }
+        }
+
+        //later spans win where they overlap, so they go in the order of their source's precedence. The dimmed line stays first.
+        let precedence = &self.settings.precedence;
+        format_instructions.sort_by_key(|(source, _, _)| {
+            source.map(|source| precedence.iter().position(|ranked| *ranked == source))
+        });
+
+        if let Some(start) = start {
+            self.settings
+                .report(|language| stats::Usage::HighlightLine {
+                    language,
+                    bytes: line.len(),
+                    duration: start.elapsed(),
+                    shared: is_shared,
+                });
+        }
+
         //iterate throuht the event chain, and transform them into a list of _formats_ + their range.
-        Box::new(format_instructions.into_iter())
+        Box::new(
+            format_instructions
+                .into_iter()
+                .map(|(_, range, highlight)| (range, highlight)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{document, error::Error, testing};
+
+    #[test]
+    fn shared_configurations_are_not_reconfigured() {
+        let settings = testing::rust()
+            .with_captures(&["keyword", "function.macro"])
+            .unwrap();
+        assert_eq!(settings.highlight_names(), ["keyword", "function.macro"]);
+        assert_eq!(settings.capture_index("function.macro.call"), Some(1));
+
+        let clone = settings.clone();
+        let error = settings.with_captures(&["comment"]).err().unwrap();
+        assert!(matches!(&error, Error::Shared { language } if language == "rust"));
+        drop(clone);
+    }
+
+    #[test]
+    fn syntax_errors_add_their_capture() {
+        let settings = testing::rust().with_syntax_errors(true).unwrap();
+        assert_eq!(
+            settings.highlight_names().last().map(String::as_str),
+            Some(document::ERROR_CAPTURE)
+        );
+        //the name is there already, so the shared configuration needn't change
+        let clone = settings.clone();
+        assert!(settings.with_syntax_errors(true).is_ok());
+        assert!(matches!(
+            clone.clone().with_syntax_errors(false),
+            Ok(settings) if !settings.syntax_errors
+        ));
+        assert!(matches!(
+            testing::rust().with_captures(&[]).and_then(|settings| {
+                let _clone = settings.clone();
+                settings.with_syntax_errors(true)
+            }),
+            Err(Error::Shared { .. })
+        ));
     }
 }
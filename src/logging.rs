@@ -0,0 +1,38 @@
+//! Internal warnings go through `log`, or through `tracing` with the `tracing` feature, so they show up in the host's logger.
+//!
+//! Each kind of problem has its own target, so hosts can filter them.
+
+///Highlight and indent queries, e.g. captures without a highlight name.
+pub(crate) const QUERY: &str = "iced_highlighter_tree_sitter::query";
+///Loading a grammar into the parser.
+pub(crate) const GRAMMAR: &str = "iced_highlighter_tree_sitter::grammar";
+///Parsing, e.g. texts that take long to parse.
+pub(crate) const PARSE: &str = "iced_highlighter_tree_sitter::parse";
+///Highlighting that failed.
+pub(crate) const HIGHLIGHT: &str = "iced_highlighter_tree_sitter::highlight";
+///Themes of other editors that can't be imported completely.
+#[cfg(any(feature = "helix", feature = "neovim"))]
+pub(crate) const THEME: &str = "iced_highlighter_tree_sitter::theme";
+///Scripts that fail while running.
+#[cfg(feature = "scripting")]
+pub(crate) const SCRIPT: &str = "iced_highlighter_tree_sitter::script";
+
+macro_rules! warning {
+    (target: $target:expr, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::warn!(target: $target, $($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::warn!(target: $target, $($arg)+);
+    }};
+}
+
+macro_rules! debug {
+    (target: $target:expr, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!(target: $target, $($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::debug!(target: $target, $($arg)+);
+    }};
+}
+
+pub(crate) use {debug, warning};
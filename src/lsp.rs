@@ -0,0 +1,159 @@
+//! Conversions between the positions, ranges and diagnostics of the crate and the types of the Language Server Protocol (the
+//! [lsp_types] crate), for hosts that run a language server next to the highlighter. Requires the `lsp-types` feature.
+//!
+//! The crate counts columns in bytes, LSP in the code units of the encoding client and server agreed on (the
+//! `PositionEncodingKind` of the server's capabilities), UTF-16 unless they agreed on another one. So positions are converted by
+//! the [Document], which knows the text of the lines, and caches the UTF-16 columns of the lines (see
+//! [Document::byte_to_utf16]). Positions the server sends past the end of a line are clamped to the end
+//! of the line, as the protocol asks, and positions past the last line to the end of the text.
+//!
+//! The diagnostics a server publishes go into an [Overlay] via [Overlay::add_lsp_diagnostics].
+
+use std::ops::Range;
+
+use lsp_types::PositionEncodingKind;
+
+use crate::{
+    document::Document,
+    overlay::{Overlay, Severity},
+};
+
+///What LSP columns count.
+#[derive(Clone, Copy)]
+enum Units {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl Units {
+    ///Encodings the protocol doesn't know count UTF-16 code units, the encoding every server has to support.
+    fn of(encoding: &PositionEncodingKind) -> Self {
+        match encoding.as_str() {
+            "utf-8" => Self::Utf8,
+            "utf-32" => Self::Utf32,
+            _ => Self::Utf16,
+        }
+    }
+
+    fn len(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+impl Document {
+    ///The LSP position of the byte `offset`, with columns counted in `encoding`. Offsets past the end are clamped to the end of
+    ///the document.
+    pub fn lsp_position(
+        &self,
+        offset: usize,
+        encoding: &PositionEncodingKind,
+    ) -> lsp_types::Position {
+        let units = Units::of(encoding);
+        if let Units::Utf16 = units {
+            let position = self.byte_to_utf16(offset);
+            return lsp_types::Position::new(position.line as u32, position.column as u32);
+        }
+        let position = self.position(offset);
+        let start = offset.min(self.text().len()) - position.column;
+        //an offset inside a character counts from its start
+        let column = self.text()[start..]
+            .char_indices()
+            .take_while(|(idx, c)| idx + c.len_utf8() <= position.column)
+            .map(|(_, c)| units.len(c))
+            .sum::<usize>();
+        lsp_types::Position::new(position.line as u32, column as u32)
+    }
+
+    ///The LSP range of the byte `range`, see [Document::lsp_position].
+    pub fn lsp_range(
+        &self,
+        range: Range<usize>,
+        encoding: &PositionEncodingKind,
+    ) -> lsp_types::Range {
+        lsp_types::Range::new(
+            self.lsp_position(range.start, encoding),
+            self.lsp_position(range.end, encoding),
+        )
+    }
+
+    ///The byte offset of the LSP `position`, with columns counted in `encoding`. See the [module](crate::lsp) documentation for
+    ///positions past the end.
+    pub fn lsp_offset(
+        &self,
+        position: lsp_types::Position,
+        encoding: &PositionEncodingKind,
+    ) -> usize {
+        let units = Units::of(encoding);
+        if let Units::Utf16 = units {
+            return self.utf16_to_byte((position.line as usize, position.character as usize));
+        }
+        let Some(line) = self.line_range(position.line as usize) else {
+            return self.text().len();
+        };
+        let mut column = 0;
+        for (idx, c) in self.text()[line.clone()].char_indices() {
+            if column >= position.character as usize {
+                return line.start + idx;
+            }
+            column += units.len(c);
+        }
+        line.end
+    }
+
+    ///The byte range of the LSP `range`, see [Document::lsp_offset].
+    pub fn lsp_byte_range(
+        &self,
+        range: lsp_types::Range,
+        encoding: &PositionEncodingKind,
+    ) -> Range<usize> {
+        self.lsp_offset(range.start, encoding)..self.lsp_offset(range.end, encoding)
+    }
+}
+
+impl From<lsp_types::DiagnosticSeverity> for Severity {
+    ///Severities the protocol doesn't define count as errors.
+    fn from(severity: lsp_types::DiagnosticSeverity) -> Self {
+        match severity {
+            lsp_types::DiagnosticSeverity::WARNING => Severity::Warning,
+            lsp_types::DiagnosticSeverity::INFORMATION => Severity::Info,
+            lsp_types::DiagnosticSeverity::HINT => Severity::Hint,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl From<Severity> for lsp_types::DiagnosticSeverity {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Error => lsp_types::DiagnosticSeverity::ERROR,
+            Severity::Warning => lsp_types::DiagnosticSeverity::WARNING,
+            Severity::Info => lsp_types::DiagnosticSeverity::INFORMATION,
+            Severity::Hint => lsp_types::DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+impl Overlay {
+    ///Adds the `diagnostics` a language server published for `document` (`textDocument/publishDiagnostics`), with columns counted
+    ///in `encoding`. Diagnostics without a severity count as errors, like most editors show them.
+    pub fn add_lsp_diagnostics(
+        &mut self,
+        document: &Document,
+        diagnostics: &[lsp_types::Diagnostic],
+        encoding: &PositionEncodingKind,
+    ) {
+        for diagnostic in diagnostics {
+            self.add_diagnostic(
+                document,
+                document.lsp_byte_range(diagnostic.range, encoding),
+                diagnostic.severity.map_or(Severity::Error, Severity::from),
+                diagnostic.message.clone(),
+            );
+        }
+    }
+}
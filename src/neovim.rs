@@ -0,0 +1,439 @@
+//! Importing Neovim colorschemes via [Theme::from_neovim].
+//!
+//! Neovim colorschemes are Lua or Vimscript run by the editor, so they are imported from what Neovim reports once one is loaded:
+//! the JSON of `:lua print(vim.json.encode(vim.api.nvim_get_hl(0, {})))`, or the listing of `:highlight` (e.g. via
+//! `:redir > groups.txt | silent highlight | redir END`).
+//!
+//! The groups of tree-sitter's captures (`@comment`, `@function.call`...) style the captures of the same names, and like in Neovim,
+//! a capture without a group of its own falls back to its parent (`function.call` to `function`). Groups that link to others get
+//! their style. Standard captures the colorscheme leaves alone are styled by the classic groups Neovim links them to by default,
+//! so older colorschemes that only set `Comment`, `Function` and the like carry over as well. Besides both colors, `bold`,
+//! `italic`, the underlines, `strikethrough` and `reverse` are kept. The `Normal` group has the colors of the editor.
+
+use iced_core::Color;
+
+use crate::{
+    color, logging,
+    theme::{ImportError, Style, Theme},
+};
+
+///Groups that link to groups that link... stop after this many.
+const MAX_LINKS: usize = 16;
+
+///Links Neovim sets up by default, for the groups a colorscheme doesn't define.
+const DEFAULT_LINKS: &[(&str, &str)] = &[
+    ("@attribute", "Macro"),
+    ("@attribute.builtin", "Special"),
+    ("@boolean", "Boolean"),
+    ("@character", "Character"),
+    ("@character.special", "SpecialChar"),
+    ("@comment", "Comment"),
+    ("@constant", "Constant"),
+    ("@constant.builtin", "Special"),
+    ("@constructor", "Special"),
+    ("@function", "Function"),
+    ("@function.builtin", "Special"),
+    ("@keyword", "Keyword"),
+    ("@label", "Label"),
+    ("@module", "Structure"),
+    ("@module.builtin", "Special"),
+    ("@number", "Number"),
+    ("@number.float", "Float"),
+    ("@operator", "Operator"),
+    ("@property", "Identifier"),
+    ("@punctuation", "Delimiter"),
+    ("@punctuation.special", "Special"),
+    ("@string", "String"),
+    ("@string.escape", "@string.special"),
+    ("@string.regexp", "@string.special"),
+    ("@string.special", "SpecialChar"),
+    ("@string.special.url", "Underlined"),
+    ("@tag", "Tag"),
+    ("@tag.builtin", "Special"),
+    ("@type", "Type"),
+    ("@type.builtin", "Special"),
+    ("@variable.builtin", "Special"),
+    ("@variable.parameter.builtin", "Special"),
+    ("Boolean", "Constant"),
+    ("Character", "Constant"),
+    ("Conditional", "Statement"),
+    ("Debug", "Special"),
+    ("Define", "PreProc"),
+    ("Delimiter", "Special"),
+    ("Exception", "Statement"),
+    ("Float", "Number"),
+    ("Function", "Identifier"),
+    ("Include", "PreProc"),
+    ("Keyword", "Statement"),
+    ("Label", "Statement"),
+    ("Macro", "PreProc"),
+    ("Number", "Constant"),
+    ("PreCondit", "PreProc"),
+    ("Repeat", "Statement"),
+    ("SpecialChar", "Special"),
+    ("SpecialComment", "Special"),
+    ("StorageClass", "Type"),
+    ("String", "Constant"),
+    ("Structure", "Type"),
+    ("Tag", "Special"),
+    ("Typedef", "Type"),
+];
+
+///Groups of captures that aren't highlighting: spell checking, concealing, and `@none` to turn highlighting off.
+const SPECIAL_CAPTURES: &[&str] = &["conceal", "none", "nospell", "spell"];
+
+///Color names colorschemes use, by their values in Neovim.
+const COLOR_NAMES: &[(&str, [u8; 3])] = &[
+    ("black", [0x00, 0x00, 0x00]),
+    ("blue", [0x00, 0x00, 0xff]),
+    ("brown", [0xa5, 0x2a, 0x2a]),
+    ("cyan", [0x00, 0xff, 0xff]),
+    ("darkblue", [0x00, 0x00, 0x8b]),
+    ("darkcyan", [0x00, 0x8b, 0x8b]),
+    ("darkgray", [0xa9, 0xa9, 0xa9]),
+    ("darkgreen", [0x00, 0x64, 0x00]),
+    ("darkgrey", [0xa9, 0xa9, 0xa9]),
+    ("darkmagenta", [0x8b, 0x00, 0x8b]),
+    ("darkred", [0x8b, 0x00, 0x00]),
+    ("gray", [0x80, 0x80, 0x80]),
+    ("green", [0x00, 0x80, 0x00]),
+    ("grey", [0x80, 0x80, 0x80]),
+    ("lightblue", [0xad, 0xd8, 0xe6]),
+    ("lightcyan", [0xe0, 0xff, 0xff]),
+    ("lightgray", [0xd3, 0xd3, 0xd3]),
+    ("lightgreen", [0x90, 0xee, 0x90]),
+    ("lightgrey", [0xd3, 0xd3, 0xd3]),
+    ("lightyellow", [0xff, 0xff, 0xe0]),
+    ("magenta", [0xff, 0x00, 0xff]),
+    ("orange", [0xff, 0xa5, 0x00]),
+    ("purple", [0x80, 0x00, 0x80]),
+    ("red", [0xff, 0x00, 0x00]),
+    ("seagreen", [0x2e, 0x8b, 0x57]),
+    ("slateblue", [0x6a, 0x5a, 0xcd]),
+    ("violet", [0xee, 0x82, 0xee]),
+    ("white", [0xff, 0xff, 0xff]),
+    ("yellow", [0xff, 0xff, 0x00]),
+];
+
+impl Theme {
+    ///Imports a Neovim colorscheme from the JSON of `nvim_get_hl` or the listing of `:highlight`, whichever `export` is. See the
+    ///[module](crate::neovim) documentation.
+    pub fn from_neovim(export: &str) -> Result<Self, ImportError> {
+        let groups = if export.trim_start().starts_with('{') {
+            Groups::from_json(export)?
+        } else {
+            Groups::from_listing(export)
+        };
+        if groups.0.is_empty() {
+            return Err(ImportError::Theme(String::from(
+                "the export has no highlight groups",
+            )));
+        }
+        Ok(groups.build())
+    }
+}
+
+///The groups of an export, in its order. Later definitions of the same name win.
+struct Groups(Vec<(String, Group)>);
+
+///Attributes of a highlight group, colors as they are written: hex, a name, `NONE`, or `fg` and `bg` for the colors of `Normal`.
+#[derive(Default)]
+struct Group {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    reverse: bool,
+    link: Option<String>,
+}
+
+impl Groups {
+    ///`nvim_get_hl` maps the names to their attributes, colors as numbers.
+    fn from_json(json: &str) -> Result<Self, ImportError> {
+        let value = serde_json::from_str::<serde_json::Value>(json).map_err(ImportError::Json)?;
+        let Some(groups) = value.as_object() else {
+            return Err(ImportError::Theme(String::from(
+                "the export has to map the highlight groups to their attributes",
+            )));
+        };
+        let groups = groups
+            .iter()
+            .filter_map(|(name, attributes)| {
+                let attributes = attributes.as_object()?;
+                let flag =
+                    |key| attributes.get(key).and_then(serde_json::Value::as_bool) == Some(true);
+                let color = |key| match attributes.get(key)? {
+                    serde_json::Value::Number(rgb) => Some(format!("#{:06x}", rgb.as_u64()?)),
+                    serde_json::Value::String(color) => Some(color.clone()),
+                    _ => None,
+                };
+                let group = Group {
+                    fg: color("fg"),
+                    bg: color("bg"),
+                    bold: flag("bold"),
+                    italic: flag("italic"),
+                    underline: [
+                        "underline",
+                        "undercurl",
+                        "underdouble",
+                        "underdotted",
+                        "underdashed",
+                    ]
+                    .into_iter()
+                    .any(flag),
+                    strikethrough: flag("strikethrough"),
+                    reverse: flag("reverse"),
+                    link: attributes
+                        .get("link")
+                        .and_then(serde_json::Value::as_str)
+                        .map(str::to_string),
+                };
+                Some((name.clone(), group))
+            })
+            .collect();
+        Ok(Self(groups))
+    }
+
+    ///`:highlight` lists a group per line (`Comment xxx guifg=#80a0ff gui=italic`, `@comment xxx links to Comment`), and wraps
+    ///long ones onto indented lines.
+    fn from_listing(listing: &str) -> Self {
+        let mut lines = Vec::<String>::new();
+        for line in listing.lines() {
+            match lines.last_mut() {
+                Some(last) if line.starts_with(char::is_whitespace) => {
+                    last.push(' ');
+                    last.push_str(line.trim());
+                }
+                _ => lines.push(line.to_string()),
+            }
+        }
+        let groups = lines
+            .iter()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let name = words.next()?;
+                //the sample of the group, which every entry has
+                if words.next()? != "xxx" {
+                    return None;
+                }
+                let mut group = Group::default();
+                let words = words.collect::<Vec<_>>();
+                for (idx, word) in words.iter().enumerate() {
+                    if let ["links", "to", link, ..] = words[idx..] {
+                        group.link = Some(link.to_string());
+                    }
+                    match word.split_once('=') {
+                        Some(("guifg", color)) => group.fg = Some(color.to_string()),
+                        Some(("guibg", color)) => group.bg = Some(color.to_string()),
+                        Some(("gui", attributes)) => {
+                            for attribute in attributes.split(',') {
+                                match attribute {
+                                    "bold" => group.bold = true,
+                                    "italic" => group.italic = true,
+                                    "underline" | "undercurl" | "underdouble" | "underdotted"
+                                    | "underdashed" => group.underline = true,
+                                    "strikethrough" => group.strikethrough = true,
+                                    "reverse" | "inverse" => group.reverse = true,
+                                    _ => {}
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Some((name.to_string(), group))
+            })
+            .collect();
+        Self(groups)
+    }
+
+    ///The group `name` stands for, following links and the default links for groups the export doesn't define.
+    fn resolve(&self, name: &str) -> Option<&Group> {
+        let mut name = name;
+        for _ in 0..MAX_LINKS {
+            match self.0.iter().rev().find(|(known, _)| known == name) {
+                Some((_, group)) => match &group.link {
+                    Some(link) => name = link,
+                    None => return Some(group),
+                },
+                None => name = DEFAULT_LINKS.iter().find(|(group, _)| *group == name)?.1,
+            }
+        }
+        logging::warning!(target: logging::THEME, "the Neovim highlight groups link in a circle, at `{name}`");
+        None
+    }
+
+    fn build(self) -> Theme {
+        let normal = self.resolve("Normal");
+        let normal_color = |pick: fn(&Group) -> &Option<String>| {
+            normal
+                .and_then(|group| pick(group).as_deref())
+                .and_then(|value| parse_color(value, None, None))
+        };
+        let background = normal_color(|group| &group.bg).unwrap_or(Color::BLACK);
+        let foreground = normal_color(|group| &group.fg).unwrap_or(Color::WHITE);
+        let color = |value: Option<&str>| {
+            let value = value?;
+            let color = parse_color(value, Some(foreground), Some(background));
+            if color.is_none() && !value.eq_ignore_ascii_case("none") {
+                logging::warning!(target: logging::THEME, "the Neovim colorscheme has no color `{value}`");
+            }
+            color
+        };
+        let style = |group: &Group| {
+            let (fg, bg) = (color(group.fg.as_deref()), color(group.bg.as_deref()));
+            Style {
+                //the colors swap
+                color: if group.reverse {
+                    bg.or(Some(background))
+                } else {
+                    fg
+                },
+                background: if group.reverse {
+                    fg.or(Some(foreground))
+                } else {
+                    bg
+                },
+                bold: group.bold,
+                italic: group.italic,
+                underline: group.underline,
+                strikethrough: group.strikethrough,
+            }
+        };
+
+        let mut theme = Theme::new(background, foreground);
+        let captures = self
+            .0
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .chain(DEFAULT_LINKS.iter().map(|(group, _)| *group))
+            .filter_map(|name| Some((name, name.strip_prefix('@')?)));
+        for (name, capture) in captures {
+            //semantic tokens of language servers aren't captures
+            let is_special = capture.starts_with("lsp.") || SPECIAL_CAPTURES.contains(&capture);
+            if is_special || theme.styles().any(|(known, _)| known == capture) {
+                continue;
+            }
+            if let Some(group) = self.resolve(name) {
+                theme.set_style(capture, style(group));
+            }
+        }
+        theme
+    }
+}
+
+///`fg` and `bg` are the colors of `Normal`, `None` for the colors of `Normal` itself.
+fn parse_color(value: &str, fg: Option<Color>, bg: Option<Color>) -> Option<Color> {
+    let value = value.trim();
+    match value.to_ascii_lowercase().as_str() {
+        "fg" | "foreground" => return fg,
+        "bg" | "background" => return bg,
+        lowercase => {
+            if let Some((_, [r, g, b])) = COLOR_NAMES.iter().find(|(name, _)| *name == lowercase) {
+                return Some(Color::from_rgb8(*r, *g, *b));
+            }
+        }
+    }
+    match color::color_literals(value).as_slice() {
+        [literal] if literal.range == (0..value.len()) => Some(literal.color),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced_core::Color;
+
+    use crate::theme::{ImportError, Style, Theme};
+
+    #[test]
+    fn imports_the_json_of_nvim_get_hl() {
+        let theme = Theme::from_neovim(
+            r##"{
+                "Normal": { "fg": 13489908, "bg": 1973806 },
+                "Comment": { "fg": 7106694, "italic": true },
+                "@comment.documentation": { "link": "Comment" },
+                "Function": { "fg": 9024762, "bold": true },
+                "Statement": { "fg": "#cba6f7", "undercurl": true },
+                "@markup.heading": { "fg": "Orange", "reverse": true },
+                "@lsp.type.function": { "fg": 16711680 },
+                "@spell": { "fg": 16711680 },
+                "A": { "link": "B" },
+                "B": { "link": "A" },
+                "@type": { "link": "A" }
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(theme.background, Color::from_rgb8(0x1e, 0x1e, 0x2e));
+        assert_eq!(theme.foreground, Color::from_rgb8(0xcd, 0xd6, 0xf4));
+        let comment = Style::color(Color::from_rgb8(0x6c, 0x70, 0x86)).italic();
+        assert_eq!(theme.style("comment"), Some(&comment));
+        assert_eq!(theme.style("comment.documentation"), Some(&comment));
+        //by the default links: `@function` to `Function`, `@keyword` to `Keyword` to `Statement`
+        assert_eq!(
+            theme.style("function.call"),
+            Some(&Style::color(Color::from_rgb8(0x89, 0xb4, 0xfa)).bold())
+        );
+        assert_eq!(
+            theme.style("keyword"),
+            Some(&Style::color(Color::from_rgb8(0xcb, 0xa6, 0xf7)).underline())
+        );
+        //the colors swap
+        let heading = theme.style("markup.heading").unwrap();
+        assert_eq!(heading.color, Some(Color::from_rgb8(0x1e, 0x1e, 0x2e)));
+        assert_eq!(heading.background, Some(Color::from_rgb8(0xff, 0xa5, 0x00)));
+        //semantic tokens, spell checking and links in a circle are left out
+        assert!(
+            theme
+                .styles()
+                .all(|(capture, _)| !["lsp.type.function", "spell", "type"].contains(&capture))
+        );
+    }
+
+    #[test]
+    fn imports_the_highlight_listing() {
+        let theme = Theme::from_neovim(
+            "Normal         xxx guifg=#cdd6f4 guibg=#1e1e2e\n\
+             Comment        xxx ctermfg=8 guifg=#6c7086 gui=italic\n\
+             @comment       xxx links to Comment\n\
+             String         xxx guifg=#a6e3a1\n                    gui=bold,underline\n\
+             @string.escape xxx guifg=fg\n\
+             garbage\n",
+        )
+        .unwrap();
+        assert_eq!(theme.background, Color::from_rgb8(0x1e, 0x1e, 0x2e));
+        assert_eq!(
+            theme.style("comment"),
+            Some(&Style::color(Color::from_rgb8(0x6c, 0x70, 0x86)).italic())
+        );
+        //a wrapped line continues the group
+        assert_eq!(
+            theme.style("string"),
+            Some(
+                &Style::color(Color::from_rgb8(0xa6, 0xe3, 0xa1))
+                    .bold()
+                    .underline()
+            )
+        );
+        assert_eq!(
+            theme.style("string.escape").and_then(|style| style.color),
+            Some(theme.foreground)
+        );
+    }
+
+    #[test]
+    fn malformed_exports_are_errors() {
+        assert!(matches!(
+            Theme::from_neovim("{ \"Normal\": "),
+            Err(ImportError::Json(_))
+        ));
+        for export in ["{}", "{ \"Normal\": 1 }", "[1, 2]", "", "not a listing"] {
+            assert!(
+                matches!(Theme::from_neovim(export), Err(ImportError::Theme(_))),
+                "{export}"
+            );
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! The heading hierarchy of prose documents (Markdown, Org, AsciiDoc), for navigation sidebars and folding by section.
+//!
+//! [Document::outline] lists the headings of the syntax tree as a tree of [Heading]s, each with the range of its section: the
+//! text up to the next heading of the same or a higher level. [Document::section_at] tells the section a position is in, e.g.
+//! to mark it in the sidebar, and [Document::fold_ranges] folds the sections, so a heading hides everything below it.
+
+use std::ops::Range;
+
+use crate::{
+    document::{Document, Position},
+    folding::FoldRange,
+};
+
+///Node kinds of headings, by the language name of their configuration. These are the kinds of the grammars the crate bundles.
+pub const HEADING_NODES: &[(&str, &[&str])] = &[
+    ("markdown", &["atx_heading", "setext_heading"]),
+    ("org", &["headline"]),
+    (
+        "asciidoc",
+        &[
+            "document_title",
+            "title1",
+            "title2",
+            "title3",
+            "title4",
+            "title5",
+        ],
+    ),
+];
+
+///A heading and its section, see the [module](self) documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Heading {
+    ///Level of the heading, 1 for the top one (`#`, `*` or `=`).
+    pub level: usize,
+    ///Byte range of the heading, without the line break after it.
+    pub range: Range<usize>,
+    ///Byte range of the text of the heading, without its markers.
+    pub title: Range<usize>,
+    ///Byte range of the section: the heading and the text up to the next heading of the same or a higher level.
+    pub section: Range<usize>,
+    ///Line of the heading.
+    pub line: usize,
+    ///The headings of the subsections, in order.
+    pub children: Vec<Heading>,
+}
+
+impl Document {
+    ///The headings of the document as a tree, the top-level ones in order. Empty for grammars without headings (see
+    ///[HEADING_NODES]), and without a tree.
+    pub fn outline(&self) -> Vec<Heading> {
+        self.settings().report_feature("outline");
+        let mut roots = Vec::new();
+        let mut open: Vec<Heading> = Vec::new();
+        for heading in self.headings() {
+            while open.last().is_some_and(|last| last.level >= heading.level) {
+                close(&mut open, &mut roots);
+            }
+            open.push(heading);
+        }
+        while !open.is_empty() {
+            close(&mut open, &mut roots);
+        }
+        roots
+    }
+
+    ///The innermost section `position` is in, `None` before the first heading.
+    pub fn section_at(&self, position: impl Into<Position>) -> Option<Heading> {
+        let offset = self.offset(position);
+        let mut headings = self.outline();
+        let mut section = None;
+        while let Some(index) = headings
+            .iter()
+            .position(|heading| heading.section.start <= offset && offset < heading.section.end)
+        {
+            let mut heading = headings.swap_remove(index);
+            headings = std::mem::take(&mut heading.children);
+            section = Some(heading);
+        }
+        section
+    }
+
+    ///Fold ranges of the sections, see [FoldRange]. Their kind is `section`.
+    pub(crate) fn section_folds(&self) -> Vec<FoldRange> {
+        self.headings()
+            .into_iter()
+            .filter_map(|heading| {
+                let start = heading.line;
+                //sections end at the start of the next heading's line
+                let end = match self.line_of(heading.section.end) {
+                    end if self.text()[..heading.section.end].ends_with('\n') => {
+                        end.saturating_sub(1)
+                    }
+                    end => end,
+                };
+                (end > start).then(|| FoldRange {
+                    lines: start..end + 1,
+                    kind: "section",
+                })
+            })
+            .collect()
+    }
+
+    ///The headings in order, without their children.
+    fn headings(&self) -> Vec<Heading> {
+        let Some(tree) = self.tree() else {
+            return Vec::new();
+        };
+        let language = &self.settings().tsconfig.language_name;
+        let Some((_, kinds)) = HEADING_NODES.iter().find(|(name, _)| name == language) else {
+            return Vec::new();
+        };
+
+        let mut headings: Vec<Heading> = Vec::new();
+        let mut cursor = tree.walk();
+        let mut descend = true;
+        loop {
+            let node = cursor.node();
+            if kinds.contains(&node.kind()) {
+                headings.extend(self.heading(node.byte_range(), node.start_position().row));
+            } else if descend && cursor.goto_first_child() {
+                continue;
+            }
+            descend = cursor.goto_next_sibling();
+            if !descend && !cursor.goto_parent() {
+                break;
+            }
+        }
+
+        //a section ends where the next heading of the same or a higher level starts
+        let ends: Vec<usize> = (0..headings.len())
+            .map(|index| {
+                headings[index + 1..]
+                    .iter()
+                    .find(|next| next.level <= headings[index].level)
+                    .map_or(self.text().len(), |next| next.range.start)
+            })
+            .collect();
+        for (heading, end) in headings.iter_mut().zip(ends) {
+            heading.section.end = end;
+        }
+        headings
+    }
+
+    ///The heading of the node at `range`, its level by the markers: the number of `#`, `*` or `=` before it, or its underline
+    ///(`===` or `---`).
+    fn heading(&self, range: Range<usize>, line: usize) -> Option<Heading> {
+        let text = &self.text()[range.clone()];
+        let text = text.trim_end_matches(['\n', '\r']);
+        let range = range.start..range.start + text.len();
+        let first = text.lines().next()?;
+        let marker = first.chars().next()?;
+        let (level, title) = if matches!(marker, '#' | '*' | '=') {
+            let after = first.trim_start_matches(marker);
+            let level = first.len() - after.len();
+            let mut title = after.trim();
+            //the closing sequence of `## Title ##`
+            if marker == '#' {
+                let closed = title.trim_end_matches('#');
+                if closed.is_empty() || closed.ends_with([' ', '\t']) {
+                    title = closed.trim_end();
+                }
+            }
+            (level, title)
+        } else {
+            //a setext heading, its title is the lines above the underline
+            let underline = text.lines().last()?.trim();
+            let level = if underline.starts_with('=') { 1 } else { 2 };
+            let title = text[..text.len() - text.lines().last()?.len()].trim();
+            (level, title)
+        };
+        let start = range.start + (title.as_ptr() as usize - text.as_ptr() as usize);
+        Some(Heading {
+            level,
+            title: start..start + title.len(),
+            section: range.start..range.end,
+            range,
+            line,
+            children: Vec::new(),
+        })
+    }
+}
+
+///Closes the last `open` heading, adding it to the one it is in or the `roots`.
+fn close(open: &mut Vec<Heading>, roots: &mut Vec<Heading>) {
+    if let Some(heading) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(heading),
+            None => roots.push(heading),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "lang-markdown"))]
+mod tests {
+    use crate::{TSSettings, document::Document, testing};
+
+    const TEXT: &str = "Intro\n\n# One\n\ntext\n\n## Two\n\nmore\n\n# Three\nend\n";
+
+    #[test]
+    fn lists_the_headings_as_a_tree() {
+        let document = Document::new(&TSSettings::markdown().unwrap(), TEXT);
+        let outline = document.outline();
+        let titles = |headings: &[super::Heading]| {
+            headings
+                .iter()
+                .map(|heading| (heading.level, &TEXT[heading.title.clone()], heading.line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&outline), [(1, "One", 2), (1, "Three", 10)]);
+        assert_eq!(titles(&outline[0].children), [(2, "Two", 6)]);
+        assert_eq!(&TEXT[outline[0].range.clone()], "# One");
+        //a section runs up to the next heading of the same or a higher level
+        assert_eq!(outline[0].section, 7..TEXT.find("# Three").unwrap());
+        assert_eq!(outline[0].children[0].section.end, outline[0].section.end);
+        assert_eq!(outline[1].section.end, TEXT.len());
+
+        assert_eq!(document.section_at((0, 0)), None);
+        assert_eq!(
+            document.section_at((8, 1)).map(|heading| heading.line),
+            Some(6)
+        );
+        assert_eq!(
+            document.section_at((4, 0)).map(|heading| heading.line),
+            Some(2)
+        );
+        let sections = document
+            .fold_ranges()
+            .into_iter()
+            .filter(|range| range.kind == "section")
+            .map(|range| range.lines)
+            .collect::<Vec<_>>();
+        //the text before the first heading is a `section` of the grammar
+        assert_eq!(sections, [0..2, 2..10, 6..10, 10..12]);
+
+        let rust = Document::new(&testing::rust(), "# not a heading");
+        assert!(rust.outline().is_empty());
+    }
+}
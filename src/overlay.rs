@@ -0,0 +1,235 @@
+//! Overlays are ranges that are highlighted on top of the syntax highlighting, like search matches or diagnostics.
+//!
+//! Build an [Overlay] from a [Document] and hand it to the highlighter via [TSSettings::with_overlay](crate::TSSettings::with_overlay).
+//! The ranges are kept as [anchors](crate::anchor), so after an edit [Overlay::apply] moves them along instead of building the
+//! overlay again.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    anchor::Anchor,
+    document::{Change, Document},
+};
+
+///What an overlay range marks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlayKind {
+    ///A search match.
+    SearchMatch,
+    ///The search match the host navigated to.
+    CurrentMatch,
+    ///A match that will be changed by a pending replacement.
+    ReplacePreview,
+    ///Range a [Diagnostic] refers to.
+    Diagnostic(Severity),
+}
+
+///Where highlighted text comes from. When ranges of different sources overlap, the source that is ranked higher by
+///[TSSettings::with_precedence](crate::TSSettings::with_precedence) is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    ///Captures of the highlight query.
+    Syntax,
+    ///[Layers](crate::layer) and [plugins](crate::plugin).
+    Layers,
+    ///[OverlayKind::SearchMatch] and [OverlayKind::ReplacePreview].
+    Search,
+    ///[OverlayKind::Diagnostic].
+    Diagnostics,
+    ///[OverlayKind::CurrentMatch], next to the cursor.
+    CurrentMatch,
+}
+
+///Lowest first. Later sources win.
+pub const DEFAULT_PRECEDENCE: &[Source] = &[
+    Source::Syntax,
+    Source::Layers,
+    Source::Search,
+    Source::Diagnostics,
+    Source::CurrentMatch,
+];
+
+impl OverlayKind {
+    pub fn source(&self) -> Source {
+        match self {
+            Self::SearchMatch | Self::ReplacePreview => Source::Search,
+            Self::CurrentMatch => Source::CurrentMatch,
+            Self::Diagnostic(_) => Source::Diagnostics,
+        }
+    }
+}
+
+///Severity of a [Diagnostic], most severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+///A message reported by the host, e.g. from a compiler or language server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+///Where an added range is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Marked {
+    ///Document-wide, moving along with the edits.
+    Text(Range<Anchor>),
+    ///Columns of a line, moving along with the lines around it.
+    Line(usize, Range<usize>),
+}
+
+///Overlay ranges, stored per line. Ranges spanning several lines are split when they are added.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Overlay {
+    ///Everything added, in order, with the diagnostic of the range.
+    added: Vec<(Marked, OverlayKind, Option<Diagnostic>)>,
+    lines: BTreeMap<usize, Vec<(Range<usize>, OverlayKind)>>,
+    ///Diagnostics by the line their range starts on.
+    diagnostics: BTreeMap<usize, Vec<Diagnostic>>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Adds the document-wide byte `range`. Text typed at its edges isn't part of it, see [Anchor::range].
+    pub fn add(&mut self, document: &Document, range: Range<usize>, kind: OverlayKind) {
+        self.added
+            .push((Marked::Text(Anchor::range(range.clone())), kind, None));
+        self.split(document, range, kind);
+    }
+
+    fn split(&mut self, document: &Document, range: Range<usize>, kind: OverlayKind) {
+        let start = document.position(range.start);
+        let end = document.position(range.end);
+
+        for line in start.line..=end.line {
+            let Some(line_range) = document.line_range(line) else {
+                break;
+            };
+            let from = if line == start.line { start.column } else { 0 };
+            let to = if line == end.line {
+                end.column
+            } else {
+                line_range.len()
+            };
+            if from < to {
+                self.lines.entry(line).or_default().push((from..to, kind));
+            }
+        }
+    }
+
+    ///Adds `columns` (byte range within the line) on `line`. After an edit, the range stays on the line, unless the line was
+    ///edited itself, which drops it.
+    pub fn add_to_line(&mut self, line: usize, columns: Range<usize>, kind: OverlayKind) {
+        self.added
+            .push((Marked::Line(line, columns.clone()), kind, None));
+        self.lines.entry(line).or_default().push((columns, kind));
+    }
+
+    ///All ranges on `line`, in the order they were added.
+    pub fn line(&self, line: usize) -> &[(Range<usize>, OverlayKind)] {
+        self.lines.get(&line).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    ///Adds a diagnostic for the document-wide byte `range`. The range is highlighted as [OverlayKind::Diagnostic], the message is
+    ///attached to the line the range starts on.
+    pub fn add_diagnostic(
+        &mut self,
+        document: &Document,
+        range: Range<usize>,
+        severity: Severity,
+        message: impl Into<String>,
+    ) {
+        let diagnostic = Diagnostic {
+            severity,
+            message: message.into(),
+        };
+        let kind = OverlayKind::Diagnostic(severity);
+        let line = document.position(range.start).line;
+        self.added.push((
+            Marked::Text(Anchor::range(range.clone())),
+            kind,
+            Some(diagnostic.clone()),
+        ));
+        self.split(document, range, kind);
+        self.diagnostics.entry(line).or_default().push(diagnostic);
+    }
+
+    ///Moves the ranges and diagnostics along `change` of `document`, which is the document after the change. Changes come from
+    ///[Document::subscribe]. Ranges whose text was deleted are dropped, the diagnostics of them stay on the line.
+    pub fn apply(&mut self, document: &Document, change: &Change) {
+        self.lines.clear();
+        self.diagnostics.clear();
+        let added = std::mem::take(&mut self.added);
+        for (mut marked, kind, diagnostic) in added {
+            match &mut marked {
+                Marked::Text(range) => {
+                    range.start.apply(change);
+                    range.end.apply(change);
+                    let range = range.start.offset..range.end.offset.max(range.start.offset);
+                    self.split(document, range.clone(), kind);
+                    if let Some(diagnostic) = &diagnostic {
+                        let line = document.position(range.start).line;
+                        self.diagnostics
+                            .entry(line)
+                            .or_default()
+                            .push(diagnostic.clone());
+                    }
+                }
+                Marked::Line(line, columns) => {
+                    if change.old_lines.contains(line) {
+                        continue;
+                    }
+                    if *line >= change.old_lines.end {
+                        *line = *line - change.old_lines.len() + change.lines.len();
+                    }
+                    self.lines
+                        .entry(*line)
+                        .or_default()
+                        .push((columns.clone(), kind));
+                }
+            }
+            self.added.push((marked, kind, diagnostic));
+        }
+    }
+
+    ///Lines that have ranges, in order, e.g. for marking them on a scrollbar.
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &[(Range<usize>, OverlayKind)])> {
+        self.lines
+            .iter()
+            .map(|(line, ranges)| (*line, ranges.as_slice()))
+    }
+
+    ///Diagnostics attached to `line`, in the order they were added.
+    pub fn diagnostics(&self, line: usize) -> &[Diagnostic] {
+        self.diagnostics
+            .get(&line)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    ///The most severe diagnostic of `line`. The first one added wins between equally severe ones.
+    pub fn worst_diagnostic(&self, line: usize) -> Option<&Diagnostic> {
+        self.diagnostics(line)
+            .iter()
+            .min_by_key(|diagnostic| diagnostic.severity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.diagnostics.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.added.clear();
+        self.lines.clear();
+        self.diagnostics.clear();
+    }
+}
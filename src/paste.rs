@@ -0,0 +1,52 @@
+//! Hints for pasted blocks that look like a different language than the document.
+
+use std::ops::Range;
+
+use crate::{detect, document::Document, prose::is_prose_language};
+
+///Pastes with less lines than this are never looked at. Small snippets are too ambiguous to guess from.
+pub const MIN_PASTE_LINES: usize = 3;
+
+///Suggestion to treat a pasted block as another language. For instance by wrapping it in a code fence, or by injecting `language` there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasteHint {
+    ///Byte range of the pasted text in the document.
+    pub range: Range<usize>,
+    ///Name of the language the pasted text looks like (e.g. `"json"`).
+    pub language: &'static str,
+}
+
+impl Document {
+    ///Call after pasting `pasted` at byte `offset` (the document must already contain it). Returns a hint if the pasted text
+    ///looks like a different language than the document's.
+    pub fn paste_hint(&self, offset: usize, pasted: &str) -> Option<PasteHint> {
+        self.settings().report_feature("paste");
+        if pasted.lines().count() < MIN_PASTE_LINES {
+            return None;
+        }
+
+        let range = offset..offset + pasted.len();
+        if self.text().get(range.clone()) != Some(pasted) {
+            return None;
+        }
+
+        let language = detect::language_from_shebang(pasted.lines().next()?)
+            .or_else(|| detect::by_content(pasted))?;
+        if language == self.settings().tsconfig.language_name {
+            return None;
+        }
+
+        //if the document's grammar is happy with the paste, it most likely _is_ the same language.
+        //Prose grammars (markdown) accept anything though, so those are always hinted.
+        let parses_cleanly = self.tree().is_some_and(|tree| {
+            tree.root_node()
+                .descendant_for_byte_range(range.start, range.end)
+                .is_some_and(|node| !node.has_error())
+        });
+        if parses_cleanly && !is_prose_language(&self.settings().tsconfig.language_name) {
+            return None;
+        }
+
+        Some(PasteHint { range, language })
+    }
+}
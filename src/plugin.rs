@@ -0,0 +1,61 @@
+//! Highlighting contributed by other crates, like a spell checker that marks misspelled words.
+//!
+//! A [HighlightPlugin] is a [Layer] that is set up once against the [TSSettings] it is added to (see [Registry]), and is told about
+//! every change of the [Document]s using those settings, so it can keep its own state (e.g. the misspelled words per line) up to
+//! date. Add plugins via [TSSettings::with_plugin].
+//!
+//! All methods besides [Layer::highlight] have default implementations. Methods added later will have them as well, so plugins
+//! keep compiling against newer versions of this crate.
+
+use crate::{
+    TSSettings,
+    document::{Change, Document},
+    layer::Layer,
+    theme::Theme,
+};
+
+pub trait HighlightPlugin: Layer {
+    ///Called once when the plugin is added to settings, e.g. to register the capture names it highlights as.
+    fn init(&mut self, registry: &mut Registry<'_>) {
+        let _ = registry;
+    }
+
+    ///Called after the text of a `document` using the plugin's settings changed. `document` already has the new text and tree.
+    ///
+    ///Also called when the document is created, with all lines as the change.
+    fn edited(&self, document: &Document, change: &Change) {
+        let _ = (document, change);
+    }
+}
+
+///What a [HighlightPlugin] can see of, and add to, the settings it is added to.
+pub struct Registry<'a> {
+    pub(crate) settings: &'a TSSettings,
+    pub(crate) highlight_names: Vec<String>,
+}
+
+impl Registry<'_> {
+    ///`language_name` of the configured language, like `rust`.
+    pub fn language_name(&self) -> &str {
+        &self.settings.tsconfig.language_name
+    }
+
+    ///Capture names of the settings, including the ones registered so far.
+    pub fn highlight_names(&self) -> &[String] {
+        &self.highlight_names
+    }
+
+    ///Adds `name` to the highlight names, so spans can be highlighted as it, and themes can style it (e.g. `spell.error`).
+    ///Names that exist already are kept.
+    pub fn register_capture(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        if !self.highlight_names.contains(&name) {
+            self.highlight_names.push(name);
+        }
+    }
+
+    ///Theme of the settings, if one is set already.
+    pub fn theme(&self) -> Option<&Theme> {
+        self.settings.theme.as_deref()
+    }
+}
@@ -0,0 +1,529 @@
+//! Highlighting a whole document a slice at a time, for hosts that highlight on the GUI thread and can't afford to stall a frame on
+//! a big file.
+//!
+//! [ProgressiveHighlights::step] highlights for at most the given time and reports how far it got. Call it once per frame (or
+//! whenever there is time to spare) until it is [done](Progress::is_done). The cursor's line and the lines on screen go first, see
+//! [ProgressiveHighlights::focus]. Editors attached to a
+//! [SharedHighlightState](crate::shared::SharedHighlightState) get the same via
+//! [set_text_progressive](crate::shared::SharedHighlightState::set_text_progressive) and
+//! [highlight_for](crate::shared::SharedHighlightState::highlight_for).
+//!
+//! Like [Document::highlights] does where it can, the highlight query runs on the document's syntax tree, one slice of the text
+//! after another. The spans are the same, except that local variables aren't resolved: a reference to a parameter is
+//! highlighted like any other variable. Neither is [embedded code](crate::TSSettings::with_injections), it stays plain.
+
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use tree_sitter::StreamingIterator;
+
+use crate::{Highlight, captures::CaptureMap, document::Document, stats::Usage, theme::Style};
+
+///Bytes highlighted between two looks at the clock.
+const SLICE: usize = 4096;
+
+///How much of a document is highlighted, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn is_done(&self) -> bool {
+        self.done >= self.total
+    }
+
+    ///Highlighted part of the document, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f32 / self.total as f32
+        }
+    }
+}
+
+///How urgent a part of the text is, see [ProgressiveHighlights::focus]. The most urgent comes first.
+///
+///Injected languages aren't highlighted (like in [Document::highlights]), so there is no work for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    ///The line of the cursor.
+    Cursor,
+    ///The lines on screen.
+    Visible,
+    ///The rest of the file.
+    Rest,
+}
+
+///A part of the text that isn't highlighted yet, see [ProgressiveHighlights::pending].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Work {
+    pub range: Range<usize>,
+    pub priority: Priority,
+}
+
+///See the [module](self) documentation.
+pub struct ProgressiveHighlights {
+    ///Hash and length of the text the spans belong to.
+    text: (u64, usize),
+    ///Parts of the text that aren't highlighted yet, sorted.
+    todo: Vec<Range<usize>>,
+    ///Byte ranges of [Priority::Cursor] and [Priority::Visible].
+    cursor_line: Range<usize>,
+    visible: Range<usize>,
+    spans: Vec<(Range<usize>, Highlight)>,
+    ///Highlight name of each capture of the query. `None` for the captures of the locals and injection queries.
+    captures: Vec<Option<Option<usize>>>,
+    styles: Vec<Option<Style>>,
+    cursor: tree_sitter::QueryCursor,
+    ///Time spent in [ProgressiveHighlights::step] so far.
+    spent: Duration,
+}
+
+impl ProgressiveHighlights {
+    ///Nothing of `document` is highlighted yet.
+    pub fn new(document: &Document) -> Self {
+        let settings = document.settings();
+        let captures = settings
+            .tsconfig
+            .query
+            .capture_names()
+            .iter()
+            .map(|name| {
+                (!name.starts_with("local.") && !name.starts_with("injection."))
+                    .then(|| settings.capture_index(name))
+            })
+            .collect();
+        let len = document.text().len();
+        Self {
+            text: (document.hash(), len),
+            todo: std::iter::once(0..len)
+                .filter(|range| !range.is_empty())
+                .collect(),
+            cursor_line: 0..0,
+            visible: 0..0,
+            spans: Vec::new(),
+            captures,
+            styles: settings.resolve_styles(),
+            cursor: tree_sitter::QueryCursor::new(),
+            spent: Duration::ZERO,
+        }
+    }
+
+    ///Highlights `cursor_line`, then the `visible` lines, before the rest of the text. Without a focus, the text is highlighted from
+    ///the start. Set it again when the cursor moves or the editor scrolls, and after the text changed.
+    pub fn focus(&mut self, document: &Document, cursor_line: usize, visible: Range<usize>) {
+        let lines = |lines: Range<usize>| {
+            if lines.is_empty() {
+                return None;
+            }
+            let start = document.line_range(lines.start)?.start;
+            //the last lines on screen may be past the end of the text
+            let last = (lines.end - 1).min(document.line_count().saturating_sub(1));
+            let end = document.line_range(last)?.end;
+            Some(start..end)
+        };
+        self.cursor_line = lines(cursor_line..cursor_line + 1).unwrap_or(0..0);
+        self.visible = lines(visible).unwrap_or(0..0);
+    }
+
+    ///Highlights the most urgent [pending](ProgressiveHighlights::pending) parts of `document` for at most `budget`, but at least a
+    ///few KiB, so every call makes progress. Starts over if the text changed since the last call. Does nothing while the
+    ///document's [parse halted](Document::is_parse_halted); [resume](Document::resume_parse) it first.
+    pub fn step(&mut self, document: &Document, budget: Duration) -> Progress {
+        self.advance(document, budget, |_, _| ())
+    }
+
+    ///[ProgressiveHighlights::step] that hands every highlighted slice and its spans to `highlighted`.
+    pub(crate) fn advance(
+        &mut self,
+        document: &Document,
+        budget: Duration,
+        mut highlighted: impl FnMut(Range<usize>, &[(Range<usize>, Highlight)]),
+    ) -> Progress {
+        let started = Instant::now();
+        let text = document.text();
+        if self.text != (document.hash(), text.len()) {
+            *self = Self::new(document);
+        }
+        let was_pending = !self.todo.is_empty();
+        let Some(tree) = document.tree() else {
+            //still to do once the parse is resumed
+            if !document.is_parse_halted() {
+                self.todo.clear();
+            }
+            return self.progress();
+        };
+
+        let mut slowest = Duration::ZERO;
+        while let Some(work) = self.pending().into_iter().next() {
+            let slice_started = Instant::now();
+            let mut end = (work.range.start + SLICE).min(work.range.end);
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+            let slice = work.range.start..end;
+            let spans = self.highlight_slice(document, tree, slice.clone());
+            let spans = document.mark_errors(spans, slice.clone());
+            highlighted(slice.clone(), &spans);
+            let at = self
+                .spans
+                .partition_point(|(range, _)| range.start < slice.start);
+            self.spans.splice(at..at, spans);
+            self.finish(slice);
+
+            slowest = slowest.max(slice_started.elapsed());
+            //stop before the next slice would overrun the budget
+            if started.elapsed() + slowest > budget {
+                break;
+            }
+        }
+
+        self.spent += started.elapsed();
+        if was_pending && self.todo.is_empty() {
+            document
+                .settings()
+                .report(|language| Usage::HighlightDocument {
+                    language,
+                    bytes: text.len(),
+                    duration: self.spent,
+                });
+        }
+        self.progress()
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            done: self.text.1 - self.todo.iter().map(ExactSizeIterator::len).sum::<usize>(),
+            total: self.text.1,
+        }
+    }
+
+    ///The parts of the text that aren't highlighted yet, in the order they will be highlighted, e.g. to see why a line still isn't
+    ///highlighted.
+    pub fn pending(&self) -> Vec<Work> {
+        let mut work = Vec::new();
+        let mut rest = self.todo.clone();
+        for (priority, focus) in [
+            (Priority::Cursor, &self.cursor_line),
+            (Priority::Visible, &self.visible),
+        ] {
+            let mut outside = Vec::new();
+            for range in rest {
+                let start = range.start.clamp(focus.start, focus.end);
+                let end = range.end.clamp(focus.start, focus.end);
+                if start < end {
+                    work.push(Work {
+                        range: start..end,
+                        priority,
+                    });
+                }
+                outside.extend(
+                    [
+                        range.start..start.min(range.end),
+                        end.max(range.start)..range.end,
+                    ]
+                    .into_iter()
+                    .filter(|part| !part.is_empty()),
+                );
+            }
+            rest = outside;
+        }
+        work.extend(rest.into_iter().map(|range| Work {
+            range,
+            priority: Priority::Rest,
+        }));
+        work
+    }
+
+    ///Whether all of `range` is highlighted.
+    pub fn is_highlighted(&self, range: Range<usize>) -> bool {
+        !self
+            .todo
+            .iter()
+            .any(|todo| todo.start < range.end.max(range.start + 1) && range.start < todo.end)
+    }
+
+    ///Spans of the highlighted parts, sorted and non-overlapping like the ones of [Document::highlights].
+    pub fn spans(&self) -> &[(Range<usize>, Highlight)] {
+        &self.spans
+    }
+
+    ///Marks `slice`, a part of one of the [pending](ProgressiveHighlights::pending) ranges, as highlighted.
+    fn finish(&mut self, slice: Range<usize>) {
+        let Some(idx) = self
+            .todo
+            .iter()
+            .position(|todo| todo.start <= slice.start && slice.end <= todo.end)
+        else {
+            return;
+        };
+        let todo = self.todo.remove(idx);
+        let parts = [todo.start..slice.start, slice.end..todo.end];
+        self.todo
+            .splice(idx..idx, parts.into_iter().filter(|part| !part.is_empty()));
+    }
+
+    ///Highlights `range` of `document` right away, with its syntax errors, e.g. the lines an edit changed. Nothing of the state
+    ///of the progressive highlighting changes.
+    pub(crate) fn highlight_range(
+        &mut self,
+        document: &Document,
+        tree: &tree_sitter::Tree,
+        range: Range<usize>,
+    ) -> Vec<(Range<usize>, Highlight)> {
+        let spans = self.highlight_slice(document, tree, range.clone());
+        document.mark_errors(spans, range)
+    }
+
+    ///Whether there is [embedded code](crate::TSSettings::with_injections) in `range` of `document`, which only
+    ///tree-sitter-highlight highlights.
+    pub(crate) fn embeds(
+        &mut self,
+        document: &Document,
+        tree: &tree_sitter::Tree,
+        range: Range<usize>,
+    ) -> bool {
+        let settings = document.settings();
+        let query = &settings.tsconfig.query;
+        let Some(content) = settings
+            .injections
+            .as_ref()
+            .and(query.capture_index_for_name("injection.content"))
+        else {
+            return false;
+        };
+        self.cursor.set_byte_range(range);
+        let mut captures =
+            self.cursor
+                .captures(query, tree.root_node(), document.text().as_bytes());
+        while let Some((query_match, idx)) = captures.next() {
+            if query_match.captures[*idx].index == content {
+                return true;
+            }
+        }
+        false
+    }
+
+    ///Like tree-sitter-highlight does it: the highlights of nested nodes nest, and the last pattern capturing a node decides its
+    ///highlight. Nodes reaching outside of `slice` are cut to it.
+    fn highlight_slice(
+        &mut self,
+        document: &Document,
+        tree: &tree_sitter::Tree,
+        slice: Range<usize>,
+    ) -> Vec<(Range<usize>, Highlight)> {
+        let query = &document.settings().tsconfig.query;
+        self.cursor.set_byte_range(slice.clone());
+        let mut captures =
+            self.cursor
+                .captures(query, tree.root_node(), document.text().as_bytes());
+
+        let mut highlighter = SliceHighlighter {
+            offset: slice.start,
+            slice,
+            styles: &self.styles,
+            names: document.settings().capture_map(),
+            spans: Vec::new(),
+            ends: Vec::new(),
+        };
+        let mut current: Option<(tree_sitter::Node<'_>, Option<usize>)> = None;
+        while let Some((query_match, idx)) = captures.next() {
+            let capture = query_match.captures[*idx];
+            let Some(highlight) = self.captures[capture.index as usize] else {
+                continue;
+            };
+            match &mut current {
+                Some((node, current)) if *node == capture.node => *current = highlight,
+                _ => {
+                    if let Some((node, highlight)) = current.take() {
+                        highlighter.start(node.byte_range(), highlight);
+                    }
+                    current = Some((capture.node, highlight));
+                }
+            }
+        }
+        if let Some((node, highlight)) = current {
+            highlighter.start(node.byte_range(), highlight);
+        }
+        highlighter.finish();
+        highlighter.spans
+    }
+}
+
+struct SliceHighlighter<'a> {
+    slice: Range<usize>,
+    styles: &'a [Option<Style>],
+    names: CaptureMap,
+    spans: Vec<(Range<usize>, Highlight)>,
+    ///End of the text spans were emitted for.
+    offset: usize,
+    ///Ends of the open highlights, innermost last.
+    ends: Vec<(usize, usize)>,
+}
+
+impl SliceHighlighter<'_> {
+    fn start(&mut self, range: Range<usize>, highlight: Option<usize>) {
+        self.close(range.start);
+        self.emit(range.start);
+        if let Some(highlight) = highlight {
+            self.ends.push((range.end, highlight));
+        }
+    }
+
+    fn finish(&mut self) {
+        self.close(usize::MAX);
+    }
+
+    ///Closes the highlights that end before `offset`.
+    fn close(&mut self, offset: usize) {
+        while let Some(&(end, _)) = self.ends.last()
+            && end <= offset
+        {
+            self.emit(end);
+            self.ends.pop();
+        }
+    }
+
+    ///Highlights the text up to `end` as the innermost open highlight.
+    fn emit(&mut self, end: usize) {
+        let end = end.min(self.slice.end);
+        if end <= self.offset {
+            return;
+        }
+        if let Some(&(_, capture)) = self.ends.last() {
+            self.spans.push((
+                self.offset..end,
+                Highlight {
+                    capture: Some(tree_sitter_highlight::Highlight(capture)),
+                    style: self.styles.get(capture).copied().flatten(),
+                    dimmed: false,
+                    overlay: None,
+                    names: self.names.clone(),
+                },
+            ));
+        }
+        self.offset = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Priority, ProgressiveHighlights, Work};
+    use crate::{document::Document, testing};
+
+    fn text() -> String {
+        "fn f() {\n    let x = \"text\"; // comment\n}\n".repeat(400)
+    }
+
+    #[test]
+    fn steps_highlight_like_one_go() {
+        let document = Document::new(&testing::rust(), text());
+        let mut whole = ProgressiveHighlights::new(&document);
+        assert!(whole.step(&document, Duration::MAX).is_done());
+
+        let mut sliced = ProgressiveHighlights::new(&document);
+        assert_eq!(sliced.progress().fraction(), 0.0);
+        let mut steps = 0;
+        while !sliced.step(&document, Duration::ZERO).is_done() {
+            steps += 1;
+            let progress = sliced.progress();
+            assert!(0.0 < progress.fraction() && progress.fraction() < 1.0);
+        }
+        //each step makes progress, of a slice at least
+        assert!(1 < steps && steps <= document.text().len() / super::SLICE);
+        assert!(sliced.is_highlighted(0..document.text().len()));
+        let captures = |spans: &[(std::ops::Range<usize>, crate::Highlight)]| {
+            spans
+                .iter()
+                .map(|(range, highlight)| {
+                    (range.clone(), highlight.capture.map(|capture| capture.0))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(captures(sliced.spans()), captures(whole.spans()));
+        assert!(
+            sliced
+                .spans()
+                .windows(2)
+                .all(|spans| spans[0].0.end <= spans[1].0.start)
+        );
+        let string = whole
+            .spans()
+            .iter()
+            .find(|(range, _)| &document.text()[range.clone()] == "\"text\"");
+        let names = document.settings().highlight_names();
+        assert_eq!(
+            string.and_then(|(_, highlight)| Some(names[highlight.capture?.0].as_str())),
+            Some("string")
+        );
+    }
+
+    #[test]
+    fn starts_over_when_the_text_changed() {
+        let mut document = Document::new(&testing::rust(), text());
+        let mut highlights = ProgressiveHighlights::new(&document);
+        highlights.step(&document, Duration::MAX);
+        document.edit(0..0, "// new\n");
+        let progress = highlights.step(&document, Duration::ZERO);
+        assert!(!progress.is_done());
+        assert_eq!(progress.total, document.text().len());
+        //an empty text has nothing to do
+        let empty = Document::new(&testing::rust(), "");
+        assert!(ProgressiveHighlights::new(&empty).progress().is_done());
+    }
+
+    #[test]
+    fn the_cursor_and_the_screen_go_first() {
+        let document = Document::new(&testing::rust(), text());
+        let line = |line| document.line_range(line).unwrap();
+        let mut highlights = ProgressiveHighlights::new(&document);
+        highlights.focus(&document, 600, 300..330);
+        let cursor = line(600);
+        let visible = line(300).start..line(329).end;
+        let len = document.text().len();
+        assert_eq!(
+            highlights.pending(),
+            [
+                Work {
+                    range: cursor.clone(),
+                    priority: Priority::Cursor,
+                },
+                Work {
+                    range: visible.clone(),
+                    priority: Priority::Visible,
+                },
+                Work {
+                    range: 0..visible.start,
+                    priority: Priority::Rest,
+                },
+                Work {
+                    range: visible.end..cursor.start,
+                    priority: Priority::Rest,
+                },
+                Work {
+                    range: cursor.end..len,
+                    priority: Priority::Rest,
+                },
+            ]
+        );
+
+        highlights.step(&document, Duration::ZERO);
+        assert!(highlights.is_highlighted(cursor.clone()));
+        assert!(!highlights.is_highlighted(visible.clone()));
+        highlights.step(&document, Duration::ZERO);
+        assert!(highlights.is_highlighted(visible));
+        assert!(!highlights.is_highlighted(0..1));
+
+        //lines past the end are left out
+        highlights.focus(&document, 5000, 1190..1300);
+        assert_eq!(highlights.pending()[0].priority, Priority::Visible);
+        assert_eq!(highlights.pending()[0].range.end, len);
+    }
+}
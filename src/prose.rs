@@ -0,0 +1,293 @@
+//! Prose and code regions of a document, so hosts turn on spell checking, smart quotes and soft wrap only where the text is
+//! prose.
+//!
+//! In prose languages (Markdown, LaTeX, Typst, see [is_prose_language]) the text is prose, except for the [CODE_NODES] of the
+//! language, like code blocks and formulas, and the text highlighted as inline code or links ([CODE_CAPTURES]). In other
+//! languages only the comments are prose. [Document::prose_ranges] lists the prose of a document, [Document::region_at] tells
+//! the region of a position, and [Document::smart_punctuation] types curly quotes and dashes in prose, but not in code.
+//!
+//! [Document::word_count] and [Document::reading_time] count the words of the text a [CaptureFilter] selects, like the prose of
+//! a note without its code blocks and front matter, from the same tree and highlights.
+
+use std::{ops::Range, time::Duration};
+
+use crate::{
+    document::{Document, Position},
+    structural::StructuralEdit,
+};
+
+///Whether a position is in prose or in code, see the [module](self) documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    Prose,
+    Code,
+}
+
+///Node kinds that are code within the prose of a language, by the language name of its configuration. These are the kinds of
+///the Markdown, Org and AsciiDoc grammars the crate has presets for, and of the common grammars for LaTeX and Typst.
+pub const CODE_NODES: &[(&str, &[&str])] = &[
+    (
+        "markdown",
+        &[
+            "fenced_code_block",
+            "indented_code_block",
+            "html_block",
+            "link_destination",
+            "minus_metadata",
+            "plus_metadata",
+        ],
+    ),
+    (
+        "latex",
+        &[
+            "command_name",
+            "inline_formula",
+            "displayed_equation",
+            "math_environment",
+            "verbatim_environment",
+            "minted_environment",
+            "listing_environment",
+        ],
+    ),
+    ("typst", &["code", "math", "raw_span", "raw_blck"]),
+    ("org", &["block", "latex_env", "property_drawer"]),
+    (
+        "asciidoc",
+        &[
+            "listing_block",
+            "literal_block",
+            "passthrough_block",
+            "ident_block",
+            "block_macro",
+            "document_attr",
+        ],
+    ),
+];
+
+///Captures that are code within prose: inline code and links. Markdown highlights them through the injected inline grammar,
+///so they aren't nodes of the document's tree.
+pub const CODE_CAPTURES: &[&str] = &["text.literal", "text.uri"];
+
+///Words a minute of an average adult reading silently, for [Document::reading_time].
+pub const READING_SPEED: u32 = 238;
+
+///The text [Document::word_count] counts the words of. Capture names match like the highlight names do: each part of the name
+///has to be part of the capture, so `comment` selects `comment.documentation` too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CaptureFilter<'a> {
+    ///All of the text.
+    All,
+    ///The prose, see [Document::prose_ranges]. Leaves out the code blocks, inline code and front matter of Markdown.
+    #[default]
+    Prose,
+    ///The text highlighted as one of the captures, like the `comment`s of a program.
+    Only(&'a [&'a str]),
+    ///The text that isn't highlighted as one of the captures.
+    Except(&'a [&'a str]),
+}
+
+impl CaptureFilter<'_> {
+    fn matches(names: &[&str], capture: &str) -> bool {
+        names.iter().any(|name| {
+            name.split('.')
+                .all(|part| capture.split('.').any(|c| c == part))
+        })
+    }
+}
+
+///Whether `language_name` is the name of a prose language, whose text is prose unless it's code.
+pub fn is_prose_language(language_name: &str) -> bool {
+    matches!(
+        language_name,
+        "markdown" | "text" | "org" | "asciidoc" | "latex" | "typst"
+    )
+}
+
+impl Document {
+    ///Byte ranges of the prose of the document, in order, see the [module](self) documentation. Empty without a tree.
+    pub fn prose_ranges(&self) -> Vec<Range<usize>> {
+        self.settings().report_feature("prose");
+        let Some(tree) = self.tree() else {
+            return Vec::new();
+        };
+        let language = &self.settings().tsconfig.language_name;
+        let prose = is_prose_language(language);
+        let kinds = CODE_NODES
+            .iter()
+            .find(|(name, _)| name == language)
+            .map_or(&[][..], |(_, kinds)| *kinds);
+
+        //code nodes of prose languages, or the comments of the others
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut cursor = tree.walk();
+        let mut descend = true;
+        loop {
+            let node = cursor.node();
+            let found = if prose {
+                kinds.contains(&node.kind())
+            } else {
+                node.kind().contains("comment")
+            };
+            if found {
+                ranges.push(node.byte_range());
+            } else if descend && cursor.goto_first_child() {
+                continue;
+            }
+            //pre-order traversal, so the ranges are sorted by their start
+            descend = cursor.goto_next_sibling();
+            if !descend && !cursor.goto_parent() {
+                break;
+            }
+        }
+        if !prose {
+            return ranges;
+        }
+
+        ranges.extend(
+            self.spans()
+                .into_iter()
+                .filter(|span| CODE_CAPTURES.contains(&&*span.capture))
+                .map(|span| span.range),
+        );
+        ranges.sort_unstable_by_key(|range| range.start);
+        //the prose is what's between the code
+        let mut prose = Vec::new();
+        let mut start = 0;
+        for range in ranges {
+            if range.start > start {
+                prose.push(start..range.start);
+            }
+            start = start.max(range.end);
+        }
+        if start < self.text().len() {
+            prose.push(start..self.text().len());
+        }
+        prose
+    }
+
+    ///Whether the character at `position` is in prose or in code, see the [module](self) documentation. The end of a prose
+    ///range counts as prose, so text typed at the end of a comment is.
+    pub fn region_at(&self, position: impl Into<Position>) -> Region {
+        self.region_at_offset(self.offset(position))
+    }
+
+    fn region_at_offset(&self, offset: usize) -> Region {
+        let prose = self
+            .prose_ranges()
+            .into_iter()
+            .any(|range| range.start <= offset && offset <= range.end);
+        if prose { Region::Prose } else { Region::Code }
+    }
+
+    ///The edit that types `typed` at `position` with typographic punctuation, if it is in prose: curly quotes for `"` and `'`,
+    ///opening after whitespace or an opening bracket and closing (or an apostrophe) otherwise, an en dash for a second `-`, an
+    ///em dash for a third one (but not at the start of a line), and an ellipsis for a third `.`. `None` in code, and for other characters, so the host inserts
+    ///`typed` as it is.
+    pub fn smart_punctuation(
+        &self,
+        position: impl Into<Position>,
+        typed: char,
+    ) -> Option<StructuralEdit> {
+        let offset = self.offset(position);
+        let before = &self.text()[..offset];
+        let previous = before.chars().next_back();
+        let opening =
+            previous.is_none_or(|c| c.is_whitespace() || "([{<\u{2013}\u{2014}".contains(c));
+        //dashes at the start of a line are markup, like the rule `---` or a list item
+        let dashes = before.trim_end_matches(['-', '\u{2013}']);
+        let indent = dashes.trim_end_matches([' ', '\t']);
+        let line_start = indent.is_empty() || indent.ends_with('\n');
+        let (replaced, text) = match typed {
+            '"' if opening => (0, "\u{201C}"),
+            '"' => (0, "\u{201D}"),
+            '\'' if opening => (0, "\u{2018}"),
+            '\'' => (0, "\u{2019}"),
+            '-' if line_start => return None,
+            '-' if before.ends_with('\u{2013}') => ('\u{2013}'.len_utf8(), "\u{2014}"),
+            '-' if before.ends_with('-') => (1, "\u{2013}"),
+            '.' if before.ends_with("..") => (2, "\u{2026}"),
+            _ => return None,
+        };
+        if self.region_at_offset(offset) != Region::Prose {
+            return None;
+        }
+        Some(StructuralEdit {
+            range: offset - replaced..offset,
+            text: text.to_string(),
+            cursor: offset - replaced + text.len(),
+        })
+    }
+
+    ///Number of words in the text `filter` selects: runs of non-whitespace characters with a letter or digit, so `don't` and
+    ///`well-known` are one word each, and list markers or `#` aren't any.
+    pub fn word_count(&self, filter: CaptureFilter) -> usize {
+        self.settings().report_feature("word_count");
+        let text = self.text();
+        let ranges = match filter {
+            CaptureFilter::All => std::iter::once(0..text.len()).collect(),
+            CaptureFilter::Prose => self.prose_ranges(),
+            CaptureFilter::Only(names) => self
+                .spans()
+                .into_iter()
+                .filter(|span| CaptureFilter::matches(names, &span.capture))
+                .map(|span| span.range)
+                .collect(),
+            CaptureFilter::Except(names) => {
+                let mut ranges = Vec::new();
+                let mut start = 0;
+                for span in self.spans() {
+                    if CaptureFilter::matches(names, &span.capture) {
+                        ranges.push(start..span.range.start.max(start));
+                        start = start.max(span.range.end);
+                    }
+                }
+                ranges.push(start..text.len());
+                ranges
+            }
+        };
+        ranges
+            .into_iter()
+            .flat_map(|range| text[range].split_whitespace())
+            .filter(|word| word.chars().any(char::is_alphanumeric))
+            .count()
+    }
+
+    ///Time it takes to read the words `filter` selects (see [Document::word_count]) at `words_per_minute`, like
+    ///[READING_SPEED].
+    pub fn reading_time(&self, filter: CaptureFilter, words_per_minute: u32) -> Duration {
+        let words = self.word_count(filter) as f64;
+        Duration::from_secs_f64(words * 60.0 / f64::from(words_per_minute.max(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CaptureFilter;
+    use crate::{document::Document, testing};
+
+    const TEXT: &str =
+        "// Adds one, well-known.\nfn add(x: i32) -> i32 {\n    x + 1 // don't - overflow\n}\n";
+
+    #[test]
+    fn counts_the_words_the_filter_selects() {
+        let document = Document::new(&testing::rust(), TEXT);
+        //`-`, `->`, `{` and `}` aren't words
+        assert_eq!(document.word_count(CaptureFilter::All), 11);
+        assert_eq!(document.word_count(CaptureFilter::Prose), 5);
+        assert_eq!(document.word_count(CaptureFilter::Only(&["comment"])), 5);
+        assert_eq!(document.word_count(CaptureFilter::Except(&["comment"])), 6);
+        assert_eq!(document.word_count(CaptureFilter::Only(&["string"])), 0);
+        assert_eq!(
+            document.reading_time(CaptureFilter::All, 11),
+            Duration::from_secs(60)
+        );
+        //no division by zero
+        assert!(document.reading_time(CaptureFilter::All, 0) > Duration::ZERO);
+        assert_eq!(
+            Document::new(&testing::rust(), "").word_count(CaptureFilter::All),
+            0
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! Configurations shared by the documents of an IDE-style app, and what they cost.
+//!
+//! Compiling the queries of a language takes a few hundred KiB, so the documents of a language should share one configuration:
+//! clones of [TSSettings] do, settings built again (like another `TSSettings::rust()`) compile their own. A [LanguageRegistry]
+//! hands out the registered settings, and [LanguageRegistry::usage] reports how many of the tracked documents share each compiled
+//! configuration, and the memory attributable to it.
+//!
+//! Tree-sitter doesn't tell the size of its grammars, queries and trees, so the memory is estimated from what it does tell: parse
+//! states, query sources and node counts. The estimates are close enough to see which languages and documents take the memory,
+//! not to the byte.
+//!
+//! The registry also resolves the languages of embedded code, see [Injections].
+
+use std::sync::{Arc, OnceLock};
+
+use tree_sitter_highlight::HighlightConfiguration;
+
+use crate::{
+    TSSettings, detect, logging,
+    shared::{SharedHighlightState, WeakHighlightState},
+};
+
+///Measured bytes per visible node of a syntax tree, including the hidden nodes between them.
+const TREE_NODE: usize = 100;
+///Measured bytes of a compiled query per byte of its source. Varies a lot, by the predicates and the shape of the patterns.
+const QUERY_SOURCE: usize = 40;
+
+///Estimated memory in bytes, see the [module](self) documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Memory {
+    ///Parse tables of the grammar. They are static data of the program, shared by all configurations of the language.
+    pub grammar: usize,
+    ///The compiled highlight, injection and locals queries, and the indent and tags queries of the settings.
+    pub queries: usize,
+    ///Syntax trees of the documents, except the [hibernating](crate::document::Document::hibernate) ones. Trees of earlier
+    ///texts, kept for undo, share most of their nodes with the current one and aren't counted.
+    pub trees: usize,
+}
+
+impl Memory {
+    pub fn total(&self) -> usize {
+        self.grammar + self.queries + self.trees
+    }
+}
+
+///A compiled configuration and the documents sharing it, see [LanguageRegistry::usage].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConfigUsage {
+    ///Name the configuration was registered as, or the language name of the configuration for one that isn't registered.
+    pub name: String,
+    ///Whether the configuration is registered. If not, the documents compiled their own, which the registered one could replace.
+    pub registered: bool,
+    ///Tracked documents using the configuration.
+    pub documents: usize,
+    pub memory: Memory,
+}
+
+///The languages code embedded in a text is highlighted with, built by [LanguageRegistry::injections] and set via
+///[TSSettings::with_injections].
+///
+///The injection query of a language names the embedded language (like the info string of a Markdown code block, or `css` for the
+///styles of HTML). It is looked up by the name it was registered as, ignoring case, and then as a file extension (`rs`, `py`,
+///`js`, see [detect::EXTENSIONS]). Names no registered language has go to the [InjectionResolver], if the settings have one.
+///Embedded code of a language that neither knows stays plain.
+#[derive(Default)]
+pub struct Injections {
+    languages: Vec<(String, Arc<HighlightConfiguration>)>,
+    resolver: Option<InjectionResolver>,
+    ///Answers of the resolver, first one first.
+    resolved: OnceLock<Box<Resolved>>,
+}
+
+///Finds the configuration of an embedded language by the name the injection query gives it, for grammars a [LanguageRegistry]
+///doesn't have. Set via [TSSettings::with_injection_resolver].
+///
+///The highlights of embedded code refer to their captures by index, so configure the configurations with the highlight names of
+///the settings (`config.configure(settings.highlight_names())`, or build them as [TSSettings] and take their `tsconfig`). The
+///resolver is asked once per name, the answer is kept as long as the settings are.
+pub type InjectionResolver = Arc<dyn Fn(&str) -> Option<Arc<HighlightConfiguration>> + Send + Sync>;
+
+///An answer of the [InjectionResolver]. They are only ever appended, so the highlighter can borrow them without holding a lock.
+struct Resolved {
+    name: String,
+    config: Option<Arc<HighlightConfiguration>>,
+    next: OnceLock<Box<Resolved>>,
+}
+
+impl Injections {
+    ///The configuration of the language called `name`.
+    pub fn resolve(&self, name: &str) -> Option<&HighlightConfiguration> {
+        let find = |name: &str| {
+            self.languages
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                .map(|(_, config)| config.as_ref())
+        };
+        find(name)
+            .or_else(|| {
+                let (_, language) = detect::EXTENSIONS
+                    .iter()
+                    .find(|(extension, _)| extension.eq_ignore_ascii_case(name))?;
+                find(language)
+            })
+            //the attributes of an AsciiDoc listing block, `[source,rust]`
+            .or_else(|| {
+                let (_, attributes) = name.split_once(',')?;
+                let language = attributes.split(',').next()?.trim();
+                (!language.is_empty())
+                    .then(|| self.resolve(language))
+                    .flatten()
+            })
+            .or_else(|| self.resolved(name))
+    }
+
+    ///Asks the resolver for `name`, unless it was asked before.
+    fn resolved(&self, name: &str) -> Option<&HighlightConfiguration> {
+        let resolver = self.resolver.as_ref()?;
+        let mut slot = &self.resolved;
+        loop {
+            //another thread may append a different name first, then the answer goes after it
+            let answer = slot.get_or_init(|| {
+                Box::new(Resolved {
+                    name: name.to_string(),
+                    config: resolver(name),
+                    next: OnceLock::new(),
+                })
+            });
+            if answer.name == name {
+                return answer.config.as_deref();
+            }
+            slot = &answer.next;
+        }
+    }
+
+    ///These injections with `resolver` for the names no language has, instead of the one they had.
+    pub(crate) fn with_resolver(&self, resolver: InjectionResolver) -> Self {
+        Self {
+            languages: self.languages.clone(),
+            resolver: Some(resolver),
+            resolved: OnceLock::new(),
+        }
+    }
+
+    ///Names of the languages that can be embedded.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.languages.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+///See the [module](self) documentation.
+#[derive(Default)]
+pub struct LanguageRegistry {
+    languages: Vec<(String, TSSettings)>,
+    ///Documents opened or tracked, until they are dropped.
+    documents: Vec<WeakHighlightState>,
+}
+
+impl LanguageRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///A registry with all bundled languages whose feature is enabled, by their language names.
+    #[cfg(feature = "any-lang")]
+    pub fn bundled() -> Self {
+        crate::languages::all()
+            .into_iter()
+            .fold(Self::new(), |registry, settings| {
+                let name = settings.tsconfig.language_name.clone();
+                registry.with_language(name, settings)
+            })
+    }
+
+    pub fn with_language(mut self, name: impl Into<String>, settings: TSSettings) -> Self {
+        self.register(name, settings);
+        self
+    }
+
+    ///Registers `settings` as `name`, or replaces the settings that had the name. Documents opened before keep theirs.
+    pub fn register(&mut self, name: impl Into<String>, settings: TSSettings) {
+        let name = name.into();
+        match self.languages.iter_mut().find(|(known, _)| *known == name) {
+            Some((_, known)) => *known = settings,
+            None => self.languages.push((name, settings)),
+        }
+    }
+
+    ///Registers `language` as `name` with its queries, see [LanguageRegistry::register]. Pass the grammar's locals query
+    ///(`locals.scm`) rather than an empty one where it has one: without it, the uses of parameters aren't highlighted as
+    ///`variable.parameter`, and shadowed variables keep the highlight of the first definition. The bundled languages come
+    ///with theirs.
+    #[cfg(feature = "presets")]
+    pub fn register_grammar(
+        &mut self,
+        name: impl Into<String>,
+        language: tree_sitter::Language,
+        highlights: &str,
+        injections: &str,
+        locals: &str,
+    ) -> crate::Result<()> {
+        let name = name.into();
+        let settings = TSSettings::try_new(language, &name, highlights, injections, locals)?;
+        self.register(name, settings);
+        Ok(())
+    }
+
+    ///The settings registered as `name`, sharing their compiled configuration.
+    pub fn settings(&self, name: &str) -> Option<TSSettings> {
+        self.languages
+            .iter()
+            .find(|(known, _)| known == name)
+            .map(|(_, settings)| settings.clone())
+    }
+
+    ///Names of the registered languages, in the order they were registered.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.languages.iter().map(|(name, _)| name.as_str())
+    }
+
+    ///A document of `text` highlighted as the language registered as `name`, tracked by the registry. `None` if there is no such
+    ///language.
+    pub fn open(&mut self, name: &str, text: impl Into<String>) -> Option<SharedHighlightState> {
+        let state = SharedHighlightState::new(&self.settings(name)?, text);
+        self.track(&state);
+        Some(state)
+    }
+
+    ///The registered languages as [Injections] for documents with the `host` settings, and the languages and the resolver `host`
+    ///had before. The highlights of embedded code refer to their captures by index, so languages configured with other highlight
+    ///names than `host` are left out.
+    pub fn injections(&self, host: &TSSettings) -> Injections {
+        let names = host.highlight_names();
+        let languages = self
+            .languages
+            .iter()
+            .filter(|(name, settings)| {
+                //plugins only add names, the configuration knows the ones before them
+                let known = settings.highlight_names();
+                let same = names.starts_with(known) || known.starts_with(names);
+                if !same {
+                    logging::warning!(
+                        target: logging::QUERY,
+                        "{name} is configured with other highlight names than {}, and isn't highlighted when embedded",
+                        host.tsconfig.language_name
+                    );
+                }
+                same
+            })
+            .map(|(name, settings)| (name.clone(), settings.tsconfig.clone()))
+            .collect::<Vec<_>>();
+        //the languages the settings came with, like the inline grammar of Markdown, stay
+        let before = host.injections.as_deref();
+        let kept = before
+            .map(|injections| injections.languages.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(|(name, _)| !languages.iter().any(|(known, _)| known == name))
+            .cloned()
+            .collect::<Vec<_>>();
+        Injections {
+            languages: languages.into_iter().chain(kept).collect(),
+            resolver: before.and_then(|injections| injections.resolver.clone()),
+            resolved: OnceLock::new(),
+        }
+    }
+
+    ///Counts the document of `state` in the [usage](LanguageRegistry::usage) until it is dropped, whichever settings it has.
+    pub fn track(&mut self, state: &SharedHighlightState) {
+        self.documents
+            .retain(|document| document.upgrade().is_some());
+        if !self.documents.iter().any(|document| document.is_of(state)) {
+            self.documents.push(state.downgrade());
+        }
+    }
+
+    ///Each compiled configuration with the number of tracked documents using it and its memory: the registered ones in the order
+    ///they were registered, then the ones only documents use. Configurations are told apart by identity, so two settings built
+    ///for the same language are listed twice.
+    pub fn usage(&self) -> Vec<ConfigUsage> {
+        self.configs().into_iter().map(|(_, usage)| usage).collect()
+    }
+
+    ///Memory of all configurations and documents. Each grammar counts once, however many configurations use it.
+    pub fn memory(&self) -> Memory {
+        let mut grammars = Vec::new();
+        let mut total = Memory::default();
+        for (settings, usage) in self.configs() {
+            let language = &settings.tsconfig.language_name;
+            if !grammars.contains(language) {
+                total.grammar += usage.memory.grammar;
+                grammars.push(language.clone());
+            }
+            total.queries += usage.memory.queries;
+            total.trees += usage.memory.trees;
+        }
+        total
+    }
+
+    fn configs(&self) -> Vec<(TSSettings, ConfigUsage)> {
+        let usage = |name: &str, registered, settings: &TSSettings| ConfigUsage {
+            name: name.to_string(),
+            registered,
+            documents: 0,
+            memory: Memory {
+                grammar: grammar_bytes(&settings.tsconfig.language),
+                queries: query_bytes(settings),
+                trees: 0,
+            },
+        };
+        let mut configs: Vec<_> = self
+            .languages
+            .iter()
+            .map(|(name, settings)| (settings.clone(), usage(name, true, settings)))
+            .collect();
+        for state in self
+            .documents
+            .iter()
+            .filter_map(WeakHighlightState::upgrade)
+        {
+            let (settings, nodes) = state.with_document(|document| {
+                //hibernating documents aren't woken up to be counted
+                let nodes = document
+                    .parsed_tree()
+                    .map_or(0, |tree| tree.root_node().descendant_count());
+                (document.settings().clone(), nodes)
+            });
+            let idx = configs
+                .iter()
+                .position(|(known, _)| Arc::ptr_eq(&known.tsconfig, &settings.tsconfig))
+                .unwrap_or_else(|| {
+                    let config = usage(&settings.tsconfig.language_name, false, &settings);
+                    configs.push((settings, config));
+                    configs.len() - 1
+                });
+            configs[idx].1.documents += 1;
+            configs[idx].1.memory.trees += nodes * TREE_NODE;
+        }
+        configs
+    }
+}
+
+///The parse table has an entry per state and symbol, most of them compressed away.
+fn grammar_bytes(language: &tree_sitter::Language) -> usize {
+    language.parse_state_count() * language.node_kind_count() * 4 / 5
+}
+
+fn query_bytes(settings: &TSSettings) -> usize {
+    let source = |query: &tree_sitter::Query| {
+        (0..query.pattern_count())
+            .map(|pattern| {
+                query.end_byte_for_pattern(pattern) - query.start_byte_for_pattern(pattern)
+            })
+            .sum::<usize>()
+    };
+    let queries = [settings.indents.as_deref(), settings.tags.as_deref()];
+    (source(&settings.tsconfig.query) + queries.into_iter().flatten().map(source).sum::<usize>())
+        * QUERY_SOURCE
+}
@@ -0,0 +1,184 @@
+//! Themes and highlight [layers](crate::layer) written in [Rhai](https://rhai.rs), so users of an editor can extend the
+//! highlighting without recompiling it. Requires the `scripting` feature.
+//!
+//! A theme script evaluates to a map. Colors are hex or `rgb(..)` literals, a style is either a color or a map:
+//!
+//! ```rhai
+//! #{
+//!     background: "#1e1e1e",
+//!     foreground: "#d4d4d4",
+//!     styles: #{
+//!         comment: #{ color: "#6a9955", italic: true },
+//!         keyword: "#569cd6",
+//!     },
+//! }
+//! ```
+//!
+//! A layer script defines `highlight(line, text)`, which returns `[start, end, capture]` arrays. Like Rhai's string functions,
+//! positions count characters, not bytes:
+//!
+//! ```rhai
+//! fn highlight(line, text) {
+//!     let at = text.index_of("TODO");
+//!     if at < 0 { return []; }
+//!     [[at, at + 4, "keyword"]]
+//! }
+//! ```
+//!
+//! Scripts run with an operation limit, so a script that loops forever fails instead of freezing the editor.
+
+use std::ops::Range;
+
+use rhai::{Dynamic, Engine, EvalAltResult, Map};
+
+use crate::{
+    color, layer, logging,
+    theme::{Style, Theme},
+};
+
+///Operations a single script run may take.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine
+}
+
+impl Theme {
+    ///Evaluates the theme `script`, see the [module](self) documentation.
+    pub fn from_script(script: &str) -> Result<Self, Box<EvalAltResult>> {
+        let map = engine()
+            .eval::<Dynamic>(script)?
+            .try_cast::<Map>()
+            .ok_or("a theme script has to evaluate to a map")?;
+
+        let color_of = |key: &str| -> Result<_, Box<EvalAltResult>> {
+            match map.get(key) {
+                Some(value) => parse_color(value),
+                None => Err(format!("the theme has no {key}").into()),
+            }
+        };
+        let mut theme = Self::new(color_of("background")?, color_of("foreground")?);
+        if let Some(styles) = map.get("styles") {
+            let styles = styles
+                .clone()
+                .try_cast::<Map>()
+                .ok_or("`styles` has to be a map")?;
+            for (capture, style) in styles {
+                theme.set_style(capture.as_str(), parse_style(&style)?);
+            }
+        }
+        Ok(theme)
+    }
+}
+
+fn parse_color(value: &Dynamic) -> Result<iced_core::Color, Box<EvalAltResult>> {
+    let text = value
+        .clone()
+        .into_string()
+        .map_err(|_| format!("expected a color, found {}", value.type_name()))?;
+    match color::color_literals(&text).as_slice() {
+        [literal] if literal.range == (0..text.len()) => Ok(literal.color),
+        _ => Err(format!("`{text}` is not a color").into()),
+    }
+}
+
+fn parse_style(value: &Dynamic) -> Result<Style, Box<EvalAltResult>> {
+    let Some(map) = value.clone().try_cast::<Map>() else {
+        return Ok(Style::color(parse_color(value)?));
+    };
+    let mut style = Style::plain();
+    for (key, value) in map {
+        let flag = || {
+            value
+                .as_bool()
+                .map_err(|_| format!("`{key}` has to be true or false"))
+        };
+        match key.as_str() {
+            "color" => style.color = Some(parse_color(&value)?),
+            "background" => style.background = Some(parse_color(&value)?),
+            "bold" => style.bold = flag()?,
+            "italic" => style.italic = flag()?,
+            "underline" => style.underline = flag()?,
+            "strikethrough" => style.strikethrough = flag()?,
+            _ => return Err(format!("unknown style key `{key}`").into()),
+        }
+    }
+    Ok(style)
+}
+
+///A [Layer](layer::Layer) that runs the `highlight` function of a script, see the [module](self) documentation.
+///
+///A run that fails is logged and highlights nothing.
+pub struct ScriptLayer {
+    engine: Engine,
+    ast: rhai::AST,
+}
+
+impl ScriptLayer {
+    ///Compiles `script`. Fails if it doesn't compile, or doesn't define `highlight(line, text)`.
+    pub fn new(script: &str) -> Result<Self, Box<EvalAltResult>> {
+        let engine = engine();
+        let ast = engine.compile(script)?;
+        if !ast
+            .iter_functions()
+            .any(|function| function.name == "highlight" && function.params.len() == 2)
+        {
+            return Err("the layer script has no `highlight(line, text)` function".into());
+        }
+        Ok(Self { engine, ast })
+    }
+
+    fn run(
+        &self,
+        line: usize,
+        text: &str,
+    ) -> Result<Vec<(Range<usize>, String)>, Box<EvalAltResult>> {
+        let spans = self.engine.call_fn::<rhai::Array>(
+            &mut rhai::Scope::new(),
+            &self.ast,
+            "highlight",
+            (line as rhai::INT, text.to_string()),
+        )?;
+
+        //character positions to byte offsets, the text's end included
+        let offsets = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<_>>();
+        let offset = |value: &Dynamic| -> Result<usize, Box<EvalAltResult>> {
+            let position = value
+                .as_int()
+                .map_err(|_| format!("expected a position, found {}", value.type_name()))?;
+            Ok(offsets[usize::try_from(position)
+                .unwrap_or(0)
+                .min(offsets.len() - 1)])
+        };
+
+        spans
+            .into_iter()
+            .map(|span| {
+                let span = span
+                    .try_cast::<rhai::Array>()
+                    .filter(|span| span.len() == 3)
+                    .ok_or("`highlight` has to return [start, end, capture] arrays")?;
+                let capture = span[2]
+                    .clone()
+                    .into_string()
+                    .map_err(|_| "the capture has to be a string")?;
+                Ok((offset(&span[0])?..offset(&span[1])?, capture))
+            })
+            .collect()
+    }
+}
+
+impl layer::Layer for ScriptLayer {
+    fn highlight(&self, line: usize, text: &str) -> Vec<(Range<usize>, String)> {
+        self.run(line, text).unwrap_or_else(|err| {
+            logging::warning!(target: logging::SCRIPT, "highlight layer failed on line {line}: {err}");
+            Vec::new()
+        })
+    }
+}
@@ -0,0 +1,464 @@
+//! Finding text in a [Document].
+
+use std::ops::Range;
+
+use crate::{
+    document::{Document, Position},
+    overlay::{Overlay, OverlayKind},
+};
+
+///All occurrences of some text, e.g. for a "5 of 12 matches" status.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Occurrences {
+    ///Byte ranges of all occurrences, sorted.
+    pub ranges: Vec<Range<usize>>,
+    ///Index into [Occurrences::ranges] of the occurrence the query was made from (the one under the cursor), if any.
+    pub current: Option<usize>,
+}
+
+impl Occurrences {
+    pub fn count(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    ///Index of the occurrence that contains (or touches) `offset`.
+    pub fn index_of(&self, offset: usize) -> Option<usize> {
+        let idx = self.ranges.partition_point(|range| range.end < offset);
+        self.ranges
+            .get(idx)
+            .filter(|range| range.start <= offset)
+            .map(|_| idx)
+    }
+}
+
+///Characters that make up a _word_ when looking at the cursor.
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+///Finds all occurrences of `needle` in `haystack`. If `whole_word` is set, matches that are directly next to another word character are skipped.
+pub(crate) fn find_all(haystack: &str, needle: &str, whole_word: bool) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    haystack
+        .match_indices(needle)
+        .map(|(start, m)| start..start + m.len())
+        .filter(|range| !whole_word || is_whole_word(haystack, range))
+        .collect()
+}
+
+pub(crate) fn is_whole_word(haystack: &str, range: &Range<usize>) -> bool {
+    let before = haystack[..range.start].chars().next_back();
+    let after = haystack[range.end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+impl Document {
+    ///Byte range of the word at `position`. Also finds the word if the cursor is placed directly behind it.
+    pub fn word_at(&self, position: impl Into<Position>) -> Option<Range<usize>> {
+        let offset = self.offset(position);
+        let text = self.text();
+
+        let start = text[..offset]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_word_char(*c))
+            .last()
+            .map(|(idx, _)| idx)
+            .unwrap_or(offset);
+        let end = text[offset..]
+            .char_indices()
+            .find(|(_, c)| !is_word_char(*c))
+            .map(|(idx, _)| offset + idx)
+            .unwrap_or(text.len());
+
+        (start < end).then_some(start..end)
+    }
+
+    ///All occurrences of `needle` (e.g. the current selection).
+    pub fn occurrences(&self, needle: &str) -> Occurrences {
+        Occurrences {
+            ranges: find_all(self.text(), needle, false),
+            current: None,
+        }
+    }
+
+    ///All whole-word occurrences of the word under the cursor. [Occurrences::current] is the one at `position`.
+    pub fn cursor_word_occurrences(&self, position: impl Into<Position>) -> Occurrences {
+        let Some(word) = self.word_at(position) else {
+            return Occurrences::default();
+        };
+
+        let ranges = find_all(self.text(), &self.text()[word.clone()], true);
+        let current = ranges.binary_search_by_key(&word.start, |r| r.start).ok();
+        Occurrences { ranges, current }
+    }
+
+    ///Like [Document::occurrences], but [Occurrences::current] is set to the occurrence at `selection`.
+    ///Use it with the selection's byte range, e.g. from the cursor position.
+    pub fn selection_occurrences(&self, selection: Range<usize>) -> Occurrences {
+        let Some(needle) = self.text().get(selection.clone()) else {
+            return Occurrences::default();
+        };
+
+        let mut occurrences = self.occurrences(needle);
+        occurrences.current = occurrences
+            .ranges
+            .binary_search_by_key(&selection.start, |r| r.start)
+            .ok();
+        occurrences
+    }
+
+    ///_Select next occurrence_ (Ctrl+D in most editors).
+    ///
+    ///Returns the next occurrence of the text in `selection` after it, wrapping around at the end of the document. An empty `selection` selects the
+    ///word it is placed in instead. If the selection is a whole word, only whole-word occurrences are considered.
+    ///
+    ///`selected` are the ranges that are already selected (multi-cursor); they are skipped. Returns `None` once every occurrence is selected.
+    pub fn next_occurrence(
+        &self,
+        selection: Range<usize>,
+        selected: &[Range<usize>],
+    ) -> Option<Range<usize>> {
+        if selection.is_empty() {
+            return self.word_at(self.position(selection.start));
+        }
+
+        let text = self.text();
+        let needle = text.get(selection.clone())?;
+        let whole_word = needle.chars().all(is_word_char) && is_whole_word(text, &selection);
+        let ranges = find_all(text, needle, whole_word);
+
+        let first_after = ranges.partition_point(|r| r.start < selection.end);
+        ranges[first_after..]
+            .iter()
+            .chain(ranges[..first_after].iter())
+            .find(|range| **range != selection && !selected.contains(range))
+            .cloned()
+    }
+}
+
+///A match that is about to be replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplacePreview {
+    ///Byte range of the match.
+    pub range: Range<usize>,
+    ///Text the range will become.
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchOptions {
+    ///Interpret the pattern as regular expression ([regex] syntax). Otherwise it is matched literally.
+    pub regex: bool,
+    ///Only report matches that aren't surrounded by other word characters.
+    pub whole_word: bool,
+    pub case_sensitive: bool,
+}
+
+///A compiled search pattern. Runs on a [Document], so the host doesn't need to keep its own copy of the text around.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pattern: String,
+    options: SearchOptions,
+    regex: regex::Regex,
+}
+
+impl Search {
+    ///Compiles `pattern`. Only fails for invalid regular expressions.
+    pub fn new(pattern: impl Into<String>, options: SearchOptions) -> Result<Self, regex::Error> {
+        let pattern = pattern.into();
+        let source = if options.regex {
+            pattern.clone()
+        } else {
+            regex::escape(&pattern)
+        };
+        let regex = regex::RegexBuilder::new(&source)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .build()?;
+
+        Ok(Self {
+            pattern,
+            options,
+            regex,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    ///All matches in `document`, as sorted byte ranges. Empty matches are skipped.
+    pub fn all_matches(&self, document: &Document) -> Vec<Range<usize>> {
+        self.matches_from(document.text(), 0).collect()
+    }
+
+    ///First match that starts at, or after `from`. Wraps around to the start of the document if there is no match after `from`.
+    pub fn next_match(&self, document: &Document, from: usize) -> Option<Range<usize>> {
+        let text = document.text();
+        self.matches_from(text, from)
+            .next()
+            .or_else(|| self.matches_from(text, 0).next())
+    }
+
+    ///Last match that starts before `from`. Wraps around to the end of the document.
+    pub fn prev_match(&self, document: &Document, from: usize) -> Option<Range<usize>> {
+        let matches = self.all_matches(document);
+        let idx = matches.partition_point(|m| m.start < from);
+        if idx == 0 {
+            matches.last().cloned()
+        } else {
+            Some(matches[idx - 1].clone())
+        }
+    }
+
+    ///All matches as [Occurrences], with [Occurrences::current] being the match at `offset`.
+    pub fn occurrences(&self, document: &Document, offset: usize) -> Occurrences {
+        let mut occurrences = Occurrences {
+            ranges: self.all_matches(document),
+            current: None,
+        };
+        occurrences.current = occurrences.index_of(offset);
+        occurrences
+    }
+
+    ///Overlay marking all matches. `current` (e.g. the match the host navigated to) is marked as [OverlayKind::CurrentMatch].
+    pub fn overlay(&self, document: &Document, current: Option<Range<usize>>) -> Overlay {
+        let mut overlay = Overlay::new();
+        for m in self.all_matches(document) {
+            let kind = if current.as_ref() == Some(&m) {
+                OverlayKind::CurrentMatch
+            } else {
+                OverlayKind::SearchMatch
+            };
+            overlay.add(document, m, kind);
+        }
+        overlay
+    }
+
+    ///What `range` (a match of this search) would be replaced with. In regex mode `$1`/`${name}` groups in `replacement` are expanded,
+    ///otherwise `replacement` is used as is.
+    pub fn expand_replacement(
+        &self,
+        document: &Document,
+        range: Range<usize>,
+        replacement: &str,
+    ) -> String {
+        if !self.options.regex {
+            return replacement.to_string();
+        }
+
+        let text = document.text();
+        match self.regex.captures_at(text, range.start) {
+            Some(captures) if captures.get(0).map(|m| m.range()) == Some(range) => {
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                expanded
+            }
+            _ => replacement.to_string(),
+        }
+    }
+
+    ///Preview of a pending _replace all_: every match together with the text it would become. Applying the previews back to front
+    ///is exactly the replacement.
+    pub fn replace_preview(&self, document: &Document, replacement: &str) -> Vec<ReplacePreview> {
+        self.all_matches(document)
+            .into_iter()
+            .map(|range| ReplacePreview {
+                replacement: self.expand_replacement(document, range.clone(), replacement),
+                range,
+            })
+            .collect()
+    }
+
+    ///Like [Search::overlay], but marks the matches that `replacement` changes as [OverlayKind::ReplacePreview], so hosts can
+    ///show what is about to be replaced, together with the [previews](Search::replace_preview) the marks are made of, to draw
+    ///the text each one becomes. `current` stays marked as [OverlayKind::CurrentMatch].
+    pub fn replace_overlay(
+        &self,
+        document: &Document,
+        replacement: &str,
+        current: Option<Range<usize>>,
+    ) -> (Overlay, Vec<ReplacePreview>) {
+        let previews = self.replace_preview(document, replacement);
+        let mut overlay = Overlay::new();
+        for preview in &previews {
+            let kind = if current.as_ref() == Some(&preview.range) {
+                OverlayKind::CurrentMatch
+            } else if document.text()[preview.range.clone()] == preview.replacement {
+                OverlayKind::SearchMatch
+            } else {
+                OverlayKind::ReplacePreview
+            };
+            overlay.add(document, preview.range.clone(), kind);
+        }
+        (overlay, previews)
+    }
+
+    fn matches_from<'a>(
+        &'a self,
+        text: &'a str,
+        from: usize,
+    ) -> impl Iterator<Item = Range<usize>> + 'a {
+        //NOTE: searching the whole text (instead of slicing at `from`) keeps anchors and word boundaries intact.
+        self.regex
+            .find_iter(text)
+            .map(|m| m.range())
+            .filter(move |range| range.start >= from && !range.is_empty())
+            .filter(move |range| !self.options.whole_word || is_whole_word(text, range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Search, SearchOptions};
+    use crate::{document::Document, overlay::OverlayKind, testing};
+
+    const TEXT: &str = "let foo = 1;\nlet foobar = foo + Foo;\nfoo_2(foo);\n";
+
+    fn document() -> Document {
+        Document::new(&testing::rust(), TEXT)
+    }
+
+    #[test]
+    fn finds_the_word_at_and_behind_the_cursor() {
+        let document = document();
+        assert_eq!(document.word_at((0, 4)), Some(4..7));
+        assert_eq!(document.word_at((0, 7)), Some(4..7));
+        assert_eq!(document.word_at((0, 8)), None);
+        assert_eq!(document.word_at((2, 2)), Some(37..42));
+    }
+
+    #[test]
+    fn cursor_word_occurrences_are_whole_words() {
+        let occurrences = document().cursor_word_occurrences((1, 14));
+        assert_eq!(occurrences.ranges, [4..7, 26..29, 43..46]);
+        assert_eq!(occurrences.current, Some(1));
+        assert_eq!(occurrences.index_of(29), Some(1));
+        assert_eq!(occurrences.index_of(30), None);
+    }
+
+    #[test]
+    fn next_occurrence_wraps_around_and_skips_the_selected_ones() {
+        let document = document();
+        //a whole word only selects whole words
+        assert_eq!(document.next_occurrence(26..29, &[]), Some(43..46));
+        assert_eq!(document.next_occurrence(43..46, &[]), Some(4..7));
+        let selected = [4..7, 26..29];
+        assert_eq!(
+            document.next_occurrence(43..46, &selected[..1]),
+            Some(26..29)
+        );
+        assert_eq!(document.next_occurrence(43..46, &selected), None);
+        //part of a word selects parts of words
+        assert_eq!(document.next_occurrence(4..6, &[]), Some(17..19));
+        //an empty selection selects the word
+        assert_eq!(document.next_occurrence(5..5, &[]), Some(4..7));
+    }
+
+    #[test]
+    fn searches_by_the_options() {
+        let document = document();
+        let search = |pattern: &str, options: SearchOptions| {
+            Search::new(pattern, options)
+                .unwrap()
+                .all_matches(&document)
+        };
+        let case_sensitive = SearchOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            search("foo", case_sensitive),
+            [4..7, 17..20, 26..29, 37..40, 43..46]
+        );
+        assert_eq!(search("foo", SearchOptions::default()).len(), 6);
+        let whole_word = SearchOptions {
+            whole_word: true,
+            ..case_sensitive
+        };
+        assert_eq!(search("foo", whole_word), [4..7, 26..29, 43..46]);
+        let regex = SearchOptions {
+            regex: true,
+            ..case_sensitive
+        };
+        assert_eq!(search("^let", regex), [0..3, 13..16]);
+        assert!(search("^let", case_sensitive).is_empty());
+        assert!(Search::new("(", regex).is_err());
+        assert!(Search::new("(", case_sensitive).is_ok());
+    }
+
+    #[test]
+    fn next_and_prev_match_wrap_around() {
+        let document = document();
+        let search = Search::new("let", SearchOptions::default()).unwrap();
+        assert_eq!(search.next_match(&document, 1), Some(13..16));
+        assert_eq!(search.next_match(&document, 14), Some(0..3));
+        assert_eq!(search.prev_match(&document, 13), Some(0..3));
+        assert_eq!(search.prev_match(&document, 0), Some(13..16));
+        assert_eq!(search.occurrences(&document, 14).current, Some(1));
+    }
+
+    #[test]
+    fn replacing_back_to_front_is_the_replacement() {
+        let document = document();
+        let options = SearchOptions {
+            regex: true,
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let search = Search::new(r"let (\w+)", options).unwrap();
+        let mut text = TEXT.to_string();
+        for preview in search
+            .replace_preview(&document, "const ${1}_x")
+            .into_iter()
+            .rev()
+        {
+            text.replace_range(preview.range, &preview.replacement);
+        }
+        assert_eq!(
+            text,
+            "const foo_x = 1;\nconst foobar_x = foo + Foo;\nfoo_2(foo);\n"
+        );
+    }
+
+    #[test]
+    fn replace_overlay_marks_the_previews() {
+        let document = document();
+        let options = SearchOptions {
+            regex: true,
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let search = Search::new(r"foo\w*", options).unwrap();
+        let (overlay, previews) = search.replace_overlay(&document, "foo", Some(26..29));
+        assert_eq!(previews, search.replace_preview(&document, "foo"));
+        assert_eq!(
+            overlay.line(1),
+            [
+                (4..10, OverlayKind::ReplacePreview),
+                (13..16, OverlayKind::CurrentMatch)
+            ]
+        );
+        //the matches that stay the same are only matches
+        assert_eq!(overlay.line(0), [(4..7, OverlayKind::SearchMatch)]);
+        assert_eq!(
+            overlay.line(2),
+            [
+                (0..5, OverlayKind::ReplacePreview),
+                (6..9, OverlayKind::SearchMatch)
+            ]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Editor state worth keeping across sessions: folded regions, bookmarks, and the language and theme the user picked. Requires the
+//! `serde` feature.
+//!
+//! Capture it with [Session::new] and the `with_*` functions when a document is closed, store [Session::to_blob] next to the file (or
+//! in the app's settings), and [restore](Session::restore_folds) it after opening the file again.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::folding::Folds;
+
+///See the [module](self) documentation. Fields that are missing in a blob (e.g. written by an older version) are left at their
+///default.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    ///Header lines of the folded regions.
+    #[serde(rename = "f", skip_serializing_if = "BTreeSet::is_empty")]
+    pub folded: BTreeSet<usize>,
+    ///Lines with a [bookmark](crate::code_editor::Mark::Bookmark).
+    #[serde(rename = "b", skip_serializing_if = "BTreeSet::is_empty")]
+    pub bookmarks: BTreeSet<usize>,
+    ///Language the user chose instead of the detected one, by `language_name`.
+    #[serde(rename = "l", skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    ///Name of the theme the user chose. Themes aren't named by the crate, so this is whatever name the app shows them under.
+    #[serde(rename = "t", skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Remembers the folded regions of `folds`.
+    pub fn with_folds(mut self, folds: &Folds) -> Self {
+        self.folded = folds.folded().map(|range| range.lines.start).collect();
+        self
+    }
+
+    pub fn with_bookmarks(mut self, lines: impl IntoIterator<Item = usize>) -> Self {
+        self.bookmarks = lines.into_iter().collect();
+        self
+    }
+
+    pub fn with_language(mut self, language_name: impl Into<String>) -> Self {
+        self.language = Some(language_name.into());
+        self
+    }
+
+    pub fn with_theme(mut self, name: impl Into<String>) -> Self {
+        self.theme = Some(name.into());
+        self
+    }
+
+    ///Folds the remembered regions in `folds`. Regions that don't exist anymore (because the file changed in the meantime) are
+    ///skipped.
+    pub fn restore_folds(&self, folds: &mut Folds) {
+        for line in &self.folded {
+            folds.fold(*line);
+        }
+    }
+
+    ///Compact form of the session, for storing it.
+    pub fn to_blob(&self) -> Vec<u8> {
+        //NOTE: can't fail, the session only consists of numbers and strings
+        serde_json::to_vec(self).expect("session is serializable")
+    }
+
+    ///Reads a session written by [Session::to_blob].
+    pub fn from_blob(blob: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(blob)
+    }
+}
@@ -0,0 +1,886 @@
+//! Highlighting state several editors share, e.g. the panes of a split view that all show the same file.
+//!
+//! Every `text_editor` keeps its own [Highlighter](crate::Highlighter), which would parse and highlight the text once per pane. Instead, a
+//! [SharedHighlightState] parses and highlights the whole text once per change, and the highlighters of all editors attached to it
+//! (via [SharedHighlightState::settings]) just look the lines up.
+//!
+//! This is also the way to highlight a single editor by the whole document: the [Highlighter](crate::Highlighter) on its own only sees
+//! one line at a time, so block comments, raw strings and other constructs spanning several lines lose their highlighting after the
+//! first line. With the state attached, each line's highlights are sliced out of the highlights of the whole text.
+//!
+//! The settings carry a [HighlightSnapshot] of the state: the highlights as they were when the settings were fetched. An editor
+//! draws all lines of a frame from its snapshot, so they never mix old and new colors, even while another thread sets a new text or
+//! highlights [progressively](SharedHighlightState::set_text_progressive). Reading a snapshot doesn't lock the state either.
+
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::{Arc, Mutex, MutexGuard, Weak, mpsc},
+    time::Duration,
+};
+
+use crate::{
+    Highlight, TSSettings,
+    document::{Change, DEFAULT_HISTORY_LIMIT, Document, Reparsed, Replaced, Shift, text_hash},
+    progressive::{Progress, ProgressiveHighlights, Work},
+};
+
+///Captures of a line, relative to the line's start. Shared with the snapshots, so taking one costs a pointer per line.
+type Captures = Arc<Vec<(Range<usize>, tree_sitter_highlight::Highlight)>>;
+type LineCaptures = Vec<Captures>;
+///Captures of [CHUNK] lines of a snapshot, `None` for the lines that aren't highlighted yet.
+type Chunk = Arc<[Option<Captures>]>;
+
+///Lines per [Chunk]. A snapshot of a big text only builds the chunks that changed since the last one, and shares the rest.
+const CHUNK: usize = 1024;
+
+///See the [module](self) documentation. Cloning is cheap, all clones refer to the same state.
+#[derive(Clone)]
+pub struct SharedHighlightState {
+    inner: Arc<Mutex<Inner>>,
+}
+
+///A [SharedHighlightState] that is gone once all its clones are dropped.
+pub(crate) struct WeakHighlightState(Weak<Mutex<Inner>>);
+
+impl WeakHighlightState {
+    pub(crate) fn upgrade(&self) -> Option<SharedHighlightState> {
+        self.0.upgrade().map(|inner| SharedHighlightState { inner })
+    }
+
+    pub(crate) fn is_of(&self, state: &SharedHighlightState) -> bool {
+        std::ptr::eq(self.0.as_ptr(), Arc::as_ptr(&state.inner))
+    }
+}
+
+struct Inner {
+    document: Document,
+    lines: LineCaptures,
+    ///[text_hash] of each line, to tell whether an editor shows the same text as a snapshot.
+    line_hashes: Arc<[u64]>,
+    ///Captures of earlier texts by their hash and length, most recent last. Like the document's trees, so undo and redo don't
+    ///highlight again.
+    history: VecDeque<(u64, usize, LineCaptures)>,
+    ///Bumped on every change, so editors notice that their highlights are outdated.
+    generation: u64,
+    ///Highlighting still to do after [SharedHighlightState::set_text_progressive].
+    pending: Option<ProgressiveHighlights>,
+    ///Cursor line and visible lines, see [SharedHighlightState::focus].
+    focus: (usize, Range<usize>),
+    ///Snapshot of the current generation, taken when it is first asked for.
+    frame: Option<Arc<Frame>>,
+    ///Chunks of the last snapshot, `None` for the ones that changed since.
+    chunks: Vec<Option<Chunk>>,
+    ///Captures the lines had in the previous text, shown until they are highlighted again after
+    ///[SharedHighlightState::set_text_progressive]. `None` for the lines that changed.
+    stale: Vec<Option<Captures>>,
+    ///Hash and length of the text a [HighlightJob] highlights, see [SharedHighlightState::set_text_background].
+    background: Option<(u64, usize)>,
+}
+
+///How [SharedHighlightState::replace_text] highlights the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Now,
+    Progressive,
+    Background,
+}
+
+impl Inner {
+    fn changed(&mut self) {
+        self.generation += 1;
+        self.frame = None;
+    }
+
+    ///Captures of `line` as a snapshot shows them, `None` if it isn't highlighted yet.
+    fn shown(&self, line: usize) -> Option<Captures> {
+        if self.background.is_some() {
+            return self.stale.get(line).cloned().flatten();
+        }
+        match &self.pending {
+            Some(pending)
+                if !self
+                    .document
+                    .line_range(line)
+                    .is_some_and(|range| pending.is_highlighted(range)) =>
+            {
+                self.stale.get(line).cloned().flatten()
+            }
+            _ => self.lines.get(line).cloned(),
+        }
+    }
+}
+
+///The highlights of a [SharedHighlightState] at one point in time, see the [module](self) documentation. Cloning is cheap.
+#[derive(Clone)]
+pub struct HighlightSnapshot {
+    state: SharedHighlightState,
+    frame: Arc<Frame>,
+}
+
+struct Frame {
+    generation: u64,
+    line_hashes: Arc<[u64]>,
+    chunks: Vec<Chunk>,
+}
+
+impl HighlightSnapshot {
+    ///The state the snapshot was taken of.
+    pub fn state(&self) -> &SharedHighlightState {
+        &self.state
+    }
+
+    ///Number of changes of the state before the snapshot was taken. Snapshots of the same generation have the same highlights.
+    pub fn generation(&self) -> u64 {
+        self.frame.generation
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.frame, &other.frame)
+    }
+
+    ///First line an editor that drew `self` has to draw again for `newer`: the first line with a different text, or highlights
+    ///that changed. Lines `newer` doesn't have highlights for yet keep what the editor drew until they are highlighted. `None`
+    ///if nothing changed.
+    pub(crate) fn first_change(&self, newer: &Self) -> Option<usize> {
+        if !Arc::ptr_eq(&self.state.inner, &newer.state.inner) {
+            return Some(0);
+        }
+        let (old, new) = (&*self.frame, &*newer.frame);
+        let same_text = Arc::ptr_eq(&old.line_hashes, &new.line_hashes);
+        for (idx, (old_chunk, new_chunk)) in old.chunks.iter().zip(&new.chunks).enumerate() {
+            if same_text && Arc::ptr_eq(old_chunk, new_chunk) {
+                continue;
+            }
+            let start = idx * CHUNK;
+            let changed = old_chunk.iter().zip(new_chunk.iter()).enumerate().position(
+                |(offset, (old_captures, new_captures))| {
+                    let line = start + offset;
+                    old.line_hashes.get(line) != new.line_hashes.get(line)
+                        || new_captures.is_some() && old_captures != new_captures
+                },
+            );
+            if let Some(offset) = changed {
+                return Some(start + offset);
+            }
+        }
+        (old.line_hashes.len() != new.line_hashes.len())
+            .then(|| old.line_hashes.len().min(new.line_hashes.len()))
+    }
+
+    ///Captures of `line`, if its text is `text` in the snapshot as well, or `Some(None)` if it isn't highlighted yet. Otherwise
+    ///the editor is ahead of the snapshot, and has to highlight on its own.
+    pub(crate) fn captures(&self, line: usize, text: &str) -> Option<Option<Captures>> {
+        if *self.frame.line_hashes.get(line)? != text_hash(text.trim_end_matches(['\r', '\n'])) {
+            return None;
+        }
+        Some(
+            self.frame
+                .chunks
+                .get(line / CHUNK)?
+                .get(line % CHUNK)?
+                .clone(),
+        )
+    }
+}
+
+impl SharedHighlightState {
+    pub fn new(settings: &TSSettings, text: impl Into<String>) -> Self {
+        let mut settings = settings.clone();
+        settings.shared = None;
+        let document = Document::new(&settings, text);
+        let lines = line_captures(&document);
+        let pending = document
+            .is_parse_halted()
+            .then(|| ProgressiveHighlights::new(&document));
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                line_hashes: line_hashes(&document),
+                document,
+                lines,
+                history: VecDeque::new(),
+                generation: 0,
+                pending,
+                focus: (0, 0..0),
+                frame: None,
+                chunks: Vec::new(),
+                stale: Vec::new(),
+                background: None,
+            })),
+        }
+    }
+
+    ///Replaces the text, e.g. after an edit in one of the editors. Parses and highlights it once for all of them.
+    ///
+    ///Only the lines the edit touched and the ones whose syntax changed are highlighted again, by the highlight query on the
+    ///edited tree. All of the text is highlighted again if the settings resolve local variables, or if there is embedded code in
+    ///those lines, which only tree-sitter-highlight does.
+    pub fn set_text(&self, text: impl Into<String>) {
+        self.replace_text(text.into(), Mode::Now);
+    }
+
+    ///Parses and highlights the text again after [token edits](crate::TSSettings::with_token_edits) patched it, e.g. on the
+    ///debounce tick of the editor, once the typing paused. Does nothing if the text wasn't patched.
+    pub fn settle(&self) {
+        let mut inner = self.lock();
+        if !inner.document.is_patched() {
+            return;
+        }
+        inner.document.settle();
+        //lines highlighted progressively are highlighted from the text, not patched
+        if inner.pending.is_none() {
+            inner.lines = line_captures(&inner.document);
+            inner.background = None;
+        }
+        inner.chunks.clear();
+        inner.changed();
+    }
+
+    ///Like [SharedHighlightState::set_text], but leaves the highlighting to [SharedHighlightState::highlight_for], so a big text
+    ///doesn't stall the GUI thread. Until a line is highlighted, the editors show the highlights it had in the previous text, or
+    ///none if it changed.
+    ///
+    ///To open a big file, create the state with an empty text and set the file's text this way.
+    pub fn set_text_progressive(&self, text: impl Into<String>) {
+        self.replace_text(text.into(), Mode::Progressive);
+    }
+
+    ///Like [SharedHighlightState::set_text], but leaves parsing and highlighting to the returned [HighlightJob], to run on a
+    ///worker thread (or in an `iced::Task`, see `HighlightJob::into_task`), so typing in a multi-megabyte file doesn't wait for
+    ///it. Until its result is [applied](SharedHighlightState::apply), the editors show the highlights the lines had in the
+    ///previous text, or none if they changed. `None` if there is nothing to do: the text is the same, or one the state had
+    ///before, like after an undo.
+    ///
+    ///Anything that needs the tree in the meantime, like [SharedHighlightState::with_document], parses the text on its own.
+    pub fn set_text_background(&self, text: impl Into<String>) -> Option<HighlightJob> {
+        self.replace_text(text.into(), Mode::Background)
+    }
+
+    ///Shows the highlights of a [HighlightJob]. Returns whether they were taken: results of a text that was replaced since are
+    ///dropped. Fetch the [settings](SharedHighlightState::settings) again afterwards, so the editors pick them up.
+    pub fn apply(&self, highlighted: Highlighted) -> bool {
+        let mut inner = self.lock();
+        if inner.background != Some((highlighted.hash, highlighted.len)) {
+            return false;
+        }
+        inner
+            .document
+            .adopt_tree(highlighted.hash, highlighted.tree);
+        inner.lines = highlighted.lines;
+        inner.background = None;
+        inner.stale = Vec::new();
+        inner.chunks.clear();
+        inner.changed();
+        true
+    }
+
+    ///Highlights the text set by [SharedHighlightState::set_text_progressive] for at most `budget`, see
+    ///[ProgressiveHighlights::step]. Fetch the [settings](SharedHighlightState::settings) again afterwards, so the editors pick up
+    ///the highlighted lines.
+    ///
+    ///Also carries on with a parse that [halted](Document::is_parse_halted) at the
+    ///[timeout](crate::TSSettings::with_parse_timeout), which leaves the text to be highlighted this way too.
+    pub fn highlight_for(&self, budget: Duration) -> Progress {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        if inner.document.is_parse_halted() {
+            inner.document.resume_parse();
+        }
+        let Some(pending) = &mut inner.pending else {
+            let len = inner.document.text().len();
+            return Progress {
+                done: len,
+                total: len,
+            };
+        };
+        let progress = pending.advance(&inner.document, budget, |slice, spans| {
+            split_lines(&inner.document, spans, &mut inner.lines);
+            let first = inner.document.position(slice.start).line / CHUNK;
+            let last = inner.document.position(slice.end).line / CHUNK;
+            for chunk in inner.chunks.iter_mut().take(last + 1).skip(first) {
+                *chunk = None;
+            }
+        });
+        if progress.is_done() {
+            inner.pending = None;
+        }
+        inner.changed();
+        progress
+    }
+
+    ///Highlights `cursor_line` and the `visible` lines first, see [ProgressiveHighlights::focus]. Kept for the texts set later.
+    pub fn focus(&self, cursor_line: usize, visible: Range<usize>) {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        if let Some(pending) = &mut inner.pending {
+            pending.focus(&inner.document, cursor_line, visible.clone());
+        }
+        inner.focus = (cursor_line, visible);
+    }
+
+    ///Work left after [SharedHighlightState::set_text_progressive], see [ProgressiveHighlights::pending].
+    pub fn pending(&self) -> Vec<Work> {
+        self.lock()
+            .pending
+            .as_ref()
+            .map(ProgressiveHighlights::pending)
+            .unwrap_or_default()
+    }
+
+    fn replace_text(&self, text: String, mode: Mode) -> Option<HighlightJob> {
+        let mut inner = self.lock();
+        if inner.document.text() == text {
+            return None;
+        }
+
+        let key = (text_hash(&text), text.len());
+        let previous_key = (
+            text_hash(inner.document.text()),
+            inner.document.text().len(),
+        );
+        let shown = (mode != Mode::Now).then(|| {
+            (0..inner.lines.len())
+                .map(|line| inner.shown(line))
+                .collect::<Vec<_>>()
+        });
+        let replaced = match mode {
+            Mode::Background => inner.document.replace_deferred(text),
+            _ => inner.document.replace(text),
+        };
+        //a text the parser gave up on for now is highlighted once it's parsed, see `highlight_for`
+        let mode = match mode {
+            Mode::Now if inner.document.is_parse_halted() => Mode::Progressive,
+            mode => mode,
+        };
+        //half highlighted lines are no use later on
+        let complete = inner.pending.is_none() && inner.background.is_none();
+        let hashes = line_hashes(&inner.document);
+        let previous_hashes = std::mem::replace(&mut inner.line_hashes, hashes);
+        let restored = inner
+            .history
+            .iter()
+            .position(|(hash, len, _)| (*hash, *len) == key)
+            .and_then(|idx| inner.history.remove(idx))
+            .map(|(_, _, lines)| lines);
+        //lines still to be highlighted progressively have no captures to move along. The query on the tree doesn't resolve local
+        //variables, those take highlighting all of the text again
+        let restored = restored.or_else(|| match &replaced {
+            Replaced::Moved(shift) if complete => {
+                Some(shifted_captures(&inner.document, &inner.lines, shift))
+            }
+            Replaced::Parsed(reparsed)
+                if complete
+                    && mode == Mode::Now
+                    && !inner.document.settings().resolves_locals() =>
+            {
+                reparsed_captures(&inner.document, &inner.lines, reparsed)
+            }
+            _ => None,
+        });
+        let (lines, pending, background) = match restored {
+            Some(lines) => (lines, None, None),
+            None if mode == Mode::Progressive => {
+                let mut pending = ProgressiveHighlights::new(&inner.document);
+                let (cursor_line, visible) = inner.focus.clone();
+                pending.focus(&inner.document, cursor_line, visible);
+                (
+                    vec![Arc::default(); inner.document.line_count()],
+                    Some(pending),
+                    None,
+                )
+            }
+            None if mode == Mode::Background => (
+                vec![Arc::default(); inner.document.line_count()],
+                None,
+                Some(key),
+            ),
+            None => (line_captures(&inner.document), None, None),
+        };
+        inner.stale = match shown {
+            Some(shown) if pending.is_some() || background.is_some() => {
+                stale_captures(&previous_hashes, &inner.line_hashes, shown)
+            }
+            _ => Vec::new(),
+        };
+        let previous = std::mem::replace(&mut inner.lines, lines);
+        inner.pending = pending;
+        inner.background = background;
+        if complete {
+            inner
+                .history
+                .push_back((previous_key.0, previous_key.1, previous));
+        }
+        while inner.history.len() > DEFAULT_HISTORY_LIMIT {
+            inner.history.pop_front();
+        }
+        inner.chunks.clear();
+        inner.changed();
+        background.map(|_| HighlightJob {
+            settings: inner.document.settings().clone(),
+            text: inner.document.text().to_string(),
+        })
+    }
+
+    ///The settings the state was created with, attached to a [snapshot](SharedHighlightState::snapshot) of the state. Hand them
+    ///to every editor that shows the text, and fetch them again after [SharedHighlightState::set_text], so the editors
+    ///re-highlight.
+    pub fn settings(&self) -> TSSettings {
+        let mut settings = self.lock().document.settings().clone();
+        settings.shared = Some(self.snapshot());
+        settings
+    }
+
+    ///The highlights as they are now. Later changes of the state don't change the snapshot.
+    pub fn snapshot(&self) -> HighlightSnapshot {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        if inner.frame.is_none() {
+            let line_count = inner.lines.len();
+            let mut chunks = std::mem::take(&mut inner.chunks);
+            chunks.resize(line_count.div_ceil(CHUNK), None);
+            let frame = chunks
+                .iter_mut()
+                .enumerate()
+                .map(|(idx, chunk)| {
+                    let lines = idx * CHUNK..((idx + 1) * CHUNK).min(line_count);
+                    chunk
+                        .get_or_insert_with(|| lines.map(|line| inner.shown(line)).collect())
+                        .clone()
+                })
+                .collect();
+            inner.chunks = chunks;
+            inner.frame = Some(Arc::new(Frame {
+                generation: inner.generation,
+                line_hashes: inner.line_hashes.clone(),
+                chunks: frame,
+            }));
+        }
+        HighlightSnapshot {
+            state: self.clone(),
+            frame: inner.frame.clone().expect("just taken"),
+        }
+    }
+
+    ///See [Document::subscribe].
+    pub fn subscribe(&self) -> mpsc::Receiver<Change> {
+        self.lock().document.subscribe()
+    }
+
+    ///Runs `f` on the shared [Document], e.g. to compute folds or a focus scope without parsing the text again.
+    pub fn with_document<R>(&self, f: impl FnOnce(&Document) -> R) -> R {
+        f(&self.lock().document)
+    }
+
+    ///[Hibernates](Document::hibernate) the document, and drops the highlights of earlier texts, e.g. when its tab goes to the
+    ///background. The editors keep drawing the current highlights. Setting a text or anything else that needs the tree parses it
+    ///again.
+    pub fn hibernate(&self) {
+        let mut inner = self.lock();
+        inner.document.hibernate();
+        inner.history.clear();
+        inner.history.shrink_to_fit();
+    }
+
+    ///A handle that doesn't keep the state alive, see [registry](crate::registry).
+    pub(crate) fn downgrade(&self) -> WeakHighlightState {
+        WeakHighlightState(Arc::downgrade(&self.inner))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        //NOTE: a panic while highlighting doesn't leave the state half updated in a harmful way, so just carry on.
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+///Parsing and highlighting of a text set by [SharedHighlightState::set_text_background], to run away from the GUI thread.
+pub struct HighlightJob {
+    settings: TSSettings,
+    text: String,
+}
+
+impl HighlightJob {
+    ///Parses and highlights the text from scratch. Takes as long as [SharedHighlightState::set_text] would, so call it on a worker
+    ///thread, and hand the result to [SharedHighlightState::apply], e.g. through a channel, or as the message of a task.
+    pub fn run(self) -> Highlighted {
+        let document = Document::new(&self.settings, self.text);
+        Highlighted {
+            hash: document.hash(),
+            len: document.text().len(),
+            lines: line_captures(&document),
+            tree: document.tree().cloned(),
+        }
+    }
+
+    ///Runs the job on a thread of its own, and delivers the result as the message of an `iced::Task`, for the update function
+    ///to [apply](SharedHighlightState::apply). `None` if the job panicked.
+    #[cfg(feature = "gui")]
+    pub fn into_task(self) -> iced::Task<Option<Highlighted>> {
+        iced::Task::perform(
+            async move {
+                let (sender, receiver) = iced::futures::channel::oneshot::channel();
+                std::thread::spawn(move || {
+                    let _ = sender.send(self.run());
+                });
+                receiver.await.ok()
+            },
+            std::convert::identity,
+        )
+    }
+}
+
+///The highlights of a [HighlightJob], see [SharedHighlightState::apply].
+pub struct Highlighted {
+    hash: u64,
+    len: usize,
+    tree: Option<tree_sitter::Tree>,
+    lines: LineCaptures,
+}
+
+///Splits the document's highlights into lines.
+fn line_captures(document: &Document) -> LineCaptures {
+    let mut lines = vec![Arc::default(); document.line_count()];
+    split_lines(document, &document.highlights(), &mut lines);
+    lines
+}
+
+///Captures of the text after an edit the tree was parsed again for, from the `lines` of the text before: the lines the edit
+///touched and the ones whose syntax changed are highlighted again by the query on the tree, the other lines keep theirs. `None`
+///if there is embedded code in those lines, which takes highlighting all of the text again.
+fn reparsed_captures(
+    document: &Document,
+    lines: &LineCaptures,
+    reparsed: &Reparsed,
+) -> Option<LineCaptures> {
+    let tree = document.parsed_tree()?;
+    let mut captures = Vec::with_capacity(document.line_count());
+    captures.extend_from_slice(lines.get(..reparsed.old_lines.start)?);
+    captures.resize(reparsed.lines.end, Captures::default());
+    captures.extend_from_slice(lines.get(reparsed.old_lines.end..)?);
+    if captures.len() != document.line_count() {
+        return None;
+    }
+
+    let mut changed: Vec<Range<usize>> = reparsed
+        .changed
+        .iter()
+        .map(|range| document.position(range.start).line..document.position(range.end).line + 1)
+        .chain([reparsed.lines.clone()])
+        .collect();
+    changed.sort_unstable_by_key(|lines| lines.start);
+    let mut merged = Vec::<Range<usize>>::new();
+    for lines in changed {
+        match merged.last_mut() {
+            Some(last) if lines.start <= last.end => last.end = last.end.max(lines.end),
+            _ => merged.push(lines),
+        }
+    }
+
+    let ranges: Vec<(Range<usize>, Range<usize>)> = merged
+        .into_iter()
+        .filter_map(|lines| {
+            let lines = lines.start..lines.end.min(document.line_count());
+            let first = document.line_range(lines.start)?;
+            let last = document.line_range(lines.end.checked_sub(1)?)?;
+            Some((lines, first.start..last.end))
+        })
+        .collect();
+    let mut highlighter = ProgressiveHighlights::new(document);
+    if ranges
+        .iter()
+        .any(|(_, range)| highlighter.embeds(document, tree, range.clone()))
+    {
+        return None;
+    }
+    for (lines, range) in ranges {
+        captures[lines].fill(Captures::default());
+        let spans = highlighter.highlight_range(document, tree, range);
+        split_lines(document, &spans, &mut captures);
+    }
+    Some(captures)
+}
+
+///Adds the captures of `spans` to the `lines` they are on.
+fn split_lines(document: &Document, spans: &[(Range<usize>, Highlight)], lines: &mut LineCaptures) {
+    let captures = spans
+        .iter()
+        .filter_map(|(range, highlight)| Some((range.clone(), highlight.capture?)));
+    split_captures(document, captures, lines);
+}
+
+///Adds the `captures`, by byte ranges of the text, to the `lines` they are on.
+fn split_captures(
+    document: &Document,
+    captures: impl IntoIterator<Item = (Range<usize>, tree_sitter_highlight::Highlight)>,
+    lines: &mut LineCaptures,
+) {
+    for (range, capture) in captures {
+        let start = document.position(range.start);
+        let end = document.position(range.end);
+        let spanned = lines
+            .iter_mut()
+            .enumerate()
+            .take(end.line + 1)
+            .skip(start.line);
+        for (line, captures) in spanned {
+            let Some(line_range) = document.line_range(line) else {
+                break;
+            };
+            let from = if line == start.line { start.column } else { 0 };
+            let to = if line == end.line {
+                end.column.min(line_range.len())
+            } else {
+                line_range.len()
+            };
+            if from < to {
+                Arc::make_mut(captures).push((from..to, capture));
+            }
+        }
+    }
+}
+
+///Captures of the text after the edit `shift` of whitespace or of a token, from the `lines` of the text before: the captures of
+///the lines the edit touched move along with the text, the other lines keep theirs.
+fn shifted_captures(document: &Document, lines: &LineCaptures, shift: &Shift) -> LineCaptures {
+    let edit = &shift.edit;
+    let delta = |offset: usize| offset - edit.old_end_byte + edit.new_end_byte;
+    //a capture that starts at inserted whitespace starts after it, one that ends there ends before it. One that starts at
+    //replaced text (the character of a patched token) keeps its start
+    let start = |offset| match offset {
+        offset if offset < edit.start_byte => offset,
+        offset if offset == edit.start_byte && edit.start_byte < edit.old_end_byte => offset,
+        offset if offset < edit.old_end_byte => edit.new_end_byte,
+        offset => delta(offset),
+    };
+    let end = |offset| match offset {
+        offset if offset <= edit.start_byte => offset,
+        offset if offset < edit.old_end_byte => edit.new_end_byte,
+        offset => delta(offset),
+    };
+    //by bytes of the old text. A capture spanning lines is one again, so whitespace added where it continues is part of it. It
+    //continues if it takes the whitespace at the end or start of a line, captures of tokens that just happen to end a line and
+    //start the next one don't
+    let mut spans = Vec::<(Range<usize>, tree_sitter_highlight::Highlight)>::new();
+    //end of the last line with text, and whether it ends in whitespace
+    let mut line_end = None;
+    let mut line_start = shift.old_start;
+    for (line, raw) in shift.old_lines.clone().zip(shift.old_text.split('\n')) {
+        let text = raw.strip_suffix('\r').unwrap_or(raw);
+        for (range, capture) in lines[line].iter() {
+            let range = line_start + range.start..line_start + range.end;
+            match (spans.last_mut(), line_end) {
+                (Some((last, known)), _) if known == capture && last.end == range.start => {
+                    last.end = range.end;
+                }
+                (Some((last, known)), Some((end, trailing)))
+                    if known == capture
+                        && last.end == end
+                        && range.start == line_start
+                        && (trailing || text.starts_with(char::is_whitespace)) =>
+                {
+                    last.end = range.end;
+                }
+                _ => spans.push((range, *capture)),
+            }
+        }
+        if !text.is_empty() {
+            line_end = Some((line_start + text.len(), text.ends_with(char::is_whitespace)));
+        }
+        line_start += raw.len() + 1;
+    }
+    let mut moved = Vec::<(Range<usize>, tree_sitter_highlight::Highlight)>::new();
+    for (range, capture) in spans {
+        let range = start(range.start)..end(range.end);
+        match moved.last_mut() {
+            Some((last, known)) if *known == capture && last.end == range.start => {
+                last.end = range.end;
+            }
+            _ => moved.push((range, capture)),
+        }
+    }
+
+    let mut shifted = Vec::with_capacity(document.line_count());
+    shifted.extend_from_slice(&lines[..shift.old_lines.start]);
+    shifted.resize(shift.lines.end, Captures::default());
+    shifted.extend_from_slice(&lines[shift.old_lines.end..]);
+    split_captures(document, moved, &mut shifted);
+    shifted
+}
+
+///Captures of the lines the texts of `previous` and `current` (the hashes of their lines) have in common at the start and end,
+///from what the `shown` lines of the previous text were.
+fn stale_captures(
+    previous: &[u64],
+    current: &[u64],
+    mut shown: Vec<Option<Captures>>,
+) -> Vec<Option<Captures>> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(previous, current)| previous == current)
+        .count();
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(previous, current)| previous == current)
+        .count();
+    let mut stale = vec![None; current.len()];
+    for line in (0..prefix).chain(current.len() - suffix..current.len()) {
+        let previous_line = if line < prefix {
+            line
+        } else {
+            line + previous.len() - current.len()
+        };
+        stale[line] = shown.get_mut(previous_line).and_then(Option::take);
+    }
+    stale
+}
+
+fn line_hashes(document: &Document) -> Arc<[u64]> {
+    (0..document.line_count())
+        .map(|line| text_hash(document.line(line).unwrap_or_default()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedHighlightState;
+    use crate::{
+        TSSettings, document::WhitespaceEdits, registry::LanguageRegistry, stats::Usage, testing,
+    };
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    const TEXT: &str = "/// Docs\nfn main() {\n    let x = 1;\n    println!(\"{x}\");\n}\n\nstruct Point {\n    x: f32,\n}\n";
+
+    ///Sets the texts one after another, and compares the captures of the lines with the ones of a state that starts out with the
+    ///last text.
+    fn assert_highlighted_like_new(texts: &[&str]) {
+        assert_highlighted_like_new_with(&testing::rust(), texts);
+    }
+
+    fn assert_highlighted_like_new_with(settings: &TSSettings, texts: &[&str]) {
+        let state = SharedHighlightState::new(settings, TEXT);
+        for text in texts {
+            state.set_text(*text);
+            let fresh = SharedHighlightState::new(settings, *text);
+            assert_eq!(
+                state.lock().lines,
+                fresh.lock().lines,
+                "after setting {text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn edits_highlight_like_new_texts() {
+        assert_highlighted_like_new(&[
+            &TEXT.replace("let x = 1", "let x = 12"),
+            &TEXT.replace("let x = 1", "let xy = \"1\""),
+            &TEXT.replace("fn main", "pub fn main"),
+            &TEXT.replace("    x: f32,\n", ""),
+        ]);
+    }
+
+    #[test]
+    fn edits_of_the_syntax_highlight_the_lines_it_changed() {
+        //opening a block comment turns the rest of the text into a comment, closing it turns it back
+        let opened = TEXT.replace("fn main", "/* fn main");
+        assert_highlighted_like_new(&[
+            &opened,
+            &opened.replace("struct", "*/ struct"),
+            TEXT,
+            &TEXT.replace("\"{x}\"", "\"{x}"),
+        ]);
+    }
+
+    #[test]
+    fn edits_of_embedded_code_highlight_it_again() {
+        //the token trees of macros are Rust again
+        let registry = LanguageRegistry::new().with_language("rust", testing::rust());
+        let settings = testing::rust().with_injections(&registry);
+        assert_highlighted_like_new_with(
+            &settings,
+            &[
+                &TEXT.replace("println!(\"{x}\")", "println!(\"{x}\", x + 1)"),
+                &TEXT.replace("let x = 1", "let x = 2"),
+            ],
+        );
+    }
+
+    ///Settings that count the parses, to tell whether edits were moved along or patched instead (which reports a reused tree).
+    fn counting_parses(settings: TSSettings) -> (TSSettings, Arc<AtomicUsize>) {
+        let parses = Arc::new(AtomicUsize::new(0));
+        let counter = parses.clone();
+        let settings = settings.with_stats(move |usage| {
+            if let Usage::Parse { reused: false, .. } = usage {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        (settings, parses)
+    }
+
+    #[test]
+    fn whitespace_edits_move_the_highlights_like_new_texts() {
+        let (settings, parses) =
+            counting_parses(testing::rust().with_whitespace_edits(WhitespaceEdits::Whitespace));
+        let state = SharedHighlightState::new(&settings, TEXT);
+        let texts = [
+            TEXT.replace("fn main() {\n", "fn main() {\n\n"),
+            TEXT.replace("fn main() {\n", "fn main() {\n\n")
+                .replace("    let", "        let"),
+            TEXT.replace("fn main() {\n", "fn main() {\n\n")
+                .replace("    let", "        let")
+                .replace("x = 1", "x =\n    1"),
+        ];
+        for text in &texts {
+            let before = parses.load(Ordering::Relaxed);
+            state.set_text(text.as_str());
+            assert_eq!(
+                parses.load(Ordering::Relaxed),
+                before,
+                "parsed after setting {text:?}"
+            );
+            let fresh = SharedHighlightState::new(&testing::rust(), text.as_str());
+            assert_eq!(
+                state.lock().lines,
+                fresh.lock().lines,
+                "after setting {text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn token_edits_patch_the_highlights_like_new_texts() {
+        let (settings, parses) = counting_parses(testing::rust().with_token_edits(true));
+        let state = SharedHighlightState::new(&settings, TEXT);
+        let texts = [
+            TEXT.replace("let x = 1", "let xy = 1"),
+            TEXT.replace("let x = 1", "let xy = 12"),
+            TEXT.replace("let x = 1", "let x = 12"),
+        ];
+        for text in &texts {
+            let before = parses.load(Ordering::Relaxed);
+            state.set_text(text.as_str());
+            assert_eq!(
+                parses.load(Ordering::Relaxed),
+                before,
+                "parsed after setting {text:?}"
+            );
+            let fresh = SharedHighlightState::new(&testing::rust(), text.as_str());
+            assert_eq!(
+                state.lock().lines,
+                fresh.lock().lines,
+                "after setting {text:?}"
+            );
+        }
+        //settling parses the patched tokens, and keeps the highlights
+        state.settle();
+        let fresh = SharedHighlightState::new(&testing::rust(), texts[2].as_str());
+        assert_eq!(state.lock().lines, fresh.lock().lines);
+    }
+}
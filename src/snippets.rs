@@ -0,0 +1,120 @@
+//! A cache for highlighting the same snippets again and again, like views that rebuild every frame (chat messages, Markdown
+//! code blocks).
+//!
+//! [SnippetCache::highlight] highlights a snippet once, and hands out the spans it got the first time whenever the same text in the
+//! same language comes by again. The cache is bounded by the number of snippets and the size of their texts, the least recently
+//! used snippets are dropped first.
+
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use crate::{
+    Highlight, TSSettings,
+    document::{highlight_with, text_hash},
+};
+
+///Spans of a snippet, sorted and non-overlapping like the ones of [Document::highlights](crate::document::Document::highlights).
+pub type Spans = Arc<[(Range<usize>, Highlight)]>;
+
+///Snippets a [SnippetCache] keeps by default.
+pub const DEFAULT_SNIPPET_LIMIT: usize = 256;
+///Bytes of snippet text a [SnippetCache] keeps by default.
+pub const DEFAULT_BYTE_LIMIT: usize = 1 << 20;
+
+///See the [module](self) documentation.
+pub struct SnippetCache {
+    languages: Vec<TSSettings>,
+    highlighter: tree_sitter_highlight::Highlighter,
+    styles: Vec<Option<Vec<Option<crate::theme::Style>>>>,
+    ///By language, hash and length of the text. The value also holds when the snippet was used last.
+    entries: HashMap<(usize, u64, usize), (Spans, u64)>,
+    bytes: usize,
+    uses: u64,
+    snippet_limit: usize,
+    byte_limit: usize,
+}
+
+impl SnippetCache {
+    ///A cache for snippets in `languages`, matched by `language_name` like in
+    ///[highlight_many](crate::document::highlight_many).
+    pub fn new(languages: Vec<TSSettings>) -> Self {
+        Self {
+            styles: vec![None; languages.len()],
+            languages,
+            highlighter: tree_sitter_highlight::Highlighter::new(),
+            entries: HashMap::new(),
+            bytes: 0,
+            uses: 0,
+            snippet_limit: DEFAULT_SNIPPET_LIMIT,
+            byte_limit: DEFAULT_BYTE_LIMIT,
+        }
+    }
+
+    ///Keeps at most `snippets` snippets, with at most `bytes` of text in total.
+    pub fn with_limits(mut self, snippets: usize, bytes: usize) -> Self {
+        self.snippet_limit = snippets;
+        self.byte_limit = bytes;
+        self.evict();
+        self
+    }
+
+    ///Spans of `text` in `language`, highlighted only if it isn't cached yet. Snippets in languages the cache doesn't know get no
+    ///spans.
+    pub fn highlight(&mut self, text: &str, language: &str) -> Spans {
+        let Some(idx) = self
+            .languages
+            .iter()
+            .position(|settings| settings.tsconfig.language_name == language)
+        else {
+            return Arc::new([]);
+        };
+        self.uses += 1;
+        let key = (idx, text_hash(text), text.len());
+        if let Some((spans, used)) = self.entries.get_mut(&key) {
+            *used = self.uses;
+            return spans.clone();
+        }
+
+        let settings = &self.languages[idx];
+        let styles = self.styles[idx].get_or_insert_with(|| settings.resolve_styles());
+        let spans: Spans = highlight_with(&mut self.highlighter, settings, styles, text).into();
+        //NOTE: would only push everything else out, and then itself
+        if text.len() > self.byte_limit {
+            return spans;
+        }
+        self.entries.insert(key, (spans.clone(), self.uses));
+        self.bytes += text.len();
+        self.evict();
+        spans
+    }
+
+    ///Number of cached snippets.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///Drops all cached snippets.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    ///Drops the least recently used snippets until the cache is within its limits.
+    fn evict(&mut self) {
+        while self.entries.len() > self.snippet_limit || self.bytes > self.byte_limit {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.bytes -= oldest.2;
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! The highlighting of a text as spans the crate owns, for apps that render or process the highlights themselves, like the
+//! [exporters](crate::export) do. See [Document::spans](crate::document::Document::spans).
+//!
+//! A [Highlight] is made for the editor's highlighter: it refers to its capture by the index tree-sitter-highlight
+//! assigns, and carries the editor's focus and overlays. A [Span] only has what the text is: its range, the name of its capture
+//! and the theme's style for it. It is part of the stable API, its fields keep their types and meaning within a major version.
+//! New fields may come with minor versions, so spans are built via [Span::new] outside the crate.
+
+use std::{ops::Range, sync::Arc};
+
+use iced_core::Font;
+
+use crate::{Highlight, theme::Style};
+
+///A piece of highlighted text, see the [module](self) documentation.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Span {
+    ///Byte range of the text.
+    pub range: Range<usize>,
+    ///Name of the innermost capture, like `"comment"` or `"function.builtin"`.
+    pub capture: Arc<str>,
+    ///Style of the capture, if the settings have a [theme](crate::TSSettings::with_theme) that styles it.
+    pub style: Option<Style>,
+}
+
+impl Span {
+    pub fn new(range: Range<usize>, capture: impl Into<Arc<str>>, style: Option<Style>) -> Self {
+        Self {
+            range,
+            capture: capture.into(),
+            style,
+        }
+    }
+
+    ///The span of `highlight` over `range`. `None` for text that isn't captured.
+    pub fn from_highlight(range: Range<usize>, highlight: &Highlight) -> Option<Self> {
+        Some(Self::new(range, highlight.name()?, highlight.style))
+    }
+
+    ///Iced's format for the span, like [to_format](crate::to_format) picks it: the style if there is one, else the palette color
+    ///of the capture.
+    pub fn to_format(
+        &self,
+        theme: &iced_core::Theme,
+    ) -> iced_core::text::highlighter::Format<Font> {
+        match &self.style {
+            Some(style) => style.to_format(),
+            #[cfg(feature = "presets")]
+            None => crate::palette_format(&self.capture, theme.extended_palette()),
+            #[cfg(not(feature = "presets"))]
+            None => {
+                let _ = theme;
+                iced_core::text::highlighter::Format {
+                    color: None,
+                    font: Some(Font::MONOSPACE),
+                }
+            }
+        }
+    }
+}
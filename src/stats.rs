@@ -0,0 +1,44 @@
+//! Opt-in usage statistics: which languages and features an app uses, and what they cost, e.g. to decide which grammars are worth
+//! bundling.
+//!
+//! Nothing leaves the process. The crate calls the hook set via [TSSettings::with_stats](crate::TSSettings::with_stats), and the host
+//! decides what to do with the reports. Without a hook nothing is measured.
+
+use std::{sync::Arc, time::Duration};
+
+///Receives the [Usage] reports, see the [module](self) documentation. Called from whatever thread does the work, so keep it cheap.
+pub type StatsHook = Arc<dyn Fn(&Usage<'_>) + Send + Sync>;
+
+///A report for the [StatsHook].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Usage<'a> {
+    ///A [Document](crate::document::Document) parsed its text, or took the tree from its history, moved it along an edit of
+    ///[whitespace](crate::document::WhitespaceEdits) or patched a [token](crate::TSSettings::with_token_edits) (`reused`).
+    Parse {
+        language: &'a str,
+        bytes: usize,
+        duration: Duration,
+        reused: bool,
+    },
+    ///The [Highlighter](crate::Highlighter) highlighted a line, or looked it up in a
+    ///[SharedHighlightState](crate::shared::SharedHighlightState) (`shared`).
+    HighlightLine {
+        language: &'a str,
+        bytes: usize,
+        duration: Duration,
+        shared: bool,
+    },
+    ///A whole document was highlighted, see [Document::highlights](crate::document::Document::highlights). Progressive highlighting
+    ///reports once it is done, with the time of all steps.
+    HighlightDocument {
+        language: &'a str,
+        bytes: usize,
+        duration: Duration,
+    },
+    ///A feature was used, by name: `folding`, `focus`, `export`, `jump`, `paste`, `format`, `indent`, `autoclose`, `diff`,
+    ///`structural_diff`, `ffi`, `symbols`, or the name of a [structural](crate::structural) command like `move_node`.
+    Feature {
+        language: &'a str,
+        feature: &'static str,
+    },
+}
@@ -0,0 +1,806 @@
+//! Structural editing: commands that act on syntax nodes instead of characters, like moving a function up or swapping two arguments,
+//! and edits that know the language, like joining comment lines or splitting a string.
+//!
+//! Every command works on the [Document]'s syntax tree and returns a [StructuralEdit], which is either applied to the text directly, or
+//! turned into `text_editor` actions via [StructuralEdit::actions], so the edit ends up in the editor like any other.
+
+use std::{ops::Range, sync::Arc};
+
+use iced_core::text::editor::{Action, Edit, Motion};
+
+use crate::{
+    document::{Document, PAIRS, Position},
+    search::is_word_char,
+    words,
+};
+
+///Which sibling a command swaps with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    ///The previous sibling, e.g. the function above.
+    Previous,
+    ///The next sibling, e.g. the function below.
+    Next,
+}
+
+///Delimiters that [Document::splice] removes.
+const DELIMITERS: &[(&str, &str)] = &[("(", ")"), ("[", "]"), ("{", "}"), ("<", ">")];
+
+///Characters comment leaders are made of, see [Document::join_lines].
+const COMMENT_LEADERS: &[char] = &['/', '#', '*', '!', '-', ';', '%'];
+
+///How a language continues a string literal on the next line, see [Document::split_string].
+enum StringSplit {
+    ///An escaped line break, which also skips the next line's indentation.
+    Escape,
+    ///Adjacent literals are concatenated.
+    Adjacent,
+    ///Like [StringSplit::Adjacent], but the line break has to be escaped.
+    Continuation,
+    ///Concatenation with the operator.
+    Operator(&'static str),
+}
+
+fn string_split(language_name: &str) -> Option<StringSplit> {
+    Some(match language_name {
+        "rust" => StringSplit::Escape,
+        "c" | "cpp" | "objc" => StringSplit::Adjacent,
+        "python" => StringSplit::Continuation,
+        "javascript" | "typescript" | "tsx" | "java" | "go" | "c_sharp" | "kotlin" | "swift"
+        | "scala" | "dart" => StringSplit::Operator("+"),
+        "lua" => StringSplit::Operator(".."),
+        "php" | "perl" => StringSplit::Operator("."),
+        _ => return None,
+    })
+}
+
+///What [Document::surround] wraps with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surround<'a> {
+    ///One of the auto-paired characters (brackets and quotes), either the opening or closing one.
+    Pair(char),
+    ///An HTML/XML tag. Either a name (`div`), or a name with attributes (`a href="..."`).
+    Tag(&'a str),
+}
+
+///Naming conventions [Document::convert_case] converts identifiers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Case {
+    ///`snake_case`
+    Snake,
+    ///`camelCase`
+    Camel,
+    ///`PascalCase`
+    Pascal,
+    ///`SCREAMING_SNAKE_CASE`
+    ScreamingSnake,
+}
+
+impl Case {
+    ///`words` (in lowercase) joined in this case.
+    fn join(self, words: &[String]) -> String {
+        let capitalized = |word: &String| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        };
+        match self {
+            Self::Snake => words.join("_"),
+            Self::ScreamingSnake => words.join("_").to_uppercase(),
+            Self::Pascal => words.iter().map(capitalized).collect(),
+            Self::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(idx, word)| {
+                    if idx == 0 {
+                        word.clone()
+                    } else {
+                        capitalized(word)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+///Replaces `range` of the document with `text`, see the [module](self) documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructuralEdit {
+    ///Byte range of the document that is replaced.
+    pub range: Range<usize>,
+    pub text: String,
+    ///Byte offset of the cursor once the edit is applied, so it stays on the node that was acted on.
+    pub cursor: usize,
+}
+
+impl StructuralEdit {
+    ///Applies the edit to `text`, the text of the document the edit was created for.
+    pub fn apply(&self, text: &mut String) {
+        text.replace_range(self.range.clone(), &self.text);
+    }
+
+    ///Actions that perform the edit on a `text_editor::Content` with the text of `document` (the one the edit was created for), and
+    ///place the cursor afterwards.
+    ///
+    ///The cursor is moved line by line, so this assumes that the editor doesn't wrap lines, like the
+    ///[CodeEditor](crate::code_editor::CodeEditor).
+    pub fn actions(&self, document: &Document) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let start = document.position(self.range.start);
+        move_to(&mut actions, start, document.text());
+
+        let replaced = &document.text()[self.range.clone()];
+        actions.extend((0..char_count(replaced)).map(|_| Action::Select(Motion::Right)));
+        actions.push(Action::Edit(if self.text.is_empty() {
+            Edit::Delete
+        } else {
+            Edit::Paste(Arc::new(self.text.clone()))
+        }));
+
+        //where the cursor ends up, in the edited text
+        let mut before_cursor = document.text()[..self.range.start].to_string();
+        before_cursor.push_str(&self.text[..self.cursor - self.range.start]);
+        let line = before_cursor.matches('\n').count();
+        let line_start = before_cursor.rfind('\n').map_or(0, |idx| idx + 1);
+        move_to(
+            &mut actions,
+            Position::new(line, before_cursor.len() - line_start),
+            &before_cursor,
+        );
+        actions
+    }
+}
+
+///Moves the cursor to `position` from the document's start. `text` has to contain the text up to `position`.
+fn move_to(actions: &mut Vec<Action>, position: Position, text: &str) {
+    actions.push(Action::Move(Motion::DocumentStart));
+    actions.extend((0..position.line).map(|_| Action::Move(Motion::Down)));
+    let line_start = text
+        .match_indices('\n')
+        .nth(position.line.wrapping_sub(1))
+        .map_or(0, |(idx, _)| idx + 1);
+    let line = &text[line_start..(line_start + position.column).min(text.len())];
+    actions.extend((0..char_count(line)).map(|_| Action::Move(Motion::Right)));
+}
+
+///Cursor steps over `text`. A `\r\n` line break is a single step.
+fn char_count(text: &str) -> usize {
+    text.chars().count() - text.matches("\r\n").count()
+}
+
+impl Document {
+    ///Swaps the node at `position` with its sibling in `direction`, e.g. to move a function up or down. The node is the outermost
+    ///one that starts at `position`'s line and covers its lines completely, so this moves items and statements as a whole.
+    pub fn move_node(
+        &self,
+        position: impl Into<Position>,
+        direction: Direction,
+    ) -> Option<StructuralEdit> {
+        self.settings().report_feature("move_node");
+        let offset = self.offset(position);
+        let mut node = self.named_node_at(offset)?;
+        loop {
+            if self.covers_lines(node.byte_range())
+                && let Some(sibling) = sibling(node, direction)
+                && self.covers_lines(sibling.byte_range())
+            {
+                return Some(self.swap(node, sibling, offset));
+            }
+            node = node.parent()?;
+        }
+    }
+
+    ///Swaps the argument (or parameter, element, ...) at `position` with its neighbour in `direction`.
+    pub fn swap_arguments(
+        &self,
+        position: impl Into<Position>,
+        direction: Direction,
+    ) -> Option<StructuralEdit> {
+        self.settings().report_feature("swap_arguments");
+        let offset = self.offset(position);
+        let mut node = self.named_node_at(offset)?;
+        loop {
+            let parent = node.parent()?;
+            let kind = parent.kind();
+            if ["argument", "parameter", "array", "tuple", "list"]
+                .iter()
+                .any(|list| kind.contains(list))
+            {
+                return Some(self.swap(node, sibling(node, direction)?, offset));
+            }
+            node = parent;
+        }
+    }
+
+    ///Replaces the parent of the node at `position` with the node, e.g. turns `Some(value)` into `value`.
+    pub fn raise(&self, position: impl Into<Position>) -> Option<StructuralEdit> {
+        self.settings().report_feature("raise");
+        let offset = self.offset(position);
+        let node = self.named_node_at(offset)?;
+        //nodes that span the same text are the same thing for the user
+        let mut parent = node.parent()?;
+        while parent.byte_range() == node.byte_range() {
+            parent = parent.parent()?;
+        }
+        parent.parent()?;
+
+        let range = parent.byte_range();
+        Some(StructuralEdit {
+            text: self.text()[node.byte_range()].to_string(),
+            cursor: range.start + (offset - node.start_byte()),
+            range,
+        })
+    }
+
+    ///Removes the delimiters (parentheses, brackets, braces) of the innermost delimited node around `position`, keeping its content.
+    pub fn splice(&self, position: impl Into<Position>) -> Option<StructuralEdit> {
+        self.settings().report_feature("splice");
+        let offset = self.offset(position);
+        let mut node = self
+            .tree()?
+            .root_node()
+            .descendant_for_byte_range(offset, offset)?;
+        loop {
+            if let (Some(first), Some(last)) = (
+                node.child(0),
+                node.child(node.child_count().saturating_sub(1)),
+            ) && first.id() != last.id()
+                && DELIMITERS
+                    .iter()
+                    .any(|(open, close)| first.kind() == *open && last.kind() == *close)
+                && first.end_byte() <= offset
+                && offset <= last.start_byte()
+            {
+                return Some(StructuralEdit {
+                    range: node.byte_range(),
+                    text: self.text()[first.end_byte()..last.start_byte()].to_string(),
+                    cursor: offset - first.byte_range().len(),
+                });
+            }
+            node = node.parent()?;
+        }
+    }
+
+    ///Joins `line` with the next one, like `J` in vim: the line break and the next line's indentation become a single space (none
+    ///after an opening or before a closing bracket). If both lines are comments, the next line's comment leader (`//`, `#`, `*`, ...)
+    ///is removed as well.
+    pub fn join_lines(&self, line: usize) -> Option<StructuralEdit> {
+        self.settings().report_feature("join_lines");
+        let current = self.line_range(line)?;
+        let next = self.line_range(line + 1)?;
+        let text = self.text();
+        let end = current.start + text[current.clone()].trim_end().len();
+        let mut rest = next.end - text[next.clone()].trim_start().len();
+
+        let is_comment = |offset| self.context_at(self.position(offset)).in_comment;
+        if end > current.start
+            && is_comment(end - 1)
+            && is_comment(rest)
+            && !text[rest..].starts_with("*/")
+        {
+            let leader = text[rest..next.end]
+                .find(|c| !COMMENT_LEADERS.contains(&c))
+                .unwrap_or(next.end - rest);
+            rest += leader;
+            rest = next.end - text[rest..next.end].trim_start().len();
+        }
+
+        let joined = &text[rest..next.end];
+        let separator = if end == current.start
+            || joined.is_empty()
+            || text[..end].ends_with(['(', '['])
+            || joined.starts_with([')', ']', ',', ';', '.', '?'])
+        {
+            ""
+        } else {
+            " "
+        };
+        Some(StructuralEdit {
+            range: end..rest,
+            text: separator.to_string(),
+            cursor: end,
+        })
+    }
+
+    ///Splits the string literal at `position` in two, the second one starting on the next line, aligned with the first one. How the
+    ///string is continued depends on the language: an escaped line break in Rust, adjacent literals in C and Python, the concatenation
+    ///operator in most others. `None` outside of a string, in raw or multi-line strings, and for languages it doesn't know.
+    pub fn split_string(&self, position: impl Into<Position>) -> Option<StructuralEdit> {
+        self.settings().report_feature("split_string");
+        let split = string_split(&self.settings().tsconfig.language_name)?;
+        let offset = self.offset(position);
+        let mut node = self
+            .tree()?
+            .root_node()
+            .descendant_for_byte_range(offset, offset)?;
+        //the outermost string node, parts of the literal (content, escapes...) are often strings as well
+        let mut string = None;
+        loop {
+            if node.kind().contains("string") {
+                string = Some(node);
+            }
+            match node.parent() {
+                Some(parent) => node = parent,
+                None => break,
+            }
+        }
+        let string = string?;
+
+        let text = self.text();
+        let literal = &text[string.byte_range()];
+        let prefix = literal
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(literal.len());
+        let quote = literal[prefix..].chars().next()?;
+        let opening = prefix + quote.len_utf8();
+        if !matches!(quote, '"' | '\'' | '`')
+            || literal[opening..].starts_with(quote)
+            || literal[opening..].contains('\n')
+            || !literal.ends_with(quote)
+            || offset < string.start_byte() + opening
+            || offset >= string.end_byte()
+        {
+            return None;
+        }
+
+        //an escaped line break skips all whitespace after it, so keep the whitespace on the first line
+        let offset = match split {
+            StringSplit::Escape => {
+                let after = &text[offset..string.end_byte()];
+                offset + after.len() - after.trim_start().len()
+            }
+            _ => offset,
+        };
+
+        let line_start = string.start_byte() - string.start_position().column;
+        let leading = &text[line_start..string.start_byte()];
+        let indentation = leading
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(leading.len());
+        let align = format!(
+            "{}{}",
+            &leading[..indentation],
+            " ".repeat(leading[indentation..].chars().count())
+        );
+        let reopen = &literal[..opening];
+        let inserted = match split {
+            //aligned with the content instead
+            StringSplit::Escape => format!("\\\n{align}{}", " ".repeat(reopen.chars().count())),
+            StringSplit::Adjacent => format!("{quote}\n{align}{reopen}"),
+            StringSplit::Continuation => format!("{quote} \\\n{align}{reopen}"),
+            StringSplit::Operator(operator) => format!("{quote} {operator}\n{align}{reopen}"),
+        };
+        Some(StructuralEdit {
+            range: offset..offset,
+            cursor: offset + inserted.len(),
+            text: inserted,
+        })
+    }
+
+    ///Sorts `lines` alphabetically. `None` if they are sorted already.
+    pub fn sort_lines(&self, lines: Range<usize>) -> Option<StructuralEdit> {
+        self.settings().report_feature("sort_lines");
+        let start = self.line_range(lines.start)?.start;
+        let end = self.line_range(lines.end.checked_sub(1)?)?.end;
+        let text = &self.text()[start..end];
+        let mut sorted = text.lines().collect::<Vec<_>>();
+        if sorted.is_sorted() {
+            return None;
+        }
+        sorted.sort();
+        let line_break = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        Some(StructuralEdit {
+            range: start..end,
+            text: sorted.join(line_break),
+            cursor: start,
+        })
+    }
+
+    ///Sorts the node at `position` and its siblings of the same kind, like `use` declarations, match arms or the keys of a JSON object.
+    ///The siblings have to follow each other, separated by nothing but whitespace, `,` and `;`. A blank line ends them, so groups of
+    ///imports stay apart. What is between the siblings stays in place, and a wildcard (`_`) stays last. `None` if they are sorted
+    ///already.
+    pub fn sort_siblings(&self, position: impl Into<Position>) -> Option<StructuralEdit> {
+        self.settings().report_feature("sort_siblings");
+        let offset = self.offset(position);
+        let text = self.text();
+        let follow = |a: tree_sitter::Node<'_>, b: tree_sitter::Node<'_>| {
+            let between = &text[a.end_byte()..b.start_byte()];
+            a.kind() == b.kind()
+                && between
+                    .trim_matches(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                    .is_empty()
+                //a blank line
+                && between.matches('\n').count() < 2
+        };
+
+        let mut node = self.named_node_at(offset)?;
+        let siblings = loop {
+            let mut siblings = vec![node];
+            while let Some(previous) = siblings[0].prev_named_sibling()
+                && follow(previous, siblings[0])
+            {
+                siblings.insert(0, previous);
+            }
+            while let Some(next) = siblings[siblings.len() - 1].next_named_sibling()
+                && follow(siblings[siblings.len() - 1], next)
+            {
+                siblings.push(next);
+            }
+            if siblings.len() > 1 {
+                break siblings;
+            }
+            node = node.parent()?;
+        };
+
+        let is_wildcard = |node: &tree_sitter::Node<'_>| {
+            let element = &text[node.byte_range()];
+            element.starts_with('_')
+                && !element[1..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+        };
+        let mut sorted = siblings.clone();
+        sorted.sort_by_key(|node| (is_wildcard(node), &text[node.byte_range()]));
+        if sorted == siblings {
+            return None;
+        }
+
+        //elements that carry their separator (like match arms) need it everywhere but at the end
+        let separated = siblings
+            .iter()
+            .any(|node| text[node.byte_range()].ends_with(','));
+        let range = siblings[0].start_byte()..siblings[siblings.len() - 1].end_byte();
+        let mut sorted_text = String::new();
+        let mut cursor = range.start;
+        for (idx, (slot, node)) in siblings.iter().zip(&sorted).enumerate() {
+            if node.byte_range().contains(&offset) {
+                cursor = range.start + sorted_text.len() + (offset - node.start_byte());
+            }
+            let element = &text[node.byte_range()];
+            sorted_text.push_str(element);
+            if separated && idx + 1 < siblings.len() && !element.ends_with(',') {
+                sorted_text.push(',');
+            }
+            if let Some(next) = siblings.get(idx + 1) {
+                sorted_text.push_str(&text[slot.end_byte()..next.start_byte()]);
+            }
+        }
+        Some(StructuralEdit {
+            range,
+            text: sorted_text,
+            cursor,
+        })
+    }
+
+    ///Wraps `selection`, or the node at its start if it is empty, with `with`. The cursor ends up after the closing part.
+    ///
+    ///Tags around whole lines go on lines of their own, with the content indented one level deeper.
+    pub fn surround(
+        &self,
+        selection: Range<Position>,
+        with: Surround<'_>,
+    ) -> Option<StructuralEdit> {
+        self.settings().report_feature("surround");
+        let mut range = self.offset(selection.start)..self.offset(selection.end);
+        if range.is_empty() {
+            range = self.named_node_at(range.start)?.byte_range();
+        }
+        let (open, close) = match with {
+            Surround::Pair(c) => {
+                let (open, close) = PAIRS
+                    .iter()
+                    .find(|(open, close)| *open == c || *close == c)?;
+                (open.to_string(), close.to_string())
+            }
+            Surround::Tag(tag) => {
+                let name = tag.split_whitespace().next()?;
+                (format!("<{tag}>"), format!("</{name}>"))
+            }
+        };
+
+        let content = &self.text()[range.clone()];
+        let text = if matches!(with, Surround::Tag(_)) && self.covers_lines(range.clone()) {
+            let line = self.line(self.line_of(range.start)).unwrap_or_default();
+            let indentation = &line[..line.len() - line.trim_start().len()];
+            let unit = self.indent_unit();
+            let indented = content
+                .split('\n')
+                .enumerate()
+                .map(|(idx, line)| {
+                    if line.trim().is_empty() {
+                        line.to_string()
+                    } else if idx == 0 {
+                        format!("{indentation}{unit}{line}")
+                    } else {
+                        format!("{unit}{line}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{open}\n{indented}\n{indentation}{close}")
+        } else {
+            format!("{open}{content}{close}")
+        };
+        Some(StructuralEdit {
+            cursor: range.start + text.len(),
+            range,
+            text,
+        })
+    }
+
+    ///Text to insert at `position`, the start of a line that was just created with Enter, so the previous line's comment (`// `, `/// `,
+    ///`# `...), block comment (` * `) or, in Markdown, list item or quote continues. Includes the previous line's indentation, since
+    ///the `text_editor` doesn't indent on its own. `None` if there's nothing to continue.
+    ///
+    ///Typically used on every [Edit::Enter]: perform it, then insert the continuation with [Edit::Paste].
+    pub fn line_continuation(&self, position: impl Into<Position>) -> Option<String> {
+        self.settings().report_feature("line_continuation");
+        let position = position.into();
+        let previous_line = position.line.checked_sub(1)?;
+        let previous = self.line(previous_line)?;
+        let trimmed = previous.trim_start();
+        let indentation = &previous[..previous.len() - trimmed.len()];
+
+        if self.settings().tsconfig.language_name == "markdown" {
+            return markdown_continuation(trimmed).map(|prefix| format!("{indentation}{prefix}"));
+        }
+
+        let is_block = trimmed.starts_with("/*") || trimmed.starts_with('*');
+        if is_block {
+            //otherwise the comment ended on the previous line
+            if !self.context_at(position).in_comment || trimmed.starts_with("*/") {
+                return None;
+            }
+            let prefix = if trimmed.starts_with("/*") {
+                " * "
+            } else {
+                "* "
+            };
+            return Some(format!("{indentation}{prefix}"));
+        }
+
+        let comment_start = Position::new(previous_line, indentation.len());
+        if !self.context_at(comment_start).in_comment {
+            return None;
+        }
+        let leader = trimmed
+            .find(|c| !COMMENT_LEADERS.contains(&c))
+            .unwrap_or(trimmed.len());
+        (leader > 0).then(|| format!("{indentation}{} ", &trimmed[..leader]))
+    }
+
+    ///Converts the identifier at `position` (or the one it ends) to `case`, like `parse_http_server` to `parseHttpServer`.
+    ///The identifier is the token of the syntax tree, so this doesn't touch the text around it, like the `.` of `self.line_count`.
+    ///Underscores before and after it (`_private`, `__init__`) are kept. `None` if there is no identifier at `position`, it has
+    ///other characters than letters, digits and underscores (`r#type`), or it is in `case` already.
+    pub fn convert_case(
+        &self,
+        position: impl Into<Position>,
+        case: Case,
+    ) -> Option<StructuralEdit> {
+        self.settings().report_feature("convert_case");
+        let offset = self.offset(position);
+        let line = self.line_range(self.line_of(offset))?;
+        let identifier = |offset: usize| {
+            self.word_token(offset, &line)
+                .filter(|token| token.kind().contains("identifier"))
+        };
+        let token = identifier(offset).or_else(|| identifier(offset.checked_sub(1)?))?;
+        let range = token.byte_range();
+        let identifier = &self.text()[range.clone()];
+        if !identifier.chars().all(is_word_char) {
+            return None;
+        }
+
+        let content = identifier.trim_matches('_');
+        let leading = identifier.len() - identifier.trim_start_matches('_').len();
+        let mut parts: Vec<Range<usize>> = Vec::new();
+        for part in words::subwords_of(content) {
+            //digits stay with the word before them, `utf8` or `v2`
+            match parts.last_mut() {
+                Some(last)
+                    if last.end == part.start
+                        && content[part.clone()].starts_with(|c: char| c.is_numeric()) =>
+                {
+                    last.end = part.end
+                }
+                _ => parts.push(part),
+            }
+        }
+        let words: Vec<String> = parts
+            .into_iter()
+            .map(|part| content[part].to_lowercase())
+            .collect();
+        let converted = format!(
+            "{}{}{}",
+            &identifier[..leading],
+            case.join(&words),
+            &identifier[leading + content.len()..]
+        );
+        (converted != identifier).then(|| StructuralEdit {
+            cursor: range.start + converted.len(),
+            range,
+            text: converted,
+        })
+    }
+
+    ///Smallest named node that contains the character at `offset`.
+    fn named_node_at(&self, offset: usize) -> Option<tree_sitter::Node<'_>> {
+        let end = self.text()[offset..]
+            .chars()
+            .next()
+            .map_or(offset, |c| offset + c.len_utf8());
+        self.tree()?
+            .root_node()
+            .named_descendant_for_byte_range(offset, end)
+    }
+
+    ///Whether only whitespace shares lines with `range`.
+    fn covers_lines(&self, range: Range<usize>) -> bool {
+        let text = self.text();
+        let line_start = text[..range.start].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = text[range.end..]
+            .find('\n')
+            .map_or(text.len(), |idx| range.end + idx);
+        text[line_start..range.start].trim().is_empty()
+            && text[range.end..line_end].trim().is_empty()
+    }
+
+    ///Swaps the text of `node` and `other`, keeping what is between them. The cursor at `offset` (within `node`) moves along.
+    fn swap(
+        &self,
+        node: tree_sitter::Node<'_>,
+        other: tree_sitter::Node<'_>,
+        offset: usize,
+    ) -> StructuralEdit {
+        let (first, second) = if node.start_byte() < other.start_byte() {
+            (node, other)
+        } else {
+            (other, node)
+        };
+        let text = self.text();
+        let between = &text[first.end_byte()..second.start_byte()];
+        let swapped = format!(
+            "{}{between}{}",
+            &text[second.byte_range()],
+            &text[first.byte_range()]
+        );
+
+        let range = first.start_byte()..second.end_byte();
+        //where `node` starts once swapped
+        let node_start = if node.id() == first.id() {
+            range.start + second.byte_range().len() + between.len()
+        } else {
+            range.start
+        };
+        StructuralEdit {
+            range,
+            text: swapped,
+            cursor: node_start + (offset - node.start_byte()),
+        }
+    }
+}
+
+///Prefix that continues the Markdown list item or quote `line` (without indentation) starts. `None` for empty items, which end the
+///list.
+fn markdown_continuation(line: &str) -> Option<String> {
+    if let Some(quoted) = line.strip_prefix('>') {
+        return Some(format!(
+            "> {}",
+            markdown_continuation(quoted.trim_start()).unwrap_or_default()
+        ));
+    }
+
+    let (prefix, content) = if let Some(content) = ["- [ ] ", "- [x] ", "- [X] "]
+        .iter()
+        .find_map(|task| line.strip_prefix(task))
+    {
+        ("- [ ] ".to_string(), content)
+    } else if let Some(content) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|bullet| line.strip_prefix(bullet))
+    {
+        (line[..2].to_string(), content)
+    } else {
+        let digits = line.find(|c: char| !c.is_ascii_digit())?;
+        let number: usize = line[..digits].parse().ok()?;
+        let delimiter = line[digits..].chars().next()?;
+        if !matches!(delimiter, '.' | ')') {
+            return None;
+        }
+        let content = line[digits + delimiter.len_utf8()..].strip_prefix(' ')?;
+        (format!("{}{delimiter} ", number.checked_add(1)?), content)
+    };
+    (!content.trim().is_empty()).then_some(prefix)
+}
+
+fn sibling<'a>(node: tree_sitter::Node<'a>, direction: Direction) -> Option<tree_sitter::Node<'a>> {
+    match direction {
+        Direction::Previous => node.prev_named_sibling(),
+        Direction::Next => node.next_named_sibling(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StructuralEdit, markdown_continuation};
+    use crate::{document::Document, testing};
+
+    ///Text of `text` once `edit` is applied.
+    fn applied(text: &str, edit: Option<StructuralEdit>) -> String {
+        let edit = edit.expect("an edit");
+        let mut text = text.to_string();
+        text.replace_range(edit.range, &edit.text);
+        text
+    }
+
+    #[test]
+    fn continues_lists() {
+        assert_eq!(markdown_continuation("- item").as_deref(), Some("- "));
+        assert_eq!(
+            markdown_continuation("- [x] done").as_deref(),
+            Some("- [ ] ")
+        );
+        assert_eq!(markdown_continuation("9) item").as_deref(), Some("10) "));
+        assert_eq!(
+            markdown_continuation("> 1. quoted").as_deref(),
+            Some("> 2. ")
+        );
+        assert_eq!(markdown_continuation("1. "), None);
+    }
+
+    #[test]
+    fn ignores_other_delimiters() {
+        assert_eq!(markdown_continuation("1€ item"), None);
+        assert_eq!(markdown_continuation("1: item"), None);
+        assert_eq!(markdown_continuation("12"), None);
+    }
+
+    #[test]
+    fn ignores_numbers_that_overflow() {
+        assert_eq!(markdown_continuation(&format!("{}. x", usize::MAX)), None);
+        assert_eq!(markdown_continuation("99999999999999999999999. x"), None);
+    }
+
+    #[test]
+    fn sorts_lines() {
+        let text = "c\r\na\r\nb\r\nz";
+        let document = Document::new(&testing::rust(), text);
+        assert_eq!(applied(text, document.sort_lines(0..3)), "a\r\nb\r\nc\r\nz");
+        assert_eq!(document.sort_lines(1..3), None);
+        assert_eq!(document.sort_lines(0..0), None);
+    }
+
+    #[test]
+    fn sorts_use_declarations_by_group() {
+        let text = "use c;\nuse a::{y, x};\nuse b;\n\nuse e;\nuse d;\n";
+        let document = Document::new(&testing::rust(), text);
+        assert_eq!(
+            applied(text, document.sort_siblings((1, 4))),
+            "use a::{y, x};\nuse b;\nuse c;\n\nuse e;\nuse d;\n"
+        );
+        //the innermost siblings, the items of the list
+        assert_eq!(
+            applied(text, document.sort_siblings((1, 8))),
+            "use c;\nuse a::{x, y};\nuse b;\n\nuse e;\nuse d;\n"
+        );
+        //the cursor stays on `y`
+        assert_eq!(document.sort_siblings((1, 8)).unwrap().cursor, 18);
+    }
+
+    #[test]
+    fn sorts_match_arms_with_the_wildcard_last_and_separated() {
+        let text = "fn f(x: u8) {\n    match x {\n        _ => {}\n        2 => b(),\n        1 => {}\n    }\n}\n";
+        let document = Document::new(&testing::rust(), text);
+        let sorted = applied(text, document.sort_siblings((3, 8)));
+        assert_eq!(
+            sorted,
+            "fn f(x: u8) {\n    match x {\n        1 => {},\n        2 => b(),\n        _ => {}\n    }\n}\n"
+        );
+        assert_eq!(
+            Document::new(&testing::rust(), sorted).sort_siblings((3, 8)),
+            None
+        );
+    }
+}
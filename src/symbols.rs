@@ -0,0 +1,245 @@
+//! Symbol search without a language server: the definitions (functions, types, modules...) of a document, or of all documents of a
+//! [Workspace], e.g. for a quick-open symbols palette.
+//!
+//! Definitions are found by the tags query of the document's settings, see
+//! [TSSettings::with_tags_query](crate::TSSettings::with_tags_query). The bundled languages have one where the grammar ships it.
+
+use std::{collections::BTreeMap, ops::Range, sync::Arc};
+
+use tree_sitter::StreamingIterator;
+
+use crate::{
+    document::{Document, Position},
+    shared::SharedHighlightState,
+};
+
+///A definition, like a function or a type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub name: String,
+    ///What is defined, as the tags query calls it: `function`, `method`, `class`, `interface`, `module`, `macro`...
+    pub kind: String,
+    ///Byte range of the name.
+    pub range: Range<usize>,
+    ///Start of the name.
+    pub position: Position,
+}
+
+///A [Symbol] found by [Workspace::find_symbol].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SymbolMatch {
+    ///Name the document was added with.
+    pub document: String,
+    pub symbol: Symbol,
+}
+
+impl Document {
+    ///Definitions in the document, sorted by position. Empty without a tags query.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let settings = self.settings();
+        let (Some(query), Some(tree)) = (settings.tags.as_deref(), self.tree()) else {
+            return Vec::new();
+        };
+        let Some(name) = query.capture_index_for_name("name") else {
+            return Vec::new();
+        };
+        settings.report_feature("symbols");
+
+        let text = self.text();
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let mut matches = cursor.matches(query, tree.root_node(), text.as_bytes());
+        //by the range of the name. The first pattern naming a node decides the kind, e.g. methods come before functions
+        let mut found = BTreeMap::<(usize, usize), (usize, &str)>::new();
+        while let Some(query_match) = matches.next() {
+            let Some(node) = query_match
+                .captures
+                .iter()
+                .find(|capture| capture.index == name)
+                .map(|capture| capture.node)
+            else {
+                continue;
+            };
+            let Some(kind) = query_match.captures.iter().find_map(|capture| {
+                query.capture_names()[capture.index as usize].strip_prefix("definition.")
+            }) else {
+                continue;
+            };
+            let entry = found
+                .entry((node.start_byte(), node.end_byte()))
+                .or_insert((query_match.pattern_index, kind));
+            if query_match.pattern_index < entry.0 {
+                *entry = (query_match.pattern_index, kind);
+            }
+        }
+
+        found
+            .into_iter()
+            .map(|((start, end), (_, kind))| Symbol {
+                name: text[start..end].to_string(),
+                kind: kind.to_string(),
+                range: start..end,
+                position: self.position(start),
+            })
+            .collect()
+    }
+
+    ///The [symbols](Document::symbols) whose name matches `pattern`, best matches first. See [Workspace::find_symbol] for the
+    ///pattern.
+    pub fn find_symbol(&self, pattern: &str) -> Vec<Symbol> {
+        let mut found = matching(pattern, self.symbols().into_iter(), |symbol| &symbol.name);
+        found.sort_by_key(|(rank, symbol)| (*rank, symbol.name.len()));
+        found.into_iter().map(|(_, symbol)| symbol).collect()
+    }
+}
+
+///Documents to find symbols in, by name (their path, for instance). See the [module](self) documentation.
+#[derive(Default)]
+pub struct Workspace {
+    documents: Vec<Entry>,
+}
+
+struct Entry {
+    name: String,
+    state: SharedHighlightState,
+    ///Symbols of the text with the hash, found when the document was searched last.
+    symbols: Option<(u64, Arc<[Symbol]>)>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Adds the document of `state` as `name`, or replaces the document that had the name. Searches see the text the state has at
+    ///that time, so there is nothing to update after an edit.
+    pub fn add(&mut self, name: impl Into<String>, state: &SharedHighlightState) {
+        let name = name.into();
+        let entry = Entry {
+            name,
+            state: state.clone(),
+            symbols: None,
+        };
+        match self
+            .documents
+            .iter_mut()
+            .find(|document| document.name == entry.name)
+        {
+            Some(document) => *document = entry,
+            None => self.documents.push(entry),
+        }
+    }
+
+    ///Removes the document called `name`. Whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.documents.len();
+        self.documents.retain(|document| document.name != name);
+        self.documents.len() != count
+    }
+
+    ///Names of the documents, in the order they were added.
+    pub fn documents(&self) -> impl Iterator<Item = &str> {
+        self.documents.iter().map(|document| document.name.as_str())
+    }
+
+    ///Symbols of all documents whose name matches `pattern`. `*` matches any number of characters and `?` a single one, and then
+    ///the whole name has to match: `parse_*` finds `parse_args`, but not `reparse`. A pattern without either finds every name that
+    ///contains it.
+    ///
+    ///Case is ignored, but exact matches come first, then names that start with the pattern, shorter names before longer ones.
+    ///Among equally good matches, the documents keep the order they were added in. Each document is only searched for definitions
+    ///again after its text changed.
+    pub fn find_symbol(&mut self, pattern: &str) -> Vec<SymbolMatch> {
+        let mut found = Vec::new();
+        for document in &mut self.documents {
+            let hash = document.state.with_document(Document::hash);
+            let symbols = match &document.symbols {
+                Some((known, symbols)) if *known == hash => symbols.clone(),
+                _ => {
+                    let symbols: Arc<[Symbol]> =
+                        document.state.with_document(Document::symbols).into();
+                    document.symbols = Some((hash, symbols.clone()));
+                    symbols
+                }
+            };
+            found.extend(
+                matching(pattern, symbols.iter(), |symbol| &symbol.name)
+                    .into_iter()
+                    .map(|(rank, symbol)| {
+                        (
+                            rank,
+                            SymbolMatch {
+                                document: document.name.clone(),
+                                symbol: symbol.clone(),
+                            },
+                        )
+                    }),
+            );
+        }
+        found.sort_by_key(|(rank, found)| (*rank, found.symbol.name.len()));
+        found.into_iter().map(|(_, found)| found).collect()
+    }
+}
+
+///The `items` whose name matches `pattern`, with how well (lower is better).
+fn matching<T>(
+    pattern: &str,
+    items: impl Iterator<Item = T>,
+    name: impl Fn(&T) -> &str,
+) -> Vec<(u8, T)> {
+    let lowercase = pattern.to_lowercase();
+    let wildcards = pattern.contains(['*', '?']);
+    let glob_pattern = lowercase.chars().collect::<Vec<_>>();
+    items
+        .filter_map(|item| {
+            let name_of = name(&item);
+            let rank = if name_of == pattern {
+                0
+            } else {
+                let lower = name_of.to_lowercase();
+                if lower == lowercase {
+                    1
+                } else if wildcards {
+                    if !glob(&glob_pattern, &lower.chars().collect::<Vec<_>>()) {
+                        return None;
+                    }
+                    2
+                } else if lower.starts_with(&lowercase) {
+                    2
+                } else if lower.contains(&lowercase) {
+                    3
+                } else {
+                    return None;
+                }
+            };
+            Some((rank, item))
+        })
+        .collect()
+}
+
+///Whether all of `name` matches `pattern`, with `*` for any number of characters and `?` for one.
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    //where to go on if the text after the last `*` doesn't match: the pattern after it, and the name one further
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => {
+                let Some((after, from)) = star else {
+                    return false;
+                };
+                star = Some((after, from + 1));
+                p = after;
+                n = from + 1;
+            }
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,354 @@
+//! Tables of Markdown (`| a | b |`) and Org, with the boundaries of their cells, so hosts can align and format tables and move
+//! from cell to cell with Tab.
+//!
+//! [Document::tables] lists the tables of the syntax tree, [Document::table_at] the one at a position. A [Table] has its
+//! [rows](TableRow), with the [cells](TableCell) between the `|` of each, and the [Alignment] of its columns from the
+//! delimiter row (`|:--|--:|`). [Table::next_cell] and [Table::previous_cell] find the cell to move to. The tables come from the
+//! maintained tree, so they are only found in documents of a grammar that parses them, like the bundled Markdown one.
+
+use std::ops::Range;
+
+use crate::document::{Document, Position};
+
+///Node kinds of a table grammar, see [TABLE_NODES].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TableNodes {
+    pub table: &'static str,
+    ///Rows with cells, the header included.
+    pub rows: &'static [&'static str],
+    ///The rows between the header and the body, `|---|` or `|-+-|`.
+    pub delimiter: &'static str,
+    ///Cells of the rows, and of the delimiter row.
+    pub cells: &'static [&'static str],
+}
+
+///Node kinds of tables by the language name of their configuration. These are the kinds of the Markdown and Org grammars the
+///crate bundles.
+pub const TABLE_NODES: &[(&str, TableNodes)] = &[
+    (
+        "markdown",
+        TableNodes {
+            table: "pipe_table",
+            rows: &["pipe_table_header", "pipe_table_row"],
+            delimiter: "pipe_table_delimiter_row",
+            cells: &["pipe_table_cell", "pipe_table_delimiter_cell"],
+        },
+    ),
+    (
+        "org",
+        TableNodes {
+            table: "table",
+            rows: &["row"],
+            delimiter: "hr",
+            cells: &["cell"],
+        },
+    ),
+];
+
+///How the text of a column is aligned, by the colons of its delimiter cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Alignment {
+    ///`---`
+    #[default]
+    None,
+    ///`:--`
+    Left,
+    ///`:-:`
+    Center,
+    ///`--:`
+    Right,
+}
+
+///Whether a row is part of the header, the body, or the delimiter between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RowKind {
+    ///The rows before the delimiter row. Tables without one have no header.
+    Header,
+    Delimiter,
+    Body,
+}
+
+///A cell of a [TableRow].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TableCell {
+    ///Byte range between the `|` around the cell (or the start or end of the row), with the padding.
+    pub range: Range<usize>,
+    ///Byte range of the text of the cell, without the padding. Empty cells have an empty one, after the first space of the
+    ///padding, where the text would be typed.
+    pub content: Range<usize>,
+}
+
+///A row of a [Table].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TableRow {
+    ///Byte range of the row, without its line break.
+    pub range: Range<usize>,
+    pub line: usize,
+    pub kind: RowKind,
+    ///The cells of the row, in order. Empty for delimiter rows of Org (`|---+---|`).
+    pub cells: Vec<TableCell>,
+}
+
+///A table, see the [module](self) documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Table {
+    ///Byte range of the whole table, without the line break after it.
+    pub range: Range<usize>,
+    pub rows: Vec<TableRow>,
+    ///Alignment of each column, by the delimiter row. Columns it has no cell for aren't aligned.
+    pub alignments: Vec<Alignment>,
+}
+
+impl Table {
+    ///Number of columns, the cells of the longest row.
+    pub fn columns(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|row| row.cells.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    ///Alignment of `column`, see [Table::alignments].
+    pub fn alignment(&self, column: usize) -> Alignment {
+        self.alignments.get(column).copied().unwrap_or_default()
+    }
+
+    ///Row and column of the cell at the byte `offset`, the ends of the cell included. `None` on a `|` between two cells of a
+    ///row, or outside of the rows.
+    pub fn cell_at(&self, offset: usize) -> Option<(usize, usize)> {
+        self.rows.iter().enumerate().find_map(|(row, cells)| {
+            cells
+                .cells
+                .iter()
+                .position(|cell| cell.range.start <= offset && offset <= cell.range.end)
+                .map(|column| (row, column))
+        })
+    }
+
+    ///The cell after the one at `offset`, in reading order, skipping delimiter rows. `None` after the last cell.
+    pub fn next_cell(&self, offset: usize) -> Option<&TableCell> {
+        self.cells().find(|cell| cell.range.start > offset)
+    }
+
+    ///The cell before the one at `offset`, in reading order, skipping delimiter rows. `None` before the first cell.
+    pub fn previous_cell(&self, offset: usize) -> Option<&TableCell> {
+        self.cells()
+            .take_while(|cell| cell.range.end < offset)
+            .last()
+    }
+
+    ///The cells to move between, in reading order.
+    fn cells(&self) -> impl Iterator<Item = &TableCell> {
+        self.rows
+            .iter()
+            .filter(|row| row.kind != RowKind::Delimiter)
+            .flat_map(|row| &row.cells)
+    }
+}
+
+impl Document {
+    ///The tables of the document, in order. Empty for grammars without tables (see [TABLE_NODES]), and without a tree.
+    pub fn tables(&self) -> Vec<Table> {
+        self.settings().report_feature("tables");
+        let Some(tree) = self.tree() else {
+            return Vec::new();
+        };
+        let language = &self.settings().tsconfig.language_name;
+        let Some((_, nodes)) = TABLE_NODES.iter().find(|(name, _)| name == language) else {
+            return Vec::new();
+        };
+
+        let mut tables = Vec::new();
+        let mut cursor = tree.walk();
+        let mut descend = true;
+        loop {
+            let node = cursor.node();
+            if node.kind() == nodes.table {
+                tables.push(self.table(node, nodes));
+            } else if descend && cursor.goto_first_child() {
+                continue;
+            }
+            descend = cursor.goto_next_sibling();
+            if !descend && !cursor.goto_parent() {
+                return tables;
+            }
+        }
+    }
+
+    ///The table at `position`, `None` if it isn't in one.
+    pub fn table_at(&self, position: impl Into<Position>) -> Option<Table> {
+        let offset = self.offset(position);
+        self.tables()
+            .into_iter()
+            .find(|table| table.range.start <= offset && offset <= table.range.end)
+    }
+
+    fn table(&self, node: tree_sitter::Node, nodes: &TableNodes) -> Table {
+        let mut rows = Vec::new();
+        let mut alignments = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            let delimiter = child.kind() == nodes.delimiter;
+            if !delimiter && !nodes.rows.contains(&child.kind()) {
+                continue;
+            }
+            //the row ends before its line break, if the grammar counts it in
+            let start = child.start_byte();
+            let range = start
+                ..start
+                    + self.text()[child.byte_range()]
+                        .trim_end_matches(['\n', '\r'])
+                        .len();
+            let mut cells = Vec::new();
+            let mut cell_cursor = child.walk();
+            for cell in child.named_children(&mut cell_cursor) {
+                if !nodes.cells.contains(&cell.kind()) {
+                    continue;
+                }
+                if delimiter {
+                    alignments.push(alignment(cell));
+                }
+                cells.push(self.cell(cell, range.clone()));
+            }
+            rows.push(TableRow {
+                line: child.start_position().row,
+                kind: if delimiter {
+                    RowKind::Delimiter
+                } else {
+                    RowKind::Body
+                },
+                range,
+                cells,
+            });
+        }
+        //the rows before the delimiter row are the header
+        if let Some(delimiter) = rows.iter().position(|row| row.kind == RowKind::Delimiter) {
+            for row in &mut rows[..delimiter] {
+                row.kind = RowKind::Header;
+            }
+        }
+        Table {
+            range: node.start_byte()..rows.last().map_or(node.end_byte(), |row| row.range.end),
+            rows,
+            alignments,
+        }
+    }
+
+    fn cell(&self, node: tree_sitter::Node, row: Range<usize>) -> TableCell {
+        let text = self.text();
+        //the cells of some grammars start after the padding, or take the `|` before or after them
+        let start = if text[node.start_byte()..].starts_with('|') {
+            node.start_byte() + 1
+        } else {
+            row.start
+                + text[row.start..node.start_byte()]
+                    .trim_end_matches([' ', '\t'])
+                    .len()
+        };
+        let mut end = node.end_byte().min(row.end);
+        if end > start && text[..end].ends_with('|') {
+            end -= 1;
+        }
+        end = row.end - text[end..row.end].trim_start_matches([' ', '\t']).len();
+        let padded = &text[start..end];
+        let trimmed = padded.trim_start();
+        let content_start = end - trimmed.len();
+        let content = if trimmed.is_empty() {
+            let padding = padded.chars().next().map_or(0, char::len_utf8);
+            start + padding..start + padding
+        } else {
+            content_start..content_start + trimmed.trim_end().len()
+        };
+        TableCell {
+            range: start..end,
+            content,
+        }
+    }
+}
+
+///Alignment of a delimiter cell, by its colons.
+fn alignment(cell: tree_sitter::Node) -> Alignment {
+    let mut cursor = cell.walk();
+    let kinds: Vec<&str> = cell
+        .children(&mut cursor)
+        .map(|child| child.kind())
+        .collect();
+    let left = kinds.contains(&"pipe_table_align_left");
+    let right = kinds.contains(&"pipe_table_align_right");
+    match (left, right) {
+        (true, true) => Alignment::Center,
+        (true, false) => Alignment::Left,
+        (false, true) => Alignment::Right,
+        (false, false) => Alignment::None,
+    }
+}
+
+#[cfg(all(test, feature = "lang-markdown"))]
+mod tests {
+    use super::{Alignment, RowKind};
+    use crate::{TSSettings, document::Document, testing};
+
+    const TEXT: &str = "Text\n\n| a | b | c |\n|:--|:-:|--:|\n| 1 |   | 3 |\n\nafter\n";
+
+    #[test]
+    fn extracts_the_rows_cells_and_alignments() {
+        let document = Document::new(&TSSettings::markdown().unwrap(), TEXT);
+        let [table] = document.tables().try_into().unwrap();
+        assert_eq!(
+            &TEXT[table.range.clone()],
+            "| a | b | c |\n|:--|:-:|--:|\n| 1 |   | 3 |"
+        );
+        assert_eq!(
+            table
+                .rows
+                .iter()
+                .map(|row| (row.line, row.kind))
+                .collect::<Vec<_>>(),
+            [
+                (2, RowKind::Header),
+                (3, RowKind::Delimiter),
+                (4, RowKind::Body)
+            ]
+        );
+        assert_eq!(table.columns(), 3);
+        assert_eq!(
+            (0..4)
+                .map(|column| table.alignment(column))
+                .collect::<Vec<_>>(),
+            [
+                Alignment::Left,
+                Alignment::Center,
+                Alignment::Right,
+                Alignment::None
+            ]
+        );
+        let body = &table.rows[2];
+        assert_eq!(
+            body.cells
+                .iter()
+                .map(|cell| &TEXT[cell.content.clone()])
+                .collect::<Vec<_>>(),
+            ["1", "", "3"]
+        );
+        //the empty cell's text would go after the first space
+        assert_eq!(body.cells[1].content.start, body.cells[1].range.start + 1);
+
+        let a = TEXT.find('a').unwrap();
+        assert_eq!(table.cell_at(a), Some((0, 0)));
+        assert_eq!(table.cell_at(a - 2), None);
+        let next = table.next_cell(a).unwrap();
+        assert_eq!(&TEXT[next.content.clone()], "b");
+        //the delimiter row is skipped
+        let one = TEXT.find('1').unwrap();
+        let previous = table.previous_cell(one).unwrap();
+        assert_eq!(&TEXT[previous.content.clone()], "c");
+        assert!(table.previous_cell(a).is_none());
+        assert!(table.next_cell(TEXT.find('3').unwrap()).is_none());
+
+        assert!(document.table_at((2, 3)).is_some());
+        assert!(document.table_at((0, 0)).is_none());
+        let rust = Document::new(&testing::rust(), "// | a | b |");
+        assert!(rust.tables().is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+//! Task list items of Markdown (`- [ ] todo`, `- [x] done`), so task-management apps can draw checkboxes over the editor and
+//! toggle them.
+//!
+//! [Document::task_items] lists the items of the syntax tree with their states, in order, nested ones included.
+//! [TaskItem::toggle] and [Document::toggle_task] make the [StructuralEdit] that checks or unchecks one, to apply like the
+//! other edits of the [structural](crate::structural) module.
+
+use std::ops::Range;
+
+use crate::{
+    document::{Document, Position},
+    structural::StructuralEdit,
+};
+
+///A task list item, see the [module](self) documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TaskItem {
+    ///Byte range of the checkbox, `[ ]` or `[x]`.
+    pub marker: Range<usize>,
+    ///Byte range of the whole list item, with its nested items.
+    pub item: Range<usize>,
+    ///Line of the checkbox.
+    pub line: usize,
+    pub checked: bool,
+}
+
+impl TaskItem {
+    ///The edit that checks the item if it's unchecked, and unchecks it otherwise. Leaves the cursor behind the checkbox.
+    pub fn toggle(&self) -> StructuralEdit {
+        StructuralEdit {
+            range: self.marker.start + 1..self.marker.end - 1,
+            text: if self.checked { " " } else { "x" }.to_string(),
+            cursor: self.marker.end,
+        }
+    }
+}
+
+impl Document {
+    ///The task list items of the document, in order. Empty for other grammars than Markdown, and without a tree.
+    pub fn task_items(&self) -> Vec<TaskItem> {
+        self.settings().report_feature("tasks");
+        let Some(tree) = self.tree() else {
+            return Vec::new();
+        };
+        let mut items = Vec::new();
+        let mut cursor = tree.walk();
+        let mut descend = true;
+        loop {
+            let node = cursor.node();
+            let checked = match node.kind() {
+                "task_list_marker_checked" => Some(true),
+                "task_list_marker_unchecked" => Some(false),
+                _ => None,
+            };
+            if let Some(checked) = checked {
+                let item = node.parent().unwrap_or(node);
+                items.push(TaskItem {
+                    marker: node.byte_range(),
+                    item: item.byte_range(),
+                    line: node.start_position().row,
+                    checked,
+                });
+            }
+            if descend && cursor.goto_first_child() {
+                continue;
+            }
+            descend = cursor.goto_next_sibling();
+            if !descend && !cursor.goto_parent() {
+                return items;
+            }
+        }
+    }
+
+    ///Toggles the task item on the line of `position`, see [TaskItem::toggle]. `None` if there is none.
+    pub fn toggle_task(&self, position: impl Into<Position>) -> Option<StructuralEdit> {
+        let line = self.line_of(self.offset(position));
+        self.task_items()
+            .into_iter()
+            .find(|item| item.line == line)
+            .map(|item| item.toggle())
+    }
+}
+
+#[cfg(all(test, feature = "lang-markdown"))]
+mod tests {
+    use crate::{TSSettings, document::Document, testing};
+
+    #[test]
+    fn lists_and_toggles_the_task_items() {
+        let text = "# Todo\n\n- [ ] write\n  - [x] nested\n- [y] no task\n- plain\n";
+        let mut document = Document::new(&TSSettings::markdown().unwrap(), text);
+        let items = document.task_items();
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (&text[item.marker.clone()], item.line, item.checked))
+                .collect::<Vec<_>>(),
+            [("[ ]", 2, false), ("[x]", 3, true)]
+        );
+        //the nested item is part of its parent
+        assert!(items[0].item.contains(&items[1].marker.start));
+
+        assert_eq!(document.toggle_task((4, 0)), None);
+        let edit = document.toggle_task((2, 5)).unwrap();
+        assert_eq!(edit.cursor, items[0].marker.end);
+        document.edit(edit.range, &edit.text);
+        let edit = items[1].toggle();
+        document.edit(edit.range, &edit.text);
+        assert!(
+            document
+                .text()
+                .starts_with("# Todo\n\n- [x] write\n  - [ ] nested\n")
+        );
+        //other grammars have no task items
+        let rust = Document::new(&testing::rust(), "// - [ ] todo");
+        assert!(rust.task_items().is_empty());
+    }
+}
@@ -0,0 +1,31 @@
+//! Settings for the tests, from the Rust grammar of the dev-dependencies, so they run without the `lang-*` features.
+
+use crate::TSSettings;
+
+///Highlight names of the tests, enough for the captures they look at.
+pub const NAMES: &[&str] = &[
+    "attribute",
+    "comment",
+    "constant",
+    "function",
+    "keyword",
+    "number",
+    "operator",
+    "punctuation",
+    "string",
+    "type",
+    "variable",
+];
+
+///Rust, with the queries of its grammar.
+pub fn rust() -> TSSettings {
+    let config = tree_sitter_highlight::HighlightConfiguration::new(
+        tree_sitter_rust::LANGUAGE.into(),
+        "rust",
+        tree_sitter_rust::HIGHLIGHTS_QUERY,
+        tree_sitter_rust::INJECTIONS_QUERY,
+        "",
+    )
+    .expect("the grammar's queries compile");
+    TSSettings::with_highlight_names(config, NAMES)
+}
@@ -0,0 +1,585 @@
+//! Importing the themes of other editors: TextMate and Sublime Text `.tmTheme` files via [Theme::from_tm_theme], and VSCode color
+//! themes via [Theme::from_vscode].
+//!
+//! Those themes style TextMate scopes like `entity.name.function` or `keyword.control`, which the importer maps to the captures
+//! of tree-sitter by [SCOPES]. Like in TextMate, a rule styles a scope and the scopes below it (`keyword` styles
+//! `keyword.control`), the most specific rule wins, and the colors and the font style are picked independently. Rules that only
+//! apply within other scopes (`meta.embedded keyword`) are skipped, tree-sitter has no such context.
+
+use iced_core::Color;
+
+use crate::{
+    color,
+    theme::{ImportError, Style, Theme},
+};
+
+///The TextMate scopes each standard capture is styled like, most fitting first. A scope also matches the rules of its parents,
+///so only scopes of different branches are listed. Captures that aren't listed are styled like the scope of the same name, so
+///`string.special.path` gets the style of `string`.
+pub const SCOPES: &[(&str, &[&str])] = &[
+    (
+        "attribute",
+        &["entity.other.attribute-name", "meta.attribute"],
+    ),
+    ("comment", &["comment"]),
+    ("constant", &["variable.other.constant", "constant.other"]),
+    ("constant.builtin", &["constant.language"]),
+    (
+        "constructor",
+        &["entity.name.function.constructor", "entity.name.type"],
+    ),
+    ("embedded", &["meta.embedded"]),
+    ("error", &["invalid"]),
+    ("function", &["entity.name.function", "support.function"]),
+    (
+        "function.builtin",
+        &["support.function", "entity.name.function"],
+    ),
+    ("keyword", &["keyword.control", "storage.type", "keyword"]),
+    (
+        "module",
+        &[
+            "entity.name.namespace",
+            "entity.name.module",
+            "entity.name.type",
+        ],
+    ),
+    ("number", &["constant.numeric"]),
+    ("operator", &["keyword.operator"]),
+    (
+        "property",
+        &["variable.other.property", "support.type.property-name"],
+    ),
+    (
+        "property.builtin",
+        &["support.variable.property", "variable.other.property"],
+    ),
+    ("punctuation", &["punctuation"]),
+    (
+        "punctuation.bracket",
+        &["punctuation.section.brackets", "punctuation"],
+    ),
+    (
+        "punctuation.delimiter",
+        &["punctuation.separator", "punctuation"],
+    ),
+    (
+        "punctuation.special",
+        &["punctuation.section.embedded", "punctuation"],
+    ),
+    ("string", &["string.quoted", "string"]),
+    ("string.special", &["string.regexp", "string"]),
+    ("tag", &["entity.name.tag"]),
+    ("text.emphasis", &["markup.italic"]),
+    ("text.literal", &["markup.raw", "markup.inline.raw"]),
+    (
+        "text.reference",
+        &["string.other.link", "markup.underline.link"],
+    ),
+    ("text.strong", &["markup.bold"]),
+    ("text.title", &["markup.heading", "entity.name.section"]),
+    ("text.uri", &["markup.underline.link"]),
+    ("type", &["entity.name.type", "support.type"]),
+    (
+        "type.builtin",
+        &["support.type", "storage.type", "entity.name.type"],
+    ),
+    ("variable", &["variable.other.readwrite", "variable"]),
+    ("variable.builtin", &["variable.language"]),
+    ("variable.parameter", &["variable.parameter"]),
+];
+
+impl Theme {
+    ///Imports a TextMate or Sublime Text theme, the XML property list of a `.tmTheme` file. See the [module](crate::textmate)
+    ///documentation.
+    pub fn from_tm_theme(plist: &str) -> Result<Self, ImportError> {
+        let options = roxmltree::ParsingOptions {
+            //the files declare the property list DTD
+            allow_dtd: true,
+            ..Default::default()
+        };
+        let xml =
+            roxmltree::Document::parse_with_options(plist, options).map_err(ImportError::Xml)?;
+        let Some(Plist::Dict(root)) = xml.root_element().children().find_map(Plist::parse) else {
+            return Err(ImportError::Theme(String::from(
+                "a tmTheme has to be a property list of a dict",
+            )));
+        };
+        let Some(Plist::Array(items)) = lookup(&root, "settings") else {
+            return Err(ImportError::Theme(String::from(
+                "the tmTheme has no settings",
+            )));
+        };
+
+        let mut colors = (None, None);
+        let mut rules = Vec::new();
+        for item in items {
+            let Plist::Dict(item) = item else {
+                continue;
+            };
+            let Some(Plist::Dict(settings)) = lookup(item, "settings") else {
+                continue;
+            };
+            let text = |key| match lookup(settings, key) {
+                Some(Plist::String(text)) => Some(text.as_str()),
+                _ => None,
+            };
+            match lookup(item, "scope") {
+                Some(Plist::String(scope)) => rules.push(Rule::new(
+                    scope,
+                    [text("foreground"), text("background")],
+                    text("fontStyle"),
+                )),
+                //the rule without a scope has the colors of the editor
+                _ => colors = (text("background"), text("foreground")),
+            }
+        }
+        Ok(build(colors.0, colors.1, true, &rules))
+    }
+
+    ///Imports a VSCode color theme, the JSON file an extension lists under `contributes.themes`. Comments and trailing commas are
+    ///allowed like in VSCode. See the [module](crate::textmate) documentation.
+    ///
+    ///Themes that `include` another one only get their own rules, and `tokenColors` that refer to a `.tmTheme` file aren't
+    ///followed: load that one with [Theme::from_tm_theme]. Semantic token colors are ignored.
+    pub fn from_vscode(json: &str) -> Result<Self, ImportError> {
+        let value: serde_json::Value =
+            serde_json::from_str(&strip_jsonc(json)).map_err(ImportError::Json)?;
+        fn text<'a>(value: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+            value.get(key).and_then(serde_json::Value::as_str)
+        }
+
+        let token_colors = match value.get("tokenColors") {
+            None => &[][..],
+            Some(serde_json::Value::Array(rules)) => rules,
+            Some(serde_json::Value::String(path)) => {
+                return Err(ImportError::Theme(format!(
+                    "the token colors are in `{path}`, import that file instead"
+                )));
+            }
+            Some(_) => {
+                return Err(ImportError::Theme(String::from(
+                    "`tokenColors` has to be a list",
+                )));
+            }
+        };
+        let mut global = (None, None);
+        let mut rules = Vec::new();
+        for rule in token_colors {
+            let Some(settings) = rule.get("settings") else {
+                continue;
+            };
+            let scopes = match rule.get("scope") {
+                Some(serde_json::Value::String(scope)) => vec![scope.as_str()],
+                Some(serde_json::Value::Array(scopes)) => scopes
+                    .iter()
+                    .filter_map(serde_json::Value::as_str)
+                    .collect(),
+                _ => {
+                    global = (text(settings, "background"), text(settings, "foreground"));
+                    continue;
+                }
+            };
+            rules.push(Rule::new(
+                &scopes.join(","),
+                [text(settings, "foreground"), text(settings, "background")],
+                text(settings, "fontStyle"),
+            ));
+        }
+
+        let colors = value.get("colors");
+        let editor = |key, global| colors.and_then(|colors| text(colors, key)).or(global);
+        let dark = !text(&value, "type")
+            .is_some_and(|kind| kind.starts_with("light") || kind == "hcLight");
+        Ok(build(
+            editor("editor.background", global.0),
+            editor("editor.foreground", global.1),
+            dark,
+            &rules,
+        ))
+    }
+}
+
+///A rule of a theme: the style of the scopes its selectors match.
+struct Rule {
+    ///The last scope of each selector, with how specific it is.
+    selectors: Vec<(String, usize)>,
+    foreground: Option<Color>,
+    background: Option<Color>,
+    ///Bold, italic, underline and strikethrough. An empty font style is set as well, and overrides the styles of less specific
+    ///rules.
+    font_style: Option<(bool, bool, bool, bool)>,
+}
+
+impl Rule {
+    fn new(
+        selector: &str,
+        [foreground, background]: [Option<&str>; 2],
+        font_style: Option<&str>,
+    ) -> Self {
+        let selectors = selector
+            .split([',', '|'])
+            .filter_map(|selector| {
+                //exclusions only make a rule apply less, so applying it everywhere is close enough
+                let selector = selector.split(" -").next()?;
+                let mut scopes = selector.split_whitespace().rev();
+                let scope = scopes.next()?;
+                //the rest are ancestors of the scope, only the language root is fine, as everything is in there
+                scopes
+                    .all(|ancestor| ancestor.starts_with("source") || ancestor.starts_with("text"))
+                    .then(|| (scope.to_string(), scope.split('.').count()))
+            })
+            .collect();
+        Self {
+            selectors,
+            foreground: foreground.and_then(parse_color),
+            background: background.and_then(parse_color),
+            font_style: font_style.map(|style| {
+                let has = |word| style.split_whitespace().any(|part| part == word);
+                (
+                    has("bold"),
+                    has("italic"),
+                    has("underline"),
+                    has("strikethrough"),
+                )
+            }),
+        }
+    }
+
+    ///How specific the most specific selector matching `scope` is.
+    fn specificity(&self, scope: &str) -> Option<usize> {
+        self.selectors
+            .iter()
+            .filter(|(selector, _)| {
+                scope
+                    .strip_prefix(selector.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .map(|(_, specificity)| *specificity)
+            .max()
+    }
+}
+
+fn build(background: Option<&str>, foreground: Option<&str>, dark: bool, rules: &[Rule]) -> Theme {
+    //the defaults of VSCode
+    let (default_background, default_foreground) = if dark {
+        (
+            Color::from_rgb8(0x1e, 0x1e, 0x1e),
+            Color::from_rgb8(0xd4, 0xd4, 0xd4),
+        )
+    } else {
+        (Color::WHITE, Color::BLACK)
+    };
+    let background = background
+        .and_then(parse_color)
+        .unwrap_or(default_background);
+    let foreground = foreground
+        .and_then(parse_color)
+        .unwrap_or(default_foreground);
+    let mut theme = Theme::new(
+        color::blend_over(background, Color::BLACK),
+        color::blend_over(foreground, background),
+    );
+
+    for (capture, scopes) in SCOPES {
+        //the first scope some rule styles
+        let Some(scope) = scopes
+            .iter()
+            .find(|scope| rules.iter().any(|rule| rule.specificity(scope).is_some()))
+        else {
+            continue;
+        };
+        //the most specific rule setting each, and the later one of equally specific rules
+        let pick = |has: fn(&Rule) -> bool| {
+            rules
+                .iter()
+                .filter(|rule| has(rule))
+                .filter_map(|rule| Some((rule.specificity(scope)?, rule)))
+                .max_by_key(|(specificity, _)| *specificity)
+                .map(|(_, rule)| rule)
+        };
+        let color = pick(|rule| rule.foreground.is_some()).and_then(|rule| rule.foreground);
+        let background = pick(|rule| rule.background.is_some()).and_then(|rule| rule.background);
+        let font_style = pick(|rule| rule.font_style.is_some()).and_then(|rule| rule.font_style);
+        if color.is_none() && background.is_none() && font_style.is_none() {
+            continue;
+        }
+        let (bold, italic, underline, strikethrough) = font_style.unwrap_or_default();
+        let background = background.map(|color| color::blend_over(color, theme.background));
+        theme.set_style(
+            *capture,
+            Style {
+                color: color
+                    .map(|color| color::blend_over(color, background.unwrap_or(theme.background))),
+                background,
+                bold,
+                italic,
+                underline,
+                strikethrough,
+            },
+        );
+    }
+    theme
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    let text = text.trim();
+    match color::color_literals(text).as_slice() {
+        [literal] if literal.range == (0..text.len()) => Some(literal.color),
+        _ => None,
+    }
+}
+
+///The values of a property list.
+enum Plist {
+    String(String),
+    Array(Vec<Plist>),
+    Dict(Vec<(String, Plist)>),
+    ///Numbers, dates, flags..., which themes don't use.
+    Other,
+}
+
+impl Plist {
+    ///`None` for the text between the elements.
+    fn parse(node: roxmltree::Node<'_, '_>) -> Option<Self> {
+        if !node.is_element() {
+            return None;
+        }
+        let children = || node.children().filter_map(Self::parse);
+        Some(match node.tag_name().name() {
+            "string" => Self::String(node.text().unwrap_or_default().to_string()),
+            "array" => Self::Array(children().collect()),
+            "dict" => {
+                let keys = node
+                    .children()
+                    .filter(|child| child.has_tag_name("key"))
+                    .map(|key| key.text().unwrap_or_default().to_string());
+                let values = node
+                    .children()
+                    .filter(|child| child.is_element() && !child.has_tag_name("key"))
+                    .filter_map(Self::parse);
+                Self::Dict(keys.zip(values).collect())
+            }
+            _ => Self::Other,
+        })
+    }
+}
+
+fn lookup<'a>(dict: &'a [(String, Plist)], key: &str) -> Option<&'a Plist> {
+    dict.iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+}
+
+///`json` without the comments and trailing commas VSCode allows.
+fn strip_jsonc(json: &str) -> String {
+    let mut stripped = String::with_capacity(json.len());
+    let mut chars = json.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                stripped.push(c);
+                while let Some(c) = chars.next() {
+                    stripped.push(c);
+                    match c {
+                        '\\' => stripped.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                //keeps tokens around the comment apart
+                stripped.push(' ');
+            }
+            ']' | '}' => {
+                //a comma followed only by whitespace
+                let trimmed = stripped.trim_end().len();
+                if stripped[..trimmed].ends_with(',') {
+                    stripped.truncate(trimmed - 1);
+                }
+                stripped.push(c);
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use iced_core::Color;
+
+    use super::ImportError;
+    use crate::theme::Theme;
+
+    ///The color of the style of `capture`, whether it's bold, italic and underlined. The colors are blended over the background,
+    ///so they are compared as bytes.
+    fn style(theme: &Theme, capture: &str) -> Option<([u8; 3], [bool; 3])> {
+        let style = theme.style(capture)?;
+        let [r, g, b, _] = style.color?.into_rgba8();
+        Some(([r, g, b], [style.bold, style.italic, style.underline]))
+    }
+
+    fn rgb(color: Color) -> [u8; 3] {
+        let [r, g, b, _] = color.into_rgba8();
+        [r, g, b]
+    }
+
+    const TM_THEME: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key>
+    <string>Sample</string>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>background</key>
+                <string>#272822</string>
+                <key>foreground</key>
+                <string>#F8F8F2</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>comment</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#75715E</string>
+                <key>fontStyle</key>
+                <string>italic</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>keyword, storage</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#F92672</string>
+                <key>fontStyle</key>
+                <string>bold</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>keyword.control</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#66D9EF</string>
+                <key>fontStyle</key>
+                <string></string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>meta.embedded string, source.rust string</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#E6DB74</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>"#;
+
+    #[test]
+    fn imports_tm_themes_by_the_most_specific_rules() {
+        let theme = Theme::from_tm_theme(TM_THEME).unwrap();
+        assert_eq!(rgb(theme.background), [0x27, 0x28, 0x22]);
+        assert_eq!(rgb(theme.foreground), [0xf8, 0xf8, 0xf2]);
+        assert_eq!(
+            style(&theme, "comment"),
+            Some(([0x75, 0x71, 0x5e], [false, true, false]))
+        );
+        //`keyword.control` is more specific than `keyword`, and its empty font style wins as well
+        assert_eq!(
+            style(&theme, "keyword"),
+            Some(([0x66, 0xd9, 0xef], [false; 3]))
+        );
+        //`operator` is `keyword.operator`, of which only `keyword` has a rule
+        assert_eq!(
+            style(&theme, "operator"),
+            Some(([0xf9, 0x26, 0x72], [true, false, false]))
+        );
+        //within the language root is everywhere, within embedded code isn't
+        assert_eq!(
+            style(&theme, "string"),
+            Some(([0xe6, 0xdb, 0x74], [false; 3]))
+        );
+        assert_eq!(theme.style("function"), None);
+    }
+
+    #[test]
+    fn imports_vscode_themes_with_comments_and_trailing_commas() {
+        let theme = Theme::from_vscode(
+            r##"{
+                // a light theme
+                "type": "light",
+                "colors": { "editor.background": "#fafafa", },
+                "tokenColors": [
+                    { "settings": { "foreground": "#383a42" } },
+                    {
+                        "scope": ["comment", "punctuation.definition.comment"],
+                        "settings": { "foreground": "#a0a1a7", "fontStyle": "italic underline" }, /* trailing */
+                    },
+                    { "scope": "string.quoted", "settings": { "foreground": "#50a14f80" } },
+                ],
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(rgb(theme.background), [0xfa, 0xfa, 0xfa]);
+        assert_eq!(rgb(theme.foreground), [0x38, 0x3a, 0x42]);
+        assert_eq!(
+            style(&theme, "comment"),
+            Some(([0xa0, 0xa1, 0xa7], [false, true, true]))
+        );
+        //translucent colors are blended over the background
+        let string = theme.style("string").unwrap().color.unwrap();
+        assert!(string.a == 1.0 && string.g > string.r && string.r > 0x50 as f32 / 255.0);
+        assert_eq!(theme.style("keyword"), None);
+    }
+
+    #[test]
+    fn malformed_themes_are_errors() {
+        assert!(matches!(
+            Theme::from_tm_theme("<plist>"),
+            Err(ImportError::Xml(_))
+        ));
+        assert!(matches!(
+            Theme::from_tm_theme("<plist><array/></plist>"),
+            Err(ImportError::Theme(_))
+        ));
+        assert!(matches!(
+            Theme::from_tm_theme("<plist><dict><key>name</key><string>x</string></dict></plist>"),
+            Err(ImportError::Theme(_))
+        ));
+        assert!(matches!(
+            Theme::from_vscode("{ \"colors\": "),
+            Err(ImportError::Json(_))
+        ));
+        assert!(matches!(
+            Theme::from_vscode(r#"{ "tokenColors": "./themes/sample.tmTheme" }"#),
+            Err(ImportError::Theme(_))
+        ));
+        assert!(matches!(
+            Theme::from_vscode(r#"{ "tokenColors": {} }"#),
+            Err(ImportError::Theme(_))
+        ));
+    }
+}
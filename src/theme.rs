@@ -0,0 +1,604 @@
+//! Highlighting themes that are independent of Iced's palette.
+//!
+//! A [Theme] maps capture names (`"comment"`, `"function.builtin"`, ...) to a [Style]. Set it via [TSSettings::with_theme](crate::TSSettings::with_theme)
+//! and the highlighter resolves the styles while highlighting, so [to_format](crate::to_format) can keep being used as the formatting function.
+//!
+//! Ready-made themes of popular color schemes are in the `themes` module.
+//!
+//! With the `serde` feature, themes can be loaded from files the user edits, in any format serde reads (JSON via
+//! `Theme::from_json`, TOML via the `toml` crate, ...). A style is either a color, or a table of an optional `color` and
+//! `background` and the `bold`, `italic`, `underline` and `strikethrough` flags. Colors are written like in CSS: `#rgb`, `#rrggbb`, `#rrggbbaa` or `rgb(r, g, b)`.
+//!
+//! ```toml
+//! background = "#1e1e2e"
+//! foreground = "#cdd6f4"
+//!
+//! [styles]
+//! comment = { color = "#6c7086", italic = true }
+//! keyword = "#cba6f7"
+//! "function.builtin" = { color = "#89b4fa", bold = true }
+//! ```
+
+use iced_core::{Color, Font};
+
+use crate::color;
+
+///How a single capture is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Style {
+    ///Text color. `None` keeps the editor's color.
+    pub color: Option<Color>,
+    ///Color behind the text. `None` keeps the editor's background.
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    ///Iced's [Format](iced_core::text::highlighter::Format) can't express backgrounds, underlines and strikethroughs, so
+    ///they only reach consumers that can render them: the [exporters](crate::export), and `rich_text` via [Style::to_span].
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl Style {
+    pub const fn color(color: Color) -> Self {
+        Self {
+            color: Some(color),
+            background: None,
+            bold: false,
+            italic: false,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    ///A style without color, for themes that only use the font's shape.
+    pub const fn plain() -> Self {
+        Self {
+            color: None,
+            background: None,
+            bold: false,
+            italic: false,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    pub const fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub const fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    pub const fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub const fn strikethrough(mut self) -> Self {
+        self.strikethrough = true;
+        self
+    }
+
+    pub const fn background(mut self, background: Color) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    ///Converts the style into Iced's [Format](iced_core::text::highlighter::Format), which only has the color and the font.
+    ///The background, underline and strikethrough are left out.
+    pub fn to_format(&self) -> iced_core::text::highlighter::Format<Font> {
+        let font = if self.bold || self.italic {
+            Some(Font {
+                weight: if self.bold {
+                    iced_core::font::Weight::Bold
+                } else {
+                    iced_core::font::Weight::Normal
+                },
+                style: if self.italic {
+                    iced_core::font::Style::Italic
+                } else {
+                    iced_core::font::Style::Normal
+                },
+                ..Font::MONOSPACE
+            })
+        } else {
+            Some(Font::MONOSPACE)
+        };
+
+        iced_core::text::highlighter::Format {
+            color: self.color,
+            font,
+        }
+    }
+
+    ///A [Span](iced_core::text::Span) of `text` in the style, for Iced's `rich_text`, which renders all of it.
+    pub fn to_span<'a, Link>(
+        &self,
+        text: impl iced_core::text::IntoFragment<'a>,
+    ) -> iced_core::text::Span<'a, Link, Font> {
+        let format = self.to_format();
+        iced_core::text::Span::new(text)
+            .font_maybe(format.font)
+            .color_maybe(self.color)
+            .background_maybe(self.background)
+            .underline(self.underline)
+            .strikethrough(self.strikethrough)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    ///Background the theme was designed for. Apply it to your editor's style, otherwise the colors might not fit.
+    pub background: Color,
+    ///Color of text that has no capture.
+    pub foreground: Color,
+    styles: Vec<(String, Style)>,
+}
+
+impl Theme {
+    pub fn new(background: Color, foreground: Color) -> Self {
+        Self {
+            background,
+            foreground,
+            styles: Vec::new(),
+        }
+    }
+
+    ///Sets the style of `capture`, overwriting any style that was set before.
+    pub fn with_style(mut self, capture: impl Into<String>, style: Style) -> Self {
+        self.set_style(capture, style);
+        self
+    }
+
+    pub fn set_style(&mut self, capture: impl Into<String>, style: Style) {
+        let capture = capture.into();
+        if let Some(existing) = self.styles.iter_mut().find(|(name, _)| *name == capture) {
+            existing.1 = style;
+        } else {
+            self.styles.push((capture, style));
+        }
+    }
+
+    ///Resolves the style of `capture`. If there is no exact match, the name is shortened at its last `.` until
+    ///something matches. So `function.builtin` falls back to `function`.
+    pub fn style(&self, capture: &str) -> Option<&Style> {
+        let mut name = capture;
+        loop {
+            if let Some((_, style)) = self.styles.iter().find(|(n, _)| n == name) {
+                return Some(style);
+            }
+            name = &name[..name.rfind('.')?];
+        }
+    }
+
+    ///The formatting function for `highlight_with`, for settings the theme is set on via
+    ///[TSSettings::with_theme](crate::TSSettings::with_theme):
+    ///`text_editor(&content).highlight_with::<Highlighter>(settings.with_theme(theme), Theme::format_fn())`.
+    ///
+    ///Iced takes a plain function rather than a closure, so the theme travels with the settings, and the highlights carry the
+    ///styles it resolves. That makes this [to_format](crate::to_format).
+    pub fn format_fn()
+    -> fn(&crate::Highlight, &iced_core::Theme) -> iced_core::text::highlighter::Format<Font> {
+        crate::to_format
+    }
+
+    ///All explicitly set `(capture, style)` pairs.
+    pub fn styles(&self) -> impl Iterator<Item = (&str, &Style)> {
+        self.styles
+            .iter()
+            .map(|(name, style)| (name.as_str(), style))
+    }
+
+    ///Clamps all colors of the theme, so that they have _at least_ a contrast ratio of `min_ratio` to [Theme::background], or to
+    ///the background of their style.
+    ///
+    ///The ratio follows [WCAG](https://www.w3.org/TR/WCAG21/#contrast-minimum): `4.5` is the _AA_ level for normal text, `7.0` the _AAA_ level.
+    pub fn with_min_contrast(mut self, min_ratio: f32) -> Self {
+        self.foreground = color::ensure_contrast(self.foreground, self.background, min_ratio);
+        for (_, style) in self.styles.iter_mut() {
+            let background = style.background.unwrap_or(self.background);
+            if let Some(c) = style.color.as_mut() {
+                *c = color::ensure_contrast(*c, background, min_ratio);
+            }
+        }
+        self
+    }
+
+    ///High-contrast theme for low-vision users. Pure black background, all colors have at least a 7:1 contrast ratio (WCAG AAA).
+    #[cfg(feature = "presets")]
+    pub fn high_contrast() -> Self {
+        Self::new(Color::BLACK, Color::WHITE)
+            .with_style(
+                "comment",
+                Style::color(Color::from_rgb8(0x7c, 0xfc, 0x00)).italic(),
+            )
+            .with_style(
+                "keyword",
+                Style::color(Color::from_rgb8(0xff, 0xd7, 0x00)).bold(),
+            )
+            .with_style("operator", Style::color(Color::WHITE))
+            .with_style("string", Style::color(Color::from_rgb8(0xff, 0xa0, 0x7a)))
+            .with_style("constant", Style::color(Color::from_rgb8(0xff, 0x8c, 0xff)))
+            .with_style("number", Style::color(Color::from_rgb8(0xff, 0x8c, 0xff)))
+            .with_style("function", Style::color(Color::from_rgb8(0x00, 0xff, 0xff)))
+            .with_style(
+                "constructor",
+                Style::color(Color::from_rgb8(0x87, 0xce, 0xff)),
+            )
+            .with_style("type", Style::color(Color::from_rgb8(0x87, 0xce, 0xff)))
+            .with_style(
+                "attribute",
+                Style::color(Color::from_rgb8(0xff, 0xd7, 0x00)),
+            )
+            .with_style("module", Style::color(Color::WHITE))
+            .with_style("property", Style::color(Color::from_rgb8(0xe0, 0xe0, 0xff)))
+            .with_style("variable", Style::color(Color::WHITE))
+            .with_style(
+                "variable.builtin",
+                Style::color(Color::from_rgb8(0xff, 0x8c, 0xff)).bold(),
+            )
+            .with_style(
+                "punctuation",
+                Style::color(Color::from_rgb8(0xe0, 0xe0, 0xe0)),
+            )
+            .with_style("tag", Style::color(Color::from_rgb8(0x00, 0xff, 0xff)))
+            .with_style(
+                "text.title",
+                Style::color(Color::from_rgb8(0xff, 0xd7, 0x00)).bold(),
+            )
+            .with_style("text.strong", Style::plain().bold())
+            .with_style("text.emphasis", Style::plain().italic())
+            .with_style(
+                "text.literal",
+                Style::color(Color::from_rgb8(0xff, 0xa0, 0x7a)),
+            )
+            .with_style(
+                "text.uri",
+                Style::color(Color::from_rgb8(0x00, 0xff, 0xff)).underline(),
+            )
+            .with_style(
+                "text.reference",
+                Style::color(Color::from_rgb8(0x00, 0xff, 0xff)),
+            )
+            .with_style(
+                "error",
+                Style::color(Color::from_rgb8(0xff, 0x55, 0x55)).underline(),
+            )
+            .with_min_contrast(7.0)
+    }
+
+    ///Theme without any colors. Token categories are only told apart by weight and italics, which is all Iced's
+    ///[Format](iced_core::text::highlighter::Format) renders, and the [exporters](crate::export) also underline types,
+    ///builtins and links. Meant for printing, e-ink displays, or anyone who prefers minimal color.
+    ///
+    ///Text uses the editor's own color, so this works on any background.
+    #[cfg(feature = "presets")]
+    pub fn monochrome() -> Self {
+        Self::new(Color::WHITE, Color::BLACK)
+            .with_style("comment", Style::plain().italic())
+            .with_style("keyword", Style::plain().bold())
+            .with_style("string", Style::plain().italic())
+            .with_style("constant", Style::plain().bold().italic())
+            .with_style("number", Style::plain())
+            .with_style("function", Style::plain().bold())
+            .with_style("function.builtin", Style::plain().bold().underline())
+            .with_style("type", Style::plain().bold().italic().underline())
+            .with_style("constructor", Style::plain().bold().italic().underline())
+            .with_style("attribute", Style::plain().italic())
+            .with_style("tag", Style::plain().bold())
+            .with_style("variable.builtin", Style::plain().bold())
+            .with_style("variable.parameter", Style::plain().italic())
+            .with_style("text.title", Style::plain().bold())
+            .with_style("text.strong", Style::plain().bold())
+            .with_style("text.emphasis", Style::plain().italic())
+            .with_style("text.uri", Style::plain().italic().underline())
+            .with_style("error", Style::plain().strikethrough())
+    }
+}
+
+///Why the theme of another editor couldn't be imported, see the `textmate`, `helix` and `neovim` modules.
+#[cfg(any(feature = "helix", feature = "neovim", feature = "textmate"))]
+#[derive(Debug)]
+pub enum ImportError {
+    ///The `.tmTheme` isn't valid XML.
+    #[cfg(feature = "textmate")]
+    Xml(roxmltree::Error),
+    ///The VSCode theme isn't valid JSON, even with its comments and trailing commas removed, or the Neovim export isn't.
+    #[cfg(any(feature = "neovim", feature = "textmate"))]
+    Json(serde_json::Error),
+    ///The Helix theme isn't valid TOML.
+    #[cfg(feature = "helix")]
+    Toml(toml_edit::TomlError),
+    ///The file is well-formed, but not a theme.
+    Theme(String),
+}
+
+#[cfg(any(feature = "helix", feature = "neovim", feature = "textmate"))]
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "textmate")]
+            Self::Xml(error) => write!(f, "invalid tmTheme: {error}"),
+            #[cfg(any(feature = "neovim", feature = "textmate"))]
+            Self::Json(error) => write!(f, "invalid JSON: {error}"),
+            #[cfg(feature = "helix")]
+            Self::Toml(error) => write!(f, "invalid Helix theme: {error}"),
+            Self::Theme(message) => f.write_str(message),
+        }
+    }
+}
+
+#[cfg(any(feature = "helix", feature = "neovim", feature = "textmate"))]
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "textmate")]
+            Self::Xml(error) => Some(error),
+            #[cfg(any(feature = "neovim", feature = "textmate"))]
+            Self::Json(error) => Some(error),
+            #[cfg(feature = "helix")]
+            Self::Toml(error) => Some(error),
+            Self::Theme(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Theme {
+    ///Reads a theme file in JSON, see the [module](self) documentation for its fields.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    ///The theme as JSON, e.g. to write a preset out for the user to edit.
+    pub fn to_json(&self) -> String {
+        //NOTE: can't fail, the theme only consists of strings and flags
+        serde_json::to_string_pretty(self).expect("theme is serializable")
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialization {
+    use std::fmt;
+
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer,
+        de::{self, MapAccess, Visitor},
+        ser::SerializeStruct,
+    };
+
+    use super::{Color, Style, Theme};
+    use crate::color::{self, Notation};
+
+    ///A color written like in CSS.
+    struct Css(Color);
+
+    impl Serialize for Css {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let literal = color::ColorLiteral {
+                range: 0..0,
+                color: self.0,
+                notation: if self.0.a < 1.0 {
+                    Notation::HexAlpha
+                } else {
+                    Notation::Hex
+                },
+            };
+            serializer.serialize_str(&literal.replacement(self.0))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Css {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let text = String::deserialize(deserializer)?;
+            let text = text.trim();
+            match color::color_literals(text).as_slice() {
+                [literal] if literal.range == (0..text.len()) => Ok(Css(literal.color)),
+                _ => Err(de::Error::custom(format!(
+                    "`{text}` is no color, expected `#rrggbb` or `rgb(r, g, b)`"
+                ))),
+            }
+        }
+    }
+
+    impl Serialize for Style {
+        ///Just the color, if the style has no flags.
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let flags = [self.bold, self.italic, self.underline, self.strikethrough];
+            if let Some(color) = self.color
+                && self.background.is_none()
+                && !flags.contains(&true)
+            {
+                return Css(color).serialize(serializer);
+            }
+            let colors = [("color", self.color), ("background", self.background)];
+            let fields = colors.iter().filter(|(_, color)| color.is_some()).count()
+                + flags.iter().filter(|flag| **flag).count();
+            let mut style = serializer.serialize_struct("Style", fields)?;
+            for (name, color) in colors {
+                match color {
+                    Some(color) => style.serialize_field(name, &Css(color))?,
+                    None => style.skip_field(name)?,
+                }
+            }
+            let names = ["bold", "italic", "underline", "strikethrough"];
+            for (name, flag) in names.into_iter().zip(flags) {
+                if flag {
+                    style.serialize_field(name, &true)?;
+                } else {
+                    style.skip_field(name)?;
+                }
+            }
+            style.end()
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Style {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            #[derive(Deserialize)]
+            #[serde(deny_unknown_fields)]
+            struct Fields {
+                color: Option<Css>,
+                background: Option<Css>,
+                #[serde(default)]
+                bold: bool,
+                #[serde(default)]
+                italic: bool,
+                #[serde(default)]
+                underline: bool,
+                #[serde(default)]
+                strikethrough: bool,
+            }
+
+            struct StyleVisitor;
+
+            impl<'de> Visitor<'de> for StyleVisitor {
+                type Value = Style;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str(
+                        "a color, or a table of `color`, `background`, `bold`, `italic`, `underline` and \
+                         `strikethrough`",
+                    )
+                }
+
+                fn visit_str<E: de::Error>(self, text: &str) -> Result<Style, E> {
+                    Css::deserialize(de::value::StrDeserializer::new(text))
+                        .map(|css| Style::color(css.0))
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Style, A::Error> {
+                    let fields = Fields::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                    Ok(Style {
+                        color: fields.color.map(|css| css.0),
+                        background: fields.background.map(|css| css.0),
+                        bold: fields.bold,
+                        italic: fields.italic,
+                        underline: fields.underline,
+                        strikethrough: fields.strikethrough,
+                    })
+                }
+            }
+
+            deserializer.deserialize_any(StyleVisitor)
+        }
+    }
+
+    ///The styles of a theme, in the order of the file.
+    struct Styles(Vec<(String, Style)>);
+
+    impl<'de> Deserialize<'de> for Styles {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct StylesVisitor;
+
+            impl<'de> Visitor<'de> for StylesVisitor {
+                type Value = Styles;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str("a table of capture names and their styles")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Styles, A::Error> {
+                    let mut theme = Theme::new(Color::BLACK, Color::WHITE);
+                    while let Some((capture, style)) = map.next_entry::<String, Style>()? {
+                        theme.set_style(capture, style);
+                    }
+                    Ok(Styles(theme.styles))
+                }
+            }
+
+            deserializer.deserialize_map(StylesVisitor)
+        }
+    }
+
+    impl Serialize for Theme {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            struct Styles<'a>(&'a [(String, Style)]);
+
+            impl Serialize for Styles<'_> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_map(self.0.iter().map(|(capture, style)| (capture, style)))
+                }
+            }
+
+            let mut theme = serializer.serialize_struct("Theme", 3)?;
+            theme.serialize_field("background", &Css(self.background))?;
+            theme.serialize_field("foreground", &Css(self.foreground))?;
+            theme.serialize_field("styles", &Styles(&self.styles))?;
+            theme.end()
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Theme {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            #[derive(Deserialize)]
+            #[serde(deny_unknown_fields)]
+            struct Fields {
+                background: Css,
+                foreground: Css,
+                styles: Option<Styles>,
+            }
+
+            let fields = Fields::deserialize(deserializer)?;
+            Ok(Theme {
+                background: fields.background.0,
+                foreground: fields.foreground.0,
+                styles: fields.styles.map(|styles| styles.0).unwrap_or_default(),
+            })
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use iced_core::Color;
+
+    use super::{Style, Theme};
+
+    #[test]
+    fn theme_files_round_trip() {
+        let theme = Theme::from_json(
+            r##"{
+                "background": "#1e1e2e",
+                "foreground": "rgb(205, 214, 244)",
+                "styles": {
+                    "comment": { "color": "#6c7086", "italic": true },
+                    "keyword": "#cba6f7",
+                    "error": { "underline": true }
+                }
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(theme.background, Color::from_rgb8(0x1e, 0x1e, 0x2e));
+        assert_eq!(theme.foreground, Color::from_rgb8(205, 214, 244));
+        assert_eq!(
+            theme.style("comment.line"),
+            Some(&Style::color(Color::from_rgb8(0x6c, 0x70, 0x86)).italic())
+        );
+        assert_eq!(
+            theme.style("keyword"),
+            Some(&Style::color(Color::from_rgb8(0xcb, 0xa6, 0xf7)))
+        );
+        let error = theme.style("error").unwrap();
+        assert_eq!(error.color, None);
+        assert!(error.underline && !error.bold);
+        assert_eq!(Theme::from_json(&theme.to_json()).unwrap(), theme);
+    }
+
+    #[test]
+    fn malformed_theme_files_are_errors() {
+        for json in [
+            r##"{ "foreground": "#fff" }"##,
+            r##"{ "background": "#000", "foreground": "white" }"##,
+            r##"{ "background": "#000", "foreground": "#fff", "styles": { "comment": { "colour": "#888" } } }"##,
+            r##"{ "background": "#000", "foreground": "#fff", "styles": { "comment": 1 } }"##,
+            r##"{ "background": "#000", "foreground": "#fff", "styles": [] }"##,
+            "{",
+        ] {
+            assert!(Theme::from_json(json).is_err(), "{json}");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Ready-made themes of popular color schemes, independent of Iced's palette: `settings.with_theme(themes::gruvbox())`.
+//!
+//! Each scheme's palette is mapped to the standard captures the same way, so switching themes only changes the colors:
+//! comments are italic, builtins (`self`, `u32`, `println!`...) get a color of their own, and the punctuation is toned down.
+//! Apply the theme's [background](Theme::background) to the editor as well, the colors are made for it.
+
+use iced_core::Color;
+
+use crate::theme::{Style, Theme};
+
+///The colors of a scheme, by what they color.
+struct Palette {
+    background: u32,
+    foreground: u32,
+    comment: u32,
+    keyword: u32,
+    operator: u32,
+    string: u32,
+    ///Escapes and other special parts of strings.
+    escape: u32,
+    ///Constants and numbers.
+    constant: u32,
+    function: u32,
+    builtin: u32,
+    ///Types and constructors.
+    kind: u32,
+    attribute: u32,
+    module: u32,
+    property: u32,
+    parameter: u32,
+    punctuation: u32,
+    tag: u32,
+    ///Syntax errors.
+    error: u32,
+}
+
+///[Gruvbox](https://github.com/morhetz/gruvbox), dark with medium contrast.
+pub fn gruvbox() -> Theme {
+    build(Palette {
+        background: 0x282828,
+        foreground: 0xebdbb2,
+        comment: 0x928374,
+        keyword: 0xfb4934,
+        operator: 0xfe8019,
+        string: 0xb8bb26,
+        escape: 0xfe8019,
+        constant: 0xd3869b,
+        function: 0xb8bb26,
+        builtin: 0xfabd2f,
+        kind: 0xfabd2f,
+        attribute: 0x8ec07c,
+        module: 0x8ec07c,
+        property: 0x83a598,
+        parameter: 0x83a598,
+        punctuation: 0xa89984,
+        tag: 0x8ec07c,
+        error: 0xfb4934,
+    })
+}
+
+///[Dracula](https://draculatheme.com).
+pub fn dracula() -> Theme {
+    build(Palette {
+        background: 0x282a36,
+        foreground: 0xf8f8f2,
+        comment: 0x6272a4,
+        keyword: 0xff79c6,
+        operator: 0xff79c6,
+        string: 0xf1fa8c,
+        escape: 0xff79c6,
+        constant: 0xbd93f9,
+        function: 0x50fa7b,
+        builtin: 0x8be9fd,
+        kind: 0x8be9fd,
+        attribute: 0x50fa7b,
+        module: 0x8be9fd,
+        property: 0xf8f8f2,
+        parameter: 0xffb86c,
+        punctuation: 0xf8f8f2,
+        tag: 0xff79c6,
+        error: 0xff5555,
+    })
+}
+
+///[Solarized](https://ethanschoonover.com/solarized) on its dark background.
+pub fn solarized_dark() -> Theme {
+    solarized(0x002b36, 0x839496, 0x586e75, 0x93a1a1)
+}
+
+///[Solarized](https://ethanschoonover.com/solarized) on its light background.
+pub fn solarized_light() -> Theme {
+    solarized(0xfdf6e3, 0x657b83, 0x93a1a1, 0x586e75)
+}
+
+///Both Solarized themes share the accents, only the base colors swap.
+fn solarized(background: u32, foreground: u32, comment: u32, emphasized: u32) -> Theme {
+    build(Palette {
+        background,
+        foreground,
+        comment,
+        keyword: 0x859900,
+        operator: 0x859900,
+        string: 0x2aa198,
+        escape: 0xdc322f,
+        constant: 0xd33682,
+        function: 0x268bd2,
+        builtin: 0xcb4b16,
+        kind: 0xb58900,
+        attribute: 0xcb4b16,
+        module: 0x6c71c4,
+        property: foreground,
+        parameter: emphasized,
+        punctuation: foreground,
+        tag: 0x268bd2,
+        error: 0xdc322f,
+    })
+}
+
+///[One Dark](https://github.com/atom/one-dark-syntax), Atom's default.
+pub fn one_dark() -> Theme {
+    build(Palette {
+        background: 0x282c34,
+        foreground: 0xabb2bf,
+        comment: 0x5c6370,
+        keyword: 0xc678dd,
+        operator: 0x56b6c2,
+        string: 0x98c379,
+        escape: 0x56b6c2,
+        constant: 0xd19a66,
+        function: 0x61afef,
+        builtin: 0x56b6c2,
+        kind: 0xe5c07b,
+        attribute: 0xd19a66,
+        module: 0xe5c07b,
+        property: 0xe06c75,
+        parameter: 0xabb2bf,
+        punctuation: 0xabb2bf,
+        tag: 0xe06c75,
+        error: 0xe06c75,
+    })
+}
+
+///[Catppuccin](https://catppuccin.com), the Mocha flavor.
+pub fn catppuccin() -> Theme {
+    build(Palette {
+        background: 0x1e1e2e,
+        foreground: 0xcdd6f4,
+        comment: 0x9399b2,
+        keyword: 0xcba6f7,
+        operator: 0x89dceb,
+        string: 0xa6e3a1,
+        escape: 0xf5c2e7,
+        constant: 0xfab387,
+        function: 0x89b4fa,
+        builtin: 0xf38ba8,
+        kind: 0xf9e2af,
+        attribute: 0xf9e2af,
+        module: 0xf5e0dc,
+        property: 0xb4befe,
+        parameter: 0xeba0ac,
+        punctuation: 0x9399b2,
+        tag: 0xcba6f7,
+        error: 0xf38ba8,
+    })
+}
+
+///All themes of the module by their names, e.g. for a theme picker.
+pub fn all() -> Vec<(&'static str, Theme)> {
+    vec![
+        ("Gruvbox", gruvbox()),
+        ("Dracula", dracula()),
+        ("Solarized Dark", solarized_dark()),
+        ("Solarized Light", solarized_light()),
+        ("One Dark", one_dark()),
+        ("Catppuccin", catppuccin()),
+    ]
+}
+
+fn build(palette: Palette) -> Theme {
+    let rgb = |hex: u32| Color::from_rgb8((hex >> 16) as u8, (hex >> 8) as u8, hex as u8);
+    let style = |hex| Style::color(rgb(hex));
+    Theme::new(rgb(palette.background), rgb(palette.foreground))
+        .with_style("comment", style(palette.comment).italic())
+        .with_style("keyword", style(palette.keyword))
+        .with_style("operator", style(palette.operator))
+        .with_style("string", style(palette.string))
+        .with_style("string.special", style(palette.escape))
+        .with_style("constant", style(palette.constant))
+        .with_style("number", style(palette.constant))
+        .with_style("function", style(palette.function))
+        .with_style("function.builtin", style(palette.builtin))
+        .with_style("type", style(palette.kind))
+        .with_style("type.builtin", style(palette.builtin))
+        .with_style("constructor", style(palette.kind))
+        .with_style("attribute", style(palette.attribute))
+        .with_style("module", style(palette.module))
+        .with_style("property", style(palette.property))
+        .with_style("variable.builtin", style(palette.builtin))
+        .with_style("variable.parameter", style(palette.parameter).italic())
+        .with_style("punctuation", style(palette.punctuation))
+        .with_style("tag", style(palette.tag))
+        .with_style("text.title", style(palette.keyword).bold())
+        .with_style("text.strong", Style::plain().bold())
+        .with_style("text.emphasis", Style::plain().italic())
+        .with_style("text.literal", style(palette.string))
+        .with_style("text.uri", style(palette.function).underline())
+        .with_style("text.reference", style(palette.function))
+        .with_style("error", style(palette.error).underline())
+}
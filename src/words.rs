@@ -0,0 +1,188 @@
+//! Word boundaries that know the tokens of the language, for double-click selection.
+//!
+//! Splitting at whitespace and punctuation cuts tokens that are one word to the language: `foo-bar` in CSS, `#fff` in a
+//! stylesheet or `r#type` in Rust. [Document::word_range_at] selects the syntax token under the cursor instead, and falls back
+//! to the characters around it in comments, strings and text the grammar doesn't split into tokens.
+//!
+//! For sub-word motion (Alt+arrow), [Document::next_subword_boundary] and [Document::prev_subword_boundary] stop at the parts of
+//! `camelCase`, `PascalCase`, `snake_case` and `kebab-case` identifiers. They split the identifier tokens of the language (and
+//! the words of comments and strings), but move over other tokens, like the numbers `1.5e3` or `0x1F`, in one step.
+
+use std::ops::Range;
+
+use crate::{
+    color,
+    document::{Document, Position},
+    search::is_word_char,
+};
+
+impl Document {
+    ///Byte range of the word at `position`, e.g. to select it on a double-click: a hex color, the token of the syntax tree, or
+    ///else the word characters (or the other non-space characters) around it. Looks at the character after `position`, or the
+    ///one before at the end of a word. Empty if there is neither, like on an empty line.
+    pub fn word_range_at(&self, position: impl Into<Position>) -> Range<usize> {
+        let offset = self.offset(position);
+        let line = self
+            .line_range(self.line_of(offset))
+            .unwrap_or(offset..offset);
+        let text = &self.text()[line.clone()];
+        let column = offset - line.start;
+        let after = text[column..].chars().next();
+        let before = text[..column].chars().next_back();
+        let (column, c) = match (after, before) {
+            (Some(after), _) if !after.is_whitespace() => (column, after),
+            (_, Some(before)) if !before.is_whitespace() => (column - before.len_utf8(), before),
+            _ => return offset..offset,
+        };
+
+        //hex colors wherever they are, even in comments and strings
+        if let Some(literal) = color::color_literals(text).into_iter().find(|literal| {
+            literal.range.contains(&column) && text[literal.range.clone()].starts_with('#')
+        }) {
+            return line.start + literal.range.start..line.start + literal.range.end;
+        }
+        if let Some(token) = self.word_token(line.start + column, &line) {
+            return token.byte_range();
+        }
+
+        let same = |other: char| !other.is_whitespace() && (is_word_char(other) == is_word_char(c));
+        let start = text[..column]
+            .char_indices()
+            .rev()
+            .take_while(|(_, other)| same(*other))
+            .last()
+            .map_or(column, |(idx, _)| idx);
+        let end = text[column..]
+            .char_indices()
+            .find(|(_, other)| !same(*other))
+            .map_or(text.len(), |(idx, _)| column + idx);
+        line.start + start..line.start + end
+    }
+
+    ///Byte offset of the end of the sub-word at or after `position`, e.g. to move the cursor on Alt+Right. Goes on to the next
+    ///lines if there is no sub-word after `position` on its line, and to the end of the text if there is none at all.
+    pub fn next_subword_boundary(&self, position: impl Into<Position>) -> usize {
+        let offset = self.offset(position);
+        (self.line_of(offset)..self.line_count())
+            .flat_map(|line| self.subwords(line))
+            .find(|subword| subword.end > offset)
+            .map_or(self.text().len(), |subword| subword.end)
+    }
+
+    ///Byte offset of the start of the sub-word at or before `position`, e.g. to move the cursor on Alt+Left. Goes back to the
+    ///previous lines if there is no sub-word before `position` on its line, and to the start of the text if there is none at
+    ///all.
+    pub fn prev_subword_boundary(&self, position: impl Into<Position>) -> usize {
+        let offset = self.offset(position);
+        (0..=self.line_of(offset))
+            .rev()
+            .flat_map(|line| self.subwords(line).into_iter().rev())
+            .find(|subword| subword.start < offset)
+            .map_or(0, |subword| subword.start)
+    }
+
+    ///Byte ranges of the sub-words of `line`, in order. Identifier tokens and words outside of tokens are split, other word
+    ///tokens are one sub-word.
+    fn subwords(&self, line: usize) -> Vec<Range<usize>> {
+        let Some(line) = self.line_range(line) else {
+            return Vec::new();
+        };
+        let text = &self.text()[line.clone()];
+        let mut subwords = Vec::new();
+        let mut column = 0;
+        while let Some(c) = text[column..].chars().next() {
+            let token = self
+                .word_token(line.start + column, &line)
+                .filter(|token| token.start_byte() == line.start + column);
+            if let Some(token) = token {
+                let range = token.start_byte() - line.start..token.end_byte() - line.start;
+                if token.kind().contains("identifier") {
+                    split(text, range.clone(), &mut subwords);
+                } else {
+                    subwords.push(range.clone());
+                }
+                column = range.end;
+            } else if is_word_char(c) {
+                let end = text[column..]
+                    .char_indices()
+                    .find(|(_, other)| !is_word_char(*other))
+                    .map_or(text.len(), |(idx, _)| column + idx);
+                split(text, column..end, &mut subwords);
+                column = end;
+            } else {
+                column += c.len_utf8();
+            }
+        }
+        subwords
+            .into_iter()
+            .map(|subword| line.start + subword.start..line.start + subword.end)
+            .collect()
+    }
+
+    ///The token at `offset` if it's a word to the language: a leaf of the tree within `line`, with word characters and no
+    ///whitespace, that isn't a comment or a string.
+    pub(crate) fn word_token(
+        &self,
+        offset: usize,
+        line: &Range<usize>,
+    ) -> Option<tree_sitter::Node<'_>> {
+        let node = self
+            .tree()?
+            .root_node()
+            .descendant_for_byte_range(offset, offset + 1)?;
+        let range = node.byte_range();
+        let text = &self.text()[range.clone()];
+        let is_word = node.child_count() == 0
+            && !node.is_error()
+            && !node.is_missing()
+            && !node.kind().contains("comment")
+            && !node.kind().contains("string")
+            && range.start >= line.start
+            && range.end <= line.end
+            && text.chars().any(is_word_char)
+            && !text.chars().any(char::is_whitespace);
+        is_word.then_some(node)
+    }
+}
+
+///Byte ranges of the sub-words of `text`, see [split].
+pub(crate) fn subwords_of(text: &str) -> Vec<Range<usize>> {
+    let mut subwords = Vec::new();
+    split(text, 0..text.len(), &mut subwords);
+    subwords
+}
+
+///Pushes the sub-words of `text[range]`: runs of letters and digits, split where lowercase turns into uppercase (`fooBar`),
+///before the last of several uppercase letters followed by a lowercase one (`HTTPServer`), and between letters and digits.
+///Underscores, hyphens and other characters separate them.
+fn split(text: &str, range: Range<usize>, subwords: &mut Vec<Range<usize>>) {
+    let chars: Vec<(usize, char)> = text[range.clone()]
+        .char_indices()
+        .map(|(idx, c)| (range.start + idx, c))
+        .collect();
+    let mut start = None;
+    for (i, &(idx, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if let Some(start) = start.take() {
+                subwords.push(start..idx);
+            }
+            continue;
+        }
+        let Some(part) = start else {
+            start = Some(idx);
+            continue;
+        };
+        let prev = chars[i - 1].1;
+        let next = chars.get(i + 1).map(|(_, next)| *next);
+        let boundary = (prev.is_lowercase() && c.is_uppercase())
+            || (prev.is_uppercase() && c.is_uppercase() && next.is_some_and(char::is_lowercase))
+            || (prev.is_numeric() != c.is_numeric());
+        if boundary {
+            subwords.push(part..idx);
+            start = Some(idx);
+        }
+    }
+    if let Some(start) = start {
+        subwords.push(start..range.end);
+    }
+}